@@ -0,0 +1,255 @@
+//! Compare the table-driven decoders of the short binary codes to the matrix decoders
+//! they're checked against, and measure the receivers that use them.
+
+#![feature(test)]
+
+extern crate p25;
+extern crate test;
+
+use p25::bits::{Dibit, SoftDibit};
+use p25::coding::{cyclic, golay, hamming};
+use p25::consts::FRAME_DIBITS;
+use p25::message::nid::NetworkAccessCode;
+use p25::message::receiver::{MessageEvent, MessageReceiver};
+use p25::message::status::StatusCode;
+use p25::voice::control::LinkControlFields;
+use p25::voice::crypto::CryptoControlFields;
+use p25::voice::frame::VoiceFrame;
+use p25::voice::header::VoiceHeaderFields;
+use p25::voice::transmit::VoiceTransmitter;
+use test::Bencher;
+
+/// Number of words decoded in each iteration.
+const WORDS: usize = 1024;
+
+/// Generate words spread across the space of words with the given number of bits.
+fn words(bits: usize) -> Vec<u32> {
+    (0..WORDS as u32).map(|i| i.wrapping_mul(2654435761) >> (32 - bits)).collect()
+}
+
+/// Generate the chunks of 9 voice frames.
+fn chunks() -> [[u32; 8]; 9] {
+    let mut frames = [[0; 8]; 9];
+
+    for (i, f) in frames.iter_mut().enumerate() {
+        let i = i as u32;
+        *f = [0xABC ^ i, 0x123 + i, 0xFFF - i, 0x555, 0x7FF - i, 0x2AA + i, 0x155, 0x7F];
+    }
+
+    frames
+}
+
+/// Add a symbol error to every 50th symbol of the given data unit after its sync and NID.
+fn corrupt(dibits: Vec<Dibit>) -> Vec<Dibit> {
+    dibits.iter().enumerate().map(|(i, &d)| if i > 64 && i % 50 == 0 {
+        Dibit::new(d.bits() ^ 0b01)
+    } else {
+        d
+    }).collect()
+}
+
+/// Generate the symbols of a call made up of a header, both kinds of frame group, and a
+/// terminator, with symbol errors in each data unit.
+fn call() -> Vec<f32> {
+    let tx = VoiceTransmitter::new(NetworkAccessCode::Default, StatusCode::InboundIdle);
+    let frames = chunks();
+    let lc = LinkControlFields::new([0, 0, 0x80, 0, 0x12, 0x34, 0xDE, 0xAD, 0xBE]);
+    let cc = CryptoControlFields::new([1, 2, 3, 4, 5, 6, 7, 8, 9, 0x80, 0, 0]);
+
+    let mut dibits = vec![Dibit::new(0b01); 24];
+    dibits.extend(corrupt(tx.header(&VoiceHeaderFields::new([0; 15]))));
+    dibits.extend(corrupt(tx.lc_frame_group(&frames, &lc, 0xBEEF)));
+    dibits.extend(corrupt(tx.cc_frame_group(&frames, &cc, 0x1234)));
+    dibits.extend(corrupt(tx.lc_terminator(&lc)));
+
+    dibits.iter().map(|&d| SoftDibit::ideal(d).value()).collect()
+}
+
+/// Feed the given call through a receiver and count the voice frames received.
+fn receive(symbols: &[f32], soft: bool) -> usize {
+    let mut recv = MessageReceiver::new();
+    recv.recv.set_soft(soft);
+
+    symbols.iter().filter_map(|&s| recv.feed_symbol(s)).filter(|e| match *e {
+        MessageEvent::VoiceFrame(_) => true,
+        _ => false,
+    }).count()
+}
+
+#[bench]
+fn golay_standard(b: &mut Bencher) {
+    let words = words(23);
+
+    for &w in words.iter() {
+        assert_eq!(golay::standard::decode(w), golay::standard::decode_matrix(w));
+    }
+
+    b.iter(|| for &w in words.iter() {
+        test::black_box(golay::standard::decode(w));
+    });
+}
+
+#[bench]
+fn golay_standard_matrix(b: &mut Bencher) {
+    let words = words(23);
+    b.iter(|| for &w in words.iter() {
+        test::black_box(golay::standard::decode_matrix(w));
+    });
+}
+
+#[bench]
+fn golay_extended(b: &mut Bencher) {
+    let words = words(24);
+
+    for &w in words.iter() {
+        assert_eq!(golay::extended::decode(w), golay::extended::decode_matrix(w));
+    }
+
+    b.iter(|| for &w in words.iter() {
+        test::black_box(golay::extended::decode(w));
+    });
+}
+
+#[bench]
+fn golay_extended_matrix(b: &mut Bencher) {
+    let words = words(24);
+    b.iter(|| for &w in words.iter() {
+        test::black_box(golay::extended::decode_matrix(w));
+    });
+}
+
+#[bench]
+fn golay_shortened(b: &mut Bencher) {
+    let words = words(18);
+
+    for &w in words.iter() {
+        assert_eq!(golay::shortened::decode(w), golay::shortened::decode_matrix(w));
+    }
+
+    b.iter(|| for &w in words.iter() {
+        test::black_box(golay::shortened::decode(w));
+    });
+}
+
+#[bench]
+fn golay_shortened_matrix(b: &mut Bencher) {
+    let words = words(18);
+    b.iter(|| for &w in words.iter() {
+        test::black_box(golay::shortened::decode_matrix(w));
+    });
+}
+
+#[bench]
+fn hamming_standard(b: &mut Bencher) {
+    let words = words(15).iter().map(|&w| w as u16).collect::<Vec<_>>();
+
+    for &w in words.iter() {
+        assert_eq!(hamming::standard::decode(w), hamming::standard::decode_matrix(w));
+    }
+
+    b.iter(|| for &w in words.iter() {
+        test::black_box(hamming::standard::decode(w));
+    });
+}
+
+#[bench]
+fn hamming_standard_matrix(b: &mut Bencher) {
+    let words = words(15).iter().map(|&w| w as u16).collect::<Vec<_>>();
+    b.iter(|| for &w in words.iter() {
+        test::black_box(hamming::standard::decode_matrix(w));
+    });
+}
+
+#[bench]
+fn hamming_shortened(b: &mut Bencher) {
+    let words = words(10).iter().map(|&w| w as u16).collect::<Vec<_>>();
+
+    for &w in words.iter() {
+        assert_eq!(hamming::shortened::decode(w), hamming::shortened::decode_matrix(w));
+    }
+
+    b.iter(|| for &w in words.iter() {
+        test::black_box(hamming::shortened::decode(w));
+    });
+}
+
+#[bench]
+fn hamming_shortened_matrix(b: &mut Bencher) {
+    let words = words(10).iter().map(|&w| w as u16).collect::<Vec<_>>();
+    b.iter(|| for &w in words.iter() {
+        test::black_box(hamming::shortened::decode_matrix(w));
+    });
+}
+
+#[bench]
+fn cyclic(b: &mut Bencher) {
+    let words = words(16).iter().map(|&w| w as u16).collect::<Vec<_>>();
+
+    for &w in words.iter() {
+        assert_eq!(cyclic::decode(w), cyclic::decode_matrix(w));
+    }
+
+    b.iter(|| for &w in words.iter() {
+        test::black_box(cyclic::decode(w));
+    });
+}
+
+#[bench]
+fn cyclic_matrix(b: &mut Bencher) {
+    let words = words(16).iter().map(|&w| w as u16).collect::<Vec<_>>();
+    b.iter(|| for &w in words.iter() {
+        test::black_box(cyclic::decode_matrix(w));
+    });
+}
+
+#[bench]
+fn voice_frame(b: &mut Bencher) {
+    let frames = chunks();
+    let coded = frames.iter().map(|f| {
+        let mut dibits = VoiceFrame::encode(f);
+
+        // Put an error in each coded word of the frame.
+        for i in 0..dibits.len() / 10 {
+            dibits[i * 10] = Dibit::new(dibits[i * 10].bits() ^ 0b10);
+        }
+
+        dibits
+    }).collect::<Vec<[Dibit; FRAME_DIBITS]>>();
+
+    b.iter(|| for d in coded.iter() {
+        test::black_box(VoiceFrame::new(d).unwrap());
+    });
+}
+
+#[bench]
+fn voice_frame_soft(b: &mut Bencher) {
+    let frames = chunks();
+    let coded = frames.iter().map(|f| {
+        let dibits = VoiceFrame::encode(f);
+        let mut soft = [SoftDibit::default(); FRAME_DIBITS];
+
+        for (s, &d) in soft.iter_mut().zip(dibits.iter()) {
+            *s = SoftDibit::ideal(d);
+        }
+
+        soft
+    }).collect::<Vec<[SoftDibit; FRAME_DIBITS]>>();
+
+    b.iter(|| for s in coded.iter() {
+        test::black_box(VoiceFrame::new_soft(s).unwrap());
+    });
+}
+
+#[bench]
+fn receive_call(b: &mut Bencher) {
+    let symbols = call();
+    assert_eq!(receive(&symbols[..], false), 18);
+    b.iter(|| test::black_box(receive(&symbols[..], false)));
+}
+
+#[bench]
+fn receive_call_soft(b: &mut Bencher) {
+    let symbols = call();
+    assert_eq!(receive(&symbols[..], true), 18);
+    b.iter(|| test::black_box(receive(&symbols[..], true)));
+}
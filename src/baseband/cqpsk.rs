@@ -0,0 +1,313 @@
+//! Demodulate a CQPSK (LSM) signal from complex baseband samples.
+//!
+//! Simulcast systems transmit the same symbols from several sites with a linear,
+//! differentially encoded π/4 QPSK modulation, where an FM discriminator performs poorly
+//! due to multipath between the sites. This demodulator instead tracks the carrier and
+//! symbol timing directly on the I/Q samples and makes symbol decisions on the phase
+//! change between symbols. Symbol timing is recovered with the same Gardner clock used
+//! for C4FM.
+//!
+//! Each dibit maps to the same phase change as the C4FM deviation of the same symbol, so
+//! the demodulated symbols are scaled to the C4FM levels of ±1 and ±3.
+
+use std;
+
+use num::complex::Complex32;
+
+use baseband::decode::Decider;
+use baseband::timing::GardnerClock;
+use bits::Dibit;
+use consts::SYMBOL_PERIOD;
+
+/// Phase step between ideal symbol phase changes.
+const PHASE_STEP: f32 = std::f32::consts::PI / 4.0;
+/// Gain of the carrier loop's phase correction.
+const PHASE_GAIN: f32 = 0.1;
+/// Gain of the carrier loop's frequency correction.
+const FREQ_GAIN: f32 = 0.005;
+/// Smoothing factor of the symbol power estimates.
+const LOCK_WEIGHT: f32 = 0.05;
+/// Spread of symbol power, relative to average power, above which symbol timing is
+/// considered to be hung up between symbols.
+const LOCK_THRESH: f32 = 0.3;
+/// Number of symbols measured after a timing jump before lock is checked again.
+const LOCK_SYMBOLS: u32 = 40;
+
+/// Quantize the given phase to the nearest multiple of the given step and return the
+/// difference.
+fn quant_err(phase: f32, step: f32) -> f32 {
+    phase - (phase / step).round() * step
+}
+
+/// Wrap the given phase into -π to π.
+fn wrap(phase: f32) -> f32 {
+    use std::f32::consts::PI;
+
+    let mut p = phase;
+
+    while p > PI { p -= 2.0 * PI; }
+    while p < -PI { p += 2.0 * PI; }
+
+    p
+}
+
+/// Create a decider for the symbols produced by `CqpskDemod`.
+pub fn decider() -> Decider { Decider::new(2.0, 0.0, -2.0) }
+
+/// Recovers symbols from complex baseband CQPSK samples.
+///
+/// Samples are expected at the standard sample rate. Carrier frequency and phase are
+/// tracked by a decision-directed loop on the symbol constellation, and symbol timing
+/// is tracked by a `GardnerClock` on the derotated samples.
+///
+/// Since the symbol phase changes are spaced by π/2, the carrier loop can only pull in
+/// offsets of less than π/4 per symbol, or 600Hz.
+pub struct CqpskDemod {
+    /// Symbol timing recovery on derotated samples.
+    clock: GardnerClock<Complex32>,
+    /// Carrier phase estimate, in radians.
+    phase: f32,
+    /// Carrier frequency estimate, in radians per sample.
+    freq: f32,
+    /// Previous symbol sample.
+    prev: Complex32,
+    /// Average symbol power.
+    power: f32,
+    /// Average squared deviation of symbol power from the average, relative to the
+    /// average.
+    spread: f32,
+    /// Number of symbols since the demodulator was started or timing last jumped.
+    count: u32,
+}
+
+impl CqpskDemod {
+    /// Create a new `CqpskDemod` with no carrier offset and nominal symbol timing.
+    pub fn new() -> CqpskDemod {
+        CqpskDemod {
+            clock: GardnerClock::new(SYMBOL_PERIOD),
+            phase: 0.0,
+            freq: 0.0,
+            prev: Complex32::new(0.0, 0.0),
+            power: 0.0,
+            spread: 0.0,
+            count: 0,
+        }
+    }
+
+    /// Current carrier frequency offset estimate, in radians per sample.
+    pub fn freq(&self) -> f32 { self.freq }
+
+    /// Current symbol period estimate, in samples.
+    pub fn period(&self) -> f32 { self.clock.period() }
+
+    /// Feed in a complex baseband sample. Return `Some(sym)` with the demodulated symbol,
+    /// scaled to ±1 and ±3, at each symbol instant and `None` otherwise.
+    pub fn feed(&mut self, sample: Complex32) -> Option<f32> {
+        let rot = Complex32::new(self.phase.cos(), -self.phase.sin());
+
+        self.phase = wrap(self.phase + self.freq);
+
+        let cur = match self.clock.step(sample * rot) {
+            Some(cur) => cur,
+            None => return None,
+        };
+
+        // Phase change between symbols, which should be an odd multiple of π/4, and
+        // phase of each symbol, which should be a multiple of π/4.
+        let diff = (cur * self.prev.conj()).arg();
+        let ferr = quant_err(diff - PHASE_STEP, 2.0 * PHASE_STEP);
+        let perr = quant_err(cur.arg(), PHASE_STEP);
+
+        self.freq += FREQ_GAIN * ferr / self.clock.period();
+        self.phase = wrap(self.phase + PHASE_GAIN * perr);
+        self.prev = cur;
+
+        // The signal has a constant envelope at the symbol instants, but halfway between
+        // symbols its power depends on the phase change, so a large spread in power means
+        // the timing loop has hung up there. Rather than wait for the loop to drift away,
+        // jump to the other half of the symbol period. Unlike the phase errors, this is
+        // unaffected by any carrier offset that hasn't been pulled in yet.
+        let power = cur.norm_sqr();
+
+        if self.power == 0.0 {
+            self.power = power;
+        }
+
+        self.power += (power - self.power) * LOCK_WEIGHT;
+
+        let dev = power / self.power.max(1e-9) - 1.0;
+
+        self.spread += (dev * dev - self.spread) * LOCK_WEIGHT;
+        self.count += 1;
+
+        if self.count >= LOCK_SYMBOLS && self.spread > LOCK_THRESH {
+            self.clock.skip_half();
+            self.spread = 0.0;
+            self.count = 0;
+        }
+
+        Some(diff / PHASE_STEP)
+    }
+}
+
+/// Demodulates complex baseband CQPSK samples into dibits.
+pub struct CqpskDecoder {
+    /// Symbol demodulator.
+    demod: CqpskDemod,
+    /// Symbol decider.
+    decider: Decider,
+}
+
+impl CqpskDecoder {
+    /// Create a new `CqpskDecoder` in the initial state.
+    pub fn new() -> CqpskDecoder {
+        CqpskDecoder {
+            demod: CqpskDemod::new(),
+            decider: decider(),
+        }
+    }
+
+    /// Feed in a complex baseband sample, possibly producing a decoded dibit.
+    pub fn feed(&mut self, sample: Complex32) -> Option<Dibit> {
+        match self.demod.feed(sample) {
+            Some(s) => Some(self.decider.decide(s)),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use num::complex::Complex32;
+    use baseband::timing::test::{dibits, pulse};
+    use bits::{Dibit, SoftDibit};
+    use consts::{SAMPLE_RATE, SYMBOL_PERIOD};
+
+    /// Modulate the given dibits into CQPSK samples with the given carrier offset in Hz
+    /// and phase in radians.
+    fn modulate(dibits: &[Dibit], offset: f32, phase: f32) -> Vec<Complex32> {
+        use std::f32::consts::PI;
+
+        const SPAN: isize = 6;
+
+        let mut cur = 0.0f32;
+        let symbols: Vec<Complex32> = dibits.iter().map(|d| {
+            cur += SoftDibit::ideal(*d).value() * PI / 4.0;
+
+            Complex32::new(cur.cos(), cur.sin())
+        }).collect();
+
+        let n = symbols.len() * SYMBOL_PERIOD;
+
+        (0..n).map(|i| {
+            let t = i as f32 / SYMBOL_PERIOD as f32;
+            let center = (i / SYMBOL_PERIOD) as isize;
+
+            let mut s = Complex32::new(0.0, 0.0);
+
+            for k in center - SPAN...center + SPAN {
+                if k < 0 || k as usize >= symbols.len() {
+                    continue;
+                }
+
+                s = s + symbols[k as usize].scale(pulse(t - k as f32));
+            }
+
+            let rot = 2.0 * PI * offset * i as f32 / SAMPLE_RATE as f32 + phase;
+            s * Complex32::new(rot.cos(), rot.sin())
+        }).collect()
+    }
+
+    /// Check that symbols are recovered with the given carrier offset in Hz and number
+    /// of initial samples skipped.
+    fn check(offset: f32, skip: usize) {
+        let tx = dibits(1500);
+        let samples = modulate(&tx[..], offset, 1.0);
+
+        let mut d = CqpskDecoder::new();
+        let rx: Vec<Dibit> = samples[skip..].iter().filter_map(|&s| d.feed(s)).collect();
+
+        // Allow the loops to settle, then find the symbol alignment.
+        let start = 500;
+        let window = &rx[start..start + 800];
+
+        let aligned = (start - 3..start + 3).any(|k| &tx[k..k + 800] == window);
+        assert!(aligned);
+    }
+
+    #[test]
+    fn test_decode() {
+        // Every starting phase is acquired, including skipping 7 samples, where the first
+        // symbol instant lies exactly between symbols.
+        for &offset in [0.0, 150.0, -300.0, 450.0].iter() {
+            for skip in 0..SYMBOL_PERIOD {
+                check(offset, skip);
+            }
+        }
+    }
+
+    #[test]
+    fn test_receive() {
+        use message::nid::{DataUnit, NetworkAccessCode};
+        use message::receiver::{MessageEvent, MessageReceiver};
+        use message::status::StatusCode;
+        use trunking::tsbk::{TsbkBuilder, TsbkOpcode, TsbkTransmitter};
+
+        let tsbks = [
+            TsbkBuilder::new(TsbkOpcode::UnitCallAlert).payload(&[1; 8]).build(),
+            TsbkBuilder::new(TsbkOpcode::PhoneAlert).payload(&[2; 8]).build(),
+        ];
+
+        let burst = TsbkTransmitter::new(NetworkAccessCode::Default,
+                                         StatusCode::InboundIdle).burst(&tsbks[..]);
+
+        // Give the carrier and timing loops time to settle before the burst.
+        let mut tx = dibits(500);
+        tx.extend(burst);
+        tx.extend(dibits(100));
+
+        let samples = modulate(&tx[..], 150.0, 1.0);
+
+        for &soft in [false, true].iter() {
+            let mut demod = CqpskDemod::new();
+            let mut recv = MessageReceiver::new();
+            recv.recv.set_soft(soft);
+
+            let events = samples.iter()
+                .filter_map(|&s| demod.feed(s))
+                .filter_map(|s| recv.feed_symbol(s))
+                .collect::<Vec<_>>();
+
+            assert_eq!(events.len(), 3);
+
+            match events[0] {
+                MessageEvent::PacketNID(ref nid) => {
+                    assert_eq!(nid.data_unit, DataUnit::TrunkingSignaling);
+                },
+                _ => panic!(),
+            }
+
+            for (e, t) in events[1..].iter().zip(tsbks.iter()) {
+                match *e {
+                    MessageEvent::TrunkingControl(ref r) => {
+                        assert_eq!(r.opcode(), t.opcode());
+                        assert_eq!(r.payload(), t.payload());
+                        assert!(r.crc_valid());
+                    },
+                    _ => panic!(),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_quant_err() {
+        use std::f32::consts::PI;
+
+        assert!((quant_err(PI / 4.0 + 0.1, PI / 4.0) - 0.1).abs() < 1e-6);
+        assert!((quant_err(-PI / 2.0 - 0.1, PI / 4.0) + 0.1).abs() < 1e-6);
+        assert!((wrap(3.0 * PI) - PI).abs() < 1e-5);
+        assert!((wrap(-2.5 * PI) + 0.5 * PI).abs() < 1e-5);
+    }
+}
@@ -1,13 +1,14 @@
 //! Utilities for decoding a C4FM signal into symbols.
 
+use baseband::timing::{FixedClock, SymbolClock};
 use bits;
 use consts;
 
 /// Decodes symbol from sample at each symbol instant.
 #[derive(Copy, Clone)]
-pub struct Decoder {
-    /// Sample index into current symbol period.
-    pos: usize,
+pub struct Decoder<C: SymbolClock = FixedClock> {
+    /// Clock that determines each symbol instant.
+    clock: C,
     /// Decider used for decoding symbol at each symbol instant.
     decider: Decider,
 }
@@ -16,11 +17,22 @@ impl Decoder {
     /// Create a new `Decoder` with the given symbol decider, initialized to decode the
     /// first symbol after the frame sync has been detected.
     pub fn new(decider: Decider) -> Decoder {
+        Self::with_period(decider, consts::SYMBOL_PERIOD)
+    }
+
+    /// Create a new `Decoder` with the given symbol decider and symbol period in samples,
+    /// initialized to decode the first symbol after the sync sequence has been detected.
+    pub fn with_period(decider: Decider, period: usize) -> Decoder {
+        Decoder::with_clock(decider, FixedClock::new(period))
+    }
+}
+
+impl<C: SymbolClock> Decoder<C> {
+    /// Create a new `Decoder` with the given symbol decider and symbol clock, which must
+    /// be aligned to the sync sequence that has just been detected.
+    pub fn with_clock(decider: Decider, clock: C) -> Decoder<C> {
         Decoder {
-            // The frame sync sequence is detected one sample after its last symbol
-            // instant (i.e., the first sample in the next symbol period after the
-            // sequence), so take that sample into account.
-            pos: 1,
+            clock: clock,
             decider: decider,
         }
     }
@@ -28,13 +40,46 @@ impl Decoder {
     /// Examine the given sample and, based on the symbol clock, decode it into a symbol
     /// or do nothing.
     pub fn feed(&mut self, s: f32) -> Option<bits::Dibit> {
-        self.pos += 1;
-        self.pos %= consts::SYMBOL_PERIOD;
+        match self.clock.feed(s) {
+            Some(s) => Some(self.decider.decide(s)),
+            None => None,
+        }
+    }
+}
 
-        if self.pos == 0 {
-            Some(self.decider.decide(s))
-        } else {
-            None
+/// Decodes soft symbol from sample at each symbol instant.
+#[derive(Copy, Clone)]
+pub struct SoftDecoder<C: SymbolClock = FixedClock> {
+    /// Clock that determines each symbol instant.
+    clock: C,
+    /// Decider used for scaling the sample at each symbol instant.
+    decider: Decider,
+}
+
+impl SoftDecoder {
+    /// Create a new `SoftDecoder` with the given symbol decider, initialized to decode
+    /// the first symbol after the frame sync has been detected.
+    pub fn new(decider: Decider) -> SoftDecoder {
+        SoftDecoder::with_clock(decider, FixedClock::new(consts::SYMBOL_PERIOD))
+    }
+}
+
+impl<C: SymbolClock> SoftDecoder<C> {
+    /// Create a new `SoftDecoder` with the given symbol decider and symbol clock, which
+    /// must be aligned to the sync sequence that has just been detected.
+    pub fn with_clock(decider: Decider, clock: C) -> SoftDecoder<C> {
+        SoftDecoder {
+            clock: clock,
+            decider: decider,
+        }
+    }
+
+    /// Examine the given sample and, based on the symbol clock, decode it into a soft
+    /// symbol or do nothing.
+    pub fn feed(&mut self, s: f32) -> Option<bits::SoftDibit> {
+        match self.clock.feed(s) {
+            Some(s) => Some(self.decider.soft(s)),
+            None => None,
         }
     }
 }
@@ -73,6 +118,13 @@ impl Decider {
             bits::Dibit::new(0b11)
         }
     }
+
+    /// Scale the given sample so the thresholds lie at 2, 0, and -2, keeping the
+    /// distance of the sample from each threshold as a measure of its reliability.
+    pub fn soft(&self, sample: f32) -> bits::SoftDibit {
+        let spacing = (self.pthresh - self.nthresh) / 4.0;
+        bits::SoftDibit::new((sample - self.mthresh) / spacing)
+    }
 }
 
 #[cfg(test)]
@@ -87,6 +139,13 @@ mod test {
         assert_eq!(d.decide(-0.052).bits(), 0b00);
         assert_eq!(d.decide(-0.148).bits(), 0b10);
         assert_eq!(d.decide(-0.244).bits(), 0b11);
+
+        for &s in [0.044, -0.052, -0.148, -0.244, 0.0, -0.19].iter() {
+            assert_eq!(d.soft(s).hard(), d.decide(s));
+        }
+
+        assert!((d.soft(0.044).value() - 3.0).abs() < 1e-4);
+        assert!((d.soft(-0.148).value() + 1.0).abs() < 1e-4);
     }
 
     #[test]
@@ -114,4 +173,32 @@ mod test {
         assert!(d.feed(0.1816711425781250).is_none());
         assert!(d.feed(0.1799926757812500).is_some());
     }
+
+    #[test]
+    fn test_soft_decoder() {
+        let decider = Decider::new(0.08, 0.0, -0.08);
+        let mut soft = SoftDecoder::new(decider);
+        let mut hard = Decoder::new(decider);
+        let mut values = vec![];
+
+        for &s in [0.12, 0.04, -0.04, -0.12, 0.06, -0.1].iter() {
+            for _ in 0..consts::SYMBOL_PERIOD {
+                match (soft.feed(s), hard.feed(s)) {
+                    (Some(x), Some(d)) => {
+                        assert_eq!(x.hard(), d);
+                        values.push(x.value());
+                    },
+                    (None, None) => {},
+                    _ => panic!(),
+                }
+            }
+        }
+
+        let expect = [3.0, 1.0, -1.0, -3.0, 1.5, -2.5];
+        assert_eq!(values.len(), expect.len());
+
+        for (v, e) in values.iter().zip(expect.iter()) {
+            assert!((v - e).abs() < 1e-4);
+        }
+    }
 }
@@ -1,5 +1,7 @@
 //! Sychronization and symbol encoding/decoding of C4FM signal used by P25.
 
+pub mod cqpsk;
 pub mod decode;
 pub mod encode;
 pub mod sync;
+pub mod timing;
@@ -5,6 +5,9 @@ use collect_slice::CollectSlice;
 use static_ewma::{MovingAverageWeight, MovingAverage};
 use static_fir::FIRFilter;
 
+use bits::Dibit;
+use consts::SYNC_SYMBOLS;
+
 /// Empirically-determined power threshold for detecting correlation power with
 /// fingerprint, scaled by average power of signal under test.
 const THRESH_FACTOR: f32 = 0.1506734989540087;
@@ -13,7 +16,7 @@ const THRESH_FACTOR: f32 = 0.1506734989540087;
 const FINGERPRINT_SAMPS: usize = 231;
 
 /// Smoothing factor for signal power EWMA.
-struct PowerSmoothing;
+pub struct PowerSmoothing;
 
 impl MovingAverageWeight for PowerSmoothing {
     fn weight() -> f32 {
@@ -92,7 +95,7 @@ fn calc_averages(samples: &[f32; FINGERPRINT_SAMPS]) -> (f32, f32) {
 
 /// Calculate the upper, mid, and lower thresholds for symbol decisions from the given
 /// positive and negative sample values.
-fn calc_thresholds(pavg: f32, navg: f32) -> (f32, f32, f32) {
+pub fn calc_thresholds(pavg: f32, navg: f32) -> (f32, f32, f32) {
     let mthresh = (pavg + navg) / 2.0;
     let pthresh = mthresh + (pavg - mthresh) * (2.0 / 3.0);
     let nthresh = mthresh + (navg - mthresh) * (2.0 / 3.0);
@@ -135,6 +138,40 @@ impl SyncDetector {
     }
 }
 
+/// Maximum number of bit errors allowed when matching the frame sync sequence against
+/// decided symbols.
+const MAX_SYNC_ERRORS: u32 = 4;
+
+/// Detects the frame sync sequence in a stream of already-decided symbols, such as those
+/// recovered by a demodulator that does its own symbol timing.
+#[derive(Copy, Clone, Debug)]
+pub struct DibitSyncDetector {
+    /// Bits of the most recent symbols, with the newest in the low bits.
+    bits: u64,
+    /// Bits of the frame sync sequence.
+    sync: u64,
+}
+
+impl DibitSyncDetector {
+    /// Create a new `DibitSyncDetector` in the default state.
+    pub fn new() -> DibitSyncDetector {
+        DibitSyncDetector {
+            bits: 0,
+            sync: SYNC_GENERATOR.iter().fold(0, |s, &b| s << 8 | b as u64),
+        }
+    }
+
+    /// Consider the given symbol. Return `true` if it completed a frame sync sequence and
+    /// `false` otherwise.
+    pub fn feed(&mut self, d: Dibit) -> bool {
+        let mask = (1 << (SYNC_SYMBOLS * 2)) - 1;
+
+        self.bits = (self.bits << 2 | d.bits() as u64) & mask;
+
+        (self.bits ^ self.sync).count_ones() <= MAX_SYNC_ERRORS
+    }
+}
+
 /// Fingerprint of 24-symbol frame sync waveform in "volts".
 impl_fir!(SyncFingerprint, f32, FINGERPRINT_SAMPS, [
     0.1800000071525574,
@@ -383,6 +420,8 @@ pub const SYNC_GENERATOR: &'static [u8] = &[
 #[cfg(test)]
 mod test {
     use super::{SyncFingerprint, calc_averages, calc_thresholds, SyncDetector};
+    use super::{DibitSyncDetector, SYNC_GENERATOR};
+    use bits::{Dibit, Dibits};
     use static_fir::FIRFilter;
 
     #[test]
@@ -474,6 +513,37 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_dibit_detector() {
+        let sync = Dibits::new(SYNC_GENERATOR.iter().cloned()).collect::<Vec<_>>();
+        let mut d = DibitSyncDetector::new();
+
+        for _ in 0..30 {
+            assert!(!d.feed(Dibit::new(0b10)));
+        }
+
+        let (last, rest) = sync.split_last().unwrap();
+
+        for &s in rest {
+            assert!(!d.feed(s));
+        }
+
+        assert!(d.feed(*last));
+        assert!(!d.feed(Dibit::new(0b10)));
+
+        // A few symbol errors are tolerated.
+        let mut errs = sync.clone();
+        errs[3] = Dibit::new(errs[3].bits() ^ 0b11);
+        errs[17] = Dibit::new(errs[17].bits() ^ 0b01);
+
+        assert!(errs.iter().map(|&s| d.feed(s)).last().unwrap());
+
+        errs[9] = Dibit::new(errs[9].bits() ^ 0b11);
+        errs[20] = Dibit::new(errs[20].bits() ^ 0b10);
+
+        assert!(!errs.iter().any(|&s| d.feed(s)));
+    }
+
     #[test]
     fn test_corr_self() {
         // Verify result of correlating fingerprint with itself. Result verified with
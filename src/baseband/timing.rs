@@ -0,0 +1,365 @@
+//! Recover symbol timing from a baseband signal.
+//!
+//! After a sync sequence is detected, a symbol clock determines the instant of each
+//! following symbol. The simplest clock counts a fixed number of samples per symbol,
+//! which slips symbols when the transmitter's symbol rate differs slightly from the
+//! nominal rate. The Gardner clock instead measures the timing error at each symbol and
+//! continuously adjusts the symbol instant and period throughout a packet.
+
+use num::complex::Complex32;
+
+use consts::SYMBOL_PERIOD;
+
+/// Number of recent samples kept for interpolation.
+const HIST_SAMPS: usize = 2 * SYMBOL_PERIOD;
+/// Gain of the timing loop's phase correction.
+const TIMING_GAIN: f32 = 0.05;
+/// Gain of the timing loop's symbol period correction.
+const PERIOD_GAIN: f32 = 0.0005;
+/// Maximum deviation of the tracked symbol period from nominal, as a fraction.
+const MAX_PERIOD_DEV: f32 = 0.005;
+/// Smoothing factor of the signal power estimate.
+const POWER_WEIGHT: f32 = 0.01;
+/// Maximum magnitude of the normalized timing error applied at each symbol.
+const MAX_TIMING_ERR: f32 = 2.0;
+
+/// Determines the symbol instants in a baseband signal.
+///
+/// A clock is created aligned to a sync sequence that was detected one sample after its
+/// last symbol instant, so the first sample fed in is the second sample after that
+/// instant.
+pub trait SymbolClock: Copy {
+    /// Feed in a baseband sample. Return `Some(s)` with the signal at the symbol instant
+    /// if one occurred since the previous sample, and `None` otherwise.
+    fn feed(&mut self, s: f32) -> Option<f32>;
+}
+
+/// Symbol clock that counts a fixed number of samples per symbol.
+#[derive(Copy, Clone)]
+pub struct FixedClock {
+    /// Sample index into current symbol period.
+    pos: usize,
+    /// Number of samples in each symbol period.
+    period: usize,
+}
+
+impl FixedClock {
+    /// Create a new `FixedClock` with the given symbol period in samples.
+    pub fn new(period: usize) -> FixedClock {
+        FixedClock {
+            // The sync sequence is detected one sample after its last symbol instant
+            // (i.e., the first sample in the next symbol period after the sequence), so
+            // take that sample into account.
+            pos: 1,
+            period: period,
+        }
+    }
+}
+
+impl SymbolClock for FixedClock {
+    fn feed(&mut self, s: f32) -> Option<f32> {
+        self.pos += 1;
+        self.pos %= self.period;
+
+        if self.pos == 0 {
+            Some(s)
+        } else {
+            None
+        }
+    }
+}
+
+/// A sample that can be interpolated and measured by a Gardner timing error detector.
+pub trait GardnerSample: Copy {
+    /// Sample with zero value.
+    fn zero() -> Self;
+    /// Interpolate linearly between the given samples, where `frac` is the fraction of
+    /// the way from `a` to `b`.
+    fn lerp(a: Self, b: Self, frac: f32) -> Self;
+    /// Power of the sample.
+    fn power(&self) -> f32;
+    /// Gardner timing error of the given symbol samples and the sample halfway between
+    /// them, not normalized by signal power.
+    fn timing_error(prev: Self, cur: Self, mid: Self) -> f32;
+}
+
+impl GardnerSample for f32 {
+    fn zero() -> Self { 0.0 }
+    fn lerp(a: Self, b: Self, frac: f32) -> Self { a * (1.0 - frac) + b * frac }
+    fn power(&self) -> f32 { self * self }
+    fn timing_error(prev: Self, cur: Self, mid: Self) -> f32 { (cur - prev) * mid }
+}
+
+impl GardnerSample for Complex32 {
+    fn zero() -> Self { Complex32::new(0.0, 0.0) }
+    fn lerp(a: Self, b: Self, frac: f32) -> Self { a.scale(1.0 - frac) + b.scale(frac) }
+    fn power(&self) -> f32 { self.norm_sqr() }
+    fn timing_error(prev: Self, cur: Self, mid: Self) -> f32 {
+        ((cur - prev) * mid.conj()).re
+    }
+}
+
+/// Symbol clock that tracks symbol timing with a Gardner timing error detector.
+///
+/// The signal at each symbol instant and halfway between symbol instants is linearly
+/// interpolated between samples, so the symbol instant can fall anywhere within a
+/// sample period. The symbol period is allowed to drift by a small fraction from
+/// nominal. The clock works on real baseband samples as a `SymbolClock` and on complex
+/// samples for the CQPSK demodulator.
+#[derive(Copy, Clone)]
+pub struct GardnerClock<T: GardnerSample = f32> {
+    /// Ring buffer of recent samples.
+    hist: [T; HIST_SAMPS],
+    /// Index of the newest sample in `hist`.
+    idx: usize,
+    /// Time until the next symbol instant, in samples.
+    next: f32,
+    /// Current symbol period estimate, in samples.
+    period: f32,
+    /// Nominal symbol period, in samples.
+    nominal: f32,
+    /// Previous symbol sample, or `None` if no symbol has been seen yet.
+    prev: Option<T>,
+    /// Average power of symbol samples.
+    power: f32,
+}
+
+impl<T: GardnerSample> GardnerClock<T> {
+    /// Create a new `GardnerClock` with the given nominal symbol period in samples.
+    pub fn new(period: usize) -> GardnerClock<T> {
+        assert!(period > 1 && period <= SYMBOL_PERIOD);
+
+        GardnerClock {
+            hist: [T::zero(); HIST_SAMPS],
+            idx: 0,
+            // The sync sequence is detected one sample after its last symbol instant.
+            next: period as f32 - 1.0,
+            period: period as f32,
+            nominal: period as f32,
+            prev: None,
+            power: 0.0,
+        }
+    }
+
+    /// Current symbol period estimate, in samples.
+    pub fn period(&self) -> f32 { self.period }
+
+    /// Delay the following symbol instants by half a symbol period.
+    ///
+    /// The timing error is zero both at the symbol instants and halfway between them,
+    /// and the loop can take a long time to move away from the latter, so this can be
+    /// used to jump to the correct timing once a hang-up has been detected.
+    pub fn skip_half(&mut self) {
+        self.next += self.period / 2.0;
+    }
+
+    /// Feed in a sample. Return `Some(s)` with the signal at the symbol instant if one
+    /// occurred since the previous sample, and `None` otherwise.
+    pub fn step(&mut self, s: T) -> Option<T> {
+        self.idx = (self.idx + 1) % HIST_SAMPS;
+        self.hist[self.idx] = s;
+        self.next -= 1.0;
+
+        if self.next > 0.0 {
+            return None;
+        }
+
+        // The symbol instant lies between the previous sample and this one.
+        let cur = self.interp(self.next);
+
+        let prev = match self.prev {
+            Some(prev) => prev,
+            None => {
+                // The samples before the first symbol instant belong to the sync
+                // sequence and weren't seen, so the timing can't be measured yet.
+                self.prev = Some(cur);
+                self.power = cur.power();
+                self.next += self.period;
+
+                return Some(cur);
+            },
+        };
+
+        let mid = self.interp(self.next - self.period / 2.0);
+
+        self.power += (cur.power() - self.power) * POWER_WEIGHT;
+
+        // Gardner timing error, normalized by signal power and limited so an impulse
+        // can't move the next symbol instant by more than a fraction of a sample.
+        let terr = (T::timing_error(prev, cur, mid) / self.power.max(1e-9))
+            .max(-MAX_TIMING_ERR).min(MAX_TIMING_ERR);
+        let dev = self.nominal * MAX_PERIOD_DEV;
+
+        self.period = (self.period - PERIOD_GAIN * terr).max(self.nominal - dev)
+            .min(self.nominal + dev);
+        self.next += self.period - TIMING_GAIN * terr;
+        self.prev = Some(cur);
+
+        Some(cur)
+    }
+
+    /// Linearly interpolate the signal at the given time relative to the newest sample,
+    /// which must be between `-HIST_SAMPS + 1` and 0.
+    fn interp(&self, t: f32) -> T {
+        debug_assert!(t <= 0.0 && t > -(HIST_SAMPS as f32) + 1.0);
+
+        let whole = t.floor();
+        let back = (-whole) as usize;
+
+        T::lerp(self.hist[(self.idx + HIST_SAMPS - back) % HIST_SAMPS],
+                self.hist[(self.idx + HIST_SAMPS + 1 - back) % HIST_SAMPS],
+                t - whole)
+    }
+}
+
+impl SymbolClock for GardnerClock<f32> {
+    fn feed(&mut self, s: f32) -> Option<f32> { self.step(s) }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use bits::{Dibit, SoftDibit};
+    use consts::SYMBOL_PERIOD;
+
+    /// Generate pseudorandom dibits.
+    pub fn dibits(n: usize) -> Vec<Dibit> {
+        let mut state = 0x1234u32;
+
+        (0..n).map(|_| {
+            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+            Dibit::new((state >> 16) as u8 & 0b11)
+        }).collect()
+    }
+
+    /// Raised cosine pulse at the given time in symbol periods.
+    pub fn pulse(t: f32) -> f32 {
+        use std::f32::consts::PI;
+
+        const ALPHA: f32 = 0.2;
+
+        if t.abs() < 1e-6 {
+            return 1.0;
+        }
+
+        let denom = 1.0 - (2.0 * ALPHA * t).powi(2);
+
+        if denom.abs() < 1e-6 {
+            return (PI / 4.0) * (PI * t).sin() / (PI * t);
+        }
+
+        (PI * t).sin() / (PI * t) * (PI * ALPHA * t).cos() / denom
+    }
+
+    /// Generate pseudorandom symbol levels.
+    fn levels(n: usize) -> Vec<f32> {
+        dibits(n).iter().map(|&d| SoftDibit::ideal(d).value() * 0.06).collect()
+    }
+
+    /// Shape the given symbol levels into a baseband signal with the given symbol period
+    /// in samples, starting two samples after the first symbol instant.
+    fn shape(levels: &[f32], period: f32) -> Vec<f32> {
+        const SPAN: isize = 6;
+
+        let n = (levels.len() as f32 * period) as usize;
+
+        (2..n).map(|i| {
+            let t = i as f32 / period;
+            let center = t as isize;
+
+            (center - SPAN...center + SPAN)
+                .filter(|&k| k >= 0 && (k as usize) < levels.len())
+                .fold(0.0, |s, k| s + levels[k as usize] * pulse(t - k as f32))
+        }).collect()
+    }
+
+    /// Count the symbol decisions that differ from the given levels after recovering
+    /// them with the given clock.
+    fn errors<C: SymbolClock>(mut clock: C, levels: &[f32], samples: &[f32]) -> usize {
+        let rx: Vec<f32> = samples.iter().filter_map(|&s| clock.feed(s)).collect();
+
+        // Count missing symbols at the end as errors.
+        levels.len() - 1 - rx.len().min(levels.len() - 1) +
+            levels[1..].iter().zip(rx.iter())
+                .filter(|&(&l, &r)| (l - r).abs() > 0.06)
+                .count()
+    }
+
+    #[test]
+    fn test_fixed() {
+        let mut c = FixedClock::new(SYMBOL_PERIOD);
+
+        for _ in 0..SYMBOL_PERIOD - 2 {
+            assert!(c.feed(0.0).is_none());
+        }
+
+        assert_eq!(c.feed(1.0), Some(1.0));
+
+        for _ in 0..SYMBOL_PERIOD - 1 {
+            assert!(c.feed(0.0).is_none());
+        }
+
+        assert_eq!(c.feed(2.0), Some(2.0));
+    }
+
+    #[test]
+    fn test_gardner() {
+        let lv = levels(2000);
+
+        // Nominal timing is recovered by both clocks.
+        let samples = shape(&lv[..], SYMBOL_PERIOD as f32);
+        assert_eq!(errors(FixedClock::new(SYMBOL_PERIOD), &lv[..], &samples[..]), 0);
+        let gardner: GardnerClock = GardnerClock::new(SYMBOL_PERIOD);
+        assert_eq!(errors(gardner, &lv[..], &samples[..]), 0);
+
+        // Transmitter clocks off by 500ppm slip symbols with a fixed count but are
+        // tracked by the Gardner clock.
+        for &period in [9.995, 10.005].iter() {
+            let samples = shape(&lv[..], period);
+
+            assert!(errors(FixedClock::new(SYMBOL_PERIOD), &lv[..], &samples[..]) > 100);
+
+            let mut c: GardnerClock = GardnerClock::new(SYMBOL_PERIOD);
+            assert_eq!(errors(c, &lv[..], &samples[..]), 0);
+
+            for &s in samples.iter() {
+                c.feed(s);
+            }
+
+            assert!((c.period() - period).abs() < 0.01);
+        }
+    }
+
+    #[test]
+    fn test_impulse() {
+        let lv = levels(400);
+        let mut samples = shape(&lv[..], SYMBOL_PERIOD as f32);
+
+        samples[1503] = 1000.0 * 0.18;
+
+        let mut c: GardnerClock = GardnerClock::new(SYMBOL_PERIOD);
+        let mut gap = SYMBOL_PERIOD;
+        let mut rx = vec![];
+
+        for &s in samples.iter() {
+            gap += 1;
+
+            if let Some(x) = c.feed(s) {
+                // Symbol instants never jump by more than a sample.
+                assert!(gap >= SYMBOL_PERIOD - 1);
+
+                gap = 0;
+                rx.push(x);
+            }
+        }
+
+        assert_eq!(rx.len(), lv.len() - 1);
+
+        // Only the symbols next to the impulse are corrupted.
+        let errs = lv[1..].iter().zip(rx.iter())
+            .filter(|&(&l, &r)| (l - r).abs() > 0.06)
+            .count();
+
+        assert!(errs <= 2);
+    }
+}
@@ -71,6 +71,60 @@ impl Dibit {
     pub fn lo(&self) -> u8 { self.0 & 1 }
 }
 
+/// Dibit symbol with soft reliability information.
+///
+/// The symbol is measured on a scale where the ideal symbols `01`, `00`, `10`, and `11`
+/// lie at 3, 1, -1, and -3, and the decision thresholds between them lie at 2, 0, and
+/// -2.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct SoftDibit(f32);
+
+impl SoftDibit {
+    /// Construct a new `SoftDibit` from the given scaled symbol value.
+    pub fn new(value: f32) -> SoftDibit { SoftDibit(value) }
+
+    /// Construct a `SoftDibit` at the ideal symbol level of the given dibit.
+    pub fn ideal(dibit: Dibit) -> SoftDibit {
+        SoftDibit(match dibit.bits() {
+            0b01 => 3.0,
+            0b00 => 1.0,
+            0b10 => -1.0,
+            0b11 => -3.0,
+            _ => unreachable!(),
+        })
+    }
+
+    /// Get the scaled symbol value.
+    pub fn value(&self) -> f32 { self.0 }
+
+    /// Decide which dibit the symbol is closest to.
+    pub fn hard(&self) -> Dibit {
+        Dibit::new(if self.0 > 2.0 {
+            0b01
+        } else if self.0 > 0.0 {
+            0b00
+        } else if self.0 > -2.0 {
+            0b10
+        } else {
+            0b11
+        })
+    }
+
+    /// Distance of the symbol from the nearest decision threshold, from 0 for a symbol
+    /// on a threshold to 1 for an ideal symbol.
+    pub fn reliability(&self) -> f32 {
+        [2.0, 0.0, -2.0].iter()
+            .fold(1.0, |r: f32, &t: &f32| r.min((self.0 - t).abs()))
+    }
+
+    /// Reliability of the MSB, which is the distance of the symbol from the 0 threshold.
+    pub fn hi_reliability(&self) -> f32 { self.0.abs() }
+
+    /// Reliability of the LSB, which is the distance of the symbol from the nearest of
+    /// the 2 and -2 thresholds.
+    pub fn lo_reliability(&self) -> f32 { (self.0.abs() - 2.0).abs() }
+}
+
 /// Parameters for `Dibits` iterator.
 pub struct DibitParams;
 
@@ -304,6 +358,33 @@ mod test {
         assert!(d.next().is_none());
     }
 
+    #[test]
+    fn test_soft_dibit() {
+        assert_eq!(SoftDibit::new(3.5).hard().bits(), 0b01);
+        assert_eq!(SoftDibit::new(0.8).hard().bits(), 0b00);
+        assert_eq!(SoftDibit::new(-0.1).hard().bits(), 0b10);
+        assert_eq!(SoftDibit::new(-2.1).hard().bits(), 0b11);
+
+        assert_eq!(SoftDibit::new(3.0).reliability(), 1.0);
+        assert_eq!(SoftDibit::new(-1.0).reliability(), 1.0);
+        assert!((SoftDibit::new(1.75).reliability() - 0.25).abs() < 1e-6);
+        assert_eq!(SoftDibit::new(-2.0).reliability(), 0.0);
+
+        assert_eq!(SoftDibit::new(-0.5).hi_reliability(), 0.5);
+        assert_eq!(SoftDibit::new(-0.5).lo_reliability(), 1.5);
+        assert_eq!(SoftDibit::new(2.25).hi_reliability(), 2.25);
+        assert_eq!(SoftDibit::new(2.25).lo_reliability(), 0.25);
+
+        for b in 0..4 {
+            let s = SoftDibit::ideal(Dibit::new(b));
+            assert_eq!(s.hard().bits(), b);
+            assert_eq!(s.reliability(), 1.0);
+        }
+
+        assert_eq!(SoftDibit::ideal(Dibit::new(0b01)).value(), 3.0);
+        assert_eq!(SoftDibit::ideal(Dibit::new(0b11)).value(), -3.0);
+    }
+
     #[test]
     fn test_dibit_bytes() {
         let dibits = [
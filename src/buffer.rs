@@ -77,8 +77,9 @@ storage_type!(VoiceHeaderStorage, [bits::Hexbit; consts::HEADER_HEXBITS]);
 storage_type!(VoiceFrameStorage, [bits::Dibit; consts::FRAME_DIBITS]);
 /// Stores hexbits that make up a voice extra packet.
 storage_type!(VoiceExtraStorage, [bits::Hexbit; consts::EXTRA_HEXBITS]);
-/// Stores dibits that make up a data/TSBK payload packet.
-storage_type!(DataPayloadStorage, [bits::Dibit; consts::CODING_DIBITS]);
+/// Stores dibits that make up a data/TSBK payload packet, with hard dibits stored as
+/// their ideal soft symbols.
+storage_type!(DataPayloadStorage, [bits::SoftDibit; consts::CODING_DIBITS]);
 /// Stores dibits that make up the NID word.
 small_storage_type!(NidStorage, consts::NID_DIBITS);
 /// Stores dibits that make up each coded word in a voice extra component.
@@ -3,8 +3,6 @@
 //! These algorithms are derived from *Coding Theory and Cryptography: The Essentials*,
 //! Hankerson, Hoffman, et al, 2000.
 
-use std;
-
 use coding::galois::{GaloisField, P25Field, P25Codeword, Polynomial, PolynomialCoefs};
 use coding::bmcf;
 
@@ -113,7 +111,6 @@ fn syndromes(word: u64) -> BchPolynomial {
 
 #[cfg(test)]
 mod test {
-    use std;
     use super::*;
     use super::{syndromes, BchCoefs};
     use coding::galois::{PolynomialCoefs, P25Codeword, Polynomial};
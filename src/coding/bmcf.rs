@@ -27,6 +27,20 @@
 //! techniques exist to perform these steps relatively efficiently: the Berlekamp-Massey
 //! algorithm for step 2, Chien Search for step 3, and the Forney algorithm for step 5.
 //!
+//! # Erasures
+//!
+//! When the locations m<sub>1</sub>, ..., m<sub>f</sub> of some errors ("erasures") are
+//! known beforehand, such as from a failed inner code, up to e errors and f erasures can
+//! be corrected as long as 2e + f ≤ 2t. The procedure is modified as follows:
+//!
+//! 1. Build the erasure locator polynomial Γ(x) = (1 + α<sup>m<sub>1</sub></sup>x) ···
+//!    (1 + α<sup>m<sub>f</sub></sup>x).
+//! 2. Compute the modified syndromes T(x) = Γ(x)s(x) mod x<sup>2t</sup>. The
+//!    coefficients T<sub>f</sub>, ..., T<sub>2t-1</sub> depend only on the unknown
+//!    errors, so Λ(x) is built from these 2t - f syndromes.
+//! 3. Find the roots of, and evaluate errors with, the combined locator Ψ(x) =
+//!    Λ(x)Γ(x) in place of Λ(x).
+//!
 //! # Berlekamp-Massey Algorithm
 //!
 //! The Berlekamp-Massey algorithm has many variants [1], [2], [9], [12], [13] mostly with
@@ -62,7 +76,7 @@ use std;
 
 use collect_slice::CollectSlice;
 
-use coding::galois::{Polynomial, PolynomialCoefs, P25Codeword, P25Field, GaloisField};
+use coding::galois::{Codeword, GaloisField, Polynomial, PolynomialCoefs};
 
 /// Finds the error location polynomial Λ(x) from the syndrome polynomial s(x).
 ///
@@ -81,30 +95,41 @@ pub struct ErrorLocator<P: PolynomialCoefs> {
     deg_saved: usize,
     /// Degree-related term of previous p polynomial: D<sub>i-1</sub>.
     deg_cur: usize,
+    /// Number of syndromes used to build the polynomial, usually 2t.
+    syndromes: usize,
 }
 
 impl<P: PolynomialCoefs> ErrorLocator<P> {
     /// Construct a new `ErrorLocator` from the given syndrome polynomial s(x).
     pub fn new(syn: Polynomial<P>) -> ErrorLocator<P> {
+        Self::with_syndromes(syn, P::syndromes())
+    }
+
+    /// Construct a new `ErrorLocator` from the first n coefficients of the given
+    /// syndrome polynomial, where n is the given number of syndromes.
+    pub fn with_syndromes(syn: Polynomial<P>, n: usize) -> ErrorLocator<P> {
+        assert!(n <= P::syndromes());
+
         ErrorLocator {
             // Compute 1 + s(x).
             q_saved: Polynomial::new(
-                std::iter::once(P25Codeword::for_power(0))
-                    .chain(syn.iter().take(P::syndromes()).cloned())
+                std::iter::once(Codeword::for_power(0))
+                    .chain(syn.iter().take(n).cloned())
             ),
-            q_cur: syn,
-            // Compute x^{2t+1}.
-            p_saved: Polynomial::unit_power(P::syndromes() + 1),
-            // Compute x^{2t}.
-            p_cur: Polynomial::unit_power(P::syndromes()),
+            q_cur: Polynomial::new(syn.iter().take(n).cloned()),
+            // Compute x^{n+1}.
+            p_saved: Polynomial::unit_power(n + 1),
+            // Compute x^n.
+            p_cur: Polynomial::unit_power(n),
             deg_saved: 0,
             deg_cur: 1,
+            syndromes: n,
         }
     }
 
     /// Construct the error locator polynomial Λ(x).
     pub fn build(mut self) -> Polynomial<P> {
-        for _ in 0..P::syndromes() {
+        for _ in 0..self.syndromes {
             self.step();
         }
 
@@ -174,7 +199,7 @@ impl<P: PolynomialCoefs> PolynomialRoots<P> {
     pub fn new(loc: Polynomial<P>) -> Self {
         PolynomialRoots {
             loc: loc,
-            pow: 0..P25Field::size(),
+            pow: 0..P::Field::size(),
         }
     }
 
@@ -182,19 +207,19 @@ impl<P: PolynomialCoefs> PolynomialRoots<P> {
     /// power.
     fn update_terms(&mut self) {
         for (pow, term) in self.loc.iter_mut().enumerate() {
-            *term = *term * P25Codeword::for_power(pow);
+            *term = *term * Codeword::for_power(pow);
         }
     }
 
     /// Compute Λ(α<sup>i</sup>), where i is the current power.
-    fn eval(&self) -> P25Codeword {
-        self.loc.iter().fold(P25Codeword::default(), |sum, &x| sum + x)
+    fn eval(&self) -> Codeword<P::Field> {
+        self.loc.iter().fold(Codeword::default(), |sum, &x| sum + x)
     }
 }
 
 /// Iterate over all roots α<sup>i</sup> of Λ(x).
 impl<P: PolynomialCoefs> Iterator for PolynomialRoots<P> {
-    type Item = P25Codeword;
+    type Item = Codeword<P::Field>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -211,7 +236,7 @@ impl<P: PolynomialCoefs> Iterator for PolynomialRoots<P> {
 
             // Yield α^i if Λ(α^i) = 0.
             if eval.zero() {
-                return Some(P25Codeword::for_power(pow));
+                return Some(Codeword::for_power(pow));
             }
         }
     }
@@ -243,7 +268,7 @@ impl<P: PolynomialCoefs> ErrorDescriptions<P> {
 
     /// Compute the error location and pattern for the given root
     /// a<sub>i</sub><sup>-1</sup> of Λ(x).
-    pub fn for_root(&self, root: P25Codeword) -> (usize, P25Codeword) {
+    pub fn for_root(&self, root: Codeword<P::Field>) -> (usize, Codeword<P::Field>) {
         (
             // If Λ(α^i) = 0, then the error location is m ≡ -i (modulo the field.)
             root.invert().power().unwrap(),
@@ -273,19 +298,61 @@ impl<P: PolynomialCoefs> Errors<P> {
     /// of detected errors and `errs` is the error iterator. Otherwise, return `None` to
     /// indicate an unrecoverable error.
     pub fn new(syn: Polynomial<P>) -> Option<(usize, Self)> {
+        Self::with_erasures(syn, std::iter::empty())
+    }
+
+    /// Create a new `Errors` decoder from the given syndrome polynomial s(x) and
+    /// locations of erased symbols.
+    ///
+    /// If decoding was sucessful, return `Some((nerr, errs))`, where `nerr` is the number
+    /// of detected errors plus the number of erasures and `errs` is the error iterator.
+    /// Otherwise, return `None` to indicate an unrecoverable error.
+    pub fn with_erasures<E>(syn: Polynomial<P>, erasures: E) -> Option<(usize, Self)>
+        where E: Iterator<Item = usize>
+    {
+        // Compute erasure locator polynomial Γ(x) and count the erasures f.
+        let (eras, nerase) = erasures.fold((Polynomial::<P>::unit_power(0), 0),
+            |(p, n), loc| (p * Polynomial::new([
+                Codeword::for_power(0),
+                Codeword::for_power(loc),
+            ].iter().cloned()), n + 1));
+
+        if nerase > P::syndromes() {
+            return None;
+        }
+
+        // Compute the modified syndromes T(x) = Γ(x)s(x) mod x^{2t} and keep the 2t - f
+        // that are unaffected by erasures.
+        let modified = Polynomial::<P>::new((eras * syn).iter()
+            .take(P::syndromes())
+            .skip(nerase)
+            .cloned());
+
         // Compute error locator polynomial Λ(x).
-        let loc = ErrorLocator::new(syn).build();
-        // If e = deg(Λ), then e ≤ t and e represents the number of detected errors.
-        let errors = loc.degree().expect("invalid error polynomial");
+        let errloc = ErrorLocator::with_syndromes(modified, P::syndromes() - nerase)
+            .build();
+
+        // If e = deg(Λ), then e represents the number of detected errors, which must
+        // satisfy 2e + f ≤ 2t.
+        let nerr = errloc.degree().expect("invalid error polynomial");
+
+        if 2 * nerr + nerase > P::syndromes() {
+            return None;
+        }
+
+        // Compute the combined locator Ψ(x) = Λ(x)Γ(x), which is just Λ(x) without
+        // erasures.
+        let loc = errloc * eras;
+        let errors = nerr + nerase;
 
-        // Find the roots a_i of Λ(x). These are buffered before processing them because
-        // if the number of found roots ends up unequal to deg(Λ(x)), all the roots are
+        // Find the roots a_i of Ψ(x). These are buffered before processing them because
+        // if the number of found roots ends up unequal to deg(Ψ(x)), all the roots are
         // invalid, and processing them before checking this can cause behavior like
         // divide-by-zero.
         let mut roots = Polynomial::<P>::default();
         let nroots = PolynomialRoots::new(loc).collect_slice_exhaust(&mut roots[..]);
 
-        // If the number of computed roots is different than deg(Λ), then the roots are
+        // If the number of computed roots is different than deg(Ψ), then the roots are
         // invalid and the codeword is unrecoverable [1, p3], [2, p48], [3, p22].
         if nroots != errors {
             return None;
@@ -301,7 +368,7 @@ impl<P: PolynomialCoefs> Errors<P> {
 
 /// Iterate over detected errors, yielding the location and pattern of each error.
 impl<P: PolynomialCoefs> Iterator for Errors<P> {
-    type Item = (usize, P25Codeword);
+    type Item = (usize, Codeword<P::Field>);
 
     fn next(&mut self) -> Option<Self::Item> {
         self.pos.next().map(|i| self.descs.for_root(self.roots[i]))
@@ -310,10 +377,9 @@ impl<P: PolynomialCoefs> Iterator for Errors<P> {
 
 #[cfg(test)]
 mod test {
-    use std;
     use collect_slice::CollectSlice;
     use super::*;
-    use coding::galois::{P25Codeword, PolynomialCoefs, Polynomial};
+    use coding::galois::{P25Codeword, Polynomial};
 
     impl_polynomial_coefs!(TestCoefs, 9);
     type TestPolynomial = Polynomial<TestCoefs>;
@@ -0,0 +1,154 @@
+//! Chase-II soft-decision decoding of the short binary block codes.
+//!
+//! A hard-decision decoder can only correct up to ⌊(d-1)/2⌋ bit errors, where d is the
+//! minimum distance of the code. Given a reliability for each received bit, the Chase-II
+//! algorithm inverts every combination of the p least reliable bits and hard-decodes
+//! each of these 2<sup>p</sup> test patterns. Of the resulting candidate codewords, the
+//! one that differs from the received word in the least reliable set of bits (the one
+//! with the smallest "analog weight") is chosen. This often recovers words with more
+//! errors than the hard-decision capacity, since noise tends to cause errors in
+//! unreliable bits.
+//!
+//! This algorithm is sourced from "A Class of Algorithms for Decoding Block Codes with
+//! Channel Measurement Information," Chase, 1972.
+
+use bits::SoftDibit;
+
+/// Maximum number of bits in a supported word.
+const MAX_BITS: usize = 24;
+
+/// Maximum number of least reliable bits to invert.
+const MAX_FLIPS: usize = 6;
+
+/// Try to decode the given word, where `rel` holds the reliability of each bit from MSB
+/// to LSB, by inverting each combination of the given number of least reliable bits.
+///
+/// The given hard-decision decoder maps a word to `Some((data, code))`, where `data` is
+/// the decoded data and `code` is the corresponding codeword, or `None` if the word is
+/// unrecoverable. Return `Some((data, code))` for the candidate with the smallest analog
+/// weight, or `None` if every test pattern was unrecoverable.
+pub fn decode<D, F>(word: u32, rel: &[f32], flips: usize, hard: F) -> Option<(D, u32)>
+    where F: Fn(u32) -> Option<(D, u32)>
+{
+    let bits = rel.len();
+
+    assert!(bits <= MAX_BITS && word >> bits == 0);
+    assert!(flips <= MAX_FLIPS && flips <= bits);
+
+    // Masks of the least reliable bits, from least to most reliable.
+    let mut least = [0u32; MAX_FLIPS];
+
+    for i in 0..flips {
+        let pos = (0..bits)
+            .filter(|&pos| !least[..i].contains(&mask(bits, pos)))
+            .fold(None, |min: Option<usize>, pos| match min {
+                Some(m) if rel[m] <= rel[pos] => Some(m),
+                _ => Some(pos),
+            })
+            .unwrap();
+
+        least[i] = mask(bits, pos);
+    }
+
+    (0..1 << flips).filter_map(|pat: u32| {
+        let test = least[..flips].iter().enumerate()
+            .filter(|&(i, _)| pat >> i & 1 == 1)
+            .fold(word, |w, (_, &m)| w ^ m);
+
+        hard(test)
+    }).fold(None, |best: Option<(D, u32, f32)>, (data, code)| {
+        let weight = analog_weight(code ^ word, rel);
+
+        match best {
+            Some(b) => if b.2 <= weight { Some(b) } else { Some((data, code, weight)) },
+            None => Some((data, code, weight)),
+        }
+    }).map(|(data, code, _)| (data, code))
+}
+
+/// Compute the mask of the bit at the given position, counted from the MSB of a word
+/// with the given number of bits.
+fn mask(bits: usize, pos: usize) -> u32 { 1 << (bits - pos - 1) }
+
+/// Sum the reliabilities of the bits set in the given error pattern.
+fn analog_weight(err: u32, rel: &[f32]) -> f32 {
+    rel.iter().enumerate()
+        .filter(|&(pos, _)| err & mask(rel.len(), pos) != 0)
+        .fold(0.0, |s, (_, &r)| s + r)
+}
+
+/// Collects the reliability of each bit in a received word from soft symbols.
+#[derive(Copy, Clone)]
+pub struct Reliabilities {
+    /// Reliability of each bit, from MSB to LSB.
+    rel: [f32; MAX_BITS],
+    /// Number of bits received.
+    len: usize,
+}
+
+impl Reliabilities {
+    /// Create a new `Reliabilities` with no received bits.
+    pub fn new() -> Reliabilities {
+        Reliabilities {
+            rel: [0.0; MAX_BITS],
+            len: 0,
+        }
+    }
+
+    /// Record the reliabilities of the two bits in the given symbol, high bit first.
+    pub fn feed(&mut self, s: SoftDibit) {
+        self.rel[self.len] = s.hi_reliability();
+        self.rel[self.len + 1] = s.lo_reliability();
+        self.len += 2;
+    }
+
+    /// Reliabilities of the bits received so far.
+    pub fn bits(&self) -> &[f32] { &self.rel[..self.len] }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::SoftDibit;
+
+    /// Decode the (6, 1, 6) repetition code, correcting up to 2 errors.
+    fn repetition(word: u32) -> Option<(bool, u32)> {
+        match word.count_ones() {
+            0...2 => Some((false, 0)),
+            4...6 => Some((true, 0b111111)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let rel = [1.0, 1.0, 1.0, 1.0, 1.0, 1.0];
+
+        assert_eq!(decode(0b110111, &rel[..], 0, repetition), Some((true, 0b111111)));
+        assert_eq!(decode(0b100100, &rel[..], 0, repetition), Some((false, 0)));
+        assert_eq!(decode(0b101100, &rel[..], 0, repetition), None);
+
+        // Errors in unreliable bits are corrected beyond the hard capacity.
+        let rel = [1.0, 0.1, 1.0, 0.2, 0.3, 1.0];
+        assert_eq!(decode(0b101001, &rel[..], 3, repetition), Some((true, 0b111111)));
+        assert_eq!(decode(0b010110, &rel[..], 3, repetition), Some((false, 0)));
+
+        // Unreliable bits that agree with each other outweigh a reliable bit.
+        let rel = [0.1, 0.1, 0.1, 1.0, 1.0, 1.0];
+        assert_eq!(decode(0b111000, &rel[..], 2, repetition), Some((false, 0)));
+        let rel = [1.0, 1.0, 1.0, 0.1, 0.1, 0.1];
+        assert_eq!(decode(0b111000, &rel[..], 2, repetition), Some((true, 0b111111)));
+    }
+
+    #[test]
+    fn test_reliabilities() {
+        let mut r = Reliabilities::new();
+        assert!(r.bits().is_empty());
+
+        r.feed(SoftDibit::new(3.0));
+        r.feed(SoftDibit::new(-1.75));
+        r.feed(SoftDibit::new(0.5));
+
+        assert_eq!(r.bits(), &[3.0, 1.0, 1.75, 0.25, 0.5, 1.5]);
+    }
+}
@@ -5,6 +5,9 @@
 //! was derived from *Error Control Coding*, Lin and Costello, 1983. The key information
 //! that this code is shortened from a (17, 8, 5) code came from "Standard APCO25 Physical
 //! Layer of the Radio Transmission Chain", Simon, 2014.
+//!
+//! Decoding looks up the corrections in tables built on first use by the cyclic decoder,
+//! which is kept as `decode_matrix` to check the tables against.
 
 /// Encode the given 8 data bits into a 16-bit codeword.
 pub fn encode(data: u8) -> u16 {
@@ -18,6 +21,16 @@ pub fn encode(data: u8) -> u16 {
 /// bits and `err` is the number of corrected bits. Otherwise, return `None` to indicate
 /// an unrecoverable error.
 pub fn decode(word: u16) -> Option<(u8, usize)> {
+    let t = tables();
+    let s = t.syn_hi[word as usize >> 8] ^ t.syn_lo[word as usize & 0xFF];
+
+    t.fix[s as usize].map(|(a, err)| ((word >> 8) as u8 ^ a, err as usize))
+}
+
+/// Try to decode the given 16-bit word with the cyclic decoder, without lookup tables.
+///
+/// This gives the same results as `decode` and is used to build and check its tables.
+pub fn decode_matrix(word: u16) -> Option<(u8, usize)> {
     // Go through a full cycle of the codeword, so the data bits end up in their original
     // position. The word is expanded to 32 bits so it can be treated as the 17-bit word
     // the shortened code is derived from.
@@ -98,6 +111,54 @@ fn rotate_17(word: u32) -> u32 {
     word >> 1 | lsb << 16
 }
 
+/// Lookup tables for decoding.
+struct Tables {
+    /// Maps the high byte of a word to its term in the syndrome.
+    syn_hi: [u8; 256],
+    /// Maps the low byte of a word to its term in the syndrome.
+    syn_lo: [u8; 256],
+    /// Maps each syndrome to the error pattern in the data bits and the number of
+    /// corrected bits, or `None` if the syndrome is unrecoverable.
+    fix: [Option<(u8, u8)>; 256],
+}
+
+impl Tables {
+    /// Build the tables with the cyclic decoder.
+    fn new() -> Tables {
+        let mut t = Tables {
+            syn_hi: [0; 256],
+            syn_lo: [0; 256],
+            fix: [None; 256],
+        };
+
+        for i in 0..256 {
+            t.syn_hi[i] = matrix_mul!((i as u32) << 8, PAR, u8);
+            t.syn_lo[i] = matrix_mul!(i as u32, PAR, u8);
+        }
+
+        // Since the code is linear, the corrections made by the decoder depend only on
+        // the syndrome, so decode one word with each syndrome.
+        let mut seen = [false; 256];
+
+        for w in 0..1u32 << 16 {
+            let s = t.syn_hi[w as usize >> 8] ^ t.syn_lo[w as usize & 0xFF];
+
+            if seen[s as usize] {
+                continue;
+            }
+
+            seen[s as usize] = true;
+            t.fix[s as usize] = decode_matrix(w as u16).map(|(data, err)| {
+                (data ^ (w >> 8) as u8, err as u8)
+            });
+        }
+
+        t
+    }
+}
+
+lazy_table!(tables: Tables = Tables::new());
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -138,6 +199,13 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_tables() {
+        for w in 0..1u32 << 16 {
+            assert_eq!(decode(w as u16), decode_matrix(w as u16));
+        }
+    }
+
     #[test]
     fn test_rotate_17() {
         assert_eq!(rotate_17(0b00000000000000000), 0b00000000000000000);
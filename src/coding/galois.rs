@@ -1,4 +1,49 @@
 //! Galois field arithmetic for codewords and polynomials.
+//!
+//! The P25 codes use the GF(2<sup>6</sup>) `P25Field`, which is backed by lookup tables.
+//! Other GF(2<sup>r</sup>) fields, such as those used by DMR and NXDN, can be created
+//! with `PrimitiveField` from their width and primitive polynomial.
+//!
+//! The `impl_field_params` and `impl_polynomial_coefs` macros define the parameters of
+//! such a field and the polynomial storage of a code over it, which can then be used
+//! with the generic Reed-Solomon codec. For example, a (15, 11, 5) code over
+//! GF(2<sup>8</sup>):
+//!
+//! ```
+//! #![feature(const_fn)]
+//!
+//! #[macro_use]
+//! extern crate p25;
+//!
+//! use p25::coding::galois::{Codeword, PrimitiveField};
+//! use p25::coding::reed_solomon::generic;
+//!
+//! impl_field_params!(pub ByteParams, 8, 0b100011101);
+//! impl_polynomial_coefs!(pub ByteCoefs: PrimitiveField<ByteParams>, 5, 15);
+//!
+//! type ByteCodeword = Codeword<PrimitiveField<ByteParams>>;
+//!
+//! fn main() {
+//!     let mut word = [ByteCodeword::default(); 15];
+//!
+//!     for (i, w) in word[..11].iter_mut().enumerate() {
+//!         *w = ByteCodeword::new(i as u8 * 7 + 1);
+//!     }
+//!
+//!     {
+//!         let (data, parity) = word.split_at_mut(11);
+//!         generic::encode::<ByteCoefs>(data, parity);
+//!     }
+//!
+//!     let sent = word;
+//!
+//!     word[3] = ByteCodeword::new(0xFF);
+//!     word[9] = ByteCodeword::new(0);
+//!
+//!     assert_eq!(generic::decode::<ByteCoefs>(&mut word[..], &[]), Some(2));
+//!     assert_eq!(word, sent);
+//! }
+//! ```
 
 use std;
 
@@ -159,6 +204,112 @@ impl GaloisField for P25Field {
     }
 }
 
+/// Parameters of a GF(2<sup>r</sup>) field generated by a primitive polynomial.
+pub trait FieldParams: Copy + Clone {
+    /// Width r of each codeword in bits, which must be at most 8.
+    fn width() -> usize;
+    /// Primitive polynomial h(x) of degree r, with the coefficient of x<sup>i</sup> at
+    /// bit i.
+    fn poly() -> u16;
+    /// Lookup tables for the field, which should be built once from `width` and `poly`.
+    fn tables() -> &'static FieldTables;
+}
+
+/// Lookup tables of the codewords and powers of a GF(2<sup>r</sup>) field.
+pub struct FieldTables {
+    /// Codeword α<sup>i</sup> at index i.
+    codewords: [u8; 255],
+    /// Power i of the codeword α<sup>i</sup> at the zero-based index α<sup>i</sup> - 1.
+    powers: [usize; 255],
+}
+
+impl FieldTables {
+    /// Build the tables for the field of the given width characterized by the given
+    /// primitive polynomial, by repeatedly multiplying by α.
+    pub fn new(width: usize, poly: u16) -> FieldTables {
+        assert!(width >= 1 && width <= 8);
+        assert!(poly >> width == 1);
+
+        let size = (1 << width) - 1;
+        let mut t = FieldTables {
+            codewords: [0; 255],
+            powers: [0; 255],
+        };
+
+        let mut cur = 1u16;
+
+        for pow in 0..size {
+            // A repeated codeword means the polynomial isn't primitive.
+            assert!(pow == 0 || cur != 1, "invalid primitive polynomial");
+
+            t.codewords[pow] = cur as u8;
+            t.powers[cur as usize - 1] = pow;
+
+            cur <<= 1;
+
+            if cur >> width & 1 == 1 {
+                cur ^= poly;
+            }
+        }
+
+        assert!(cur == 1, "invalid primitive polynomial");
+
+        t
+    }
+}
+
+/// Define a unit struct with the given name that implements `FieldParams` for the field
+/// of the given width and primitive polynomial, with lookup tables built on first use.
+/// The struct is public if the name is preceded by `pub`.
+#[macro_export]
+macro_rules! impl_field_params {
+    (pub $name:ident, $width:expr, $poly:expr) => {
+        impl_field_params!(@impl [pub] $name, $width, $poly);
+    };
+    ($name:ident, $width:expr, $poly:expr) => {
+        impl_field_params!(@impl [] $name, $width, $poly);
+    };
+    (@impl [$($vis:tt)*] $name:ident, $width:expr, $poly:expr) => {
+        #[derive(Copy, Clone)]
+        $($vis)* struct $name;
+
+        impl $crate::coding::galois::FieldParams for $name {
+            fn width() -> usize { $width }
+            fn poly() -> u16 { $poly }
+
+            fn tables() -> &'static $crate::coding::galois::FieldTables {
+                lazy_table!(tables: $crate::coding::galois::FieldTables =
+                            $crate::coding::galois::FieldTables::new($width, $poly));
+                tables()
+            }
+        }
+    };
+}
+
+/// GF(2<sup>r</sup>) field characterized by the primitive polynomial in the given
+/// parameters.
+///
+/// Like `P25Field`, each codeword α<sup>i</sup> and power i is found with lookup tables,
+/// which are provided by the parameters.
+#[derive(Copy, Clone)]
+pub struct PrimitiveField<P: FieldParams>(std::marker::PhantomData<P>);
+
+impl<P: FieldParams> GaloisField for PrimitiveField<P> {
+    fn size() -> usize { (1 << P::width()) - 1 }
+    fn valid_codeword(bits: u8) -> bool { (bits as usize) >> P::width() == 0 }
+
+    fn codeword(pow: usize) -> u8 {
+        assert!(pow < Self::size());
+        P::tables().codewords[pow]
+    }
+
+    fn power(codeword: usize) -> usize {
+        // The codeword is given as a zero-based index.
+        assert!(codeword < Self::size());
+        P::tables().powers[codeword]
+    }
+}
+
 /// A GF(2<sup>r</sup>) Galois field.
 pub trait GaloisField {
     /// Number of unique codewords in the field: 2<sup>r</sup> - 1.
@@ -316,25 +467,28 @@ impl<F: GaloisField> std::fmt::Debug for Codeword<F> {
 
 /// Coefficient storage for a bounded-degree Galois polynomial of a particular code.
 pub trait PolynomialCoefs: Default + Copy + Clone +
-    std::ops::Deref<Target = [P25Codeword]> + std::ops::DerefMut
+    std::ops::Deref<Target = [Codeword<<Self as PolynomialCoefs>::Field>]> +
+    std::ops::DerefMut
 {
+    /// Field of the coefficients.
+    type Field: GaloisField + Copy;
+
     /// The minimum Hamming distance, d, in (n,k,d).
     fn distance() -> usize;
 
     /// Maximum number of correctable errors: t.
     fn errors() -> usize {
-        // Since d is odd, d = 2t+1 ⇒ t = (d-1)/2 = floor(d / 2). Formula taken from [1,
-        // p135-137].
-        Self::distance() / 2
+        // Since d ≥ 2t+1 ⇒ t = floor((d-1)/2). Formula taken from [1, p135-137].
+        (Self::distance() - 1) / 2
     }
 
-    /// Number of syndromes: 2t.
-    fn syndromes() -> usize { 2 * Self::errors() }
+    /// Number of syndromes: d - 1, which is 2t when d is odd.
+    fn syndromes() -> usize { Self::distance() - 1 }
 
     /// Verify the implementer is well-formed.
     fn validate(&self) {
-        // Distance must be odd.
-        assert!(Self::distance() % 2 == 1);
+        // Distance must allow for at least one syndrome.
+        assert!(Self::distance() >= 2);
         // Storage must at least be able to hold the polynomials used in the
         // Berlekamp-Massey algorithm.
         assert!(self.len() >= Self::syndromes() + 1);
@@ -343,45 +497,67 @@ pub trait PolynomialCoefs: Default + Copy + Clone +
 
 /// Create a coefficient storage buffer for the code of given distance. In the first form,
 /// the polynomial is large enough to store the Berlekamp-Massey decoding polynomials. In
-/// the second form, the polynomial has the given size.
+/// the second form, the polynomial has the given size. Both of these use P25's
+/// GF(2<sup>6</sup>) field. In the third form, the polynomial has the given size and
+/// field. In each form, the struct is public if the name is preceded by `pub`.
+#[macro_export]
 macro_rules! impl_polynomial_coefs {
+    (pub $name:ident: $field:ty, $dist:expr, $len:expr) => {
+        impl_polynomial_coefs!(@impl [pub] $name, $field, $dist, $len);
+    };
+    (pub $name:ident, $dist:expr) => {
+        impl_polynomial_coefs!(pub $name, $dist, $dist + 1);
+    };
+    (pub $name:ident, $dist:expr, $len:expr) => {
+        impl_polynomial_coefs!(@impl [pub] $name, $crate::coding::galois::P25Field,
+                               $dist, $len);
+    };
+    ($name:ident: $field:ty, $dist:expr, $len:expr) => {
+        impl_polynomial_coefs!(@impl [] $name, $field, $dist, $len);
+    };
     ($name:ident, $dist:expr) => {
         impl_polynomial_coefs!($name, $dist, $dist + 1);
     };
     ($name:ident, $dist:expr, $len:expr) => {
+        impl_polynomial_coefs!(@impl [] $name, $crate::coding::galois::P25Field,
+                               $dist, $len);
+    };
+    (@impl [$($vis:tt)*] $name:ident, $field:ty, $dist:expr, $len:expr) => {
         #[derive(Copy)]
-        struct $name([P25Codeword; $len]);
+        $($vis)* struct $name([$crate::coding::galois::Codeword<$field>; $len]);
+
+        impl $crate::coding::galois::PolynomialCoefs for $name {
+            type Field = $field;
 
-        impl PolynomialCoefs for $name {
             fn distance() -> usize { $dist }
         }
 
         impl Default for $name {
             fn default() -> Self {
-                $name([P25Codeword::default(); $len])
+                $name([$crate::coding::galois::Codeword::default(); $len])
             }
         }
 
         impl Clone for $name {
             fn clone(&self) -> Self {
-                let mut coefs = [P25Codeword::default(); $len];
+                let mut coefs = [$crate::coding::galois::Codeword::default(); $len];
                 coefs.copy_from_slice(&self.0[..]);
                 $name(coefs)
             }
         }
 
-        impl std::ops::Deref for $name {
-            type Target = [P25Codeword];
+        impl ::std::ops::Deref for $name {
+            type Target = [$crate::coding::galois::Codeword<$field>];
             fn deref(&self) -> &Self::Target { &self.0[..] }
         }
 
-        impl std::ops::DerefMut for $name {
+        impl ::std::ops::DerefMut for $name {
             fn deref_mut(&mut self) -> &mut Self::Target { &mut self.0[..] }
         }
     };
 }
 
-/// Polynomial with P25's GF(2<sup>6</sup>) codewords as coefficients.
+/// Polynomial with Galois field codewords as coefficients.
 #[derive(Copy, Clone)]
 pub struct Polynomial<P: PolynomialCoefs> {
     /// Coefficients of the polynomial. The maximum degree span in the algorithm is [0,
@@ -398,7 +574,7 @@ impl<P: PolynomialCoefs> Polynomial<P> {
     ///
     /// The resulting polynomial has the form p(x) = c<sub>0</sub> + c<sub>1</sub>x + ···
     /// + c<sub>k</sub>x<sup>k</sup>.
-    pub fn new<T: Iterator<Item = P25Codeword>>(mut init: T) -> Self {
+    pub fn new<T: Iterator<Item = Codeword<P::Field>>>(mut init: T) -> Self {
         // Start with all zero coefficients and add in the given ones.
         let mut coefs = P::default();
         init.collect_slice_exhaust(&mut coefs[..]);
@@ -423,7 +599,7 @@ impl<P: PolynomialCoefs> Polynomial<P> {
     }
 
     /// Retrieve the degree-0 coefficient, c<sub>0</sub>.
-    pub fn constant(&self) -> P25Codeword {
+    pub fn constant(&self) -> Codeword<P::Field> {
         self.coefs[self.start]
     }
 
@@ -449,32 +625,32 @@ impl<P: PolynomialCoefs> Polynomial<P> {
     pub fn shift(mut self) -> Polynomial<P> {
         assert!(self.constant().zero());
 
-        self.coefs[self.start] = P25Codeword::default();
+        self.coefs[self.start] = Codeword::default();
         self.start += 1;
         self
     }
 
     /// Retrieve the coefficient at the given absolute index into the storage buffer, or 0
     /// if the index is out of bounds.
-    fn get(&self, idx: usize) -> P25Codeword {
+    fn get(&self, idx: usize) -> Codeword<P::Field> {
         match self.coefs.get(idx) {
             Some(&c) => c,
-            None => P25Codeword::default(),
+            None => Codeword::default(),
         }
     }
 
     /// Retrieve the coefficient c<sub>i</sub> associated with the x<sup>i</sup> term.
     ///
     /// If i > deg(p(x)), 0 is returned.
-    pub fn coef(&self, i: usize) -> P25Codeword {
+    pub fn coef(&self, i: usize) -> Codeword<P::Field> {
         self.get(self.start + i)
     }
 
     /// Evaluate p(x), substituting in the given x.
-    pub fn eval(&self, x: P25Codeword) -> P25Codeword {
+    pub fn eval(&self, x: Codeword<P::Field>) -> Codeword<P::Field> {
         // This uses Horner's method which, unlike the naive method, doesn't require a
         // call to `pow()` at each term.
-        self.iter().rev().fold(P25Codeword::default(), |s, &coef| s * x + coef)
+        self.iter().rev().fold(Codeword::default(), |s, &coef| s * x + coef)
     }
 
     /// Truncate the polynomial so that deg(p(x)) ≤ d, where d is the given degree.
@@ -482,7 +658,7 @@ impl<P: PolynomialCoefs> Polynomial<P> {
     /// This is a O(n) operation.
     pub fn truncate(mut self, deg: usize) -> Polynomial<P> {
         for i in (self.start + deg + 1)..self.coefs.len() {
-            self.coefs[i] = P25Codeword::default();
+            self.coefs[i] = Codeword::default();
         }
 
         self
@@ -494,7 +670,7 @@ impl<P: PolynomialCoefs> Polynomial<P> {
             self.coefs[i] = if (i - self.start) % 2 == 0 {
                 self.get(i + 1)
             } else {
-                P25Codeword::default()
+                Codeword::default()
             };
         }
 
@@ -512,7 +688,7 @@ impl<P: PolynomialCoefs> Default for Polynomial<P> {
 /// Provides a slice of coefficients starting at the degree-0 term, [c<sub>0</sub>,
 /// c<sub>1</sub>, ...].
 impl<P: PolynomialCoefs> std::ops::Deref for Polynomial<P> {
-    type Target = [P25Codeword];
+    type Target = [Codeword<P::Field>];
     fn deref(&self) -> &Self::Target { &self.coefs[self.start..] }
 }
 
@@ -538,10 +714,10 @@ impl<P: PolynomialCoefs> std::ops::Add for Polynomial<P> {
 }
 
 /// Scale polynomial by a codeword.
-impl<P: PolynomialCoefs> std::ops::Mul<P25Codeword> for Polynomial<P> {
+impl<P: PolynomialCoefs> std::ops::Mul<Codeword<P::Field>> for Polynomial<P> {
     type Output = Polynomial<P>;
 
-    fn mul(mut self, rhs: P25Codeword) -> Self::Output {
+    fn mul(mut self, rhs: Codeword<P::Field>) -> Self::Output {
         for coef in self.coefs.iter_mut() {
             *coef = *coef * rhs;
         }
@@ -582,7 +758,6 @@ impl<P: PolynomialCoefs> std::fmt::Debug for Polynomial<P> {
 
 #[cfg(test)]
 mod test {
-    use std;
     use super::*;
 
     impl_polynomial_coefs!(TestCoefs, 23);
@@ -597,6 +772,49 @@ mod test {
         assert_eq!(TestCoefs::syndromes(), 22);
     }
 
+    impl_field_params!(P25Params, 6, 0b1000011);
+    impl_field_params!(ByteParams, 8, 0b100011101);
+
+    type ByteCodeword = Codeword<PrimitiveField<ByteParams>>;
+
+    #[test]
+    fn test_primitive_field() {
+        type F = PrimitiveField<P25Params>;
+
+        assert_eq!(F::size(), 63);
+        assert!(F::valid_codeword(0b111111));
+        assert!(!F::valid_codeword(0b1000000));
+
+        for pow in 0..63 {
+            assert_eq!(F::codeword(pow), P25Field::codeword(pow));
+            assert_eq!(F::power(pow), P25Field::power(pow));
+        }
+
+        type B = PrimitiveField<ByteParams>;
+
+        assert_eq!(B::size(), 255);
+        assert_eq!(B::codeword(8), 0b00011101);
+        assert_eq!(B::codeword(254), 0b10001110);
+
+        let a = ByteCodeword::new(0x53);
+        let b = ByteCodeword::new(0xCA);
+        assert_eq!(a * b, 0x8F);
+        assert_eq!((a * b) / b, 0x53);
+        assert_eq!(a * a.invert(), 1);
+
+        for c in 1..256 {
+            let c = ByteCodeword::new(c as u8);
+            assert_eq!(ByteCodeword::for_power(c.power().unwrap()), c);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_nonprimitive_field() {
+        // x^4 + x^3 + x^2 + x + 1 is irreducible but α has order 5.
+        FieldTables::new(4, 0b11111);
+    }
+
     #[test]
     fn test_for_power() {
         assert!(P25Codeword::for_power(0) == 0b000001);
@@ -2,10 +2,16 @@
 //! 8) shortened Golay codes described by P25.
 //!
 //! These algorithms are sourced from *Coding Theory and Cryptography: The Essentials*,
-//! Hankerson, Hoffman, et al, 2000.
+//! Hankerson, Hoffman, et al, 2000. Each code can also be soft-decoded with the
+//! reliability of each received bit using the Chase-II algorithm.
+//!
+//! Decoding looks up the syndrome and error pattern in tables built on first use by the
+//! matrix decoder, which is kept as `decode_matrix` to check the tables against.
 
 /// Encoding and decoding of the (23, 12, 7) code.
 pub mod standard {
+    use coding::chase;
+
     /// Encode the given 12 data bits into a 23-bit codeword.
     pub fn encode(data: u16) -> u32 {
         assert!(data >> 12 == 0);
@@ -21,6 +27,25 @@ pub mod standard {
     pub fn decode(word: u32) -> Option<(u16, usize)> {
         assert!(word >> 23 == 0);
 
+        // Create a 24-bit codeword with odd weight.
+        let expanded = word << 1 | (word.count_ones() + 1) % 2;
+        let s = super::syndrome_lookup(expanded);
+
+        if s == *super::CORE.last().unwrap() {
+            Some((super::word_data(expanded), 0))
+        } else {
+            super::decode_syndrome_lookup(super::word_data(expanded), s)
+        }
+    }
+
+    /// Try to decode the given 23-bit word with the matrix decoder, without lookup
+    /// tables.
+    ///
+    /// This gives the same results as `decode` and is used to build and check its
+    /// tables.
+    pub fn decode_matrix(word: u32) -> Option<(u16, usize)> {
+        assert!(word >> 23 == 0);
+
         // Create a 24-bit codeword with odd weight.
         let expanded = if word.count_ones() % 2 == 0 {
             word << 1 | 1
@@ -37,17 +62,33 @@ pub mod standard {
             super::decode_syndrome(data, s)
         }
     }
+
+    /// Try to decode the given 23-bit word to the nearest codeword, given the
+    /// reliability of each bit from MSB to LSB.
+    ///
+    /// The return value has the same meaning as for `decode`.
+    pub fn decode_soft(word: u32, rel: &[f32]) -> Option<(u16, usize)> {
+        assert!(rel.len() == 23);
+
+        chase::decode(word, rel, super::CHASE_FLIPS, |w| {
+            decode(w).map(|(data, _)| (data, encode(data)))
+        }).map(|(data, _)| {
+            (data, (data ^ (word >> 11) as u16).count_ones() as usize)
+        })
+    }
 }
 
 /// Encoding and decoding of the (24, 12, 8) code.
 pub mod extended {
+    use coding::chase;
+
     /// Encode the given 12 data bits into a 24-bit codeword.
     pub fn encode(data: u16) -> u32 {
         assert!(data >> 12 == 0);
         matrix_mul_systematic!(data, super::CORE, u32)
     }
 
-    /// Try to decode the given  24-bit word to the nearest codeword, correcting up to 3
+    /// Try to decode the given 24-bit word to the nearest codeword, correcting up to 3
     /// errors.
     ///
     /// If decoding was successful, return `Some((data, err))`, where `data` is the 12
@@ -55,12 +96,39 @@ pub mod extended {
     /// return `None` to indicate an unrecoverable error.
     pub fn decode(word: u32) -> Option<(u16, usize)> {
         assert!(word >> 24 == 0);
+
+        let s = super::syndrome_lookup(word);
+        super::decode_syndrome_lookup(super::word_data(word), s)
+    }
+
+    /// Try to decode the given 24-bit word with the matrix decoder, without lookup
+    /// tables.
+    ///
+    /// This gives the same results as `decode` and is used to build and check its
+    /// tables.
+    pub fn decode_matrix(word: u32) -> Option<(u16, usize)> {
+        assert!(word >> 24 == 0);
         super::decode_syndrome(super::word_data(word), super::syndrome_24(word))
     }
+
+    /// Try to decode the given 24-bit word to the nearest codeword, given the
+    /// reliability of each bit from MSB to LSB.
+    ///
+    /// The return value has the same meaning as for `decode`.
+    pub fn decode_soft(word: u32, rel: &[f32]) -> Option<(u16, usize)> {
+        assert!(rel.len() == 24);
+
+        chase::decode(word, rel, super::CHASE_FLIPS, |w| {
+            decode(w).map(|(data, _)| (data, encode(data)))
+        }).map(|(data, _)| {
+            (data, (data ^ super::word_data(word)).count_ones() as usize)
+        })
+    }
 }
 
 /// Encoding and decoding of the (18, 6, 8) code.
 pub mod shortened {
+    use coding::chase;
     use super::extended;
 
     /// Encode the given 6 data bits to an 18-bit codeword.
@@ -87,8 +155,43 @@ pub mod shortened {
             None => None,
         }
     }
+
+    /// Try to decode the given 18-bit word with the matrix decoder, without lookup
+    /// tables.
+    ///
+    /// This gives the same results as `decode` and is used to build and check its
+    /// tables.
+    pub fn decode_matrix(word: u32) -> Option<(u8, usize)> {
+        assert!(word >> 18 == 0);
+
+        match extended::decode_matrix(word) {
+            Some((data, err)) => if data >> 6 != 0 {
+                None
+            } else {
+                Some((data as u8, err))
+            },
+            None => None,
+        }
+    }
+
+    /// Try to decode the given 18-bit word to the nearest codeword, given the
+    /// reliability of each bit from MSB to LSB.
+    ///
+    /// The return value has the same meaning as for `decode`.
+    pub fn decode_soft(word: u32, rel: &[f32]) -> Option<(u8, usize)> {
+        assert!(rel.len() == 18);
+
+        chase::decode(word, rel, super::CHASE_FLIPS, |w| {
+            decode(w).map(|(data, _)| (data, encode(data)))
+        }).map(|(data, _)| {
+            (data, (data ^ super::word_data(word) as u8).count_ones() as usize)
+        })
+    }
 }
 
+/// Number of least reliable bits inverted by the Chase-II decoder.
+const CHASE_FLIPS: usize = 4;
+
 /// The core matrix used to create the generator and syndrome matrices. It's usually
 /// cyclic, but not in the case of P25.
 const CORE: [u16; 12] = [
@@ -179,11 +282,23 @@ fn decode_parity(s: u16, matrix: &[u16; 12]) -> Option<(u16, u16)> {
     None
 }
 
+/// Try to correct errors in the given data bits using the given first-level syndrome
+/// and the lookup tables.
+fn decode_syndrome_lookup(data: u16, s: u16) -> Option<(u16, usize)> {
+    tables().fix[s as usize].map(|a| (data ^ a, a.count_ones() as usize))
+}
+
 /// Calculate the first-level syndrome.
 fn syndrome_24(word: u32) -> u16 {
     matrix_mul!(word, PAR, u16)
 }
 
+/// Calculate the first-level syndrome using the lookup tables.
+fn syndrome_lookup(word: u32) -> u16 {
+    let t = tables();
+    t.syn_data[word as usize >> 12] ^ t.syn_par[word as usize & 0xFFF]
+}
+
 /// Calculate the second-level syndrome.
 fn syndrome_12(syn: u16) -> u16 {
     matrix_mul!(syn, CORE, u16)
@@ -194,10 +309,110 @@ fn word_data(word: u32) -> u16 {
     (word >> 12) as u16
 }
 
+/// Lookup tables for decoding the extended code.
+struct Tables {
+    /// Maps the 12 data bits of a word to their term in the first-level syndrome.
+    syn_data: [u16; 1 << 12],
+    /// Maps the 12 parity bits of a word to their term in the first-level syndrome.
+    syn_par: [u16; 1 << 12],
+    /// Maps each first-level syndrome to the error pattern in the data bits, or `None`
+    /// if the syndrome is unrecoverable.
+    fix: [Option<u16>; 1 << 12],
+}
+
+impl Tables {
+    /// Build the tables with the matrix decoder.
+    fn new() -> Tables {
+        let mut t = Tables {
+            syn_data: [0; 1 << 12],
+            syn_par: [0; 1 << 12],
+            fix: [None; 1 << 12],
+        };
+
+        for i in 0..1 << 12 {
+            t.syn_data[i] = syndrome_24((i as u32) << 12);
+            t.syn_par[i] = syndrome_24(i as u32);
+            t.fix[i] = decode_syndrome(0, i as u16).map(|(a, _)| a);
+        }
+
+        t
+    }
+}
+
+lazy_table!(tables: Tables = Tables::new());
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    /// Create bit reliabilities with the given positions from the MSB unreliable.
+    fn unreliable(bits: usize, pos: &[usize]) -> Vec<f32> {
+        (0..bits).map(|i| if pos.contains(&i) { 0.1 } else { 1.0 }).collect()
+    }
+
+    /// Invert the bits at the given positions from the MSB.
+    fn flip(word: u32, bits: usize, pos: &[usize]) -> u32 {
+        pos.iter().fold(word, |w, &i| w ^ 1 << (bits - i - 1))
+    }
+
+    #[test]
+    fn test_soft() {
+        let w = 0xABC;
+        let e = standard::encode(w);
+
+        assert_eq!(standard::decode_soft(e, &unreliable(23, &[])[..]), Some((w, 0)));
+
+        let pos = [0, 5, 9, 17];
+        let rx = flip(e, 23, &pos[..]);
+        assert!(standard::decode(rx).unwrap().0 != w);
+        assert_eq!(standard::decode_soft(rx, &unreliable(23, &pos[..])[..]),
+                   Some((w, 3)));
+
+        let e = extended::encode(w);
+        let pos = [1, 2, 20, 23];
+        let rx = flip(e, 24, &pos[..]);
+        assert!(extended::decode(rx).map(|(d, _)| d) != Some(w));
+        assert_eq!(extended::decode_soft(rx, &unreliable(24, &pos[..])[..]),
+                   Some((w, 2)));
+
+        // Errors beyond capacity in reliable bits aren't corrected.
+        let rx = flip(e, 24, &[3, 4, 5, 6]);
+        assert!(extended::decode_soft(rx, &unreliable(24, &pos[..])[..])
+                .map(|(d, _)| d) != Some(w));
+
+        let w = 0b101010;
+        let e = shortened::encode(w);
+        let pos = [0, 7, 8, 11, 15];
+        let rx = flip(e, 18, &pos[..]);
+        assert!(shortened::decode(rx).map(|(d, _)| d) != Some(w));
+        assert_eq!(shortened::decode_soft(rx, &unreliable(18, &pos[..])[..]),
+                   Some((w, 1)));
+    }
+
+    #[test]
+    fn test_tables() {
+        // Every pattern of up to 4 errors.
+        let errs = (0..24).flat_map(|a| (a..24).flat_map(move |b| {
+            (b..24).flat_map(move |c| (c..24).map(move |d| {
+                1 << a | 1 << b | 1 << c | 1 << d
+            }))
+        }));
+
+        for (i, e) in errs.enumerate() {
+            let w = extended::encode(i as u16 & 0xFFF) ^ e;
+            assert_eq!(extended::decode(w), extended::decode_matrix(w));
+            assert_eq!(standard::decode(w >> 1), standard::decode_matrix(w >> 1));
+            assert_eq!(shortened::decode(w & 0x3FFFF),
+                       shortened::decode_matrix(w & 0x3FFFF));
+        }
+
+        for w in (0..4093).map(|i| i * 4099) {
+            assert_eq!(extended::decode(w), extended::decode_matrix(w));
+            assert_eq!(standard::decode(w >> 1), standard::decode_matrix(w >> 1));
+            assert_eq!(shortened::decode(w >> 6), shortened::decode_matrix(w >> 6));
+        }
+    }
+
     #[test]
     fn test_shortened() {
         let w = 0b101010;
@@ -2,10 +2,16 @@
 //! codes described by P25.
 //!
 //! Both codes can correct up to 1 error. These algorithms are sourced from *Coding Theory
-//! and Cryptography: The Essentials*, Hankerson, Hoffman, et al, 2000.
+//! and Cryptography: The Essentials*, Hankerson, Hoffman, et al, 2000. Each code can
+//! also be soft-decoded with the reliability of each received bit using the Chase-II
+//! algorithm, which can correct more errors when they fall in unreliable bits.
+//!
+//! Decoding calculates the syndrome with lookup tables built on first use, and the
+//! matrix calculation is kept as `decode_matrix` to check the tables against.
 
 /// Encoding and decoding of the (15, 11, 3) code.
 pub mod standard {
+    use coding::chase;
     use super::HammingDecoder;
 
     /// Encode the given 11 bits of data into a 15-bit codeword.
@@ -25,6 +31,27 @@ pub mod standard {
         StandardHamming::decode(word)
     }
 
+    /// Try to decode the given 15-bit word with the matrix syndrome calculation,
+    /// without lookup tables.
+    ///
+    /// This gives the same results as `decode` and is used to check its tables.
+    pub fn decode_matrix(word: u16) -> Option<(u16, usize)> {
+        assert!(word >> 15 == 0);
+        StandardHamming::decode_matrix(word)
+    }
+
+    /// Try to decode the given 15-bit word to the nearest codeword, given the
+    /// reliability of each bit from MSB to LSB.
+    ///
+    /// The return value has the same meaning as for `decode`.
+    pub fn decode_soft(word: u16, rel: &[f32]) -> Option<(u16, usize)> {
+        assert!(rel.len() == 15);
+
+        chase::decode(word as u32, rel, super::CHASE_FLIPS, |w| {
+            decode(w as u16).map(|(data, _)| (data, encode(data) as u32))
+        }).map(|(data, code)| (data, (code ^ word as u32).count_ones() as usize))
+    }
+
     /// Generator patterns for 4 parity bits.
     const GEN: [u16; 4] = [
         0b11111110000,
@@ -69,11 +96,15 @@ pub mod standard {
         fn data(word: u16) -> u16 { word >> 4 }
         fn par() -> [u16; 4] { PAR }
         fn locs() -> [u16; 16] { LOCATIONS }
+        fn syndromes() -> &'static super::Syndromes { tables() }
     }
+
+    lazy_table!(tables: super::Syndromes = super::Syndromes::new(PAR));
 }
 
 /// Encoding and decoding of the (10, 6, 3) code.
 pub mod shortened {
+    use coding::chase;
     use super::HammingDecoder;
 
     /// Encode the given 6 data bits into a 10-bit codeword.
@@ -93,6 +124,27 @@ pub mod shortened {
         ShortHamming::decode(word)
     }
 
+    /// Try to decode the given 10-bit word with the matrix syndrome calculation,
+    /// without lookup tables.
+    ///
+    /// This gives the same results as `decode` and is used to check its tables.
+    pub fn decode_matrix(word: u16) -> Option<(u8, usize)> {
+        assert!(word >> 10 == 0);
+        ShortHamming::decode_matrix(word)
+    }
+
+    /// Try to decode the given 10-bit word to the nearest codeword, given the
+    /// reliability of each bit from MSB to LSB.
+    ///
+    /// The return value has the same meaning as for `decode`.
+    pub fn decode_soft(word: u16, rel: &[f32]) -> Option<(u8, usize)> {
+        assert!(rel.len() == 10);
+
+        chase::decode(word as u32, rel, super::CHASE_FLIPS, |w| {
+            decode(w as u16).map(|(data, _)| (data, encode(data) as u32))
+        }).map(|(data, code)| (data, (code ^ word as u32).count_ones() as usize))
+    }
+
     const GEN: [u8; 4] = [
         0b111001,
         0b110101,
@@ -134,9 +186,15 @@ pub mod shortened {
         fn data(word: u16) -> u8 { (word >> 4) as u8 }
         fn par() -> [u16; 4] { PAR }
         fn locs() -> [u16; 16] { LOCATIONS }
+        fn syndromes() -> &'static super::Syndromes { tables() }
     }
+
+    lazy_table!(tables: super::Syndromes = super::Syndromes::new(PAR));
 }
 
+/// Number of least reliable bits inverted by the Chase-II decoder.
+const CHASE_FLIPS: usize = 3;
+
 /// Defines code-specific decoding functions.
 trait HammingDecoder {
     /// The type of the data bit output.
@@ -151,11 +209,22 @@ trait HammingDecoder {
     /// Return the syndrome-error location map.
     fn locs() -> [u16; 16];
 
-    /// Use the current decoder to decode the given word.
+    /// Return the syndrome lookup tables.
+    fn syndromes() -> &'static Syndromes;
+
+    /// Use the current decoder to decode the given word with the syndrome lookup tables.
     fn decode(word: u16) -> Option<(Self::Data, usize)> {
+        Self::correct(word, Self::syndromes().get(word))
+    }
+
+    /// Use the current decoder to decode the given word.
+    fn decode_matrix(word: u16) -> Option<(Self::Data, usize)> {
         // Compute the 4-bit syndrome.
-        let s = matrix_mul!(word, Self::par(), u8);
+        Self::correct(word, matrix_mul!(word, Self::par(), u8))
+    }
 
+    /// Try to correct the given word using its syndrome.
+    fn correct(word: u16, s: u8) -> Option<(Self::Data, usize)> {
         // A zero syndrome means it's a valid codeword (possibly different from the
         // transmitted codeword.)
         if s == 0 {
@@ -171,10 +240,78 @@ trait HammingDecoder {
     }
 }
 
+/// Lookup tables that map each byte of a word to its term in the syndrome.
+struct Syndromes {
+    /// Terms for the high byte.
+    hi: [u8; 256],
+    /// Terms for the low byte.
+    lo: [u8; 256],
+}
+
+impl Syndromes {
+    /// Build the tables from the given parity-check patterns.
+    fn new(par: [u16; 4]) -> Syndromes {
+        let mut t = Syndromes {
+            hi: [0; 256],
+            lo: [0; 256],
+        };
+
+        for i in 0..256 {
+            t.hi[i] = matrix_mul!((i as u16) << 8, par, u8);
+            t.lo[i] = matrix_mul!(i as u16, par, u8);
+        }
+
+        t
+    }
+
+    /// Calculate the syndrome of the given word.
+    fn get(&self, word: u16) -> u8 {
+        self.hi[word as usize >> 8] ^ self.lo[word as usize & 0xFF]
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn test_soft() {
+        let w = 0b10101010101;
+        let e = standard::encode(w);
+        let mut rel = [1.0; 15];
+
+        assert_eq!(standard::decode_soft(e, &rel[..]), Some((w, 0)));
+        assert_eq!(standard::decode_soft(e ^ 0b100, &rel[..]), Some((w, 1)));
+
+        rel[1] = 0.2;
+        rel[9] = 0.1;
+        let rx = e ^ 0b010000000100000;
+        assert!(standard::decode(rx).map(|(d, _)| d) != Some(w));
+        assert_eq!(standard::decode_soft(rx, &rel[..]), Some((w, 2)));
+
+        let w = 0b110011;
+        let e = shortened::encode(w);
+        let mut rel = [1.0; 10];
+
+        rel[0] = 0.3;
+        rel[4] = 0.2;
+        rel[8] = 0.1;
+        let rx = e ^ 0b1000100010;
+        assert!(shortened::decode(rx).map(|(d, _)| d) != Some(w));
+        assert_eq!(shortened::decode_soft(rx, &rel[..]), Some((w, 3)));
+    }
+
+    #[test]
+    fn test_tables() {
+        for w in 0..1 << 15 {
+            assert_eq!(standard::decode(w), standard::decode_matrix(w));
+        }
+
+        for w in 0..1 << 10 {
+            assert_eq!(shortened::decode(w), shortened::decode_matrix(w));
+        }
+    }
+
     #[test]
     fn test_standard() {
         let w = 0b10101010101;
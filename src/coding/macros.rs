@@ -23,3 +23,23 @@ macro_rules! accum_rows {
         })
     };
 }
+
+/// Define a function with the given name that returns a static reference to a value of
+/// the given type, which is built from the given expression on the first call.
+///
+/// This is exported only so `impl_field_params` can be used outside the crate.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! lazy_table {
+    ($name:ident: $ty:ty = $init:expr) => {
+        fn $name() -> &'static $ty {
+            static INIT: ::std::sync::Once = ::std::sync::Once::new();
+            static mut TABLE: *const $ty = 0 as *const $ty;
+
+            unsafe {
+                INIT.call_once(|| TABLE = Box::into_raw(Box::new($init)));
+                &*TABLE
+            }
+        }
+    };
+}
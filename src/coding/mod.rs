@@ -40,6 +40,7 @@ pub mod galois;
 
 pub mod bch;
 pub mod bmcf;
+pub mod chase;
 pub mod cyclic;
 pub mod golay;
 pub mod hamming;
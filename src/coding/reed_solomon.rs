@@ -1,16 +1,20 @@
 //! Encoding and decoding of the (24, 12, 13) short, (24, 16, 9) medium, and (36, 20, 17)
-//! long Reed-Solomon codes described by P25.
+//! long Reed-Solomon codes described by P25, as well as general (n, k) Reed-Solomon
+//! codes over any Galois field.
 //!
 //! These algorithms are sourced from *Coding Theory and Cryptography: The Essentials*,
 //! Hankerson, Hoffman, et al, 2000.
-
-use std;
+//!
+//! Each code can also be decoded with the positions of known erroneous ("erased")
+//! symbols, such as those whose inner code couldn't be decoded. Each erasure uses half
+//! the correction capacity of an unknown error, so a code that corrects t errors can
+//! correct up to 2t erasures.
 
 use collect_slice::CollectSlice;
 
 use bits::Hexbit;
-use coding::bmcf;
-use coding::galois::{P25Codeword, Polynomial, PolynomialCoefs};
+use coding::galois::{P25Codeword, P25Field, PolynomialCoefs};
+use consts::HEADER_HEXBITS;
 
 /// Encoding and decoding of the (24, 12, 13) code.
 pub mod short {
@@ -46,9 +50,23 @@ pub mod short {
     /// data hexbits and `err` is the number of corrected hexbits. Otherwise, return
     /// `None` to indicate an unrecoverable error.
     pub fn decode(buf: &mut [Hexbit; 24]) -> Option<(&[Hexbit], usize)> {
-        super::decode::<super::ShortCoefs>(buf).map(move |(poly, err)| {
-            (super::extract_data(poly, &mut buf[..12]), err)
-        })
+        decode_erasures(buf, &[])
+    }
+
+    /// Try to decode the given 24-hexbit word to the nearest codeword, given the indexes
+    /// of erased hexbits within the word. Up to e errors and f erasures can be corrected
+    /// as long as 2e + f ≤ 12.
+    ///
+    /// If decoding was successful, return `Some((data, err))`, where `data` is the 12
+    /// data hexbits and `err` is the number of corrected hexbits, including erasures.
+    /// Otherwise, return `None` to indicate an unrecoverable error.
+    pub fn decode_erasures<'a>(buf: &'a mut [Hexbit; 24], erasures: &[usize])
+        -> Option<(&'a [Hexbit], usize)>
+    {
+        match super::decode::<super::ShortCoefs>(&mut buf[..], erasures) {
+            Some(err) => Some((&buf[..12], err)),
+            None => None,
+        }
     }
 }
 
@@ -82,9 +100,23 @@ pub mod medium {
     /// data hexbits and `err` is the number of corrected hexbits. Otherwise, return
     /// `None` to indicate an unrecoverable error.
     pub fn decode(buf: &mut [Hexbit; 24]) -> Option<(&[Hexbit], usize)> {
-        super::decode::<super::MedCoefs>(buf).map(move |(poly, err)| {
-            (super::extract_data(poly, &mut buf[..16]), err)
-        })
+        decode_erasures(buf, &[])
+    }
+
+    /// Try to decode the given 24-hexbit word to the nearest codeword, given the indexes
+    /// of erased hexbits within the word. Up to e errors and f erasures can be corrected
+    /// as long as 2e + f ≤ 8.
+    ///
+    /// If decoding was successful, return `Some((data, err))`, where `data` is the 16
+    /// data hexbits and `err` is the number of corrected hexbits, including erasures.
+    /// Otherwise, return `None` to indicate an unrecoverable error.
+    pub fn decode_erasures<'a>(buf: &'a mut [Hexbit; 24], erasures: &[usize])
+        -> Option<(&'a [Hexbit], usize)>
+    {
+        match super::decode::<super::MedCoefs>(&mut buf[..], erasures) {
+            Some(err) => Some((&buf[..16], err)),
+            None => None,
+        }
     }
 }
 
@@ -126,9 +158,23 @@ pub mod long {
     /// data hexbits and `err` is the number of corrected hexbits. Otherwise, return
     /// `None` to indicate an unrecoverable error.
     pub fn decode(buf: &mut [Hexbit; 36]) -> Option<(&[Hexbit], usize)> {
-        super::decode::<super::LongCoefs>(buf).map(move |(poly, err)| {
-            (super::extract_data(poly, &mut buf[..20]), err)
-        })
+        decode_erasures(buf, &[])
+    }
+
+    /// Try to decode the given 36-hexbit word to the nearest codeword, given the indexes
+    /// of erased hexbits within the word. Up to e errors and f erasures can be corrected
+    /// as long as 2e + f ≤ 16.
+    ///
+    /// If decoding was successful, return `Some((data, err))`, where `data` is the 20
+    /// data hexbits and `err` is the number of corrected hexbits, including erasures.
+    /// Otherwise, return `None` to indicate an unrecoverable error.
+    pub fn decode_erasures<'a>(buf: &'a mut [Hexbit; 36], erasures: &[usize])
+        -> Option<(&'a [Hexbit], usize)>
+    {
+        match super::decode::<super::LongCoefs>(&mut buf[..], erasures) {
+            Some(err) => Some((&buf[..20], err)),
+            None => None,
+        }
     }
 }
 
@@ -146,49 +192,177 @@ fn encode<'g, G>(data: &[Hexbit], parity: &mut [Hexbit], gen: G)
     }).map(Hexbit::new).collect_slice_checked(parity);
 }
 
-/// Try to fix any errors in the given word.
+/// Try to fix any errors in the given word in place, with erasures at the given indexes
+/// into the word.
 ///
-/// On success, return `Some((poly, err))`, where `poly` is the polynomial representation
-/// of the corrected word (with the last data symbol as the degree-0 coefficient) and
-/// `err` is the number of corrected hexbit symbols. Otherwise, return `None` to indicate
-/// an unrecoverable error.
-fn decode<P: PolynomialCoefs>(word: &[Hexbit]) -> Option<(Polynomial<P>, usize)> {
-    // In the polynomial representation, the first received symbol corresponds to the
-    // coefficient of the highest-degree term.
-    let mut poly = Polynomial::new(word.iter().rev().map(|&b|
-        P25Codeword::new(b.bits())
-    ));
-
-    bmcf::Errors::new(syndromes(&poly)).and_then(|(nerr, errs)| {
-        for (loc, pat) in errs {
-            match poly.get_mut(loc) {
-                Some(coef) => *coef = *coef + pat,
-                None => return None,
-            }
+/// On success, return `Some(err)`, where `err` is the number of corrected hexbit
+/// symbols. Otherwise, return `None` to indicate an unrecoverable error.
+fn decode<P>(word: &mut [Hexbit], erasures: &[usize]) -> Option<usize>
+    where P: PolynomialCoefs<Field = P25Field>
+{
+    let mut buf = [P25Codeword::default(); HEADER_HEXBITS];
+    let codewords = &mut buf[..word.len()];
+
+    for (c, b) in codewords.iter_mut().zip(word.iter()) {
+        *c = P25Codeword::new(b.bits());
+    }
+
+    let err = match generic::decode::<P>(codewords, erasures) {
+        Some(err) => err,
+        None => return None,
+    };
+
+    for (b, c) in word.iter_mut().zip(codewords.iter()) {
+        *b = Hexbit::new(c.bits());
+    }
+
+    Some(err)
+}
+
+/// Records which symbols of a received word are erased, such as those whose inner code
+/// couldn't be decoded.
+#[derive(Copy, Clone)]
+pub struct Erasures {
+    /// Index of each erased symbol within the word.
+    pos: [usize; HEADER_HEXBITS],
+    /// Number of erased symbols.
+    len: usize,
+    /// Index of the next symbol within the word.
+    idx: usize,
+}
+
+impl Erasures {
+    /// Create a new `Erasures` at the start of a word with no erased symbols.
+    pub fn new() -> Erasures {
+        Erasures {
+            pos: [0; HEADER_HEXBITS],
+            len: 0,
+            idx: 0,
+        }
+    }
+
+    /// Record the next symbol in the word, marking it as erased if the given flag is
+    /// set.
+    pub fn feed(&mut self, erased: bool) {
+        if erased {
+            self.pos[self.len] = self.idx;
+            self.len += 1;
         }
 
-        Some((poly, nerr))
-    })
+        self.idx += 1;
+    }
+
+    /// Indexes of the erased symbols recorded so far.
+    pub fn positions(&self) -> &[usize] { &self.pos[..self.len] }
 }
 
-/// Generate the syndrome polynomial s(x) from the given received word r(x).
+/// Encoding and decoding of general (n, k, d) Reed-Solomon codes.
 ///
-/// The resulting polynomial has the form s(x) = s<sub>1</sub> + s<sub>2</sub>x + ··· +
-/// s<sub>2t</sub>x<sup>2t</sup>, where s<sub>i</sub> = r(α<sup>i</sup>).
-fn syndromes<P: PolynomialCoefs>(word: &Polynomial<P>) -> Polynomial<P> {
-    Polynomial::new((1...P::syndromes()).map(|p| {
-        // Compute r(α^p).
-        word.eval(P25Codeword::for_power(p))
-    }))
-}
+/// Each code is described by its polynomial coefficients, which determine the field,
+/// the distance d = n - k + 1, and the maximum word size n. The generator polynomial is
+/// g(x) = (x + α)(x + α<sup>2</sup>) ··· (x + α<sup>n-k</sup>), and any code can be
+/// shortened by encoding fewer data symbols, as long as n ≤ 2<sup>r</sup> - 1. The
+/// first symbol of each word is the coefficient of the highest-degree term.
+pub mod generic {
+    use coding::bmcf;
+    use coding::galois::{Codeword, GaloisField, Polynomial, PolynomialCoefs};
+
+    /// Calculate the n - k parity symbols for the given k data symbols, placing them in
+    /// the given parity buffer.
+    pub fn encode<P>(data: &[Codeword<P::Field>], parity: &mut [Codeword<P::Field>])
+        where P: PolynomialCoefs
+    {
+        assert!(parity.len() == P::syndromes());
+        assert!(data.len() + parity.len() <= P::Field::size());
+
+        let gen = generator::<P>();
+
+        for p in parity.iter_mut() {
+            *p = Codeword::default();
+        }
 
-/// Extract the data symbols from the given polynomial-form word and write them to the
-/// given slice.
-fn extract_data<P>(poly: Polynomial<P>, data: &mut [Hexbit]) -> &[Hexbit]
-    where P: PolynomialCoefs
-{
-    poly.iter().rev().map(|coef| Hexbit::new(coef.bits())).collect_slice_fill(data);
-    data
+        // Compute the remainder of d(x)x^{n-k} / g(x) with a shift register, where each
+        // parity symbol holds the coefficient of the next highest-degree term.
+        for &d in data.iter() {
+            let feedback = d + parity[0];
+
+            for i in 0..parity.len() {
+                let next = match parity.get(i + 1) {
+                    Some(&p) => p,
+                    None => Codeword::default(),
+                };
+
+                parity[i] = next + feedback * gen.coef(parity.len() - i - 1);
+            }
+        }
+    }
+
+    /// Try to decode the given n-symbol word to the nearest codeword in place, given the
+    /// indexes of erased symbols within the word. Up to e errors and f erasures can be
+    /// corrected as long as 2e + f ≤ n - k.
+    ///
+    /// If decoding was successful, return `Some(err)`, where `err` is the number of
+    /// corrected symbols, including erasures. Otherwise, return `None` to indicate an
+    /// unrecoverable error and leave the word unmodified.
+    pub fn decode<P>(word: &mut [Codeword<P::Field>], erasures: &[usize]) -> Option<usize>
+        where P: PolynomialCoefs
+    {
+        assert!(word.len() <= P::default().len());
+        assert!(word.len() <= P::Field::size());
+
+        // In the polynomial representation, the first received symbol corresponds to the
+        // coefficient of the highest-degree term.
+        let mut poly = Polynomial::<P>::new(word.iter().rev().cloned());
+
+        let locs = erasures.iter().map(|&idx| {
+            assert!(idx < word.len());
+            word.len() - 1 - idx
+        });
+
+        let err = match bmcf::Errors::with_erasures(syndromes(&poly), locs) {
+            Some((nerr, errs)) => {
+                for (loc, pat) in errs {
+                    // Reject corrections outside of the (possibly shortened) word.
+                    if loc >= word.len() {
+                        return None;
+                    }
+
+                    poly[loc] = poly[loc] + pat;
+                }
+
+                nerr
+            },
+            None => return None,
+        };
+
+        for (w, &c) in word.iter_mut().rev().zip(poly.iter()) {
+            *w = c;
+        }
+
+        Some(err)
+    }
+
+    /// Generate the syndrome polynomial s(x) from the given received word r(x).
+    ///
+    /// The resulting polynomial has the form s(x) = s<sub>1</sub> + s<sub>2</sub>x + ···
+    /// + s<sub>2t</sub>x<sup>2t</sup>, where s<sub>i</sub> = r(α<sup>i</sup>).
+    fn syndromes<P: PolynomialCoefs>(word: &Polynomial<P>) -> Polynomial<P> {
+        Polynomial::new((1...P::syndromes()).map(|p| {
+            // Compute r(α^p).
+            word.eval(Codeword::for_power(p))
+        }))
+    }
+
+    /// Compute the generator polynomial g(x) = (x + α)(x + α<sup>2</sup>) ···
+    /// (x + α<sup>2t</sup>).
+    fn generator<P: PolynomialCoefs>() -> Polynomial<P> {
+        (1...P::syndromes()).fold(Polynomial::unit_power(0), |g, p| {
+            g * Polynomial::new([
+                Codeword::for_power(p),
+                Codeword::for_power(0),
+            ].iter().cloned())
+        })
+    }
 }
 
 /// Polynomial coefficients for the short code.
@@ -204,7 +378,13 @@ impl_polynomial_coefs!(LongCoefs, 17, 36);
 mod test {
     use super::*;
     use super::{MedCoefs, ShortCoefs, LongCoefs};
-    use coding::galois::{PolynomialCoefs, P25Codeword, Polynomial};
+    use coding::galois::{
+        Codeword,
+        P25Codeword,
+        Polynomial,
+        PolynomialCoefs,
+        PrimitiveField,
+    };
     use bits::Hexbit;
     use collect_slice::CollectSlice;
 
@@ -503,6 +683,192 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_decode_erasures() {
+        let exp = [
+           Hexbit::new(1), Hexbit::new(0), Hexbit::new(0), Hexbit::new(0), Hexbit::new(0),
+           Hexbit::new(0), Hexbit::new(0), Hexbit::new(0), Hexbit::new(0), Hexbit::new(0),
+           Hexbit::new(0), Hexbit::new(0),
+        ];
+
+        // Erasures at twice the error capacity.
+        let mut buf = [Hexbit::default(); 24];
+        [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].iter()
+             .map(|&b| Hexbit::new(b)).collect_slice(&mut buf[..]);
+        short::encode(&mut buf);
+
+        let erasures = [0, 1, 3, 5, 8, 9, 12, 14, 17, 20, 22, 23];
+
+        for &i in erasures.iter() {
+            buf[i] = Hexbit::new(0o77);
+        }
+
+        let mut copy = buf;
+        assert_eq!(short::decode(&mut copy), None);
+        assert_eq!(short::decode_erasures(&mut buf, &erasures[..]), Some((&exp[..], 12)));
+
+        // Mix of erasures and errors.
+        let mut buf = [Hexbit::default(); 24];
+        [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].iter()
+             .map(|&b| Hexbit::new(b)).collect_slice(&mut buf[..]);
+        short::encode(&mut buf);
+
+        buf[2] = Hexbit::new(0o12);
+        buf[6] = Hexbit::new(0o34);
+        buf[10] = Hexbit::new(0o56);
+        buf[15] = Hexbit::new(0o01);
+        buf[19] = Hexbit::new(0o70);
+        buf[21] = Hexbit::new(0o05);
+
+        let mut copy = buf;
+        assert_eq!(short::decode_erasures(&mut copy, &[2, 6, 10, 15]),
+                   Some((&exp[..], 6)));
+
+        // Erasures of correct symbols still use up correction capacity.
+        let mut copy = buf;
+        assert_eq!(short::decode_erasures(&mut copy, &[0, 1, 2, 3, 4, 6, 10, 15]),
+                   Some((&exp[..], 10)));
+
+        // Too many errors for the given erasures.
+        let mut copy = buf;
+        assert_eq!(short::decode_erasures(&mut copy, &[0, 1, 2, 3, 4, 5, 6, 10, 15]),
+                   None);
+
+        let exp = [
+           Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77),
+           Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77),
+           Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77),
+           Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77),
+           Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77), Hexbit::new(0o77),
+        ];
+
+        // Erasure in each location along with 7 errors.
+        for i in 0..36 {
+            let mut buf = [Hexbit::default(); 36];
+            [0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77,
+             0o77, 0o77, 0o77, 0o77, 0o77, 0o77, 0o77].iter().cloned()
+                .map(Hexbit::new).collect_slice(&mut buf[..]);
+            long::encode(&mut buf);
+
+            buf[i] = Hexbit::new(0);
+
+            for j in 1..8 {
+                buf[(i + 5 * j) % 36] = Hexbit::new(0);
+            }
+
+            let dec = long::decode_erasures(&mut buf, &[i]);
+            assert_eq!(dec, Some((&exp[..], 8)));
+        }
+
+        // Erasures of medium code.
+        let mut buf = [Hexbit::default(); 24];
+        [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0].iter()
+             .map(|&b| Hexbit::new(b)).collect_slice(&mut buf[..]);
+        medium::encode(&mut buf);
+
+        buf[0] = Hexbit::new(0o00);
+        buf[4] = Hexbit::new(0o11);
+        buf[11] = Hexbit::new(0o22);
+        buf[17] = Hexbit::new(0o33);
+        buf[23] = Hexbit::new(0o44);
+
+        let dec = medium::decode_erasures(&mut buf, &[0, 4, 11, 23]);
+        assert_eq!(dec.map(|(d, e)| (d[0], e)), Some((Hexbit::new(1), 5)));
+    }
+
+    impl_field_params!(ByteParams, 8, 0b100011101);
+
+    type ByteCodeword = Codeword<PrimitiveField<ByteParams>>;
+
+    impl_polynomial_coefs!(ByteCoefs: PrimitiveField<ByteParams>, 4, 12);
+    impl_polynomial_coefs!(ShortenedCoefs, 23, 52);
+
+    #[test]
+    fn test_generic() {
+        ByteCoefs::default().validate();
+        ShortenedCoefs::default().validate();
+
+        // Generic encoding matches the P25 generator matrix.
+        let mut buf = [Hexbit::default(); 24];
+        [0o01, 0o23, 0o45, 0o67, 0o10, 0o32, 0o54, 0o76, 0o11, 0o22, 0o33, 0o44].iter()
+             .map(|&b| Hexbit::new(b)).collect_slice(&mut buf[..]);
+        short::encode(&mut buf);
+
+        let mut word = [P25Codeword::default(); 24];
+
+        for (w, b) in word.iter_mut().zip(buf.iter()) {
+            *w = P25Codeword::new(b.bits());
+        }
+
+        let (data, parity) = word.split_at(12);
+        let mut gen = [P25Codeword::default(); 12];
+        generic::encode::<ShortCoefs>(data, &mut gen[..]);
+        assert_eq!(&gen[..], parity);
+
+        // (12, 9, 4) code over GF(2^8) corrects 1 error or 3 erasures.
+        let mut word = [ByteCodeword::default(); 12];
+
+        for (i, w) in word[..9].iter_mut().enumerate() {
+            *w = ByteCodeword::new(0x11 * i as u8 + 0x05);
+        }
+
+        let (data, parity) = word.split_at_mut(9);
+        generic::encode::<ByteCoefs>(data, parity);
+
+        let exp = word;
+        assert_eq!(generic::decode::<ByteCoefs>(&mut word[..], &[]), Some(0));
+
+        word[4] = ByteCodeword::new(0xFF);
+        assert_eq!(generic::decode::<ByteCoefs>(&mut word[..], &[]), Some(1));
+        assert_eq!(word, exp);
+
+        word[0] = ByteCodeword::new(0);
+        word[5] = ByteCodeword::new(1);
+        word[11] = ByteCodeword::new(2);
+        assert_eq!(generic::decode::<ByteCoefs>(&mut word[..], &[0, 5, 11]), Some(3));
+        assert_eq!(word, exp);
+
+        // Unrecoverable words are left unmodified.
+        word[2] = ByteCodeword::new(0);
+        word[7] = ByteCodeword::new(0);
+        let bad = word;
+        assert_eq!(generic::decode::<ByteCoefs>(&mut word[..], &[]), None);
+        assert_eq!(word, bad);
+
+        // (52, 30, 23) shortened code over the P25 field corrects 11 errors.
+        let mut word = [P25Codeword::default(); 52];
+
+        for (i, w) in word[..30].iter_mut().enumerate() {
+            *w = P25Codeword::for_power(i * 5);
+        }
+
+        let (data, parity) = word.split_at_mut(30);
+        generic::encode::<ShortenedCoefs>(data, parity);
+
+        let exp = word;
+
+        for i in 0..11 {
+            word[i * 4 + 1] = word[i * 4 + 1] + P25Codeword::for_power(i);
+        }
+
+        assert_eq!(generic::decode::<ShortenedCoefs>(&mut word[..], &[]), Some(11));
+        assert_eq!(&word[..], &exp[..]);
+    }
+
+    #[test]
+    fn test_erasures() {
+        let mut e = Erasures::new();
+        assert!(e.positions().is_empty());
+
+        e.feed(false);
+        e.feed(true);
+        e.feed(false);
+        e.feed(true);
+        e.feed(true);
+
+        assert_eq!(e.positions(), &[1, 3, 4]);
+    }
+
     #[test]
     fn test_unrecoverable() {
         // These unrecoverable received words caused divide-by-zero errors due to
@@ -4,6 +4,10 @@
 //! Encoding is done with a state machine and decoding is done with the Viterbi algorithm,
 //! adapted from *Coding Theory and Cryptography: The Essentials*, Hankerson, Hoffman, et
 //! al, 2000.
+//!
+//! The decoder accepts either hard `Dibit` symbols, compared by Hamming distance, or
+//! `SoftDibit` symbols, compared by Euclidean distance between the received symbol and
+//! each ideal symbol.
 
 use std;
 use std::ops::{Deref, DerefMut};
@@ -14,16 +18,19 @@ use bits;
 
 use self::Decision::*;
 
+/// Scale applied to squared Euclidean distances before rounding them to branch metrics.
+const SOFT_SCALE: f32 = 4.0;
+
 /// Half-rate convolutional ("trellis") code state machine.
 pub type DibitFSM = TrellisFSM<DibitStates>;
 
 /// 3/4-rate convolutional ("trellis") code state machine.
 pub type TribitFSM = TrellisFSM<TribitStates>;
 
-/// Half-rate convolution ("trellis") code decoder.
+/// Half-rate convolution ("trellis") code decoder, over hard or soft dibits.
 pub type DibitDecoder<T> = ViterbiDecoder<DibitStates, DibitHistory, DibitWalks, T>;
 
-/// 3/4-rate convolution ("trellis") code decoder.
+/// 3/4-rate convolution ("trellis") code decoder, over hard or soft dibits.
 pub type TribitDecoder<T> = ViterbiDecoder<TribitStates, TribitHistory, TribitWalks, T>;
 
 pub trait States {
@@ -157,6 +164,35 @@ impl<S: States> TrellisFSM<S> {
     }
 }
 
+/// Received symbol that can be compared with the dibits on a trellis transition.
+pub trait TrellisSymbol: Copy {
+    /// Compute the branch metric between the given received pair of symbols and the
+    /// given pair of dibits, where a smaller metric means a closer match.
+    fn distance(input: (Self, Self), pair: (bits::Dibit, bits::Dibit)) -> usize;
+}
+
+impl TrellisSymbol for bits::Dibit {
+    fn distance(input: (Self, Self), pair: (bits::Dibit, bits::Dibit)) -> usize {
+        Edge::new(input).distance(Edge::new(pair))
+    }
+}
+
+impl TrellisSymbol for bits::SoftDibit {
+    fn distance(input: (Self, Self), pair: (bits::Dibit, bits::Dibit)) -> usize {
+        let dist = soft_distance(input.0, pair.0) + soft_distance(input.1, pair.1);
+        (dist * SOFT_SCALE).round() as usize
+    }
+}
+
+/// Compute the squared Euclidean distance between the given soft symbol and the ideal
+/// symbol of the given dibit. Symbols beyond the outer ideal symbols are clamped, since
+/// they're no more likely to be any other symbol.
+fn soft_distance(soft: bits::SoftDibit, dibit: bits::Dibit) -> f32 {
+    let diff = soft.value().max(-3.0).min(3.0) - bits::SoftDibit::ideal(dibit).value();
+
+    diff * diff
+}
+
 pub trait WalkHistory: Copy + Clone + Default +
     Deref<Target = [Option<usize>]> + DerefMut
 {
@@ -232,7 +268,7 @@ impl_walks!(TribitWalks, TribitHistory, 8);
 /// Decodes a received convolutional code dibit stream to a nearby codeword using the
 /// truncated Viterbi algorithm.
 pub struct ViterbiDecoder<S, H, W, T> where
-    S: States, H: WalkHistory, W: Walks<H>, T: Iterator<Item = bits::Dibit>
+    S: States, H: WalkHistory, W: Walks<H>, T: Iterator, T::Item: TrellisSymbol
 {
     states: std::marker::PhantomData<S>,
     history: std::marker::PhantomData<H>,
@@ -247,9 +283,9 @@ pub struct ViterbiDecoder<S, H, W, T> where
 }
 
 impl<S, H, W, T> ViterbiDecoder<S, H, W, T> where
-    S: States, H: WalkHistory, W: Walks<H>, T: Iterator<Item = bits::Dibit>
+    S: States, H: WalkHistory, W: Walks<H>, T: Iterator, T::Item: TrellisSymbol
 {
-    /// Construct a new `ViterbiDecoder` over the given dibit source.
+    /// Construct a new `ViterbiDecoder` over the given hard or soft dibit source.
     pub fn new(src: T) -> ViterbiDecoder<S, H, W, T> {
         debug_assert!(S::size() == W::states());
 
@@ -277,11 +313,11 @@ impl<S, H, W, T> ViterbiDecoder<S, H, W, T> where
     }
 
     fn step(&mut self) -> bool {
-        let input = Edge::new(match (self.src.next(), self.src.next()) {
+        let input = match (self.src.next(), self.src.next()) {
             (Some(hi), Some(lo)) => (hi, lo),
             (None, None) => return false,
             _ => panic!("dibits ended on boundary"),
-        });
+        };
 
         self.remain += 1;
         self.switch_walk();
@@ -295,12 +331,12 @@ impl<S, H, W, T> ViterbiDecoder<S, H, W, T> where
     }
 
     ///
-    fn search(&self, state: usize, input: Edge) -> (Walk<H>, bool) {
+    fn search(&self, state: usize, input: (T::Item, T::Item)) -> (Walk<H>, bool) {
         self.walks[self.prev].iter()
             .enumerate()
-            .map(|(i, w)| (Edge::new(S::pair(i, state)), w))
-            .fold((Walk::default(), false), |(walk, amb), (e, w)| {
-                match w.distance.checked_add(input.distance(e)) {
+            .map(|(i, w)| (S::pair(i, state), w))
+            .fold((Walk::default(), false), |(walk, amb), (p, w)| {
+                match w.distance.checked_add(T::Item::distance(input, p)) {
                     Some(sum) if sum < walk.distance => (walk.replace(&w, sum), false),
                     Some(sum) if sum == walk.distance => (walk.combine(&w, sum), true),
                     _ => (walk, amb),
@@ -323,7 +359,7 @@ impl<S, H, W, T> ViterbiDecoder<S, H, W, T> where
 }
 
 impl<S, H, W, T> Iterator for ViterbiDecoder<S, H, W, T> where
-    S: States, H: WalkHistory, W: Walks<H>, T: Iterator<Item = bits::Dibit>
+    S: States, H: WalkHistory, W: Walks<H>, T: Iterator, T::Item: TrellisSymbol
 {
     type Item = Result<S::Symbol, ()>;
 
@@ -551,4 +587,82 @@ mod test {
         assert_eq!(dec.next().unwrap().unwrap().bits(), 7);
         assert_eq!(dec.next().unwrap().unwrap().bits(), 0);
     }
+
+    /// Push the given symbols just over the threshold toward zero, so their hard
+    /// decisions are wrong but they're still closest to the transmitted symbol.
+    fn perturb(soft: &mut [SoftDibit]) {
+        for s in soft.iter_mut() {
+            let v = s.value();
+            *s = SoftDibit::new(if v > 0.0 { v - 1.1 } else { v + 1.1 });
+        }
+    }
+
+    #[test]
+    fn test_soft_dibit_decoder() {
+        let bits = [1, 2, 2, 2, 2, 1, 3, 3, 0, 2, 0, 1, 3, 2, 1, 0];
+        let stream = bits.iter().map(|&bits| Dibit::new(bits));
+
+        let mut dibits = vec![];
+        let mut fsm = DibitFSM::new();
+
+        for dibit in stream {
+            let (hi, lo) = fsm.feed(dibit);
+            dibits.push(hi);
+            dibits.push(lo);
+        }
+
+        let (hi, lo) = fsm.finish();
+        dibits.push(hi);
+        dibits.push(lo);
+
+        let mut soft = dibits.iter().map(|&d| SoftDibit::ideal(d)).collect::<Vec<_>>();
+        perturb(&mut soft[4..10]);
+
+        let hard: Vec<Dibit> = soft.iter().map(|s| s.hard()).collect();
+        assert_eq!(hard.iter().zip(dibits.iter()).filter(|&(a, b)| a != b).count(), 6);
+
+        // The burst of errors is too dense to correct with hard decisions.
+        let decoded: Vec<_> = DibitDecoder::new(hard.iter().cloned()).collect();
+        assert!(decoded.iter().zip(bits.iter()).any(|(d, &b)| match *d {
+            Ok(d) => d.bits() != b,
+            Err(()) => true,
+        }));
+
+        let decoded: Vec<_> = DibitDecoder::new(soft.iter().cloned()).collect();
+        assert_eq!(decoded.len(), bits.len());
+
+        for (d, &b) in decoded.iter().zip(bits.iter()) {
+            assert_eq!(d.unwrap().bits(), b);
+        }
+    }
+
+    #[test]
+    fn test_soft_tribit_decoder() {
+        let bits = [5, 1, 7, 3, 0, 6, 2, 4, 1, 2, 3, 4, 5, 6, 7, 0];
+        let stream = bits.iter().map(|&bits| Tribit::new(bits));
+
+        let mut dibits = vec![];
+        let mut fsm = TribitFSM::new();
+
+        for tribit in stream {
+            let (hi, lo) = fsm.feed(tribit);
+            dibits.push(hi);
+            dibits.push(lo);
+        }
+
+        let (hi, lo) = fsm.finish();
+        dibits.push(hi);
+        dibits.push(lo);
+
+        let mut soft = dibits.iter().map(|&d| SoftDibit::ideal(d)).collect::<Vec<_>>();
+        perturb(&mut soft[6..10]);
+        perturb(&mut soft[20..22]);
+
+        let decoded: Vec<_> = TribitDecoder::new(soft.iter().cloned()).collect();
+        assert_eq!(decoded.len(), bits.len());
+
+        for (d, &b) in decoded.iter().zip(bits.iter()) {
+            assert_eq!(d.unwrap().bits(), b);
+        }
+    }
 }
@@ -12,6 +12,10 @@ pub const SYNC_SYMBOLS: usize = 24;
 pub const NID_DIBITS: usize = 32;
 /// Number of dibits that are input to the 1/2 or 3/4-rate trellis coder.
 pub const CODING_DIBITS: usize = 98;
+/// Number of bytes in an uncoded 1/2-rate data packet block.
+pub const DIBIT_BLOCK_BYTES: usize = 12;
+/// Number of bytes in an uncoded 3/4-rate data packet block.
+pub const TRIBIT_BLOCK_BYTES: usize = 18;
 /// Number of dibits in an uncoded TSBK packet.
 pub const TSBK_DIBITS: usize = 48;
 /// Number of bytes in an uncoded TSBK packet.
@@ -40,6 +44,21 @@ pub const HEADER_WORD_DIBITS: usize = 9;
 /// Number of dibits in each coded word that makes up the voice LC terminator packet.
 pub const LC_TERM_WORD_DIBITS: usize = 12;
 
+/// Symbols (dibits) per second on a Phase 2 TDMA channel.
+pub const P2_SYMBOL_RATE: usize = 6000;
+/// Baseband samples per Phase 2 symbol.
+pub const P2_SYMBOL_PERIOD: usize = SAMPLE_RATE / P2_SYMBOL_RATE;
+/// Number of symbols in the Phase 2 sync sequence (S-ISCH).
+pub const P2_SYNC_SYMBOLS: usize = 20;
+/// Number of dibits in a Phase 2 inter-slot signalling channel (ISCH).
+pub const ISCH_DIBITS: usize = 20;
+/// Number of dibits in a Phase 2 timeslot burst, excluding its ISCH.
+pub const BURST_DIBITS: usize = 160;
+/// Number of dibits in a Phase 2 timeslot, including its ISCH.
+pub const SLOT_DIBITS: usize = ISCH_DIBITS + BURST_DIBITS;
+/// Number of timeslots in a Phase 2 superframe.
+pub const SUPERFRAME_SLOTS: usize = 12;
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -48,5 +67,6 @@ mod test {
     fn validate_params() {
         // Don't support non-integer period.
         assert!(SAMPLE_RATE % SYMBOL_RATE == 0);
+        assert!(SAMPLE_RATE % P2_SYMBOL_RATE == 0);
     }
 }
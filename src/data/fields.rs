@@ -43,6 +43,99 @@ impl DataPacketOpcode {
     }
 }
 
+/// Type of response packet, sent in reply to a confirmed data packet.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ResponseType {
+    /// All blocks in the packet were received successfully.
+    Ack,
+    /// The packet was rejected for the given reason.
+    Nack(NackReason),
+    /// Some blocks were received with errors and should be selectively retried.
+    SelectiveAck,
+}
+
+impl ResponseType {
+    /// Convert response type to its 2-bit class and 3-bit type fields, in that order.
+    pub fn to_bits(self) -> (u8, u8) {
+        use self::ResponseType::*;
+
+        match self {
+            Ack => (0b00, 0b001),
+            Nack(r) => (0b01, r.to_bits()),
+            SelectiveAck => (0b10, 0b000),
+        }
+    }
+
+    /// Parse a response type from the given 2-bit class and 3-bit type fields.
+    pub fn from_bits(class: u8, kind: u8) -> Option<ResponseType> {
+        use self::ResponseType::*;
+
+        assert!(class >> 2 == 0);
+        assert!(kind >> 3 == 0);
+
+        match (class, kind) {
+            (0b00, 0b001) => Some(Ack),
+            (0b01, k) => NackReason::from_bits(k).map(Nack),
+            (0b10, 0b000) => Some(SelectiveAck),
+            _ => None,
+        }
+    }
+}
+
+/// Reason a confirmed data packet was rejected.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NackReason {
+    /// Packet had an illegal format.
+    IllegalFormat,
+    /// Packet checksum didn't match the received data.
+    PacketChecksum,
+    /// Receiver doesn't have enough memory for the packet.
+    MemoryFull,
+    /// FSNF was out of sequence.
+    FragmentSequence,
+    /// Packet couldn't be delivered to its destination.
+    Undeliverable,
+    /// N(S) was out of sequence.
+    PacketSequence,
+    /// Packet was addressed to a user not valid on the system.
+    InvalidUser,
+}
+
+impl NackReason {
+    /// Convert reason to its 3-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::NackReason::*;
+
+        match self {
+            IllegalFormat => 0b000,
+            PacketChecksum => 0b001,
+            MemoryFull => 0b010,
+            FragmentSequence => 0b011,
+            Undeliverable => 0b100,
+            PacketSequence => 0b101,
+            InvalidUser => 0b110,
+        }
+    }
+
+    /// Parse a reason from the given 3 bits.
+    pub fn from_bits(bits: u8) -> Option<NackReason> {
+        use self::NackReason::*;
+
+        assert!(bits >> 3 == 0);
+
+        match bits {
+            0b000 => Some(IllegalFormat),
+            0b001 => Some(PacketChecksum),
+            0b010 => Some(MemoryFull),
+            0b011 => Some(FragmentSequence),
+            0b100 => Some(Undeliverable),
+            0b101 => Some(PacketSequence),
+            0b110 => Some(InvalidUser),
+            _ => None,
+        }
+    }
+}
+
 /// Destination service for data packet.
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ServiceAccessPoint {
@@ -133,6 +226,28 @@ impl ServiceAccessPoint {
 mod test {
     use super::*;
 
+    #[test]
+    fn test_response_type() {
+        for &r in [
+            ResponseType::Ack,
+            ResponseType::Nack(NackReason::IllegalFormat),
+            ResponseType::Nack(NackReason::PacketChecksum),
+            ResponseType::Nack(NackReason::MemoryFull),
+            ResponseType::Nack(NackReason::FragmentSequence),
+            ResponseType::Nack(NackReason::Undeliverable),
+            ResponseType::Nack(NackReason::PacketSequence),
+            ResponseType::Nack(NackReason::InvalidUser),
+            ResponseType::SelectiveAck,
+        ].iter() {
+            let (class, kind) = r.to_bits();
+            assert_eq!(ResponseType::from_bits(class, kind), Some(r));
+        }
+
+        assert_eq!(ResponseType::from_bits(0b00, 0b000), None);
+        assert_eq!(ResponseType::from_bits(0b01, 0b111), None);
+        assert_eq!(ResponseType::from_bits(0b11, 0b000), None);
+    }
+
     #[test]
     #[should_panic]
     fn test_sap_validate() {
@@ -1,10 +1,12 @@
-//! Header generation for data packets.
+//! Header generation and parsing for data packets.
 //!
 //! A header has several user-filled fields followed by a 16-bit checksum over those
 //! fields.
 
 use data::crc;
 use data::fields;
+use error::{Result, P25Error};
+use util::{slice_u16, slice_u24};
 
 /// Packet header block for confirmed data packet.
 pub type ConfirmedHeader = Header<ConfirmedFields>;
@@ -12,6 +14,12 @@ pub type ConfirmedHeader = Header<ConfirmedFields>;
 /// Packet header block for unconfirmed data packet.
 pub type UnconfirmedHeader = Header<UnconfirmedFields>;
 
+/// Packet header block for response packet.
+pub type ResponseHeader = Header<ResponseFields>;
+
+/// Packet header block for multiblock trunking packet.
+pub type TrunkingHeader = Header<TrunkingFields>;
+
 /// Write some bytes into a buffer.
 pub trait BufWrite {
     fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, buf: &'b mut T);
@@ -39,6 +47,17 @@ pub struct HeaderPreamble {
     pub format: fields::DataPacketOpcode,
 }
 
+impl HeaderPreamble {
+    /// Parse a preamble from the given byte, if it contains a known packet type.
+    pub fn from_byte(byte: u8) -> Option<HeaderPreamble> {
+        fields::DataPacketOpcode::from_bits(byte & 0x1F).map(|format| HeaderPreamble {
+            confirmed: byte >> 6 & 1 == 1,
+            outbound: byte >> 5 & 1 == 1,
+            format: format,
+        })
+    }
+}
+
 impl ByteField for HeaderPreamble {
     fn byte(&self) -> u8 {
         bool_to_bit(self.confirmed) << 6 | bool_to_bit(self.outbound) << 5 |
@@ -60,6 +79,9 @@ impl ConfirmedPreamble {
 
     pub fn outbound() -> Self { Self::new(true) }
     pub fn inbound() -> Self { Self::new(false) }
+
+    /// Whether the packet is an outbound message.
+    pub fn is_outbound(&self) -> bool { self.0.outbound }
 }
 
 impl ByteField for ConfirmedPreamble {
@@ -80,15 +102,71 @@ impl UnconfirmedPreamble {
 
     pub fn outbound() -> Self { Self::new(true) }
     pub fn inbound() -> Self { Self::new(false) }
+
+    /// Whether the packet is an outbound message.
+    pub fn is_outbound(&self) -> bool { self.0.outbound }
 }
 
 impl ByteField for UnconfirmedPreamble {
     fn byte(&self) -> u8 { self.0.byte() }
 }
 
+/// Preamble for response packet.
+pub struct ResponsePreamble(HeaderPreamble);
+
+impl ResponsePreamble {
+    fn new(outbound: bool) -> ResponsePreamble {
+        ResponsePreamble(HeaderPreamble {
+            confirmed: false,
+            outbound: outbound,
+            format: fields::DataPacketOpcode::ResponsePacket,
+        })
+    }
+
+    pub fn outbound() -> Self { Self::new(true) }
+    pub fn inbound() -> Self { Self::new(false) }
+
+    /// Whether the packet is an outbound message.
+    pub fn is_outbound(&self) -> bool { self.0.outbound }
+}
+
+impl ByteField for ResponsePreamble {
+    fn byte(&self) -> u8 { self.0.byte() }
+}
+
+/// Preamble for multiblock trunking packet.
+pub struct TrunkingPreamble(HeaderPreamble);
+
+impl TrunkingPreamble {
+    fn new(outbound: bool) -> TrunkingPreamble {
+        TrunkingPreamble(HeaderPreamble {
+            confirmed: false,
+            outbound: outbound,
+            format: fields::DataPacketOpcode::TrunkingPacket,
+        })
+    }
+
+    pub fn outbound() -> Self { Self::new(true) }
+    pub fn inbound() -> Self { Self::new(false) }
+
+    /// Whether the packet is an outbound message.
+    pub fn is_outbound(&self) -> bool { self.0.outbound }
+}
+
+impl ByteField for TrunkingPreamble {
+    fn byte(&self) -> u8 { self.0.byte() }
+}
+
 /// Service access point (SAP) field.
 pub struct ServiceAccessPoint(pub fields::ServiceAccessPoint);
 
+impl ServiceAccessPoint {
+    /// Parse the field from the given byte, if it contains a known SAP.
+    pub fn from_byte(byte: u8) -> Option<ServiceAccessPoint> {
+        fields::ServiceAccessPoint::from_bits(byte & 0x3F).map(ServiceAccessPoint)
+    }
+}
+
 impl ByteField for ServiceAccessPoint {
     fn byte(&self) -> u8 {
         0b11000000 | self.0.to_bits()
@@ -105,6 +183,13 @@ impl ByteField for Manufacturer {
 /// Logical link ID field for addressing source or destination subscriber.
 pub struct LogicalLink(pub u32);
 
+impl LogicalLink {
+    /// Parse the field from the given 3 bytes.
+    pub fn from_bytes(bytes: &[u8]) -> LogicalLink {
+        LogicalLink(slice_u24(bytes))
+    }
+}
+
 impl BufWrite for LogicalLink {
     fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, mut buf: &'b mut T) {
         assert!(self.0 >> 24 == 0);
@@ -123,6 +208,16 @@ pub struct BlockCount {
     pub count: u8,
 }
 
+impl BlockCount {
+    /// Parse the fields from the given byte.
+    pub fn from_byte(byte: u8) -> BlockCount {
+        BlockCount {
+            full_pkt: byte >> 7 == 1,
+            count: byte & 0x7F,
+        }
+    }
+}
+
 impl ByteField for BlockCount {
     fn byte(&self) -> u8 {
         assert!(self.count >> 7 == 0);
@@ -133,6 +228,11 @@ impl ByteField for BlockCount {
 /// Number of pad bytes at the end of the data.
 pub struct PadCount(pub u8);
 
+impl PadCount {
+    /// Parse the field from the given byte.
+    pub fn from_byte(byte: u8) -> PadCount { PadCount(byte & 0x1F) }
+}
+
 impl ByteField for PadCount {
     fn byte(&self) -> u8 {
         assert!(self.0 >> 5 == 0);
@@ -151,6 +251,17 @@ pub struct Sequencing {
     pub frag_seq: u8,
 }
 
+impl Sequencing {
+    /// Parse the fields from the given byte.
+    pub fn from_byte(byte: u8) -> Sequencing {
+        Sequencing {
+            resync: byte >> 7 == 1,
+            pkt_seq: byte >> 4 & 0b111,
+            frag_seq: byte & 0xF,
+        }
+    }
+}
+
 impl ByteField for Sequencing {
     fn byte(&self) -> u8 {
         assert!(self.pkt_seq >> 3 == 0);
@@ -163,6 +274,11 @@ impl ByteField for Sequencing {
 /// Byte offset into data payload where data header stops and data information begins.
 pub struct DataOffset(pub u8);
 
+impl DataOffset {
+    /// Parse the field from the given byte.
+    pub fn from_byte(byte: u8) -> DataOffset { DataOffset(byte & 0x3F) }
+}
+
 impl ByteField for DataOffset {
     fn byte(&self) -> u8 {
         assert!(self.0 >> 6 == 0);
@@ -170,6 +286,51 @@ impl ByteField for DataOffset {
     }
 }
 
+/// Class, type, and status fields of a response packet.
+pub struct ResponseStatus {
+    /// Type of response.
+    pub response: fields::ResponseType,
+    /// N(R) field, the N(S) of the packet being responded to.
+    pub pkt_seq: u8,
+}
+
+impl ResponseStatus {
+    /// Parse the fields from the given byte, if it contains a known response type.
+    pub fn from_byte(byte: u8) -> Option<ResponseStatus> {
+        fields::ResponseType::from_bits(byte >> 6, byte >> 3 & 0b111).map(|r| {
+            ResponseStatus {
+                response: r,
+                pkt_seq: byte & 0b111,
+            }
+        })
+    }
+}
+
+impl ByteField for ResponseStatus {
+    fn byte(&self) -> u8 {
+        assert!(self.pkt_seq >> 3 == 0);
+
+        let (class, kind) = self.response.to_bits();
+        class << 6 | kind << 3 | self.pkt_seq
+    }
+}
+
+/// Opcode of the trunking message carried by a multiblock trunking packet, using the
+/// same 6-bit values as TSBK opcodes.
+pub struct TrunkingOpcode(pub u8);
+
+impl TrunkingOpcode {
+    /// Parse the field from the given byte.
+    pub fn from_byte(byte: u8) -> TrunkingOpcode { TrunkingOpcode(byte & 0x3F) }
+}
+
+impl ByteField for TrunkingOpcode {
+    fn byte(&self) -> u8 {
+        assert!(self.0 >> 6 == 0);
+        self.0
+    }
+}
+
 /// Header fields for confirmed packet.
 pub struct ConfirmedFields {
     pub preamble: ConfirmedPreamble,
@@ -182,6 +343,41 @@ pub struct ConfirmedFields {
     pub data_offset: DataOffset,
 }
 
+impl ConfirmedFields {
+    /// Parse the fields from the given 10 header bytes, returning `None` if the header
+    /// isn't a valid confirmed packet header.
+    pub fn from_bytes(bytes: &[u8]) -> Option<ConfirmedFields> {
+        assert!(bytes.len() == 10);
+
+        let preamble = match HeaderPreamble::from_byte(bytes[0]) {
+            Some(p) => p,
+            None => return None,
+        };
+
+        if !preamble.confirmed ||
+            preamble.format != fields::DataPacketOpcode::ConfirmedPacket
+        {
+            return None;
+        }
+
+        let sap = match ServiceAccessPoint::from_byte(bytes[1]) {
+            Some(sap) => sap,
+            None => return None,
+        };
+
+        Some(ConfirmedFields {
+            preamble: ConfirmedPreamble(preamble),
+            sap: sap,
+            mfg: Manufacturer(bytes[2]),
+            addr: LogicalLink::from_bytes(&bytes[3..6]),
+            blocks: BlockCount::from_byte(bytes[6]),
+            pads: PadCount::from_byte(bytes[7]),
+            seq: Sequencing::from_byte(bytes[8]),
+            data_offset: DataOffset::from_byte(bytes[9]),
+        })
+    }
+}
+
 impl BufWrite for ConfirmedFields {
     fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, mut buf: &'b mut T) {
         self.preamble.write(buf);
@@ -206,6 +402,40 @@ pub struct UnconfirmedFields {
     pub data_offset: DataOffset,
 }
 
+impl UnconfirmedFields {
+    /// Parse the fields from the given 10 header bytes, returning `None` if the header
+    /// isn't a valid unconfirmed packet header.
+    pub fn from_bytes(bytes: &[u8]) -> Option<UnconfirmedFields> {
+        assert!(bytes.len() == 10);
+
+        let preamble = match HeaderPreamble::from_byte(bytes[0]) {
+            Some(p) => p,
+            None => return None,
+        };
+
+        if preamble.confirmed ||
+            preamble.format != fields::DataPacketOpcode::UnconfirmedPacket
+        {
+            return None;
+        }
+
+        let sap = match ServiceAccessPoint::from_byte(bytes[1]) {
+            Some(sap) => sap,
+            None => return None,
+        };
+
+        Some(UnconfirmedFields {
+            preamble: UnconfirmedPreamble(preamble),
+            sap: sap,
+            mfg: Manufacturer(bytes[2]),
+            addr: LogicalLink::from_bytes(&bytes[3..6]),
+            blocks: BlockCount::from_byte(bytes[6]),
+            pads: PadCount::from_byte(bytes[7]),
+            data_offset: DataOffset::from_byte(bytes[9]),
+        })
+    }
+}
+
 impl BufWrite for UnconfirmedFields {
     fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, mut buf: &'b mut T) {
         self.preamble.write(buf);
@@ -219,6 +449,197 @@ impl BufWrite for UnconfirmedFields {
     }
 }
 
+/// Header fields for response packet.
+pub struct ResponseFields {
+    pub preamble: ResponsePreamble,
+    pub status: ResponseStatus,
+    pub mfg: Manufacturer,
+    pub addr: LogicalLink,
+    /// Number of data blocks in the packet.
+    pub blocks: u8,
+    /// Source subscriber, if included in the packet.
+    pub src: Option<LogicalLink>,
+}
+
+impl ResponseFields {
+    /// Parse the fields from the given 10 header bytes, returning `None` if the header
+    /// isn't a valid response packet header.
+    pub fn from_bytes(bytes: &[u8]) -> Option<ResponseFields> {
+        assert!(bytes.len() == 10);
+
+        let preamble = match HeaderPreamble::from_byte(bytes[0]) {
+            Some(p) => p,
+            None => return None,
+        };
+
+        if preamble.confirmed ||
+            preamble.format != fields::DataPacketOpcode::ResponsePacket
+        {
+            return None;
+        }
+
+        let status = match ResponseStatus::from_byte(bytes[1]) {
+            Some(s) => s,
+            None => return None,
+        };
+
+        Some(ResponseFields {
+            preamble: ResponsePreamble(preamble),
+            status: status,
+            mfg: Manufacturer(bytes[2]),
+            addr: LogicalLink::from_bytes(&bytes[3..6]),
+            blocks: bytes[6] & 0x7F,
+            // The X bit is set when the source address is omitted.
+            src: if bytes[6] >> 7 == 0 {
+                Some(LogicalLink::from_bytes(&bytes[7..10]))
+            } else {
+                None
+            },
+        })
+    }
+}
+
+impl BufWrite for ResponseFields {
+    fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, mut buf: &'b mut T) {
+        assert!(self.blocks >> 7 == 0);
+
+        self.preamble.write(buf);
+        self.status.write(buf);
+        self.mfg.write(buf);
+        self.addr.write(buf);
+
+        match self.src {
+            Some(ref src) => {
+                *buf.next().unwrap() = self.blocks;
+                src.write(buf);
+            },
+            None => {
+                *buf.next().unwrap() = 1 << 7 | self.blocks;
+                LogicalLink(0).write(buf);
+            },
+        }
+    }
+}
+
+/// Header fields for multiblock trunking packet.
+pub struct TrunkingFields {
+    pub preamble: TrunkingPreamble,
+    pub sap: ServiceAccessPoint,
+    pub mfg: Manufacturer,
+    /// Source or destination subscriber, depending on the opcode.
+    pub addr: LogicalLink,
+    pub blocks: BlockCount,
+    pub opcode: TrunkingOpcode,
+    /// Opcode-specific fields.
+    pub args: [u8; 2],
+}
+
+impl TrunkingFields {
+    /// Parse the fields from the given 10 header bytes, returning `None` if the header
+    /// isn't a valid multiblock trunking packet header.
+    pub fn from_bytes(bytes: &[u8]) -> Option<TrunkingFields> {
+        assert!(bytes.len() == 10);
+
+        let preamble = match HeaderPreamble::from_byte(bytes[0]) {
+            Some(p) => p,
+            None => return None,
+        };
+
+        if preamble.confirmed ||
+            preamble.format != fields::DataPacketOpcode::TrunkingPacket
+        {
+            return None;
+        }
+
+        let sap = match ServiceAccessPoint::from_byte(bytes[1]) {
+            Some(sap) => sap,
+            None => return None,
+        };
+
+        Some(TrunkingFields {
+            preamble: TrunkingPreamble(preamble),
+            sap: sap,
+            mfg: Manufacturer(bytes[2]),
+            addr: LogicalLink::from_bytes(&bytes[3..6]),
+            blocks: BlockCount::from_byte(bytes[6]),
+            opcode: TrunkingOpcode::from_byte(bytes[7]),
+            args: [bytes[8], bytes[9]],
+        })
+    }
+}
+
+impl BufWrite for TrunkingFields {
+    fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, mut buf: &'b mut T) {
+        self.preamble.write(buf);
+        self.sap.write(buf);
+        self.mfg.write(buf);
+        self.addr.write(buf);
+        self.blocks.write(buf);
+        self.opcode.write(buf);
+        *buf.next().unwrap() = self.args[0];
+        *buf.next().unwrap() = self.args[1];
+    }
+}
+
+/// Header fields for any type of data packet.
+pub enum DataHeader {
+    Confirmed(ConfirmedFields),
+    Unconfirmed(UnconfirmedFields),
+    Response(ResponseFields),
+    Trunking(TrunkingFields),
+}
+
+impl DataHeader {
+    /// Parse the fields from the given 10 header bytes, based on the packet type.
+    pub fn from_bytes(bytes: &[u8]) -> Option<DataHeader> {
+        use data::fields::DataPacketOpcode::*;
+
+        assert!(bytes.len() == 10);
+
+        match fields::DataPacketOpcode::from_bits(bytes[0] & 0x1F) {
+            Some(ConfirmedPacket) =>
+                ConfirmedFields::from_bytes(bytes).map(DataHeader::Confirmed),
+            Some(UnconfirmedPacket) =>
+                UnconfirmedFields::from_bytes(bytes).map(DataHeader::Unconfirmed),
+            Some(ResponsePacket) =>
+                ResponseFields::from_bytes(bytes).map(DataHeader::Response),
+            Some(TrunkingPacket) =>
+                TrunkingFields::from_bytes(bytes).map(DataHeader::Trunking),
+            None => None,
+        }
+    }
+
+    /// Number of data blocks that follow the header.
+    pub fn blocks(&self) -> usize {
+        match *self {
+            DataHeader::Confirmed(ref f) => f.blocks.count as usize,
+            DataHeader::Unconfirmed(ref f) => f.blocks.count as usize,
+            DataHeader::Response(ref f) => f.blocks as usize,
+            DataHeader::Trunking(ref f) => f.blocks.count as usize,
+        }
+    }
+
+    /// Number of pad bytes at the end of the packet data.
+    pub fn pads(&self) -> usize {
+        match *self {
+            DataHeader::Confirmed(ref f) => f.pads.0 as usize,
+            DataHeader::Unconfirmed(ref f) => f.pads.0 as usize,
+            DataHeader::Response(_) | DataHeader::Trunking(_) => 0,
+        }
+    }
+}
+
+impl BufWrite for DataHeader {
+    fn write<'a, 'b, T: Iterator<Item = &'a mut u8>>(&self, buf: &'b mut T) {
+        match *self {
+            DataHeader::Confirmed(ref f) => f.write(buf),
+            DataHeader::Unconfirmed(ref f) => f.write(buf),
+            DataHeader::Response(ref f) => f.write(buf),
+            DataHeader::Trunking(ref f) => f.write(buf),
+        }
+    }
+}
+
 /// Builds a checksummed header based on the given fields.
 pub struct Header<F: BufWrite>(F);
 
@@ -255,6 +676,26 @@ impl<F: BufWrite> Header<F> {
     }
 }
 
+impl Header<DataHeader> {
+    /// Parse the given received header block, made up of the fields followed by their
+    /// checksum. Return `Err(ChecksumMismatch)` if the checksum doesn't match and
+    /// `Err(UnknownDataHeader)` if the fields aren't recognized.
+    pub fn parse(buf: &[u8; 12]) -> Result<DataHeader> {
+        let (fields, checksum) = buf.split_at(10);
+
+        if crc::CRC16::new().feed_bytes(fields.iter().cloned()).finish() as u16 !=
+            slice_u16(checksum)
+        {
+            return Err(P25Error::ChecksumMismatch);
+        }
+
+        match DataHeader::from_bytes(fields) {
+            Some(h) => Ok(h),
+            None => Err(P25Error::UnknownDataHeader),
+        }
+    }
+}
+
 /// Convert the given Boolean to a single bit.
 fn bool_to_bit(b: bool) -> u8 {
     if b { 1 } else { 0 }
@@ -263,7 +704,8 @@ fn bool_to_bit(b: bool) -> u8 {
 #[cfg(test)]
 mod test {
     use super::*;
-    use data::fields;
+    use data::{crc, fields};
+    use error::P25Error;
 
     #[test]
     fn test_preamble() {
@@ -401,6 +843,226 @@ mod test {
         ]);
     }
 
+    #[test]
+    fn test_fields_from_bytes() {
+        let f = ConfirmedFields::from_bytes(&[
+            0b01110110,
+            0b11000100,
+            0b00010010,
+            0b00110100,
+            0b00100001,
+            0b00110100,
+            0b11111111,
+            0b00000011,
+            0b01010010,
+            0b00000000,
+        ]).unwrap();
+
+        assert!(f.preamble.is_outbound());
+        assert_eq!(f.sap.0, fields::ServiceAccessPoint::PacketData);
+        assert_eq!(f.mfg.0, 0x12);
+        assert_eq!(f.addr.0, 0x342134);
+        assert!(f.blocks.full_pkt);
+        assert_eq!(f.blocks.count, 127);
+        assert_eq!(f.pads.0, 3);
+        assert!(!f.seq.resync);
+        assert_eq!(f.seq.pkt_seq, 5);
+        assert_eq!(f.seq.frag_seq, 2);
+        assert_eq!(f.data_offset.0, 0);
+
+        let mut buf = [0; 10];
+        UnconfirmedFields {
+            preamble: UnconfirmedPreamble::inbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::Paging),
+            mfg: Manufacturer(0x90),
+            addr: LogicalLink(0xABCDEF),
+            blocks: BlockCount {
+                full_pkt: false,
+                count: 3,
+            },
+            pads: PadCount(10),
+            data_offset: DataOffset(5),
+        }.write(&mut buf.iter_mut());
+
+        let f = UnconfirmedFields::from_bytes(&buf).unwrap();
+
+        assert!(!f.preamble.is_outbound());
+        assert_eq!(f.sap.0, fields::ServiceAccessPoint::Paging);
+        assert_eq!(f.mfg.0, 0x90);
+        assert_eq!(f.addr.0, 0xABCDEF);
+        assert!(!f.blocks.full_pkt);
+        assert_eq!(f.blocks.count, 3);
+        assert_eq!(f.pads.0, 10);
+        assert_eq!(f.data_offset.0, 5);
+
+        // Wrong packet type for the fields.
+        assert!(ConfirmedFields::from_bytes(&buf).is_none());
+        // Unknown SAP.
+        buf[1] = 0b11111110;
+        assert!(UnconfirmedFields::from_bytes(&buf).is_none());
+    }
+
+    #[test]
+    fn test_response_fields() {
+        let s = ResponseStatus {
+            response: fields::ResponseType::Nack(fields::NackReason::PacketSequence),
+            pkt_seq: 6,
+        };
+        assert_eq!(s.byte(), 0b01101110);
+
+        let s = ResponseStatus::from_byte(0b00001011).unwrap();
+        assert_eq!(s.response, fields::ResponseType::Ack);
+        assert_eq!(s.pkt_seq, 3);
+
+        assert!(ResponseStatus::from_byte(0b11000000).is_none());
+
+        let mut buf = [0; 10];
+        ResponseFields {
+            preamble: ResponsePreamble::inbound(),
+            status: ResponseStatus {
+                response: fields::ResponseType::SelectiveAck,
+                pkt_seq: 2,
+            },
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: 1,
+            src: Some(LogicalLink(0xABCDEF)),
+        }.write(&mut buf.iter_mut());
+
+        assert_eq!(&buf, &[
+            0b00000011,
+            0b10000010,
+            0x00,
+            0x12,
+            0x34,
+            0x56,
+            0b00000001,
+            0xAB,
+            0xCD,
+            0xEF,
+        ]);
+
+        let f = ResponseFields::from_bytes(&buf).unwrap();
+        assert!(!f.preamble.is_outbound());
+        assert_eq!(f.status.response, fields::ResponseType::SelectiveAck);
+        assert_eq!(f.status.pkt_seq, 2);
+        assert_eq!(f.addr.0, 0x123456);
+        assert_eq!(f.blocks, 1);
+        assert_eq!(f.src.unwrap().0, 0xABCDEF);
+
+        buf[6] = 0b10000000;
+        let f = ResponseFields::from_bytes(&buf).unwrap();
+        assert_eq!(f.blocks, 0);
+        assert!(f.src.is_none());
+    }
+
+    #[test]
+    fn test_trunking_fields() {
+        let mut buf = [0; 10];
+        TrunkingFields {
+            preamble: TrunkingPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::TrunkingControl),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x00BEEF),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: 1,
+            },
+            opcode: TrunkingOpcode(0b111100),
+            args: [0x12, 0x34],
+        }.write(&mut buf.iter_mut());
+
+        assert_eq!(&buf, &[
+            0b00110111,
+            0b11111101,
+            0x00,
+            0x00,
+            0xBE,
+            0xEF,
+            0b10000001,
+            0b00111100,
+            0x12,
+            0x34,
+        ]);
+
+        let f = TrunkingFields::from_bytes(&buf).unwrap();
+        assert!(f.preamble.is_outbound());
+        assert_eq!(f.sap.0, fields::ServiceAccessPoint::TrunkingControl);
+        assert_eq!(f.addr.0, 0x00BEEF);
+        assert_eq!(f.blocks.count, 1);
+        assert_eq!(f.opcode.0, 0b111100);
+        assert_eq!(f.args, [0x12, 0x34]);
+    }
+
+    #[test]
+    fn test_parse() {
+        let (fields, checksum) = ConfirmedHeader::new(ConfirmedFields {
+            preamble: ConfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0x12),
+            addr: LogicalLink(0x342134),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: 127,
+            },
+            pads: PadCount(3),
+            seq: Sequencing {
+                resync: false,
+                pkt_seq: 5,
+                frag_seq: 2,
+            },
+            data_offset: DataOffset(0),
+        }).build();
+
+        let mut buf = [0; 12];
+        buf[..10].copy_from_slice(&fields);
+        buf[10..].copy_from_slice(&checksum);
+
+        match Header::parse(&buf) {
+            Ok(DataHeader::Confirmed(f)) => {
+                assert_eq!(f.addr.0, 0x342134);
+                assert_eq!(f.seq.pkt_seq, 5);
+            },
+            _ => panic!(),
+        }
+
+        let mut bad = buf;
+        bad[3] ^= 0b100;
+        assert_eq!(Header::parse(&bad).err(), Some(P25Error::ChecksumMismatch));
+
+        let (fields, checksum) = ResponseHeader::new(ResponseFields {
+            preamble: ResponsePreamble::outbound(),
+            status: ResponseStatus {
+                response: fields::ResponseType::Ack,
+                pkt_seq: 5,
+            },
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x342134),
+            blocks: 0,
+            src: None,
+        }).build();
+
+        buf[..10].copy_from_slice(&fields);
+        buf[10..].copy_from_slice(&checksum);
+
+        match Header::parse(&buf) {
+            Ok(DataHeader::Response(f)) => {
+                assert_eq!(f.status.response, fields::ResponseType::Ack);
+                assert_eq!(f.status.pkt_seq, 5);
+            },
+            _ => panic!(),
+        }
+
+        // Valid checksum but unknown packet type.
+        buf[0] = 0b00011111;
+
+        let checksum = crc::CRC16::new().feed_bytes(buf[..10].iter().cloned()).finish();
+        buf[10] = (checksum >> 8) as u8;
+        buf[11] = checksum as u8;
+
+        assert_eq!(Header::parse(&buf).err(), Some(P25Error::UnknownDataHeader));
+    }
+
     #[test]
     #[should_panic]
     fn test_ll_validate() {
@@ -272,13 +272,14 @@ impl Iterator for Interleaver {
     }
 }
 
-pub struct Deinterleaver<'a> {
-    dibits: &'a [bits::Dibit; consts::CODING_DIBITS],
+/// Borrows a hard or soft dibit buffer, yielding deinterleaved dibits.
+pub struct Deinterleaver<'a, T: 'a + Copy = bits::Dibit> {
+    dibits: &'a [T; consts::CODING_DIBITS],
     idx: Indexes<DeinterleaveRedirector>,
 }
 
-impl<'a> Deinterleaver<'a> {
-    pub fn new(dibits: &'a [bits::Dibit; consts::CODING_DIBITS]) -> Deinterleaver<'a> {
+impl<'a, T: Copy> Deinterleaver<'a, T> {
+    pub fn new(dibits: &'a [T; consts::CODING_DIBITS]) -> Deinterleaver<'a, T> {
         Deinterleaver {
             dibits: dibits,
             idx: Indexes::new(),
@@ -286,8 +287,8 @@ impl<'a> Deinterleaver<'a> {
     }
 }
 
-impl<'a> Iterator for Deinterleaver<'a> {
-    type Item = bits::Dibit;
+impl<'a, T: Copy> Iterator for Deinterleaver<'a, T> {
+    type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.idx.next().map(|idx| self.dibits[idx])
@@ -360,4 +361,22 @@ mod test {
 
         assert!(deint.next().is_none());
     }
+
+    #[test]
+    fn test_deinterleave_soft() {
+        let mut buf = [Dibit::default(); 98];
+        let mut soft = [SoftDibit::default(); 98];
+
+        for i in 0..98 {
+            buf[i] = Dibit::new(i as u8 % 4);
+            soft[i] = SoftDibit::new(i as f32);
+        }
+
+        let hard = Deinterleaver::new(&buf);
+        let soft = Deinterleaver::new(&soft);
+
+        for (h, s) in hard.zip(soft) {
+            assert_eq!(h.bits(), s.value() as u8 % 4);
+        }
+    }
 }
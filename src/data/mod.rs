@@ -9,16 +9,27 @@ pub mod interleave;
 pub mod packet;
 pub mod params;
 pub mod payload;
+pub mod receiver;
+pub mod session;
 
 pub use self::fragment::{ConfirmedFragments, UnconfirmedFragments};
 
 pub use self::header::{
+    DataHeader,
     ConfirmedHeader,
     ConfirmedFields,
     ConfirmedPreamble,
     UnconfirmedHeader,
     UnconfirmedFields,
     UnconfirmedPreamble,
+    ResponseHeader,
+    ResponseFields,
+    ResponsePreamble,
+    ResponseStatus,
+    TrunkingHeader,
+    TrunkingFields,
+    TrunkingPreamble,
+    TrunkingOpcode,
     ServiceAccessPoint,
     Manufacturer,
     LogicalLink,
@@ -32,3 +43,10 @@ pub use self::payload::{
     ConfirmedPayload,
     UnconfirmedPayload,
 };
+
+pub use self::receiver::{
+    ConfirmedBlock,
+    ConfirmedReassembler,
+    DataPacket,
+    DataPacketReceiver,
+};
@@ -2,33 +2,43 @@
 
 use bits;
 use data::{self, payload, coder, interleave};
+use data::header::{BufWrite, Header};
 
 /// Construct a confirmed data packet with the given header, payload blocks, and serial
 /// number generator. The returned value is the coded, interleaved set of dibit symbols
 /// that make up the packet.
 pub fn confirmed<S>(header: data::ConfirmedHeader, payload: data::ConfirmedPayload,
-                    mut sn: S)
+                    sn: S)
     -> Vec<bits::Dibit> where S: Iterator<Item = u8>
+{
+    confirmed_select(header, payload, sn, |_| true)
+}
+
+/// Construct a partial confirmed data packet, for selective retransmission, containing
+/// only the payload blocks whose serial number passes the given filter. The header should
+/// have its FMF cleared and its block count set to the number of selected blocks.
+pub fn confirmed_select<S, F>(header: data::ConfirmedHeader,
+                              payload: data::ConfirmedPayload, sn: S, filter: F)
+    -> Vec<bits::Dibit> where S: Iterator<Item = u8>, F: Fn(u8) -> bool
 {
     let mut pkt = vec![];
+    let mut sn = sn;
 
     // Add in the header.
-    pkt.extend({
-        let (fields, checksum) = header.build();
-
-        coder::DibitCoder::new()
-            .feed_bytes(fields.iter().cloned())
-            .feed_bytes(checksum.iter().cloned())
-            .finish()
-            .iter().cloned()
-    });
+    pkt.extend(header_block(header));
 
     // Add in the normal data blocks.
     for block in payload.iter() {
+        let sn = sn.next().unwrap();
+
+        if !filter(sn) {
+            continue;
+        }
+
         pkt.extend({
             let (data, pads) = block.build();
-            let header = payload::ConfirmedBlockHeader::new(sn.next().unwrap(),
-                data, pads.clone()).build();
+            let header = payload::ConfirmedBlockHeader::new(sn, data, pads.clone())
+                .build();
 
             interleave::Interleaver::new(coder::TribitCoder::new()
                 .feed_bytes(header.iter().cloned()
@@ -38,19 +48,23 @@ pub fn confirmed<S>(header: data::ConfirmedHeader, payload: data::ConfirmedPaylo
         });
     }
 
+    let sn = sn.next().unwrap();
+
     // Add in the tail block.
-    pkt.extend({
-        let (data, pads, checksum) = payload.tail().build();
-        let header = payload::ConfirmedBlockHeader::new(sn.next().unwrap(),
-            data, pads.clone()).build();
-
-        interleave::Interleaver::new(coder::TribitCoder::new()
-            .feed_bytes(header.iter().cloned()
-                .chain(data.iter().cloned())
-                .chain(pads.map(|_| 0))
-                .chain(checksum.iter().cloned()))
-            .finish())
-    });
+    if filter(sn) {
+        pkt.extend({
+            let (data, pads, checksum) = payload.tail().build();
+            let header = payload::ConfirmedBlockHeader::new(sn, data, pads.clone())
+                .build();
+
+            interleave::Interleaver::new(coder::TribitCoder::new()
+                .feed_bytes(header.iter().cloned()
+                    .chain(data.iter().cloned())
+                    .chain(pads.map(|_| 0))
+                    .chain(checksum.iter().cloned()))
+                .finish())
+        });
+    }
 
     pkt
 }
@@ -62,15 +76,42 @@ pub fn unconfirmed(header: data::UnconfirmedHeader, payload: data::UnconfirmedPa
 {
     let mut pkt = vec![];
 
-    pkt.extend({
-        let (fields, checksum) = header.build();
+    pkt.extend(header_block(header));
+    pkt.extend(unconfirmed_blocks(payload));
 
-        coder::DibitCoder::new()
-            .feed_bytes(fields.iter().cloned())
-            .feed_bytes(checksum.iter().cloned())
-            .finish()
-            .iter().cloned()
-    });
+    pkt
+}
+
+/// Construct a response packet with the given header and optional payload blocks, which
+/// use the same format as unconfirmed data packets. The returned value is the coded,
+/// interleaved set of dibit symbols that make up the packet.
+pub fn response(header: data::ResponseHeader, payload: Option<data::UnconfirmedPayload>)
+    -> Vec<bits::Dibit>
+{
+    let mut pkt = vec![];
+
+    pkt.extend(header_block(header));
+
+    if let Some(payload) = payload {
+        pkt.extend(unconfirmed_blocks(payload));
+    }
+
+    pkt
+}
+
+/// Code and interleave the given header.
+fn header_block<F: BufWrite>(header: Header<F>) -> interleave::Interleaver {
+    let (fields, checksum) = header.build();
+
+    interleave::Interleaver::new(coder::DibitCoder::new()
+        .feed_bytes(fields.iter().cloned())
+        .feed_bytes(checksum.iter().cloned())
+        .finish())
+}
+
+/// Code and interleave the given unconfirmed payload blocks.
+fn unconfirmed_blocks(payload: data::UnconfirmedPayload) -> Vec<bits::Dibit> {
+    let mut pkt = vec![];
 
     for block in payload.iter() {
         let (data, pads) = block.build();
@@ -95,3 +136,58 @@ pub fn unconfirmed(header: data::UnconfirmedHeader, payload: data::UnconfirmedPa
 
     pkt
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data::fields;
+    use data::header::*;
+    use data::payload::*;
+
+    fn header() -> data::UnconfirmedHeader {
+        UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: 1,
+            },
+            pads: PadCount(4),
+            data_offset: DataOffset(0),
+        })
+    }
+
+    #[test]
+    fn test_header_block() {
+        let (fields, checksum) = header().build();
+
+        assert_eq!(fields, [0x35, 0xC4, 0x00, 0x12, 0x34, 0x56, 0x81, 0x04, 0x00, 0x00]);
+        assert_eq!(checksum, [0x3D, 0xDE]);
+
+        let coded = coder::DibitCoder::new()
+            .feed_bytes(fields.iter().cloned())
+            .feed_bytes(checksum.iter().cloned())
+            .finish();
+
+        let pkt = unconfirmed(header(), UnconfirmedPayload::new(&[0xAA; 4][..]));
+        assert_eq!(pkt.len(), 196);
+
+        // The header block is interleaved like every other block: pairs of dibits are
+        // taken 8 apart, starting at offsets 0, 2, 4, and 6.
+        let order = [0, 2, 4, 6].iter().flat_map(|&off| {
+            (0..if off == 0 { 13 } else { 12 }).flat_map(move |k| {
+                vec![off + 8 * k, off + 8 * k + 1]
+            })
+        }).collect::<Vec<usize>>();
+
+        assert_eq!(order.len(), 98);
+
+        for (&d, &src) in pkt.iter().zip(order.iter()) {
+            assert_eq!(d, coded[src]);
+        }
+
+        assert!(pkt[..98] != coded[..]);
+    }
+}
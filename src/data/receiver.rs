@@ -0,0 +1,740 @@
+//! Receive data packets and reassemble their payloads.
+
+use std;
+
+use collect_slice::CollectSlice;
+
+use bits::{Dibit, DibitBytes, SoftDibit, Tribit, TribitBytes};
+use buffer::{Buffer, DataPayloadStorage};
+use coding::trellis;
+use consts::{CODING_DIBITS, DIBIT_BLOCK_BYTES, TRIBIT_BLOCK_BYTES};
+use data::{crc, interleave};
+use data::header::{DataHeader, Header};
+use data::payload::ConfirmedBlockHeader;
+use data::session::RetryFlags;
+use error::{Result, P25Error};
+use util::slice_u32;
+
+/// Number of bytes in the packet checksum at the end of the tail block.
+const CHECKSUM_BYTES: usize = 4;
+/// Number of data bytes in each confirmed block, following its block header.
+pub const CONFIRMED_DATA_BYTES: usize = TRIBIT_BLOCK_BYTES - 2;
+/// Number of possible 7-bit block serial numbers.
+const MAX_SERIALS: usize = 128;
+
+/// Coded block of buffered symbols.
+type Block = [SoftDibit; CODING_DIBITS];
+
+/// A fully received data packet.
+pub struct DataPacket {
+    /// Packet header.
+    pub header: DataHeader,
+    /// Data bytes carried by the packet, with pads and checksums removed. This is empty
+    /// for an incomplete confirmed packet, whose blocks must be reassembled with those
+    /// of its retries by a `ConfirmedReassembler`.
+    pub data: Vec<u8>,
+    /// Data blocks of a confirmed packet, in the order received, and empty for other
+    /// packets.
+    pub blocks: Vec<ConfirmedBlock>,
+}
+
+impl DataPacket {
+    /// Whether the packet carries its complete data: every packet other than a
+    /// confirmed packet that's a selective retry or had blocks that failed to decode.
+    pub fn complete(&self) -> bool {
+        match self.header {
+            DataHeader::Confirmed(ref f) =>
+                f.blocks.full_pkt && self.blocks.iter().all(|b| b.serial.is_some()),
+            _ => true,
+        }
+    }
+}
+
+/// Data block received in a confirmed packet.
+#[derive(Copy, Clone)]
+pub struct ConfirmedBlock {
+    /// Serial number of the block, or `None` if the block couldn't be decoded or its
+    /// checksum didn't match.
+    pub serial: Option<u8>,
+    /// Data and pad bytes carried by the block, followed by the packet checksum in the
+    /// tail block.
+    pub data: [u8; CONFIRMED_DATA_BYTES],
+}
+
+/// State machine for receiving a data packet.
+///
+/// The state machine consumes dibit symbols and performs the following steps:
+///
+/// 1. Buffer dibits until a full block's worth are available
+/// 2. Deinterleave and decode the 1/2-rate header block, verify its checksum, and parse
+///    its fields
+/// 3. Deinterleave and decode each following 3/4-rate (confirmed) or 1/2-rate (all
+///    others) data block, verifying any block checksums
+/// 4. Verify the packet checksum in the tail block and strip off pads
+///
+/// Each block is decoded with soft decisions if every symbol in the block was soft.
+///
+/// A confirmed block that fails to decode doesn't abort the packet. Instead, it's marked
+/// as invalid in the received packet, and the packet checksum is left to be verified
+/// once the blocks have been reassembled from any retries.
+pub struct DataPacketReceiver {
+    /// Current buffered dibits.
+    dibits: Buffer<DataPayloadStorage>,
+    /// Whether every buffered dibit was soft.
+    soft: bool,
+    /// Packet header, available after the first block is received.
+    header: Option<DataHeader>,
+    /// Number of data blocks remaining in the packet.
+    remain: usize,
+    /// Data, pad, and packet checksum bytes received so far.
+    data: Vec<u8>,
+    /// Confirmed blocks received so far.
+    blocks: Vec<ConfirmedBlock>,
+}
+
+impl DataPacketReceiver {
+    /// Create a new `DataPacketReceiver` in the initial state.
+    pub fn new() -> DataPacketReceiver {
+        DataPacketReceiver {
+            dibits: Buffer::new(DataPayloadStorage::new()),
+            soft: true,
+            header: None,
+            remain: 0,
+            data: vec![],
+            blocks: vec![],
+        }
+    }
+
+    /// Feed in a baseband symbol, possibly producing a complete data packet. Return
+    /// `Some(Ok(pkt))` if a packet was successfully received, `Some(Err(err))` if an
+    /// error occurred, and `None` in the case of no event.
+    pub fn feed(&mut self, dibit: Dibit) -> Option<Result<DataPacket>> {
+        self.soft = false;
+        self.feed_symbol(SoftDibit::ideal(dibit))
+    }
+
+    /// Feed in a soft baseband symbol, using the reliability of each symbol to correct
+    /// more errors in the convolutional codes. The return value has the same meaning as
+    /// for `feed`.
+    pub fn feed_soft(&mut self, s: SoftDibit) -> Option<Result<DataPacket>> {
+        self.feed_symbol(s)
+    }
+
+    /// Buffer the given symbol and handle the block once it's complete.
+    fn feed_symbol(&mut self, s: SoftDibit) -> Option<Result<DataPacket>> {
+        let buf = match self.dibits.feed(s) {
+            Some(buf) => *buf,
+            None => return None,
+        };
+
+        let soft = self.soft;
+        self.soft = true;
+
+        if let Err(err) = self.handle(&buf, soft) {
+            return Some(Err(err));
+        }
+
+        if self.remain == 0 {
+            Some(self.finish())
+        } else {
+            None
+        }
+    }
+
+    /// Decode the given coded block, using soft decisions if `soft` is set, and
+    /// accumulate its contents.
+    fn handle(&mut self, buf: &Block, soft: bool) -> Result<()> {
+        // Whether the packet is confirmed, and if so whether it's a full packet.
+        let confirmed = match self.header {
+            Some(DataHeader::Confirmed(ref f)) => Some(f.blocks.full_pkt),
+            Some(_) => None,
+            None => {
+                let header = match decode_dibits(buf, soft) {
+                    Ok(bytes) => Header::parse(&bytes),
+                    Err(err) => Err(err),
+                };
+
+                let header = match header {
+                    Ok(h) => h,
+                    Err(err) => return Err(err),
+                };
+
+                self.remain = header.blocks();
+                self.header = Some(header);
+
+                return Ok(());
+            },
+        };
+
+        let tail = self.remain == 1;
+        self.remain -= 1;
+
+        let full = match confirmed {
+            Some(full) => full,
+            None => return match decode_dibits(buf, soft) {
+                Ok(bytes) => {
+                    self.data.extend_from_slice(&bytes[..]);
+                    Ok(())
+                },
+                Err(err) => Err(err),
+            },
+        };
+
+        // The last block of a selective retry isn't necessarily the tail block.
+        let block = match decode_tribits(buf, soft) {
+            Ok(bytes) => confirmed_block(&bytes, if full { Some(tail) } else { None }),
+            Err(_) => ConfirmedBlock {
+                serial: None,
+                data: [0; CONFIRMED_DATA_BYTES],
+            },
+        };
+
+        self.data.extend_from_slice(&block.data[..]);
+        self.blocks.push(block);
+
+        Ok(())
+    }
+
+    /// Verify the packet checksum of a complete packet, strip off pads, and reset to the
+    /// initial state.
+    fn finish(&mut self) -> Result<DataPacket> {
+        let mut pkt = DataPacket {
+            header: self.header.take().expect("header not received"),
+            data: vec![],
+            blocks: std::mem::replace(&mut self.blocks, vec![]),
+        };
+
+        let data = std::mem::replace(&mut self.data, vec![]);
+
+        if !pkt.complete() {
+            return Ok(pkt);
+        }
+
+        pkt.data = match verify(data, pkt.header.blocks(), pkt.header.pads()) {
+            Ok(data) => data,
+            Err(err) => return Err(err),
+        };
+
+        Ok(pkt)
+    }
+}
+
+/// Reassembles a confirmed packet from the blocks of its initial transmission and any
+/// selective retries, placing each valid block by its serial number.
+///
+/// Packets are identified by their logical link ID and N(S), and a packet with a
+/// different identity discards the blocks collected so far. The total number of blocks
+/// is only known once a full packet has been received.
+pub struct ConfirmedReassembler {
+    /// Logical link ID and N(S) of the current packet.
+    id: Option<(u32, u8)>,
+    /// Number of blocks in the full packet, if known.
+    total: Option<usize>,
+    /// Number of pad bytes in the packet.
+    pads: usize,
+    /// Data of each block received successfully, indexed by serial number.
+    blocks: Vec<Option<[u8; CONFIRMED_DATA_BYTES]>>,
+}
+
+impl ConfirmedReassembler {
+    /// Create a new `ConfirmedReassembler` with no received blocks.
+    pub fn new() -> ConfirmedReassembler {
+        ConfirmedReassembler {
+            id: None,
+            total: None,
+            pads: 0,
+            blocks: vec![None; MAX_SERIALS],
+        }
+    }
+
+    /// Collect the valid blocks of the given packet. Return `Some(Ok(data))` with the
+    /// packet data, with pads and checksum removed, once every block has been received,
+    /// `Some(Err(err))` if the reassembled packet failed its checksum, and `None` if
+    /// blocks are still missing or the packet isn't a confirmed packet.
+    pub fn feed(&mut self, pkt: &DataPacket) -> Option<Result<Vec<u8>>> {
+        let fields = match pkt.header {
+            DataHeader::Confirmed(ref f) => f,
+            _ => return None,
+        };
+
+        let id = (fields.addr.0, fields.seq.pkt_seq);
+
+        if self.id != Some(id) {
+            self.reset();
+            self.id = Some(id);
+        }
+
+        if fields.blocks.full_pkt {
+            self.total = Some(fields.blocks.count as usize);
+        }
+
+        self.pads = fields.pads.0 as usize;
+
+        for block in pkt.blocks.iter() {
+            if let Some(sn) = block.serial {
+                self.blocks[sn as usize] = Some(block.data);
+            }
+        }
+
+        let total = match self.total {
+            Some(total) => total,
+            None => return None,
+        };
+
+        if self.blocks[..total].iter().any(|b| b.is_none()) {
+            return None;
+        }
+
+        let mut data = vec![];
+
+        for block in self.blocks[..total].iter() {
+            data.extend_from_slice(&block.unwrap()[..]);
+        }
+
+        let pads = self.pads;
+        self.reset();
+
+        Some(verify(data, total, pads))
+    }
+
+    /// Blocks of the current packet that haven't been received successfully, for a
+    /// selective retry response, or `None` if the full packet hasn't been received.
+    pub fn missing(&self) -> Option<RetryFlags> {
+        let total = match self.total {
+            Some(total) => total,
+            None => return None,
+        };
+
+        let mut flags = RetryFlags::new();
+
+        for (sn, block) in self.blocks[..total].iter().enumerate() {
+            if block.is_none() {
+                flags.set(sn as u8);
+            }
+        }
+
+        Some(flags)
+    }
+
+    /// Discard the current packet.
+    fn reset(&mut self) {
+        self.id = None;
+        self.total = None;
+        self.pads = 0;
+
+        for block in self.blocks.iter_mut() {
+            *block = None;
+        }
+    }
+}
+
+/// Parse the given decoded confirmed block and verify its checksum. The tail block's
+/// checksum covers its data and pads but not the packet checksum, so if it's unknown
+/// whether the block is the tail block, either coverage is accepted.
+fn confirmed_block(bytes: &[u8; TRIBIT_BLOCK_BYTES], tail: Option<bool>)
+    -> ConfirmedBlock
+{
+    let (header, body) = bytes.split_at(2);
+    let sn = header[0] >> 1;
+
+    let matches = |covered: &[u8]| {
+        ConfirmedBlockHeader::new(sn, covered, 0..0).build() == header
+    };
+
+    let without_checksum = &body[..body.len() - CHECKSUM_BYTES];
+
+    let valid = match tail {
+        Some(true) => matches(without_checksum),
+        Some(false) => matches(body),
+        None => matches(body) || matches(without_checksum),
+    };
+
+    let mut data = [0; CONFIRMED_DATA_BYTES];
+    body.iter().cloned().collect_slice_checked(&mut data[..]);
+
+    ConfirmedBlock {
+        serial: if valid { Some(sn) } else { None },
+        data: data,
+    }
+}
+
+/// Verify the packet checksum at the end of the given data from a packet with the given
+/// number of blocks, and strip it off along with the given number of pads.
+fn verify(data: Vec<u8>, blocks: usize, pads: usize) -> Result<Vec<u8>> {
+    let mut data = data;
+
+    // Packets without any data blocks have no packet checksum.
+    if blocks == 0 {
+        return Ok(data);
+    }
+
+    let split = data.len() - CHECKSUM_BYTES;
+    let checksum = slice_u32(&data[split..]);
+
+    data.truncate(split);
+
+    if crc::CRC32::new().feed_bytes(data.iter().cloned()).finish() as u32 != checksum {
+        return Err(P25Error::ChecksumMismatch);
+    }
+
+    if pads > data.len() {
+        return Err(P25Error::UnknownDataHeader);
+    }
+
+    let len = data.len() - pads;
+    data.truncate(len);
+
+    Ok(data)
+}
+
+/// Deinterleave and decode the given 1/2-rate coded block, using soft decisions if `soft`
+/// is set.
+fn decode_dibits(buf: &Block, soft: bool) -> Result<[u8; DIBIT_BLOCK_BYTES]> {
+    let deint = interleave::Deinterleaver::new(buf);
+
+    if soft {
+        dibit_bytes(deint)
+    } else {
+        dibit_bytes(deint.map(|s| s.hard()))
+    }
+}
+
+/// Deinterleave and decode the given 3/4-rate coded block, using soft decisions if `soft`
+/// is set.
+fn decode_tribits(buf: &Block, soft: bool) -> Result<[u8; TRIBIT_BLOCK_BYTES]> {
+    let deint = interleave::Deinterleaver::new(buf);
+
+    if soft {
+        tribit_bytes(deint)
+    } else {
+        tribit_bytes(deint.map(|s| s.hard()))
+    }
+}
+
+/// Decode the given deinterleaved 1/2-rate coded hard or soft dibits.
+fn dibit_bytes<T>(src: T) -> Result<[u8; DIBIT_BLOCK_BYTES]> where
+    T: Iterator, T::Item: trellis::TrellisSymbol
+{
+    let mut dibits = [Dibit::default(); DIBIT_BLOCK_BYTES * 4];
+    let count = trellis::DibitDecoder::new(src)
+        .filter_map(|x| x.ok())
+        .collect_slice_exhaust(&mut dibits[..]);
+
+    if count != dibits.len() {
+        return Err(P25Error::ViterbiUnrecoverable);
+    }
+
+    let mut bytes = [0; DIBIT_BLOCK_BYTES];
+    DibitBytes::new(dibits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
+
+    Ok(bytes)
+}
+
+/// Decode the given deinterleaved 3/4-rate coded hard or soft dibits.
+fn tribit_bytes<T>(src: T) -> Result<[u8; TRIBIT_BLOCK_BYTES]> where
+    T: Iterator, T::Item: trellis::TrellisSymbol
+{
+    let mut tribits = [Tribit::default(); TRIBIT_BLOCK_BYTES * 8 / 3];
+    let count = trellis::TribitDecoder::new(src)
+        .filter_map(|x| x.ok())
+        .collect_slice_exhaust(&mut tribits[..]);
+
+    if count != tribits.len() {
+        return Err(P25Error::ViterbiUnrecoverable);
+    }
+
+    let mut bytes = [0; TRIBIT_BLOCK_BYTES];
+    TribitBytes::new(tribits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data::{fields, packet};
+    use data::header::*;
+    use data::payload::*;
+
+    fn receive(dibits: &[Dibit]) -> Result<DataPacket> {
+        let mut recv = DataPacketReceiver::new();
+        let (last, rest) = dibits.split_last().unwrap();
+
+        for &d in rest {
+            assert!(recv.feed(d).is_none());
+        }
+
+        recv.feed(*last).unwrap()
+    }
+
+    #[test]
+    fn test_unconfirmed() {
+        let bytes: Vec<u8> = (0..25).collect();
+        let payload = UnconfirmedPayload::new(&bytes[..]);
+
+        let dibits = packet::unconfirmed(UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            data_offset: DataOffset(0),
+        }), payload);
+
+        let pkt = receive(&dibits[..]).unwrap();
+
+        match pkt.header {
+            DataHeader::Unconfirmed(ref f) => {
+                assert_eq!(f.addr.0, 0x123456);
+                assert_eq!(f.blocks.count, 3);
+                assert_eq!(f.pads.0, 7);
+            },
+            _ => panic!(),
+        }
+
+        assert_eq!(pkt.data, bytes);
+    }
+
+    #[test]
+    fn test_confirmed() {
+        let bytes: Vec<u8> = (0..40).map(|b| b * 3).collect();
+        let payload = ConfirmedPayload::new(&bytes[..]);
+
+        let mut dibits = packet::confirmed(ConfirmedHeader::new(ConfirmedFields {
+            preamble: ConfirmedPreamble::inbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0xABCDEF),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            seq: Sequencing {
+                resync: false,
+                pkt_seq: 3,
+                frag_seq: 0,
+            },
+            data_offset: DataOffset(0),
+        }), payload, 0..);
+
+        // Introduce some correctable errors.
+        dibits[10] = Dibit::new(dibits[10].bits() ^ 0b11);
+        dibits[150] = Dibit::new(dibits[150].bits() ^ 0b01);
+
+        let pkt = receive(&dibits[..]).unwrap();
+
+        match pkt.header {
+            DataHeader::Confirmed(ref f) => {
+                assert_eq!(f.addr.0, 0xABCDEF);
+                assert_eq!(f.blocks.count, 3);
+                assert_eq!(f.seq.pkt_seq, 3);
+            },
+            _ => panic!(),
+        }
+
+        assert!(pkt.complete());
+        assert_eq!(pkt.data, bytes);
+        assert_eq!(pkt.blocks.iter().map(|b| b.serial).collect::<Vec<_>>(),
+                   vec![Some(0), Some(1), Some(2)]);
+    }
+
+    #[test]
+    fn test_selective_retry() {
+        let bytes: Vec<u8> = (0..60).map(|b| b * 3).collect();
+
+        let header = |full_pkt, count| ConfirmedHeader::new(ConfirmedFields {
+            preamble: ConfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: full_pkt,
+                count: count,
+            },
+            pads: PadCount(ConfirmedPayload::new(&bytes[..]).pads() as u8),
+            seq: Sequencing {
+                resync: false,
+                pkt_seq: 5,
+                frag_seq: 0,
+            },
+            data_offset: DataOffset(0),
+        });
+
+        let payload = ConfirmedPayload::new(&bytes[..]);
+        assert_eq!(payload.blocks(), 4);
+
+        let mut dibits = packet::confirmed(header(true, 4), payload, 0..);
+
+        // Corrupt the second block and the tail block beyond correction.
+        for &block in [2, 4].iter() {
+            for d in dibits[block * 98..block * 98 + 40].iter_mut() {
+                *d = Dibit::new(d.bits() ^ 0b10);
+            }
+        }
+
+        // The packet is still received, with the invalid blocks marked.
+        let pkt = receive(&dibits[..]).unwrap();
+
+        assert!(!pkt.complete());
+        assert!(pkt.data.is_empty());
+        assert_eq!(pkt.blocks.iter().map(|b| b.serial).collect::<Vec<_>>(),
+                   vec![Some(0), None, Some(2), None]);
+
+        let mut asm = ConfirmedReassembler::new();
+        assert!(asm.feed(&pkt).is_none());
+
+        let missing = asm.missing().unwrap();
+        assert_eq!(missing.count(), 2);
+        assert!(missing.get(1));
+        assert!(missing.get(3));
+
+        // The retry carries only the missing blocks, including the tail block, and its
+        // packet checksum can't be verified on its own.
+        let dibits = packet::confirmed_select(header(false, 2),
+                                              ConfirmedPayload::new(&bytes[..]), 0..,
+                                              |sn| missing.get(sn));
+        assert_eq!(dibits.len(), 3 * 98);
+
+        let pkt = receive(&dibits[..]).unwrap();
+
+        assert!(!pkt.complete());
+        assert_eq!(pkt.blocks.iter().map(|b| b.serial).collect::<Vec<_>>(),
+                   vec![Some(1), Some(3)]);
+
+        assert_eq!(asm.feed(&pkt).unwrap().unwrap(), bytes);
+        assert!(asm.missing().is_none());
+    }
+
+    #[test]
+    fn test_checksum() {
+        let bytes = [0xAA; 12];
+        let payload = UnconfirmedPayload::new(&bytes[..]);
+
+        let mut dibits = packet::unconfirmed(UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            data_offset: DataOffset(0),
+        }), payload);
+
+        // Replace the first data block with a validly-coded block of different data.
+        let bad = interleave::Interleaver::new(::data::coder::DibitCoder::new()
+            .feed_bytes((0..12).map(|_| 0x55))
+            .finish());
+
+        for (dst, src) in dibits[98..196].iter_mut().zip(bad) {
+            *dst = src;
+        }
+
+        assert_eq!(receive(&dibits[..]).err(), Some(P25Error::ChecksumMismatch));
+    }
+
+    #[test]
+    fn test_header_checksum() {
+        let fields = || UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: 1,
+            },
+            pads: PadCount(4),
+            data_offset: DataOffset(0),
+        };
+
+        let (bytes, checksum) = UnconfirmedHeader::new(fields()).build();
+
+        // Code the header with a corrupted checksum, so the trellis decoder succeeds
+        // but the checksum doesn't match.
+        let dibits = interleave::Interleaver::new(::data::coder::DibitCoder::new()
+            .feed_bytes(bytes.iter().cloned())
+            .feed_bytes([checksum[0], checksum[1] ^ 0x01].iter().cloned())
+            .finish())
+            .collect::<Vec<Dibit>>();
+
+        let mut recv = DataPacketReceiver::new();
+        let (last, rest) = dibits.split_last().unwrap();
+
+        for &d in rest {
+            assert!(recv.feed(d).is_none());
+        }
+
+        assert_eq!(recv.feed(*last).unwrap().err(), Some(P25Error::ChecksumMismatch));
+
+        // The receiver starts over with the next block.
+        let dibits = packet::unconfirmed(UnconfirmedHeader::new(fields()),
+                                         UnconfirmedPayload::new(&[0xAA; 4][..]));
+        assert_eq!(receive(&dibits[..]).unwrap().data, vec![0xAA; 4]);
+    }
+
+    #[test]
+    fn test_receive_soft() {
+        let bytes: Vec<u8> = (0..12).collect();
+        let payload = UnconfirmedPayload::new(&bytes[..]);
+
+        let dibits = packet::unconfirmed(UnconfirmedHeader::new(UnconfirmedFields {
+            preamble: UnconfirmedPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::PacketData),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: payload.blocks() as u8,
+            },
+            pads: PadCount(payload.pads() as u8),
+            data_offset: DataOffset(0),
+        }), payload);
+
+        // Find where each coded dibit lands in an interleaved block.
+        let mut pos = [SoftDibit::default(); CODING_DIBITS];
+
+        for (i, p) in pos.iter_mut().enumerate() {
+            *p = SoftDibit::new(i as f32);
+        }
+
+        let order = interleave::Deinterleaver::new(&pos)
+            .map(|s| s.value() as usize)
+            .collect::<Vec<usize>>();
+
+        // Push a burst of coded symbols in each block just over the threshold toward
+        // zero, so their hard decisions are wrong but they're still closest to the
+        // transmitted symbol.
+        let mut soft = dibits.iter().map(|&d| SoftDibit::ideal(d)).collect::<Vec<_>>();
+
+        for block in soft.chunks_mut(CODING_DIBITS) {
+            for &i in &order[4..10] {
+                let v = block[i].value();
+                block[i] = SoftDibit::new(if v > 0.0 { v - 1.1 } else { v + 1.1 });
+            }
+        }
+
+        let mut recv = DataPacketReceiver::new();
+
+        assert!(soft.iter().filter_map(|s| recv.feed(s.hard())).all(|r| match r {
+            Ok(pkt) => pkt.data != bytes,
+            Err(_) => true,
+        }));
+
+        let mut recv = DataPacketReceiver::new();
+        let (last, rest) = soft.split_last().unwrap();
+
+        for &s in rest {
+            assert!(recv.feed_soft(s).is_none());
+        }
+
+        assert_eq!(recv.feed_soft(*last).unwrap().unwrap().data, bytes);
+    }
+}
@@ -0,0 +1,506 @@
+//! Confirmed data delivery, covering packet sequencing, response handling, and selective
+//! retransmission of failed blocks.
+//!
+//! A message is split into one or more fragments, each sent as a confirmed data packet.
+//! The recipient replies to each packet with a response packet, which either
+//! acknowledges the whole packet, rejects it, or requests that some of its blocks be
+//! retransmitted.
+
+use bits::Dibit;
+use data::{fields, packet};
+use data::fragment::ConfirmedFragments;
+use data::params::{PacketParams, ConfirmedParams, UnconfirmedParams};
+use util::div_ceil;
+
+use data::header::{
+    BlockCount,
+    ConfirmedFields,
+    ConfirmedHeader,
+    ConfirmedPreamble,
+    DataOffset,
+    Header,
+    LogicalLink,
+    Manufacturer,
+    PadCount,
+    ResponseFields,
+    Sequencing,
+    ServiceAccessPoint,
+};
+
+use data::payload::{ConfirmedPayload, UnconfirmedPayload};
+
+/// Number of bytes needed to hold a flag for every possible block serial number.
+const FLAG_BYTES: usize = 16;
+
+/// Flags for the blocks of a confirmed packet that need to be retransmitted, indexed by
+/// 7-bit block serial number.
+///
+/// In a selective retry response, these are carried MSB-first in the data blocks
+/// following the header.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RetryFlags([u8; FLAG_BYTES]);
+
+impl RetryFlags {
+    /// Create a new `RetryFlags` with no blocks flagged.
+    pub fn new() -> RetryFlags { RetryFlags([0; FLAG_BYTES]) }
+
+    /// Create a new `RetryFlags` with the first `blocks` serial numbers flagged.
+    pub fn all(blocks: usize) -> RetryFlags {
+        let mut flags = RetryFlags::new();
+
+        for sn in 0..blocks {
+            flags.set(sn as u8);
+        }
+
+        flags
+    }
+
+    /// Parse flags from the given response packet data.
+    pub fn from_bytes(bytes: &[u8]) -> RetryFlags {
+        let mut flags = RetryFlags::new();
+
+        for (dst, &src) in flags.0.iter_mut().zip(bytes.iter()) {
+            *dst = src;
+        }
+
+        flags
+    }
+
+    /// Flag the block with the given serial number.
+    pub fn set(&mut self, sn: u8) {
+        assert!(sn >> 7 == 0);
+        self.0[sn as usize / 8] |= 0x80 >> (sn % 8);
+    }
+
+    /// Remove the flag from the block with the given serial number.
+    pub fn clear(&mut self, sn: u8) {
+        assert!(sn >> 7 == 0);
+        self.0[sn as usize / 8] &= !(0x80 >> (sn % 8));
+    }
+
+    /// Check if the block with the given serial number is flagged.
+    pub fn get(&self, sn: u8) -> bool {
+        assert!(sn >> 7 == 0);
+        self.0[sn as usize / 8] & 0x80 >> (sn % 8) != 0
+    }
+
+    /// Number of flagged blocks.
+    pub fn count(&self) -> usize {
+        self.0.iter().map(|b| b.count_ones() as usize).sum()
+    }
+
+    /// Get the bytes needed to hold the flags for a packet with the given number of
+    /// blocks.
+    pub fn bytes(&self, blocks: usize) -> &[u8] {
+        &self.0[..div_ceil(blocks, 8)]
+    }
+
+    /// Keep only the flags that are also set in the given flags.
+    fn intersect(&mut self, other: &RetryFlags) {
+        for (dst, &src) in self.0.iter_mut().zip(other.0.iter()) {
+            *dst &= src;
+        }
+    }
+}
+
+/// Construct the response packet for the given header, with any selective retry flags
+/// carried in the payload. The header's block count must match the number of blocks
+/// needed for the flags.
+pub fn response(header: ResponseFields, flags: Option<(&RetryFlags, usize)>)
+    -> Vec<Dibit>
+{
+    packet::response(Header::new(header), flags.map(|(flags, blocks)| {
+        UnconfirmedPayload::new(flags.bytes(blocks))
+    }))
+}
+
+/// Number of payload blocks needed for a selective retry response covering a packet
+/// with the given number of blocks.
+pub fn response_blocks(blocks: usize) -> usize {
+    UnconfirmedParams::blocks(div_ceil(blocks, 8))
+}
+
+/// Outcome of handling a response packet.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DeliveryStatus {
+    /// The packet was fully received.
+    Delivered,
+    /// Some or all of the packet's blocks must be retransmitted.
+    Retry,
+    /// The packet was rejected for the given reason.
+    Rejected(fields::NackReason),
+    /// The response wasn't for the current packet.
+    Ignored,
+}
+
+/// Tracks packet sequence numbers across confirmed deliveries to a single subscriber.
+pub struct ConfirmedSession {
+    /// Whether the packets are outbound messages.
+    outbound: bool,
+    /// Destination service.
+    sap: fields::ServiceAccessPoint,
+    /// Manufacturer's ID.
+    mfg: u8,
+    /// Logical link ID of the subscriber.
+    addr: u32,
+    /// N(S) of the next new packet.
+    pkt_seq: u8,
+    /// Whether the next packet should resynchronize the recipient's sequence numbers.
+    resync: bool,
+}
+
+impl ConfirmedSession {
+    /// Create a new `ConfirmedSession` for delivering packets to/from the given
+    /// subscriber.
+    pub fn new(outbound: bool, sap: fields::ServiceAccessPoint, mfg: u8, addr: u32)
+        -> ConfirmedSession
+    {
+        assert!(addr >> 24 == 0);
+
+        ConfirmedSession {
+            outbound: outbound,
+            sap: sap,
+            mfg: mfg,
+            addr: addr,
+            pkt_seq: 0,
+            resync: true,
+        }
+    }
+
+    /// Ask the recipient to resynchronize its sequence numbers on the next packet, for
+    /// example after an out-of-sequence rejection.
+    pub fn resync(&mut self) { self.resync = true; }
+
+    /// Split the given message into fragments and create a packet transfer for each, in
+    /// the order they should be sent.
+    pub fn message<'a>(&mut self, data: &'a [u8]) -> Vec<ConfirmedTransfer<'a>> {
+        let frags: Vec<&[u8]> = if data.is_empty() {
+            vec![data]
+        } else {
+            ConfirmedFragments::new(data).collect()
+        };
+
+        let count = frags.len();
+
+        frags.into_iter().enumerate().map(|(i, frag)| {
+            let frag_seq = frag_seq(i, count);
+            self.transfer(frag, frag_seq)
+        }).collect()
+    }
+
+    /// Create a transfer for a single packet with the given data and FSNF.
+    fn transfer<'a>(&mut self, data: &'a [u8], frag_seq: u8) -> ConfirmedTransfer<'a> {
+        let seq = (self.resync, self.pkt_seq, frag_seq);
+
+        // N(S) rolls over at 3 bits.
+        self.pkt_seq = (self.pkt_seq + 1) % 8;
+        self.resync = false;
+
+        ConfirmedTransfer {
+            outbound: self.outbound,
+            sap: self.sap,
+            mfg: self.mfg,
+            addr: self.addr,
+            seq: seq,
+            data: data,
+            pending: RetryFlags::all(ConfirmedParams::blocks(data.len())),
+        }
+    }
+}
+
+/// Calculate the FSNF for the fragment at the given index out of the given number of
+/// fragments.
+fn frag_seq(idx: usize, count: usize) -> u8 {
+    // Unfragmented messages have a zero FSNF.
+    if count == 1 {
+        return 0;
+    }
+
+    // The first fragment is always numbered 0, and following fragments roll over from 7
+    // to 1.
+    let seq = if idx == 0 { 0 } else { ((idx - 1) % 7 + 1) as u8 };
+
+    // Flag the last fragment.
+    if idx + 1 == count {
+        0b1000 | seq
+    } else {
+        seq
+    }
+}
+
+/// A single confirmed packet being delivered.
+pub struct ConfirmedTransfer<'a> {
+    /// Whether the packet is an outbound message.
+    outbound: bool,
+    /// Destination service.
+    sap: fields::ServiceAccessPoint,
+    /// Manufacturer's ID.
+    mfg: u8,
+    /// Logical link ID of the subscriber.
+    addr: u32,
+    /// Syn, N(S), and FSNF fields.
+    seq: (bool, u8, u8),
+    /// Data carried by the packet.
+    data: &'a [u8],
+    /// Blocks that haven't yet been received successfully.
+    pending: RetryFlags,
+}
+
+impl<'a> ConfirmedTransfer<'a> {
+    /// N(S) of the packet.
+    pub fn pkt_seq(&self) -> u8 { self.seq.1 }
+
+    /// FSNF of the packet.
+    pub fn frag_seq(&self) -> u8 { self.seq.2 }
+
+    /// Blocks that haven't yet been received successfully.
+    pub fn pending(&self) -> &RetryFlags { &self.pending }
+
+    /// Construct the full packet, for the initial transmission.
+    pub fn packet(&self) -> Vec<Dibit> {
+        let payload = ConfirmedPayload::new(self.data);
+        let blocks = payload.blocks();
+
+        packet::confirmed(self.header(true, blocks), payload, 0..)
+    }
+
+    /// Construct a packet containing only the blocks that haven't yet been received
+    /// successfully.
+    pub fn retry(&self) -> Vec<Dibit> {
+        let payload = ConfirmedPayload::new(self.data);
+        let blocks = payload.blocks();
+
+        // A retransmission of every block is a complete packet.
+        let full = self.pending.count() == blocks;
+        let header = self.header(full, self.pending.count());
+        let pending = self.pending;
+
+        packet::confirmed_select(header, payload, 0.., |sn| pending.get(sn))
+    }
+
+    /// Update the delivery state based on the given response packet header and any data
+    /// carried with it.
+    pub fn handle(&mut self, resp: &ResponseFields, data: &[u8]) -> DeliveryStatus {
+        use data::fields::ResponseType::*;
+        use data::fields::NackReason::*;
+
+        if resp.addr.0 != self.addr || resp.status.pkt_seq != self.pkt_seq() {
+            return DeliveryStatus::Ignored;
+        }
+
+        match resp.status.response {
+            Ack => {
+                self.pending = RetryFlags::new();
+                DeliveryStatus::Delivered
+            },
+            Nack(PacketChecksum) => {
+                self.pending = RetryFlags::all(ConfirmedParams::blocks(self.data.len()));
+                DeliveryStatus::Retry
+            },
+            Nack(reason) => DeliveryStatus::Rejected(reason),
+            SelectiveAck => {
+                self.pending.intersect(&RetryFlags::from_bytes(data));
+
+                if self.pending.count() == 0 {
+                    DeliveryStatus::Delivered
+                } else {
+                    DeliveryStatus::Retry
+                }
+            },
+        }
+    }
+
+    /// Build the packet header with the given FMF and block count.
+    fn header(&self, full_pkt: bool, blocks: usize) -> ConfirmedHeader {
+        let (resync, pkt_seq, frag_seq) = self.seq;
+
+        ConfirmedHeader::new(ConfirmedFields {
+            preamble: if self.outbound {
+                ConfirmedPreamble::outbound()
+            } else {
+                ConfirmedPreamble::inbound()
+            },
+            sap: ServiceAccessPoint(self.sap),
+            mfg: Manufacturer(self.mfg),
+            addr: LogicalLink(self.addr),
+            blocks: BlockCount {
+                full_pkt: full_pkt,
+                count: blocks as u8,
+            },
+            pads: PadCount(ConfirmedParams::pads(self.data.len()) as u8),
+            seq: Sequencing {
+                resync: resync,
+                pkt_seq: pkt_seq,
+                frag_seq: frag_seq,
+            },
+            data_offset: DataOffset(0),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data::fields::{ResponseType, NackReason, ServiceAccessPoint};
+    use data::header::*;
+    use data::receiver::DataPacketReceiver;
+
+    fn resp(response: ResponseType, pkt_seq: u8, blocks: u8) -> ResponseFields {
+        ResponseFields {
+            preamble: ResponsePreamble::inbound(),
+            status: ResponseStatus {
+                response: response,
+                pkt_seq: pkt_seq,
+            },
+            mfg: Manufacturer(0),
+            addr: LogicalLink(0x123456),
+            blocks: blocks,
+            src: None,
+        }
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut f = RetryFlags::new();
+        assert_eq!(f.count(), 0);
+
+        f.set(0);
+        f.set(9);
+        f.set(127);
+
+        assert!(f.get(0));
+        assert!(!f.get(1));
+        assert!(f.get(9));
+        assert!(f.get(127));
+        assert_eq!(f.count(), 3);
+        assert_eq!(f.bytes(10), &[0b10000000, 0b01000000]);
+
+        f.clear(9);
+        assert!(!f.get(9));
+
+        let f = RetryFlags::from_bytes(&[0b00100000, 0b00000001]);
+        assert!(f.get(2));
+        assert!(f.get(15));
+        assert_eq!(f.count(), 2);
+
+        assert_eq!(RetryFlags::all(10).count(), 10);
+        assert!(!RetryFlags::all(10).get(10));
+    }
+
+    #[test]
+    fn test_frag_seq() {
+        assert_eq!(frag_seq(0, 1), 0);
+
+        assert_eq!(frag_seq(0, 2), 0b0000);
+        assert_eq!(frag_seq(1, 2), 0b1001);
+
+        assert_eq!(frag_seq(0, 10), 0);
+        assert_eq!(frag_seq(1, 10), 1);
+        assert_eq!(frag_seq(7, 10), 7);
+        assert_eq!(frag_seq(8, 10), 1);
+        assert_eq!(frag_seq(9, 10), 0b1010);
+    }
+
+    #[test]
+    fn test_sequencing() {
+        let mut s = ConfirmedSession::new(true, ServiceAccessPoint::PacketData, 0,
+                                          0x123456);
+
+        let data = [0; 5000];
+        let t = s.message(&data[..]);
+
+        assert_eq!(t.len(), 3);
+        assert_eq!(t[0].seq, (true, 0, 0b0000));
+        assert_eq!(t[1].seq, (false, 1, 0b0001));
+        assert_eq!(t[2].seq, (false, 2, 0b1010));
+
+        for _ in 0..5 {
+            s.message(&data[..10]);
+        }
+
+        let t = s.message(&data[..10]);
+        assert_eq!(t[0].seq, (false, 0, 0));
+
+        s.resync();
+        let t = s.message(&data[..10]);
+        assert_eq!(t[0].seq, (true, 1, 0));
+    }
+
+    #[test]
+    fn test_responses() {
+        let mut s = ConfirmedSession::new(true, ServiceAccessPoint::PacketData, 0,
+                                          0x123456);
+
+        let data: Vec<u8> = (0..100).collect();
+        let mut t = s.message(&data[..]).pop().unwrap();
+
+        assert_eq!(t.pending().count(), 7);
+
+        assert_eq!(t.handle(&resp(ResponseType::Ack, 1, 0), &[]),
+                   DeliveryStatus::Ignored);
+        assert_eq!(t.handle(&resp(ResponseType::Nack(NackReason::MemoryFull), 0, 0), &[]),
+                   DeliveryStatus::Rejected(NackReason::MemoryFull));
+        assert_eq!(t.handle(&resp(ResponseType::SelectiveAck, 0, 1), &[0b01000100]),
+                   DeliveryStatus::Retry);
+
+        assert_eq!(t.pending().count(), 2);
+        assert!(t.pending().get(1));
+        assert!(t.pending().get(5));
+
+        assert_eq!(t.handle(&resp(ResponseType::Nack(NackReason::PacketChecksum), 0, 0),
+                            &[]),
+                   DeliveryStatus::Retry);
+        assert_eq!(t.pending().count(), 7);
+
+        assert_eq!(t.handle(&resp(ResponseType::Ack, 0, 0), &[]),
+                   DeliveryStatus::Delivered);
+        assert_eq!(t.pending().count(), 0);
+    }
+
+    #[test]
+    fn test_retry() {
+        let mut s = ConfirmedSession::new(true, ServiceAccessPoint::PacketData, 0,
+                                          0x123456);
+
+        let data: Vec<u8> = (0..100).collect();
+        let mut t = s.message(&data[..]).pop().unwrap();
+
+        // Header and 7 blocks.
+        assert_eq!(t.packet().len(), 8 * 98);
+        assert_eq!(t.retry().len(), 8 * 98);
+
+        t.handle(&resp(ResponseType::SelectiveAck, 0, 1), &[0b00100010]);
+
+        // Header and 2 blocks.
+        assert_eq!(t.retry().len(), 3 * 98);
+    }
+
+    #[test]
+    fn test_response_packet() {
+        let mut flags = RetryFlags::new();
+        flags.set(3);
+        flags.set(12);
+
+        let blocks = response_blocks(20);
+        assert_eq!(blocks, 1);
+
+        let dibits = response(resp(ResponseType::SelectiveAck, 5, blocks as u8),
+                              Some((&flags, 20)));
+
+        let mut recv = DataPacketReceiver::new();
+        let pkt = dibits.iter().filter_map(|&d| recv.feed(d)).next().unwrap().unwrap();
+
+        match pkt.header {
+            DataHeader::Response(ref f) => {
+                assert_eq!(f.status.response, ResponseType::SelectiveAck);
+                assert_eq!(f.status.pkt_seq, 5);
+            },
+            _ => panic!(),
+        }
+
+        assert_eq!(RetryFlags::from_bytes(&pkt.data[..]), flags);
+
+        let dibits = response(resp(ResponseType::Ack, 5, 0), None);
+        assert_eq!(dibits.len(), 98);
+    }
+}
@@ -19,6 +19,12 @@ pub enum P25Error {
     ViterbiUnrecoverable,
     /// An unknown or corrupted NID was encountered.
     UnknownNid,
+    /// An unknown or unsupported data packet header was encountered.
+    UnknownDataHeader,
+    /// A received checksum didn't match the data it covers.
+    ChecksumMismatch,
+    /// A reserved IMBE pitch index was encountered.
+    InvalidPitch,
 }
 
 /// Standard result using `P25Error`.
@@ -28,5 +28,6 @@ pub mod consts;
 pub mod data;
 pub mod error;
 pub mod message;
+pub mod phase2;
 pub mod trunking;
 pub mod voice;
@@ -1,8 +1,12 @@
 //! General low-level receiver for all data units, covering frame synchronization up to
 //! symbol decoding.
 
-use baseband::decode::{Decoder, Decider};
-use baseband::sync::{SyncCorrelator, SyncDetector};
+use baseband::cqpsk;
+use baseband::decode::{Decoder, Decider, SoftDecoder};
+use baseband::sync::{DibitSyncDetector, SyncCorrelator, SyncDetector};
+use baseband::timing::{FixedClock, SymbolClock};
+use bits::SoftDibit;
+use consts::SYMBOL_PERIOD;
 use error::{P25Error, Result};
 use message::nid;
 use message::status::{StreamSymbol, StatusDeinterleaver};
@@ -14,30 +18,56 @@ use self::StateChange::*;
 /// look for sync.
 const PRIME_SAMPLES: u32 = 6000;
 
+/// Hard or soft symbol decoder.
+#[derive(Copy, Clone)]
+enum SymbolDecoder<C: SymbolClock> {
+    /// Decode hard symbols.
+    Hard(Decoder<C>),
+    /// Decode soft symbols.
+    Soft(SoftDecoder<C>),
+    /// Decide hard symbols that have already been recovered, one per input.
+    HardSymbols(Decider),
+    /// Scale soft symbols that have already been recovered, one per input.
+    SoftSymbols(Decider),
+}
+
 /// Low-level receiver for decoding samples into symbols and deinterleaving status
 /// symbols.
 #[derive(Copy, Clone)]
-struct SymbolReceiver {
+struct SymbolReceiver<C: SymbolClock> {
     /// Symbol decoder.
-    decoder: Decoder,
+    decoder: SymbolDecoder<C>,
     /// Data/Status symbol deinterleaver.
     status: StatusDeinterleaver,
 }
 
-impl SymbolReceiver {
+impl<C: SymbolClock> SymbolReceiver<C> {
     /// Create a new `SymbolReceiver` using the given symbol decoder.
-    pub fn new(decoder: Decoder) -> SymbolReceiver {
+    pub fn new(decoder: SymbolDecoder<C>) -> SymbolReceiver<C> {
         SymbolReceiver {
             decoder: decoder,
             status: StatusDeinterleaver::new(),
         }
     }
 
-    /// Feed in a baseband symbol, possibly producing a data or status symbol.
-    pub fn feed(&mut self, s: f32) -> Option<StreamSymbol> {
-        match self.decoder.feed(s) {
-            Some(dibit) => Some(self.status.feed(dibit)),
-            None => None,
+    /// Feed in a baseband symbol, possibly producing a data or status symbol along with
+    /// its soft value if decoding soft symbols.
+    pub fn feed(&mut self, s: f32) -> Option<(StreamSymbol, Option<SoftDibit>)> {
+        match self.decoder {
+            SymbolDecoder::Hard(ref mut dec) => match dec.feed(s) {
+                Some(dibit) => Some((self.status.feed(dibit), None)),
+                None => None,
+            },
+            SymbolDecoder::Soft(ref mut dec) => match dec.feed(s) {
+                Some(soft) => Some((self.status.feed(soft.hard()), Some(soft))),
+                None => None,
+            },
+            SymbolDecoder::HardSymbols(ref dec) =>
+                Some((self.status.feed(dec.decide(s)), None)),
+            SymbolDecoder::SoftSymbols(ref dec) => {
+                let soft = dec.soft(s);
+                Some((self.status.feed(soft.hard()), Some(soft)))
+            },
         }
     }
 }
@@ -48,55 +78,58 @@ impl SymbolReceiver {
 pub enum ReceiverEvent {
     /// Data or status symbol.
     Symbol(StreamSymbol),
+    /// Data symbol with soft reliability information, produced in place of
+    /// `Symbol(StreamSymbol::Data(_))` when soft decoding is enabled.
+    SoftSymbol(SoftDibit),
     /// Decoded NID information.
     NetworkId(nid::NetworkId),
 }
 
 /// Internal state of the state machine.
-enum State {
+enum State<C: SymbolClock> {
     /// Prime the signal power tracker.
     Prime(u32),
     /// Lock onto frame synchronization.
     Sync(SyncDetector),
     /// Decode NID.
-    DecodeNID(SymbolReceiver, nid::NidReceiver),
+    DecodeNID(SymbolReceiver<C>, nid::NidReceiver),
     /// Decode data and status symbols.
-    DecodePacket(SymbolReceiver),
+    DecodePacket(SymbolReceiver<C>),
     /// Flush pads at end of packet.
-    FlushPads(SymbolReceiver),
+    FlushPads(SymbolReceiver<C>),
 }
 
 /// Action the state machine should take.
-enum StateChange {
+enum StateChange<C: SymbolClock> {
     /// Change to the given state.
-    Change(State),
+    Change(State<C>),
     /// Propagate the given event.
     Event(ReceiverEvent),
     /// Change to the given state and propagate the given event.
-    EventChange(ReceiverEvent, State),
+    EventChange(ReceiverEvent, State<C>),
     /// Propagate the given error.
     Error(P25Error),
     /// No action necessary.
     NoChange,
 }
 
-impl State {
+impl<C: SymbolClock> State<C> {
     /// Initial prime state.
-    pub fn prime() -> State { Prime(1) }
+    pub fn prime() -> State<C> { Prime(1) }
 
     /// Initial synchronization state.
-    pub fn sync() -> State { Sync(SyncDetector::new()) }
+    pub fn sync() -> State<C> { Sync(SyncDetector::new()) }
 
     /// Initial NID decode state.
-    pub fn decode_nid(decoder: Decoder) -> State {
+    pub fn decode_nid(decoder: SymbolDecoder<C>) -> State<C> {
         DecodeNID(SymbolReceiver::new(decoder), nid::NidReceiver::new())
     }
 
     /// Initial symbol decode state.
-    pub fn decode_packet(recv: SymbolReceiver) -> State { DecodePacket(recv) }
+    pub fn decode_packet(recv: SymbolReceiver<C>) -> State<C> { DecodePacket(recv) }
 
     /// Initial flush padding state.
-    pub fn flush_pads(recv: SymbolReceiver) -> State { FlushPads(recv) }
+    pub fn flush_pads(recv: SymbolReceiver<C>) -> State<C> { FlushPads(recv) }
 }
 
 /// State machine for low-level data unit reception.
@@ -109,22 +142,51 @@ impl State {
 /// 3. Deinterleave status symbols
 /// 4. Decode NID information
 /// 5. Decode dibit symbols until stopped
-pub struct DataUnitReceiver {
+///
+/// Symbol instants are determined by a symbol clock, which by default counts a fixed
+/// number of samples per symbol. Data symbols are decoded as hard dibits unless soft
+/// decoding is enabled.
+///
+/// Symbols that have already been recovered by a demodulator with its own symbol timing,
+/// such as `CqpskDemod`, can instead be fed in with `feed_symbol`, in which case frame
+/// sync is detected on the decided symbols.
+pub struct DataUnitReceiver<C: SymbolClock = FixedClock> {
     /// Current state.
-    state: State,
+    state: State<C>,
     /// Tracks input signal power and frame synchronization statistics.
     corr: SyncCorrelator,
+    /// Detects frame sync in recovered symbols.
+    symsync: DibitSyncDetector,
+    /// Symbol clock copied at each frame sync.
+    clock: C,
+    /// Whether to produce soft data symbols.
+    soft: bool,
 }
 
 impl DataUnitReceiver {
     /// Create a new `DataUnitReceiver` in the initial reception state.
     pub fn new() -> DataUnitReceiver {
+        DataUnitReceiver::with_clock(FixedClock::new(SYMBOL_PERIOD))
+    }
+}
+
+impl<C: SymbolClock> DataUnitReceiver<C> {
+    /// Create a new `DataUnitReceiver` in the initial reception state that starts a copy
+    /// of the given symbol clock at each detected frame sync.
+    pub fn with_clock(clock: C) -> DataUnitReceiver<C> {
         DataUnitReceiver {
             state: State::prime(),
             corr: SyncCorrelator::new(),
+            symsync: DibitSyncDetector::new(),
+            clock: clock,
+            soft: false,
         }
     }
 
+    /// Set whether data symbols are produced as `SoftSymbol` events, starting at the next
+    /// frame sync.
+    pub fn set_soft(&mut self, soft: bool) { self.soft = soft; }
+
     /// Flush any remaining padding symbols at the end of the current packet, and reenter
     /// the frame synchronization state afterwards.
     pub fn flush_pads(&mut self) {
@@ -139,26 +201,63 @@ impl DataUnitReceiver {
     pub fn resync(&mut self) { self.state = State::sync(); }
 
     /// Determine the next action to take based on the given sample.
-    fn handle(&mut self, s: f32) -> StateChange {
+    fn handle(&mut self, s: f32) -> StateChange<C> {
         // Continuously track the input signal power.
         let (power, thresh) = self.corr.feed(s);
 
         match self.state {
-            Prime(t) => if t == PRIME_SAMPLES {
+            Prime(t) => return if t == PRIME_SAMPLES {
                 Change(State::sync())
             } else {
                 Change(Prime(t + 1))
             },
-            Sync(ref mut sync) => if sync.feed(power, thresh) {
+            Sync(ref mut sync) => return if sync.feed(power, thresh) {
                 let (p, m, n) = self.corr.thresholds();
-                Change(State::decode_nid(Decoder::new(Decider::new(p, m, n))))
+                let decider = Decider::new(p, m, n);
+
+                Change(State::decode_nid(if self.soft {
+                    SymbolDecoder::Soft(SoftDecoder::with_clock(decider, self.clock))
+                } else {
+                    SymbolDecoder::Hard(Decoder::with_clock(decider, self.clock))
+                }))
+            } else {
+                NoChange
+            },
+            _ => {},
+        }
+
+        self.decode(s)
+    }
+
+    /// Determine the next action to take based on the given recovered symbol.
+    fn handle_symbol(&mut self, s: f32) -> StateChange<C> {
+        let decider = cqpsk::decider();
+
+        // Continuously track the recent symbols for frame sync.
+        let synced = self.symsync.feed(decider.decide(s));
+
+        match self.state {
+            Prime(_) | Sync(_) => if synced {
+                Change(State::decode_nid(if self.soft {
+                    SymbolDecoder::SoftSymbols(decider)
+                } else {
+                    SymbolDecoder::HardSymbols(decider)
+                }))
             } else {
                 NoChange
             },
+            _ => self.decode(s),
+        }
+    }
+
+    /// Determine the next action to take based on the given sample or symbol after frame
+    /// sync.
+    fn decode(&mut self, s: f32) -> StateChange<C> {
+        match self.state {
             DecodeNID(ref mut recv, ref mut nid) => {
                 let dibit = match recv.feed(s) {
-                    Some(StreamSymbol::Data(d)) => d,
-                    Some(s) => return Event(ReceiverEvent::Symbol(s)),
+                    Some((StreamSymbol::Data(d), _)) => d,
+                    Some((s, _)) => return Event(ReceiverEvent::Symbol(s)),
                     None => return NoChange,
                 };
 
@@ -170,15 +269,18 @@ impl DataUnitReceiver {
                 }
             },
             DecodePacket(ref mut recv) => match recv.feed(s) {
-                Some(x) => Event(ReceiverEvent::Symbol(x)),
+                Some((StreamSymbol::Data(_), Some(soft))) =>
+                    Event(ReceiverEvent::SoftSymbol(soft)),
+                Some((x, _)) => Event(ReceiverEvent::Symbol(x)),
                 None => NoChange,
             },
             FlushPads(ref mut recv) => match recv.feed(s) {
                 /// According to the spec, the stream is padded until the next status
                 /// symbol boundary.
-                Some(StreamSymbol::Status(_)) => Change(State::sync()),
+                Some((StreamSymbol::Status(_), _)) => Change(State::sync()),
                 _ => NoChange,
             },
+            Prime(_) | Sync(_) => NoChange,
         }
     }
 
@@ -186,7 +288,20 @@ impl DataUnitReceiver {
     /// `Some(Ok(event))` for any normal event, `Some(Err(err))` for any error, and `None`
     /// if no event occurred.
     pub fn feed(&mut self, s: f32) -> Option<Result<ReceiverEvent>> {
-        match self.handle(s) {
+        let change = self.handle(s);
+        self.apply(change)
+    }
+
+    /// Feed in a symbol recovered by a demodulator, scaled so the ideal symbols lie at
+    /// ±1 and ±3. The return value has the same meaning as for `feed`.
+    pub fn feed_symbol(&mut self, s: f32) -> Option<Result<ReceiverEvent>> {
+        let change = self.handle_symbol(s);
+        self.apply(change)
+    }
+
+    /// Carry out the given action.
+    fn apply(&mut self, change: StateChange<C>) -> Option<Result<ReceiverEvent>> {
+        match change {
             Change(state) => {
                 self.state = state;
                 None
@@ -201,3 +316,49 @@ impl DataUnitReceiver {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::Dibit;
+    use message::status::StatusCode;
+
+    #[test]
+    fn test_symbol_receiver() {
+        let decider = Decider::new(0.08, 0.0, -0.08);
+        let mut hard = SymbolReceiver::new(SymbolDecoder::Hard(Decoder::new(decider)));
+        let mut soft = SymbolReceiver::new(
+            SymbolDecoder::Soft(SoftDecoder::new(decider)));
+
+        let mut symbols = vec![];
+
+        // The frame sync counts towards the first status period, so the 12th symbol
+        // is a status symbol.
+        let samples = [0.12, -0.1, 0.06, 0.12, 0.12, 0.12, 0.12, 0.12, 0.12, 0.12, 0.12,
+                       -0.04];
+
+        for &s in samples.iter() {
+            for _ in 0..SYMBOL_PERIOD {
+                match (hard.feed(s), soft.feed(s)) {
+                    (Some((h, None)), Some((x, Some(v)))) => {
+                        assert_eq!(h, x);
+                        symbols.push((x, v.value()));
+                    },
+                    (None, None) => {},
+                    _ => panic!(),
+                }
+            }
+        }
+
+        assert_eq!(symbols.len(), 12);
+
+        assert_eq!(symbols[0].0, StreamSymbol::Data(Dibit::new(0b01)));
+        assert!((symbols[0].1 - 3.0).abs() < 1e-4);
+        assert_eq!(symbols[1].0, StreamSymbol::Data(Dibit::new(0b11)));
+        assert!((symbols[1].1 + 2.5).abs() < 1e-4);
+        assert_eq!(symbols[2].0, StreamSymbol::Data(Dibit::new(0b00)));
+        assert!((symbols[2].1 - 1.5).abs() < 1e-4);
+        assert_eq!(symbols[11].0, StreamSymbol::Status(StatusCode::from_dibit(
+            Dibit::new(0b10))));
+    }
+}
@@ -1,9 +1,13 @@
 //! High-level receiver for receiving P25 voice, data, and trunking messages.
 
-use error::P25Error;
+use baseband::timing::{FixedClock, SymbolClock};
+use data::header::DataHeader;
+use data::receiver::{self, DataPacket, DataPacketReceiver};
+use error::{P25Error, Result};
 use message::data_unit::{DataUnitReceiver, ReceiverEvent};
 use message::nid::NetworkId;
 use message::status::StreamSymbol;
+use trunking::mbt::MbtFields;
 use trunking::tsbk::{TsbkFields, TsbkReceiver};
 use voice::control::LinkControlFields;
 use voice::crypto::CryptoControlFields;
@@ -37,6 +41,10 @@ pub enum MessageEvent {
     TrunkingControl(TsbkFields),
     /// A voice terminator link control was received.
     VoiceTerm(LinkControlFields),
+    /// A data packet was received.
+    DataPacket(DataPacket),
+    /// A multiple block trunking packet was received.
+    MultiBlockTrunking(MbtFields),
 }
 
 /// Internal state of the state machine.
@@ -53,6 +61,8 @@ enum State {
     DecodeLCTerminator(VoiceLCTerminatorReceiver),
     /// Decoding a trunking signalling packet.
     DecodeTSBK(TsbkReceiver),
+    /// Decoding a data packet.
+    DecodeDataPacket(DataPacketReceiver),
 }
 
 /// Action the state machine should take.
@@ -66,9 +76,12 @@ enum StateChange {
 }
 
 /// State machine for high-level message reception.
-pub struct MessageReceiver {
+///
+/// If soft decoding is enabled on the lower-level receiver with `set_soft`, soft symbols
+/// are passed to each packet receiver so they can correct more errors.
+pub struct MessageReceiver<C: SymbolClock = FixedClock> {
     /// Lower-level stream receiver.
-    pub recv: DataUnitReceiver,
+    pub recv: DataUnitReceiver<C>,
     /// Current state.
     state: State,
 }
@@ -81,11 +94,36 @@ impl MessageReceiver {
             state: State::Idle,
         }
     }
+}
+
+impl<C: SymbolClock> MessageReceiver<C> {
+    /// Create a new `MessageReceiver` in the initial state that decodes symbols with the
+    /// given symbol clock.
+    pub fn with_clock(clock: C) -> MessageReceiver<C> {
+        MessageReceiver {
+            recv: DataUnitReceiver::with_clock(clock),
+            state: State::Idle,
+        }
+    }
 
     /// Feed in a baseband sample, possibly producing a new event or message to be handled
     /// by the given handler.
     pub fn feed(&mut self, s: f32) -> Option<MessageEvent> {
-        match self.handle(s) {
+        let event = self.recv.feed(s);
+        self.apply(event)
+    }
+
+    /// Feed in a symbol recovered by a demodulator, such as `CqpskDemod`, scaled so the
+    /// ideal symbols lie at ±1 and ±3. The return value has the same meaning as for
+    /// `feed`.
+    pub fn feed_symbol(&mut self, s: f32) -> Option<MessageEvent> {
+        let event = self.recv.feed_symbol(s);
+        self.apply(event)
+    }
+
+    /// Handle the given lower-level receiver event and update state.
+    fn apply(&mut self, event: Option<Result<ReceiverEvent>>) -> Option<MessageEvent> {
+        match self.handle(event) {
             StateChange::Event(e) => Some(e),
             StateChange::EventChange(e, s) => {
                 self.state = s;
@@ -95,13 +133,13 @@ impl MessageReceiver {
         }
     }
 
-    /// Process the given sample and determine how to update state.
-    fn handle(&mut self, s: f32) -> StateChange {
+    /// Process the given lower-level receiver event and determine how to update state.
+    fn handle(&mut self, event: Option<Result<ReceiverEvent>>) -> StateChange {
         use self::State::*;
         use self::StateChange::*;
         use message::nid::DataUnit::*;
 
-        let event = match self.recv.feed(s) {
+        let event = match event {
             Some(Ok(event)) => event,
             Some(Err(err)) => {
                 self.recv.resync();
@@ -110,7 +148,7 @@ impl MessageReceiver {
             None => return NoChange,
         };
 
-        let dibit = match event {
+        let (dibit, soft) = match event {
             ReceiverEvent::NetworkId(nid) => {
                 let next = match nid.data_unit {
                     VoiceHeader =>
@@ -127,20 +165,29 @@ impl MessageReceiver {
                         DecodeCCFrameGroup(VoiceCCFrameGroupReceiver::new()),
                     TrunkingSignaling =>
                         DecodeTSBK(TsbkReceiver::new()),
-                    DataPacket => {
-                        self.recv.resync();
-                        Idle
-                    },
+                    DataPacket =>
+                        DecodeDataPacket(DataPacketReceiver::new()),
                 };
 
                 return EventChange(MessageEvent::PacketNID(nid), next);
             },
             ReceiverEvent::Symbol(StreamSymbol::Status(_)) => return NoChange,
-            ReceiverEvent::Symbol(StreamSymbol::Data(dibit)) => dibit,
+            ReceiverEvent::Symbol(StreamSymbol::Data(dibit)) => (dibit, None),
+            ReceiverEvent::SoftSymbol(s) => (s.hard(), Some(s)),
         };
 
+        // Feed the symbol to the given receiver, keeping its soft value if available.
+        macro_rules! feed {
+            ($recv:expr) => {
+                match soft {
+                    Some(s) => $recv.feed_soft(s),
+                    None => $recv.feed(dibit),
+                }
+            };
+        }
+
         match self.state {
-            DecodeHeader(ref mut head) => match head.feed(dibit) {
+            DecodeHeader(ref mut head) => match feed!(head) {
                 Some(Ok(h)) => {
                     self.recv.flush_pads();
                     EventChange(MessageEvent::VoiceHeader(h), Idle)
@@ -151,7 +198,7 @@ impl MessageReceiver {
                 },
                 None => NoChange,
             },
-            DecodeLCFrameGroup(ref mut fg) => match fg.feed(dibit) {
+            DecodeLCFrameGroup(ref mut fg) => match feed!(fg) {
                 Some(Ok(event)) => {
                     if fg.done() {
                         self.recv.flush_pads();
@@ -172,7 +219,7 @@ impl MessageReceiver {
                 },
                 None => NoChange,
             },
-            DecodeCCFrameGroup(ref mut fg) => match fg.feed(dibit) {
+            DecodeCCFrameGroup(ref mut fg) => match feed!(fg) {
                 Some(Ok(event)) => match event {
                     FrameGroupEvent::VoiceFrame(vf) => {
                         if fg.done() {
@@ -192,7 +239,7 @@ impl MessageReceiver {
                 },
                 None => NoChange,
             },
-            DecodeLCTerminator(ref mut term) => match term.feed(dibit) {
+            DecodeLCTerminator(ref mut term) => match feed!(term) {
                 Some(Ok(lc)) => {
                     self.recv.flush_pads();
                     EventChange(MessageEvent::VoiceTerm(lc), Idle)
@@ -203,7 +250,7 @@ impl MessageReceiver {
                 },
                 None => NoChange,
             },
-            DecodeTSBK(ref mut dec) => match dec.feed(dibit) {
+            DecodeTSBK(ref mut dec) => match feed!(dec) {
                 Some(Ok(tsbk)) => {
                     if tsbk.is_tail() {
                         self.recv.flush_pads();
@@ -217,6 +264,28 @@ impl MessageReceiver {
                 },
                 None => NoChange,
             },
+            DecodeDataPacket(ref mut dec) => match feed!(dec) {
+                Some(Ok(pkt)) => {
+                    self.recv.flush_pads();
+
+                    let receiver::DataPacket { header, data, blocks } = pkt;
+
+                    EventChange(match header {
+                        DataHeader::Trunking(h) =>
+                            MessageEvent::MultiBlockTrunking(MbtFields::new(h, data)),
+                        h => MessageEvent::DataPacket(receiver::DataPacket {
+                            header: h,
+                            data: data,
+                            blocks: blocks,
+                        }),
+                    }, Idle)
+                },
+                Some(Err(err)) => {
+                    self.recv.resync();
+                    EventChange(MessageEvent::Error(err), Idle)
+                },
+                None => NoChange,
+            },
             Idle => NoChange,
         }
     }
@@ -0,0 +1,7 @@
+//! Synchronize to and frame Phase 2 TDMA voice channels.
+//!
+//! Symbols are decoded at the Phase 2 symbol rate, slot timing is aligned to the S-ISCH
+//! sync sequence, and each received timeslot is provided as its raw burst symbols.
+
+pub mod receiver;
+pub mod sync;
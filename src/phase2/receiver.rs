@@ -0,0 +1,278 @@
+//! Receive Phase 2 timeslots from a baseband signal.
+//!
+//! Slot timing is aligned to each detected sync sequence, which is carried in the ISCH
+//! preceding a burst. Timeslots are then framed every `SLOT_DIBITS` symbols and numbered
+//! by their position after the most recent sync.
+
+use baseband::decode::Decoder;
+use bits::{Dibit, Dibits};
+use consts::{BURST_DIBITS, ISCH_DIBITS, P2_SYMBOL_PERIOD, SUPERFRAME_SLOTS};
+use phase2::sync::{sync_errors, SyncCorrelator, P2_SYNC_GENERATOR};
+
+/// Maximum number of slots to frame without detecting a sync sequence before timing is
+/// considered lost.
+const MAX_UNSYNCED_SLOTS: usize = 2 * SUPERFRAME_SLOTS;
+
+/// Maximum number of dibits that may differ for an ISCH to be considered a sync
+/// sequence.
+const MAX_ISCH_ERRORS: usize = 2;
+
+/// A received Phase 2 timeslot.
+#[derive(Copy, Clone)]
+pub struct Timeslot {
+    /// Position of the slot after the most recent sync sequence, modulo the slots in a
+    /// superframe.
+    pub index: usize,
+    /// Inter-slot signalling preceding the burst.
+    pub isch: [Dibit; ISCH_DIBITS],
+    /// Burst carried in the slot.
+    pub burst: [Dibit; BURST_DIBITS],
+}
+
+impl Timeslot {
+    /// Logical channel of the slot, relative to the most recent sync sequence. Slots
+    /// alternate between the two logical channels.
+    pub fn channel(&self) -> usize { self.index % 2 }
+
+    /// Whether the slot's ISCH carries the sync sequence.
+    pub fn synced(&self) -> bool { sync_errors(&self.isch[..]) <= MAX_ISCH_ERRORS }
+}
+
+/// Events emitted by the receiver.
+pub enum Phase2Event {
+    /// The sync sequence was detected and slot timing was aligned to it.
+    Sync,
+    /// A timeslot was received, carrying the burst symbols as they were received.
+    Slot(Timeslot),
+    /// No sync sequence was detected for too long, so slot timing was lost.
+    LostSync,
+}
+
+/// Internal state of the state machine.
+enum State {
+    /// Searching for the sync sequence.
+    Search,
+    /// Framing timeslots.
+    Framing(Framer),
+}
+
+/// Collects decoded symbols into timeslots.
+struct Framer {
+    /// Symbol decoder aligned to the sync sequence.
+    decoder: Decoder,
+    /// Timeslot being received.
+    slot: Timeslot,
+    /// Number of dibits received in the current timeslot.
+    pos: usize,
+    /// Number of slots framed since the most recent sync sequence.
+    count: usize,
+}
+
+impl Framer {
+    /// Create a new `Framer` aligned to a sync sequence that has just been received.
+    fn new(decoder: Decoder) -> Framer {
+        let mut isch = [Dibit::default(); ISCH_DIBITS];
+        let sync = Dibits::new(P2_SYNC_GENERATOR.iter().cloned());
+
+        for (d, s) in isch.iter_mut().zip(sync) {
+            *d = s;
+        }
+
+        Framer {
+            decoder: decoder,
+            slot: Timeslot {
+                index: 0,
+                isch: isch,
+                burst: [Dibit::default(); BURST_DIBITS],
+            },
+            pos: ISCH_DIBITS,
+            count: 0,
+        }
+    }
+
+    /// Feed in a baseband sample, possibly producing a completed timeslot.
+    fn feed(&mut self, s: f32) -> Option<Timeslot> {
+        let dibit = match self.decoder.feed(s) {
+            Some(d) => d,
+            None => return None,
+        };
+
+        if self.pos < ISCH_DIBITS {
+            self.slot.isch[self.pos] = dibit;
+        } else {
+            self.slot.burst[self.pos - ISCH_DIBITS] = dibit;
+        }
+
+        self.pos += 1;
+
+        if self.pos < ISCH_DIBITS + BURST_DIBITS {
+            return None;
+        }
+
+        let slot = self.slot;
+
+        self.pos = 0;
+        self.count += 1;
+        self.slot.index = self.count % SUPERFRAME_SLOTS;
+
+        Some(slot)
+    }
+}
+
+/// State machine for receiving Phase 2 timeslots.
+pub struct Phase2Receiver {
+    /// Sync sequence correlator.
+    corr: SyncCorrelator,
+    /// Current state.
+    state: State,
+}
+
+impl Phase2Receiver {
+    /// Create a new `Phase2Receiver` in the initial state.
+    pub fn new() -> Phase2Receiver {
+        Phase2Receiver {
+            corr: SyncCorrelator::new(),
+            state: State::Search,
+        }
+    }
+
+    /// Feed in a baseband sample, possibly producing a new event.
+    ///
+    /// Samples are expected to be the output of an FM discriminator at the standard
+    /// sample rate. Both H-CPM and H-DQPSK modulation appear as 4-level signals after
+    /// FM demodulation, so the same symbol path is used for both.
+    pub fn feed(&mut self, s: f32) -> Option<Phase2Event> {
+        if let Some(decider) = self.corr.feed(s) {
+            let decoder = Decoder::with_period(decider, P2_SYMBOL_PERIOD);
+            self.state = State::Framing(Framer::new(decoder));
+
+            return Some(Phase2Event::Sync);
+        }
+
+        let slot = match self.state {
+            State::Search => return None,
+            State::Framing(ref mut framer) => match framer.feed(s) {
+                Some(slot) => slot,
+                None => return None,
+            },
+        };
+
+        let lost = match self.state {
+            State::Framing(ref f) => f.count > MAX_UNSYNCED_SLOTS,
+            State::Search => false,
+        };
+
+        if lost {
+            self.state = State::Search;
+            return Some(Phase2Event::LostSync);
+        }
+
+        Some(Phase2Event::Slot(slot))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::{Dibit, Dibits};
+    use consts::{BURST_DIBITS, ISCH_DIBITS, P2_SYMBOL_PERIOD, SUPERFRAME_SLOTS};
+    use phase2::sync::P2_SYNC_GENERATOR;
+
+    fn level(d: Dibit) -> f32 {
+        match d.bits() {
+            0b01 => 0.18,
+            0b00 => 0.06,
+            0b10 => -0.06,
+            _ => -0.18,
+        }
+    }
+
+    fn burst(n: usize) -> Vec<Dibit> {
+        (0..BURST_DIBITS).map(|i| Dibit::new(((i * 7 + n * 3) % 4) as u8)).collect()
+    }
+
+    fn isch(n: usize) -> Vec<Dibit> {
+        (0..ISCH_DIBITS).map(|i| Dibit::new(((i + n) % 3) as u8 >> 1 << 1)).collect()
+    }
+
+    fn feed(r: &mut Phase2Receiver, dibits: &[Dibit]) -> Vec<Phase2Event> {
+        let mut events = vec![];
+
+        for &d in dibits {
+            for _ in 0..P2_SYMBOL_PERIOD {
+                if let Some(e) = r.feed(level(d)) {
+                    events.push(e);
+                }
+            }
+        }
+
+        events
+    }
+
+    #[test]
+    fn test_receiver() {
+        let mut r = Phase2Receiver::new();
+        let sync: Vec<Dibit> = Dibits::new(P2_SYNC_GENERATOR.iter().cloned()).collect();
+
+        let mut dibits = burst(99);
+        dibits.extend(sync.iter().cloned());
+
+        for n in 0..SUPERFRAME_SLOTS {
+            dibits.extend(burst(n));
+            dibits.extend(isch(n));
+        }
+
+        let events = feed(&mut r, &dibits[..]);
+
+        match events[0] {
+            Phase2Event::Sync => {},
+            _ => panic!(),
+        }
+
+        let slots: Vec<Timeslot> = events[1..].iter().map(|e| match *e {
+            Phase2Event::Slot(s) => s,
+            _ => panic!(),
+        }).collect();
+
+        // The final ISCH doesn't complete a slot.
+        assert_eq!(slots.len(), SUPERFRAME_SLOTS);
+
+        assert!(slots[0].synced());
+        assert_eq!(&slots[0].isch[..], &sync[..]);
+
+        for (n, s) in slots.iter().enumerate() {
+            assert_eq!(s.index, n);
+            assert_eq!(s.channel(), n % 2);
+            assert_eq!(&s.burst[..], &burst(n)[..]);
+
+            if n > 0 {
+                assert!(!s.synced());
+                assert_eq!(&s.isch[..], &isch(n - 1)[..]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_lost_sync() {
+        let mut r = Phase2Receiver::new();
+
+        let mut dibits: Vec<Dibit> =
+            Dibits::new(P2_SYNC_GENERATOR.iter().cloned()).collect();
+
+        for n in 0..MAX_UNSYNCED_SLOTS + 1 {
+            dibits.extend(burst(n));
+            dibits.extend(isch(n));
+        }
+
+        let events = feed(&mut r, &dibits[..]);
+        assert_eq!(events.len(), MAX_UNSYNCED_SLOTS + 2);
+
+        match events[MAX_UNSYNCED_SLOTS + 1] {
+            Phase2Event::LostSync => {},
+            _ => panic!(),
+        }
+
+        // Nothing is received until the next sync sequence.
+        assert!(feed(&mut r, &burst(0)[..]).is_empty());
+    }
+}
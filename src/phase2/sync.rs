@@ -0,0 +1,203 @@
+//! Detect the Phase 2 sync sequence (S-ISCH) and extract symbol decoding thresholds from
+//! it.
+//!
+//! Like the Phase 1 frame sync, the sync sequence is made up of only the outermost
+//! symbols, so it can be detected by correlating the baseband signal at each symbol
+//! instant with the expected polarities.
+
+use static_ewma::MovingAverage;
+
+use baseband::decode::Decider;
+use baseband::sync::{calc_thresholds, PowerSmoothing, SyncDetector};
+use bits::{Dibit, Dibits};
+use consts::{P2_SYMBOL_PERIOD, P2_SYNC_SYMBOLS};
+
+/// Symbols that make up the Phase 2 sync sequence.
+pub const P2_SYNC_GENERATOR: &'static [u8] = &[
+    0b01010111,
+    0b01011101,
+    0b01010111,
+    0b11110111,
+    0b11111111,
+];
+
+/// Number of samples spanned by the sync sequence, from first symbol instant to last.
+const SYNC_SAMPS: usize = (P2_SYNC_SYMBOLS - 1) * P2_SYMBOL_PERIOD + 1;
+/// Correlation threshold, scaled by the RMS amplitude of the signal.
+const THRESH_FACTOR: f32 = 0.8;
+/// Maximum number of symbol polarities that may differ from the sync sequence.
+const MAX_SYNC_ERRORS: usize = 2;
+
+/// Check whether the given symbol is positive (`01`) in the sync sequence, which is
+/// otherwise made up of negative (`11`) symbols.
+fn positive(d: Dibit) -> bool { d.bits() == 0b01 }
+
+/// Count the number of dibits that differ from the sync sequence.
+pub fn sync_errors(dibits: &[Dibit]) -> usize {
+    Dibits::new(P2_SYNC_GENERATOR.iter().cloned())
+        .zip(dibits.iter())
+        .filter(|&(a, &b)| a != b)
+        .count()
+}
+
+/// Continuously correlates the input signal with the sync sequence.
+pub struct SyncCorrelator {
+    /// Ring buffer of recent samples.
+    hist: [f32; SYNC_SAMPS],
+    /// Index of the oldest sample in `hist`.
+    idx: usize,
+    /// Polarity of each symbol in the sync sequence.
+    pattern: [f32; P2_SYNC_SYMBOLS],
+    /// Moving average power of input signal.
+    power: MovingAverage<PowerSmoothing>,
+    /// Peak detector for correlation power.
+    detector: SyncDetector,
+}
+
+impl SyncCorrelator {
+    /// Create a new `SyncCorrelator` with default state.
+    pub fn new() -> SyncCorrelator {
+        let mut pattern = [0.0; P2_SYNC_SYMBOLS];
+        let sync = Dibits::new(P2_SYNC_GENERATOR.iter().cloned());
+
+        for (p, d) in pattern.iter_mut().zip(sync) {
+            *p = if positive(d) { 1.0 } else { -1.0 };
+        }
+
+        SyncCorrelator {
+            hist: [0.0; SYNC_SAMPS],
+            idx: 0,
+            pattern: pattern,
+            power: MovingAverage::new(0.0),
+            detector: SyncDetector::new(),
+        }
+    }
+
+    /// Feed in a baseband sample. Return `Some(decider)` with symbol thresholds measured
+    /// from the sync sequence if the sequence ended at the previous sample, and `None`
+    /// otherwise.
+    pub fn feed(&mut self, sample: f32) -> Option<Decider> {
+        self.hist[self.idx] = sample;
+        self.idx = (self.idx + 1) % SYNC_SAMPS;
+
+        let avg = self.power.add(sample * sample);
+        let corr = self.symbols(0).iter().zip(self.pattern.iter())
+            .fold(0.0, |s, (&x, &p)| s + x * p) / P2_SYNC_SYMBOLS as f32;
+
+        if !self.detector.feed(corr, avg.sqrt() * THRESH_FACTOR) {
+            return None;
+        }
+
+        self.detector = SyncDetector::new();
+
+        // The correlation peaked at the previous sample.
+        let symbols = self.symbols(1);
+        let (pthresh, mthresh, nthresh) = self.thresholds(&symbols);
+
+        let errors = symbols.iter().zip(self.pattern.iter())
+            .filter(|&(&x, &p)| (x > mthresh) != (p > 0.0))
+            .count();
+
+        if errors > MAX_SYNC_ERRORS {
+            return None;
+        }
+
+        Some(Decider::new(pthresh, mthresh, nthresh))
+    }
+
+    /// Retrieve the samples at each symbol instant of a sync sequence that ended the
+    /// given number of samples ago.
+    fn symbols(&self, delay: usize) -> [f32; P2_SYNC_SYMBOLS] {
+        let mut symbols = [0.0; P2_SYNC_SYMBOLS];
+
+        // Index of the newest sample, offset by a full buffer to avoid underflow.
+        let last = self.idx + 2 * SYNC_SAMPS - 1;
+
+        for (i, s) in symbols.iter_mut().enumerate() {
+            let back = delay + (P2_SYNC_SYMBOLS - 1 - i) * P2_SYMBOL_PERIOD;
+            *s = self.hist[(last - back) % SYNC_SAMPS];
+        }
+
+        symbols
+    }
+
+    /// Calculate `(upper, mid, lower)` thresholds from the given sync symbol samples.
+    fn thresholds(&self, symbols: &[f32; P2_SYNC_SYMBOLS]) -> (f32, f32, f32) {
+        let (mut psum, mut pcount, mut nsum, mut ncount) = (0.0, 0, 0.0, 0);
+
+        for (&x, &p) in symbols.iter().zip(self.pattern.iter()) {
+            if p > 0.0 {
+                psum += x;
+                pcount += 1;
+            } else {
+                nsum += x;
+                ncount += 1;
+            }
+        }
+
+        calc_thresholds(psum / pcount as f32, nsum / ncount as f32)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::{Dibit, Dibits};
+    use consts::P2_SYMBOL_PERIOD;
+
+    /// Deviation level of each symbol.
+    fn level(d: Dibit) -> f32 {
+        match d.bits() {
+            0b01 => 0.18,
+            0b00 => 0.06,
+            0b10 => -0.06,
+            _ => -0.18,
+        }
+    }
+
+    #[test]
+    fn test_sync_errors() {
+        let mut dibits: Vec<Dibit> =
+            Dibits::new(P2_SYNC_GENERATOR.iter().cloned()).collect();
+
+        assert_eq!(dibits.len(), 20);
+        assert_eq!(sync_errors(&dibits[..]), 0);
+
+        dibits[3] = Dibit::new(0b00);
+        dibits[19] = Dibit::new(0b10);
+        assert_eq!(sync_errors(&dibits[..]), 2);
+    }
+
+    #[test]
+    fn test_correlator() {
+        let mut c = SyncCorrelator::new();
+
+        let noise = [0b00, 0b10, 0b01, 0b11, 0b10, 0b00, 0b10, 0b00];
+        let dibits = noise.iter().cycle().take(40).map(|&b| Dibit::new(b))
+            .chain(Dibits::new(P2_SYNC_GENERATOR.iter().cloned()))
+            .chain(noise.iter().map(|&b| Dibit::new(b)));
+
+        let mut detected = vec![];
+
+        for (i, d) in dibits.enumerate() {
+            for j in 0..P2_SYMBOL_PERIOD {
+                if let Some(dec) = c.feed(level(d)) {
+                    detected.push((i, j, dec));
+                }
+            }
+        }
+
+        assert_eq!(detected.len(), 1);
+
+        let (i, j, dec) = detected[0];
+
+        // Detected at the first sample after the last sync symbol instant.
+        assert_eq!(i, 60);
+        assert_eq!(j, 0);
+
+        assert_eq!(dec.decide(0.18).bits(), 0b01);
+        assert_eq!(dec.decide(0.06).bits(), 0b00);
+        assert_eq!(dec.decide(-0.06).bits(), 0b10);
+        assert_eq!(dec.decide(-0.18).bits(), 0b11);
+    }
+}
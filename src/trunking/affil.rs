@@ -0,0 +1,356 @@
+//! Track unit registrations and talkgroup affiliations from a control channel.
+//!
+//! Registration, location registration, deregistration, and affiliation responses are
+//! combined into a record per unit of its registration state, affiliated talkgroup, and
+//! last known site, which can then be queried by unit, talkgroup, or site.
+//!
+//! Timestamps are supplied by the caller and may be in any monotonic unit, such as
+//! sample counts or milliseconds.
+
+use std::collections::HashMap;
+
+use trunking::fields::{RegResponse, RfssStatusBroadcast, TalkGroup};
+use trunking::tsbk::{
+    GroupAffiliationResponse,
+    LocRegResponse,
+    TsbkFields,
+    TsbkOpcode,
+    UnitDeregAck,
+    UnitRegResponse,
+};
+
+/// Identifies a site within a system.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct SiteId {
+    /// RF Subsystem ID within the system.
+    pub rfss: u8,
+    /// Site ID within the RFSS.
+    pub site: u8,
+}
+
+/// Registration and affiliation state of a unit.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Unit {
+    /// Unit ID.
+    pub id: u32,
+    /// Whether the unit is currently registered with the system.
+    pub registered: bool,
+    /// Talkgroup the unit is currently affiliated with.
+    pub talkgroup: Option<TalkGroup>,
+    /// Whether the affiliation is valid system-wide rather than only at the unit's site.
+    pub global: bool,
+    /// Site the unit was last seen at.
+    pub site: Option<SiteId>,
+    /// Site the unit was seen at before roaming to its current site.
+    pub prev_site: Option<SiteId>,
+    /// Time the unit was first seen.
+    pub first_seen: u64,
+    /// Time the unit was last seen.
+    pub last_seen: u64,
+    /// Time the unit last changed its talkgroup affiliation.
+    pub affiliated: Option<u64>,
+}
+
+impl Unit {
+    /// Create a new, unregistered unit first seen at the given time.
+    fn new(id: u32, time: u64) -> Unit {
+        Unit {
+            id: id,
+            registered: false,
+            talkgroup: None,
+            global: false,
+            site: None,
+            prev_site: None,
+            first_seen: time,
+            last_seen: time,
+            affiliated: None,
+        }
+    }
+
+    /// Record that the unit was seen at the given site.
+    fn move_to(&mut self, site: Option<SiteId>) {
+        let site = match site {
+            Some(site) => site,
+            None => return,
+        };
+
+        if self.site != Some(site) {
+            self.prev_site = self.site;
+            self.site = Some(site);
+        }
+    }
+
+    /// Record the given talkgroup affiliation.
+    fn affiliate(&mut self, time: u64, tg: TalkGroup) {
+        if self.talkgroup != Some(tg) {
+            self.talkgroup = Some(tg);
+            self.affiliated = Some(time);
+        }
+    }
+}
+
+/// Database of unit registrations and affiliations.
+pub struct AffiliationDb {
+    /// Units seen, keyed by unit ID.
+    units: HashMap<u32, Unit>,
+    /// Site of the control channel, from its RFSS status broadcast.
+    site: Option<SiteId>,
+}
+
+impl AffiliationDb {
+    /// Create a new, empty `AffiliationDb`.
+    pub fn new() -> AffiliationDb {
+        AffiliationDb {
+            units: HashMap::new(),
+            site: None,
+        }
+    }
+
+    /// Number of units seen.
+    pub fn len(&self) -> usize { self.units.len() }
+
+    /// Site of the control channel, if known.
+    pub fn site(&self) -> Option<SiteId> { self.site }
+
+    /// Set the site of the control channel, used for registrations and affiliations
+    /// that don't carry their own site.
+    pub fn set_site(&mut self, site: SiteId) { self.site = Some(site); }
+
+    /// Look up the state of the given unit.
+    pub fn unit(&self, id: u32) -> Option<&Unit> { self.units.get(&id) }
+
+    /// Site the given unit was last seen at.
+    pub fn last_site(&self, id: u32) -> Option<SiteId> {
+        self.units.get(&id).and_then(|u| u.site)
+    }
+
+    /// IDs of registered units affiliated with the given talkgroup, in ascending order.
+    pub fn units_on(&self, tg: TalkGroup) -> Vec<u32> {
+        self.select(|u| u.registered && u.talkgroup == Some(tg))
+    }
+
+    /// IDs of registered units last seen at the given site, in ascending order.
+    pub fn units_at(&self, site: SiteId) -> Vec<u32> {
+        self.select(|u| u.registered && u.site == Some(site))
+    }
+
+    /// Talkgroups with at least one registered unit at the given site, in ascending
+    /// order of their ID.
+    pub fn talkgroups_at(&self, site: SiteId) -> Vec<TalkGroup> {
+        let mut tgs: Vec<TalkGroup> = vec![];
+
+        for u in self.units.values() {
+            if !u.registered || u.site != Some(site) {
+                continue;
+            }
+
+            if let Some(tg) = u.talkgroup {
+                if !tgs.contains(&tg) {
+                    tgs.push(tg);
+                }
+            }
+        }
+
+        tgs.sort_by_key(|tg| tg.to_bits());
+        tgs
+    }
+
+    /// Update the database with the given TSBK received at the given time.
+    pub fn feed(&mut self, time: u64, tsbk: &TsbkFields) {
+        if tsbk.protected() {
+            return;
+        }
+
+        let opcode = match tsbk.opcode() {
+            Some(opcode) => opcode,
+            None => return,
+        };
+
+        match opcode {
+            TsbkOpcode::RfssStatusBroadcast => {
+                let dec = RfssStatusBroadcast::new(tsbk.payload());
+
+                self.site = Some(SiteId {
+                    rfss: dec.rfss(),
+                    site: dec.site(),
+                });
+            },
+            TsbkOpcode::UnitRegResponse => {
+                let dec = UnitRegResponse::new(*tsbk);
+
+                if dec.response() != RegResponse::Accept {
+                    return;
+                }
+
+                let site = self.site;
+                let unit = self.touch(time, dec.src_addr());
+
+                unit.registered = true;
+                unit.move_to(site);
+            },
+            TsbkOpcode::LocRegResponse => {
+                let dec = LocRegResponse::new(*tsbk);
+
+                if dec.response() != RegResponse::Accept {
+                    return;
+                }
+
+                let unit = self.touch(time, dec.dest_unit());
+
+                // Location registration implies the unit is already registered with
+                // the system.
+                unit.registered = true;
+                unit.move_to(Some(SiteId {
+                    rfss: dec.rfss(),
+                    site: dec.site(),
+                }));
+                unit.affiliate(time, dec.talkgroup());
+            },
+            TsbkOpcode::GroupAffiliationResponse => {
+                let dec = GroupAffiliationResponse::new(*tsbk);
+
+                if dec.response() != RegResponse::Accept {
+                    return;
+                }
+
+                let site = self.site;
+                let unit = self.touch(time, dec.dest_unit());
+
+                unit.registered = true;
+                unit.global = dec.global();
+                unit.move_to(site);
+                unit.affiliate(time, dec.talkgroup());
+            },
+            TsbkOpcode::UnitDeregAck => {
+                let dec = UnitDeregAck::new(*tsbk);
+                let unit = self.touch(time, dec.src_unit());
+
+                unit.registered = false;
+                unit.talkgroup = None;
+            },
+            _ => {},
+        }
+    }
+
+    /// Remove units that haven't been seen since before the given time.
+    pub fn prune(&mut self, before: u64) {
+        let stale: Vec<u32> = self.select(|u| u.last_seen < before);
+
+        for id in stale {
+            self.units.remove(&id);
+        }
+    }
+
+    /// Get the record of the given unit, creating it if needed, and mark it as seen at
+    /// the given time.
+    fn touch(&mut self, time: u64, id: u32) -> &mut Unit {
+        let unit = self.units.entry(id).or_insert_with(|| Unit::new(id, time));
+        unit.last_seen = time;
+        unit
+    }
+
+    /// IDs of units that match the given predicate, in ascending order.
+    fn select<F: Fn(&Unit) -> bool>(&self, pred: F) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.units.values()
+            .filter(|u| pred(u))
+            .map(|u| u.id)
+            .collect();
+
+        ids.sort();
+        ids
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::build::*;
+    use trunking::fields::{Channel, RegResponse, SystemServices, TalkGroup};
+
+    const SITE_A: SiteId = SiteId { rfss: 1, site: 2 };
+    const SITE_B: SiteId = SiteId { rfss: 1, site: 3 };
+
+    #[test]
+    fn test_affiliation() {
+        let mut db = AffiliationDb::new();
+
+        db.feed(0, &rfss_status_broadcast(1, true, 0x123, 1, 2,
+                                          Channel::from_parts(1, 10),
+                                          SystemServices::new(0xF0)));
+        assert_eq!(db.site(), Some(SITE_A));
+
+        db.feed(1, &unit_reg_response(RegResponse::Accept, 0x123, 0xAAAAAA, 0x123456));
+        db.feed(2, &unit_reg_response(RegResponse::Accept, 0x123, 0xBBBBBB, 0x654321));
+        db.feed(3, &unit_reg_response(RegResponse::Deny, 0x123, 0xCCCCCC, 0x111111));
+        assert_eq!(db.len(), 2);
+        assert!(db.unit(0x111111).is_none());
+
+        let u = *db.unit(0x123456).unwrap();
+        assert!(u.registered);
+        assert_eq!(u.site, Some(SITE_A));
+        assert_eq!(u.talkgroup, None);
+        assert_eq!(u.first_seen, 1);
+
+        db.feed(4, &group_affiliation_response(false, RegResponse::Accept,
+                                               TalkGroup::Default,
+                                               TalkGroup::Other(1234), 0x123456));
+        db.feed(5, &group_affiliation_response(true, RegResponse::Accept,
+                                               TalkGroup::Default,
+                                               TalkGroup::Other(1234), 0x654321));
+        db.feed(6, &group_affiliation_response(true, RegResponse::Refuse,
+                                               TalkGroup::Default,
+                                               TalkGroup::Other(5678), 0x654321));
+
+        assert_eq!(db.units_on(TalkGroup::Other(1234)), vec![0x123456, 0x654321]);
+        assert!(db.units_on(TalkGroup::Other(5678)).is_empty());
+
+        let u = *db.unit(0x654321).unwrap();
+        assert!(u.global);
+        assert_eq!(u.affiliated, Some(5));
+        assert_eq!(u.last_seen, 5);
+
+        // Roaming to another site through location registration.
+        db.feed(7, &loc_reg_response(RegResponse::Accept, TalkGroup::Other(5678), 1, 3,
+                                     0x654321));
+
+        let u = *db.unit(0x654321).unwrap();
+        assert_eq!(u.site, Some(SITE_B));
+        assert_eq!(u.prev_site, Some(SITE_A));
+        assert_eq!(u.talkgroup, Some(TalkGroup::Other(5678)));
+        assert_eq!(u.affiliated, Some(7));
+
+        assert_eq!(db.last_site(0x654321), Some(SITE_B));
+        assert_eq!(db.units_at(SITE_A), vec![0x123456]);
+        assert_eq!(db.units_at(SITE_B), vec![0x654321]);
+        assert_eq!(db.talkgroups_at(SITE_A), vec![TalkGroup::Other(1234)]);
+        assert_eq!(db.talkgroups_at(SITE_B), vec![TalkGroup::Other(5678)]);
+    }
+
+    #[test]
+    fn test_dereg() {
+        let mut db = AffiliationDb::new();
+
+        db.set_site(SITE_A);
+        db.feed(10, &group_affiliation_response(false, RegResponse::Accept,
+                                                TalkGroup::Default,
+                                                TalkGroup::Other(1234), 0x123456));
+        assert_eq!(db.units_on(TalkGroup::Other(1234)), vec![0x123456]);
+
+        db.feed(20, &unit_dereg_ack(0xBEE00, 0x123, 0x123456));
+        assert!(db.units_on(TalkGroup::Other(1234)).is_empty());
+        assert!(db.units_at(SITE_A).is_empty());
+
+        // The last known site is kept after deregistration.
+        let u = *db.unit(0x123456).unwrap();
+        assert!(!u.registered);
+        assert_eq!(u.last_seen, 20);
+        assert_eq!(db.last_site(0x123456), Some(SITE_A));
+
+        db.feed(30, &unit_reg_response(RegResponse::Accept, 0x123, 0xAAAAAA, 0x654321));
+
+        db.prune(25);
+        assert_eq!(db.len(), 1);
+        assert!(db.unit(0x123456).is_none());
+        assert!(db.unit(0x654321).is_some());
+    }
+}
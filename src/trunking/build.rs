@@ -0,0 +1,864 @@
+//! Build typed TSBK packets.
+//!
+//! Each function constructs a complete packet whose fields can be decoded with the
+//! associated typed decoder in `tsbk`, `fields`, or `mfg`. Packets are marked as the tail
+//! of their TSBK group and unencrypted, which can be changed with
+//! `TsbkFields::with_tail` or by building the packet directly with `TsbkBuilder`.
+
+use trunking::fields::{
+    Channel,
+    DenyReason,
+    RegResponse,
+    RoamingStackOp,
+    ServiceOptions,
+    SiteOptions,
+    SystemServices,
+    TalkGroup,
+};
+use trunking::mfg::{MfgOpcode, MotorolaOpcode};
+use trunking::tsbk::{TsbkBuilder, TsbkFields, TsbkOpcode};
+use util::{write_u16, write_u24, write_u32};
+use voice::crypto::CryptoAlgorithm;
+
+/// Build a standard packet with the given opcode and payload.
+fn build(opcode: TsbkOpcode, payload: &[u8; 8]) -> TsbkFields {
+    TsbkBuilder::new(opcode).payload(&payload[..]).build()
+}
+
+/// Combine the given WACN and System IDs into a 32-bit word.
+fn network(wacn: u32, system: u16) -> u32 {
+    assert!(wacn >> 20 == 0);
+    assert!(system >> 12 == 0);
+
+    wacn << 12 | system as u32
+}
+
+/// Build a `GroupVoiceGrant` packet.
+pub fn group_voice_grant(opts: ServiceOptions, channel: Channel, talkgroup: TalkGroup,
+                         src_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = opts.to_bits();
+    write_u16(&mut p[1..], channel.to_bits());
+    write_u16(&mut p[3..], talkgroup.to_bits());
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::GroupVoiceGrant, &p)
+}
+
+/// Build a `GroupVoiceUpdate` or `GroupDataUpdate` packet, decoded with
+/// `GroupTrafficUpdate`.
+pub fn group_traffic_update(opcode: TsbkOpcode, updates: [(Channel, TalkGroup); 2])
+    -> TsbkFields
+{
+    assert!(opcode == TsbkOpcode::GroupVoiceUpdate ||
+            opcode == TsbkOpcode::GroupDataUpdate);
+
+    let mut p = [0; 8];
+
+    for (i, &(ch, tg)) in updates.iter().enumerate() {
+        write_u16(&mut p[i * 4..], ch.to_bits());
+        write_u16(&mut p[i * 4 + 2..], tg.to_bits());
+    }
+
+    build(opcode, &p)
+}
+
+/// Build a `UnitVoiceGrant`, `UnitVoiceUpdate`, or `UnitDataGrant` packet, decoded with
+/// `UnitTrafficChannel`.
+pub fn unit_traffic_channel(opcode: TsbkOpcode, channel: Channel, dest_unit: u32,
+                            src_unit: u32)
+    -> TsbkFields
+{
+    assert!(opcode == TsbkOpcode::UnitVoiceGrant ||
+            opcode == TsbkOpcode::UnitVoiceUpdate ||
+            opcode == TsbkOpcode::UnitDataGrant);
+
+    let mut p = [0; 8];
+
+    write_u16(&mut p[0..], channel.to_bits());
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(opcode, &p)
+}
+
+/// Build a `UnitCallRequest` packet.
+pub fn unit_call_request(opts: ServiceOptions, dest_unit: u32, src_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = opts.to_bits();
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitCallRequest, &p)
+}
+
+/// Build a `PhoneGrant` packet.
+pub fn phone_grant(opts: ServiceOptions, channel: Channel, call_timer: u16, unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = opts.to_bits();
+    write_u16(&mut p[1..], channel.to_bits());
+    write_u16(&mut p[3..], call_timer);
+    write_u24(&mut p[5..], unit);
+
+    build(TsbkOpcode::PhoneGrant, &p)
+}
+
+/// Build a `PhoneAlert` packet from the given encoded phone number digits.
+pub fn phone_alert(digits: &[u8; 5], dest_unit: u32) -> TsbkFields {
+    let mut p = [0; 8];
+
+    (&mut p[0...4]).copy_from_slice(&digits[..]);
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::PhoneAlert, &p)
+}
+
+/// Build a `GroupDataGrant` packet.
+pub fn group_data_grant(opts: ServiceOptions, channel: Channel, talkgroup: TalkGroup,
+                        src_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = opts.to_bits();
+    write_u16(&mut p[1..], channel.to_bits());
+    write_u16(&mut p[3..], talkgroup.to_bits());
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::GroupDataGrant, &p)
+}
+
+/// Build a `UnitStatusUpdate` packet.
+pub fn unit_status_update(user_status: u8, unit_status: u8, dest_unit: u32,
+                          src_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = user_status;
+    p[1] = unit_status;
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitStatusUpdate, &p)
+}
+
+/// Build a `UnitStatusQuery` packet.
+pub fn unit_status_query(dest_unit: u32, src_unit: u32) -> TsbkFields {
+    let mut p = [0; 8];
+
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitStatusQuery, &p)
+}
+
+/// Build a `UnitShortMessage` packet.
+pub fn unit_short_message(message: u16, dest_unit: u32, src_unit: u32) -> TsbkFields {
+    let mut p = [0; 8];
+
+    write_u16(&mut p[0..], message);
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitShortMessage, &p)
+}
+
+/// Build a `UnitMonitor` packet.
+pub fn unit_monitor(silent: bool, tx_mult: u8, dest_unit: u32, src_unit: u32)
+    -> TsbkFields
+{
+    assert!(tx_mult >> 2 == 0);
+
+    let mut p = [0; 8];
+
+    p[1] = (silent as u8) << 7 | tx_mult;
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitMonitor, &p)
+}
+
+/// Build a `UnitCallAlert` packet.
+pub fn unit_call_alert(dest_unit: u32, src_unit: u32) -> TsbkFields {
+    let mut p = [0; 8];
+
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitCallAlert, &p)
+}
+
+/// Build an `AckResponse` packet, with the originating unit as optional additional
+/// information.
+pub fn ack_response(service: TsbkOpcode, src_unit: Option<u32>, dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = (src_unit.is_some() as u8) << 7 | service.to_bits();
+    write_u24(&mut p[2..], src_unit.unwrap_or(0));
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::AckResponse, &p)
+}
+
+/// Build an extended `AckResponse` packet, with the WACN and System ID of the target unit
+/// as additional information.
+pub fn ack_response_extended(service: TsbkOpcode, wacn: u32, system: u16,
+                             dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = 0b11 << 6 | service.to_bits();
+    write_u32(&mut p[1..], network(wacn, system));
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::AckResponse, &p)
+}
+
+/// Build a `QueuedResponse` packet, with optional additional information.
+pub fn queued_response(service: TsbkOpcode, reason: u8, info: Option<u32>,
+                       dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = (info.is_some() as u8) << 7 | service.to_bits();
+    p[1] = reason;
+    write_u24(&mut p[2..], info.unwrap_or(0));
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::QueuedResponse, &p)
+}
+
+/// Build an `ExtendedFunctionResponse` packet.
+pub fn extended_function_response(class: u8, operand: u8, args: u32, src_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = class;
+    p[1] = operand;
+    write_u24(&mut p[2..], args);
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::ExtendedFunctionResponse, &p)
+}
+
+/// Build a `DenyResponse` packet, with optional additional information.
+pub fn deny_response(service: TsbkOpcode, reason: DenyReason, info: Option<u32>,
+                     dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = (info.is_some() as u8) << 7 | service.to_bits();
+    p[1] = reason.to_bits();
+    write_u24(&mut p[2..], info.unwrap_or(0));
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::DenyResponse, &p)
+}
+
+/// Build a `GroupAffiliationResponse` packet.
+pub fn group_affiliation_response(global: bool, response: RegResponse,
+                                  announce_group: TalkGroup, talkgroup: TalkGroup,
+                                  dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = (global as u8) << 7 | response.to_bits();
+    write_u16(&mut p[1..], announce_group.to_bits());
+    write_u16(&mut p[3..], talkgroup.to_bits());
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::GroupAffiliationResponse, &p)
+}
+
+/// Build a `SecondaryControlChannel` packet.
+pub fn secondary_control_channel(rfss: u8, site: u8, tx_channel: Channel,
+                                 rx_channel: Channel, services: SystemServices)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = rfss;
+    p[1] = site;
+    write_u16(&mut p[2..], tx_channel.to_bits());
+    write_u16(&mut p[4..], rx_channel.to_bits());
+    p[6] = services.to_bits();
+
+    build(TsbkOpcode::SecondaryControlChannel, &p)
+}
+
+/// Build a `LocRegResponse` packet.
+pub fn loc_reg_response(response: RegResponse, talkgroup: TalkGroup, rfss: u8, site: u8,
+                        dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = response.to_bits();
+    write_u16(&mut p[1..], talkgroup.to_bits());
+    p[3] = rfss;
+    p[4] = site;
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::LocRegResponse, &p)
+}
+
+/// Build a `UnitRegResponse` packet.
+pub fn unit_reg_response(response: RegResponse, system: u16, src_id: u32, src_addr: u32)
+    -> TsbkFields
+{
+    assert!(system >> 12 == 0);
+
+    let mut p = [0; 8];
+
+    write_u16(&mut p[0..], (response.to_bits() as u16) << 12 | system);
+    write_u24(&mut p[2..], src_id);
+    write_u24(&mut p[5..], src_addr);
+
+    build(TsbkOpcode::UnitRegResponse, &p)
+}
+
+/// Build a `UnitAuthCommand` packet.
+pub fn unit_auth_command(wacn: u32, system: u16, dest_id: u32) -> TsbkFields {
+    let mut p = [0; 8];
+
+    write_u32(&mut p[0..], network(wacn, system));
+    write_u24(&mut p[4..], dest_id);
+
+    build(TsbkOpcode::UnitAuthCommand, &p)
+}
+
+/// Build a `UnitDeregAck` packet.
+pub fn unit_dereg_ack(wacn: u32, system: u16, src_unit: u32) -> TsbkFields {
+    let mut p = [0; 8];
+
+    write_u32(&mut p[1..], network(wacn, system));
+    write_u24(&mut p[5..], src_unit);
+
+    build(TsbkOpcode::UnitDeregAck, &p)
+}
+
+/// Build a `RoamingAddrCommand` packet.
+pub fn roaming_addr_command(op: RoamingStackOp, wacn: u32, system: u16, dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = op.to_bits();
+    write_u32(&mut p[1..], network(wacn, system));
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::RoamingAddrCommand, &p)
+}
+
+/// Build a `RoamingAddrUpdate` packet.
+pub fn roaming_addr_update(last: bool, seq: u8, wacn: u32, system: u16, dest_unit: u32)
+    -> TsbkFields
+{
+    assert!(seq >> 4 == 0);
+
+    let mut p = [0; 8];
+
+    p[0] = (last as u8) << 7 | seq;
+    write_u32(&mut p[1..], network(wacn, system));
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::RoamingAddrUpdate, &p)
+}
+
+/// Build a `SystemServiceBroadcast` packet.
+pub fn system_service_broadcast(prio: u8, available: u32, supported: u32)
+    -> TsbkFields
+{
+    assert!(prio >> 4 == 0);
+
+    let mut p = [0; 8];
+
+    p[0] = prio;
+    write_u24(&mut p[1..], available);
+    write_u24(&mut p[4..], supported);
+
+    build(TsbkOpcode::SystemServiceBroadcast, &p)
+}
+
+/// Build an `AltControlChannel` packet.
+pub fn alt_control_channel(rfss: u8, site: u8, alts: [(Channel, SystemServices); 2])
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = rfss;
+    p[1] = site;
+
+    for (i, &(ch, svc)) in alts.iter().enumerate() {
+        write_u16(&mut p[2 + i * 3..], ch.to_bits());
+        p[4 + i * 3] = svc.to_bits();
+    }
+
+    build(TsbkOpcode::AltControlChannel, &p)
+}
+
+/// Build an `RfssStatusBroadcast` packet.
+pub fn rfss_status_broadcast(area: u8, networked: bool, system: u16, rfss: u8, site: u8,
+                             channel: Channel, services: SystemServices)
+    -> TsbkFields
+{
+    assert!(system >> 12 == 0);
+
+    let mut p = [0; 8];
+
+    p[0] = area;
+    write_u16(&mut p[1..], (networked as u16) << 12 | system);
+    p[3] = rfss;
+    p[4] = site;
+    write_u16(&mut p[5..], channel.to_bits());
+    p[7] = services.to_bits();
+
+    build(TsbkOpcode::RfssStatusBroadcast, &p)
+}
+
+/// Build a `NetworkStatusBroadcast` packet.
+pub fn network_status_broadcast(area: u8, wacn: u32, system: u16, channel: Channel,
+                                services: SystemServices)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[0] = area;
+    write_u32(&mut p[1..], network(wacn, system));
+    write_u16(&mut p[5..], channel.to_bits());
+    p[7] = services.to_bits();
+
+    build(TsbkOpcode::NetworkStatusBroadcast, &p)
+}
+
+/// Build an `AdjacentSite` packet.
+pub fn adjacent_site(area: u8, opts: SiteOptions, system: u16, rfss: u8, site: u8,
+                     channel: Channel, services: SystemServices)
+    -> TsbkFields
+{
+    assert!(system >> 12 == 0);
+
+    let mut p = [0; 8];
+
+    p[0] = area;
+    write_u16(&mut p[1..], (opts.to_bits() as u16) << 12 | system);
+    p[3] = rfss;
+    p[4] = site;
+    write_u16(&mut p[5..], channel.to_bits());
+    p[7] = services.to_bits();
+
+    build(TsbkOpcode::AdjacentSite, &p)
+}
+
+/// Build a `ChannelParamsUpdate` packet for the given channel ID, from the given base
+/// frequency (5Hz steps), bandwidth (125Hz steps), TX offset (250kHz steps), and
+/// inter-channel spacing (125Hz steps), as used by `ChannelParams::new`.
+pub fn channel_params_update(id: u8, base: u32, bandwidth: u16, offset: u16,
+                             spacing: u16)
+    -> TsbkFields
+{
+    assert!(id >> 4 == 0);
+    assert!(bandwidth >> 9 == 0);
+    assert!(offset >> 9 == 0);
+    assert!(spacing >> 10 == 0);
+
+    let mut p = [0; 8];
+
+    p[0] = id << 4 | (bandwidth >> 5) as u8;
+    p[1] = (bandwidth << 3) as u8 | (offset >> 6) as u8;
+    p[2] = (offset << 2) as u8 | (spacing >> 8) as u8;
+    p[3] = spacing as u8;
+    write_u32(&mut p[4..], base);
+
+    build(TsbkOpcode::ChannelParamsUpdate, &p)
+}
+
+/// Build a `ProtectionParamBroadcast` packet.
+pub fn protection_param_broadcast(alg: CryptoAlgorithm, key: u16) -> TsbkFields {
+    let mut p = [0; 8];
+
+    p[1] = alg.to_bits();
+    write_u16(&mut p[2..], key);
+
+    build(TsbkOpcode::ProtectionParamBroadcast, &p)
+}
+
+/// Build a `ProtectionParamUpdate` packet.
+pub fn protection_param_update(alg: CryptoAlgorithm, key: u16, dest_unit: u32)
+    -> TsbkFields
+{
+    let mut p = [0; 8];
+
+    p[1] = alg.to_bits();
+    write_u16(&mut p[2..], key);
+    write_u24(&mut p[5..], dest_unit);
+
+    build(TsbkOpcode::ProtectionParamUpdate, &p)
+}
+
+/// Build a manufacturer-specific packet, decoded with `GroupRegroup`, that adds or
+/// removes talkgroups from a supergroup.
+pub fn group_regroup(opcode: MfgOpcode, supergroup: TalkGroup, groups: [TalkGroup; 3])
+    -> TsbkFields
+{
+    let (mfg, bits) = opcode.to_bits();
+    let mut p = [0; 8];
+
+    write_u16(&mut p[0..], supergroup.to_bits());
+
+    for (i, tg) in groups.iter().enumerate() {
+        write_u16(&mut p[2 + i * 2..], tg.to_bits());
+    }
+
+    TsbkBuilder::mfg(bits, mfg).payload(&p[..]).build()
+}
+
+/// Build a Motorola `ExplicitGroupVoiceGrant` packet.
+pub fn explicit_group_voice_grant(opts: ServiceOptions, tx_channel: Channel,
+                                  rx_channel: Channel, talkgroup: TalkGroup)
+    -> TsbkFields
+{
+    let (mfg, bits) = MfgOpcode::Motorola(MotorolaOpcode::ExplicitGroupVoiceGrant)
+        .to_bits();
+    let mut p = [0; 8];
+
+    p[0] = opts.to_bits();
+    write_u16(&mut p[1..], tx_channel.to_bits());
+    write_u16(&mut p[3..], rx_channel.to_bits());
+    write_u16(&mut p[5..], talkgroup.to_bits());
+
+    TsbkBuilder::mfg(bits, mfg).payload(&p[..]).build()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::fields::*;
+    use trunking::mfg::*;
+    use trunking::tsbk::*;
+    use voice::crypto::CryptoAlgorithm;
+
+    #[test]
+    fn test_grants() {
+        let t = group_voice_grant(ServiceOptions::new(0b10100011),
+            Channel::from_parts(0b1110, 0b010111001100),
+            TalkGroup::Other(0x18E7), 0xF0783C);
+        assert!(t.crc_valid());
+        assert!(t.is_tail());
+        assert!(!t.protected());
+        assert_eq!(t.mfg(), 0);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::GroupVoiceGrant));
+        let g = GroupVoiceGrant::new(t);
+        assert!(g.opts().emergency());
+        assert!(g.opts().full_duplex());
+        assert_eq!(g.opts().prio(), 3);
+        assert_eq!(g.channel().id(), 0b1110);
+        assert_eq!(g.channel().number(), 0b010111001100);
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x18E7));
+        assert_eq!(g.src_unit(), 0xF0783C);
+
+        let t = unit_traffic_channel(TsbkOpcode::UnitDataGrant,
+            Channel::from_parts(1, 2), 0x123456, 0xABCDEF);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitDataGrant));
+        let g = UnitTrafficChannel::new(t);
+        assert_eq!(g.channel(), Channel::from_parts(1, 2));
+        assert_eq!(g.dest_unit(), 0x123456);
+        assert_eq!(g.src_unit(), 0xABCDEF);
+
+        let t = phone_grant(ServiceOptions::new(0b01000111), Channel::from_parts(3, 4),
+            600, 0x654321);
+        let g = PhoneGrant::new(t);
+        assert!(g.opts().protected());
+        assert_eq!(g.channel(), Channel::from_parts(3, 4));
+        assert_eq!(g.call_timer(), 600);
+        assert_eq!(g.unit(), 0x654321);
+
+        let t = group_data_grant(ServiceOptions::new(0b00010001),
+            Channel::from_parts(5, 6), TalkGroup::Everbody, 0x111111);
+        let g = GroupDataGrant::new(t);
+        assert!(g.opts().packet_switched());
+        assert_eq!(g.channel(), Channel::from_parts(5, 6));
+        assert_eq!(g.talkgroup(), TalkGroup::Everbody);
+        assert_eq!(g.src_unit(), 0x111111);
+
+        let t = group_traffic_update(TsbkOpcode::GroupVoiceUpdate, [
+            (Channel::from_parts(1, 1), TalkGroup::Other(0x1234)),
+            (Channel::from_parts(2, 2), TalkGroup::Default),
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::GroupVoiceUpdate));
+        let u = GroupTrafficUpdate::new(t.payload()).updates();
+        assert_eq!(u[0], (Channel::from_parts(1, 1), TalkGroup::Other(0x1234)));
+        assert_eq!(u[1], (Channel::from_parts(2, 2), TalkGroup::Default));
+    }
+
+    #[test]
+    fn test_unit_messages() {
+        let t = unit_call_request(ServiceOptions::new(0b01010101), 0x39C655, 0xEA15F0);
+        let r = UnitCallRequest::new(t.payload());
+        assert!(r.opts().protected());
+        assert_eq!(r.opts().prio(), 0b101);
+        assert_eq!(r.dest_unit(), 0x39C655);
+        assert_eq!(r.src_unit(), 0xEA15F0);
+
+        let t = phone_alert(&[1, 2, 3, 4, 5], 0xFC7E3F);
+        let a = PhoneAlert::new(t.payload());
+        assert_eq!(a.digits(), &[1, 2, 3, 4, 5]);
+        assert_eq!(a.dest_unit(), 0xFC7E3F);
+
+        let t = unit_call_alert(0x555555, 0xAAAAAA);
+        let a = UnitCallAlert::new(t.payload());
+        assert_eq!(a.dest_unit(), 0x555555);
+        assert_eq!(a.src_unit(), 0xAAAAAA);
+
+        let t = unit_status_update(0x12, 0x34, 0xABCDEF, 0x654321);
+        let u = UnitStatusUpdate::new(t);
+        assert_eq!(u.user_status(), 0x12);
+        assert_eq!(u.unit_status(), 0x34);
+        assert_eq!(u.dest_unit(), 0xABCDEF);
+        assert_eq!(u.src_unit(), 0x654321);
+
+        let t = unit_status_query(0xABCDEF, 0x654321);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitStatusQuery));
+        let q = UnitStatusQuery::new(t);
+        assert_eq!(q.dest_unit(), 0xABCDEF);
+        assert_eq!(q.src_unit(), 0x654321);
+
+        let t = unit_short_message(0xBEEF, 0xABCDEF, 0x654321);
+        let m = UnitShortMessage::new(t);
+        assert_eq!(m.message(), 0xBEEF);
+        assert_eq!(m.dest_unit(), 0xABCDEF);
+        assert_eq!(m.src_unit(), 0x654321);
+
+        let t = unit_monitor(true, 2, 0xABCDEF, 0x654321);
+        let m = UnitMonitor::new(t);
+        assert!(m.silent());
+        assert_eq!(m.tx_mult(), 2);
+        assert_eq!(m.dest_unit(), 0xABCDEF);
+        assert_eq!(m.src_unit(), 0x654321);
+    }
+
+    #[test]
+    fn test_responses() {
+        let t = ack_response(TsbkOpcode::UnitCallAlert, Some(0x123456), 0x654321);
+        let a = AckResponse::new(t);
+        assert!(a.has_info());
+        assert!(!a.extended());
+        assert_eq!(a.service(), Some(TsbkOpcode::UnitCallAlert));
+        assert_eq!(a.src_unit(), 0x123456);
+        assert_eq!(a.dest_unit(), 0x654321);
+
+        let t = ack_response_extended(TsbkOpcode::UnitCallAlert, 0xBEE00, 0x29A,
+                                      0x654321);
+        let a = AckResponse::new(t);
+        assert!(a.has_info());
+        assert!(a.extended());
+        assert_eq!(a.wacn(), 0xBEE00);
+        assert_eq!(a.system(), 0x29A);
+        assert_eq!(a.dest_unit(), 0x654321);
+
+        let t = queued_response(TsbkOpcode::GroupVoiceGrant, 0x2F, None, 0x654321);
+        let q = QueuedResponse::new(t);
+        assert!(!q.has_info());
+        assert_eq!(q.service(), Some(TsbkOpcode::GroupVoiceGrant));
+        assert_eq!(q.reason(), 0x2F);
+        assert_eq!(q.dest_unit(), 0x654321);
+
+        let t = deny_response(TsbkOpcode::UnitVoiceGrant, DenyReason::TargetRefused,
+                              Some(0xABCDEF), 0x654321);
+        let d = DenyResponse::new(t);
+        assert!(d.has_info());
+        assert_eq!(d.service(), Some(TsbkOpcode::UnitVoiceGrant));
+        assert_eq!(d.reason(), DenyReason::TargetRefused);
+        assert_eq!(d.info(), 0xABCDEF);
+        assert_eq!(d.dest_unit(), 0x654321);
+
+        let t = extended_function_response(0x00, 0x7F, 0xFFFFFD, 0x123456);
+        let e = ExtendedFunctionResponse::new(t);
+        assert_eq!(e.class(), 0x00);
+        assert_eq!(e.operand(), 0x7F);
+        assert_eq!(e.args(), 0xFFFFFD);
+        assert_eq!(e.src_unit(), 0x123456);
+
+        let t = group_affiliation_response(true, RegResponse::Refuse, TalkGroup::Default,
+                                           TalkGroup::Other(0x4321), 0x123456);
+        let g = GroupAffiliationResponse::new(t);
+        assert!(g.global());
+        assert_eq!(g.response(), RegResponse::Refuse);
+        assert_eq!(g.announce_group(), TalkGroup::Default);
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x4321));
+        assert_eq!(g.dest_unit(), 0x123456);
+    }
+
+    #[test]
+    fn test_registration() {
+        let t = loc_reg_response(RegResponse::Deny, TalkGroup::Other(0xF81C), 0xDA, 0xAA,
+                                 0xF00F33);
+        let r = LocRegResponse::new(t);
+        assert_eq!(r.response(), RegResponse::Deny);
+        assert_eq!(r.talkgroup(), TalkGroup::Other(0xF81C));
+        assert_eq!(r.rfss(), 0xDA);
+        assert_eq!(r.site(), 0xAA);
+        assert_eq!(r.dest_unit(), 0xF00F33);
+
+        let t = unit_reg_response(RegResponse::Fail, 0xAE7, 0xAA5533, 0xF83FCC);
+        let r = UnitRegResponse::new(t);
+        assert_eq!(r.response(), RegResponse::Fail);
+        assert_eq!(r.system(), 0xAE7);
+        assert_eq!(r.src_id(), 0xAA5533);
+        assert_eq!(r.src_addr(), 0xF83FCC);
+
+        let t = unit_dereg_ack(0xCC33A, 0x1F3, 0xFC03E7);
+        let a = UnitDeregAck::new(t);
+        assert_eq!(a.wacn(), 0xCC33A);
+        assert_eq!(a.system(), 0x1F3);
+        assert_eq!(a.src_unit(), 0xFC03E7);
+
+        let t = unit_auth_command(0xBEE00, 0x29A, 0x123456);
+        let a = UnitAuthCommand::new(t);
+        assert_eq!(a.wacn(), 0xBEE00);
+        assert_eq!(a.system(), 0x29A);
+        assert_eq!(a.dest_id(), 0x123456);
+
+        let t = roaming_addr_command(RoamingStackOp::Delete, 0xBEE00, 0x29A, 0x123456);
+        let c = RoamingAddrCommand::new(t);
+        assert_eq!(c.op(), Some(RoamingStackOp::Delete));
+        assert_eq!(c.wacn(), 0xBEE00);
+        assert_eq!(c.system(), 0x29A);
+        assert_eq!(c.dest_unit(), 0x123456);
+
+        let t = roaming_addr_update(false, 9, 0xBEE00, 0x29A, 0x123456);
+        let u = RoamingAddrUpdate::new(t);
+        assert!(!u.is_last());
+        assert_eq!(u.seq(), 9);
+        assert_eq!(u.wacn(), 0xBEE00);
+        assert_eq!(u.system(), 0x29A);
+        assert_eq!(u.dest_unit(), 0x123456);
+    }
+
+    #[test]
+    fn test_broadcasts() {
+        let t = system_service_broadcast(4, 0x123456, 0xABCDEF);
+        let b = SystemServiceBroadcast::new(t);
+        assert_eq!(b.prio(), 4);
+        assert_eq!(b.available(), 0x123456);
+        assert_eq!(b.supported(), 0xABCDEF);
+
+        let t = alt_control_channel(0xE3, 0x55, [
+            (Channel::from_parts(0xB, 0x6AF), SystemServices::new(0x51)),
+            (Channel::from_parts(0xE, 0xAAA), SystemServices::new(0xAE)),
+        ]);
+        let a = AltControlChannel::new(t.payload());
+        assert_eq!(a.rfss(), 0xE3);
+        assert_eq!(a.site(), 0x55);
+        assert_eq!(a.alts()[0], (Channel::from_parts(0xB, 0x6AF),
+                                 SystemServices::new(0x51)));
+        assert_eq!(a.alts()[1], (Channel::from_parts(0xE, 0xAAA),
+                                 SystemServices::new(0xAE)));
+
+        let t = rfss_status_broadcast(0xCC, true, 0x0AA, 0xE7, 0x18,
+                                      Channel::from_parts(0xD, 0x573),
+                                      SystemServices::new(0x51));
+        let r = RfssStatusBroadcast::new(t.payload());
+        assert_eq!(r.area(), 0xCC);
+        assert!(r.networked());
+        assert_eq!(r.system(), 0x0AA);
+        assert_eq!(r.rfss(), 0xE7);
+        assert_eq!(r.site(), 0x18);
+        assert_eq!(r.channel(), Channel::from_parts(0xD, 0x573));
+        assert_eq!(r.services(), SystemServices::new(0x51));
+
+        let t = network_status_broadcast(0xCA, 0xFC2BC, 0xF5B,
+                                         Channel::from_parts(0xD, 0xCE7),
+                                         SystemServices::new(0x51));
+        let n = NetworkStatusBroadcast::new(t.payload());
+        assert_eq!(n.area(), 0xCA);
+        assert_eq!(n.wacn(), 0xFC2BC);
+        assert_eq!(n.system(), 0xF5B);
+        assert_eq!(n.channel(), Channel::from_parts(0xD, 0xCE7));
+        assert_eq!(n.services(), SystemServices::new(0x51));
+
+        let t = adjacent_site(0xCC, SiteOptions::new(0b1011), 0xF3C, 0xAA, 0x55,
+                              Channel::from_parts(3, 0x67E), SystemServices::new(0x51));
+        let a = AdjacentSite::new(t.payload());
+        assert_eq!(a.area(), 0xCC);
+        assert!(a.opts().conventional());
+        assert!(!a.opts().failing());
+        assert!(a.opts().current());
+        assert!(a.opts().networked());
+        assert_eq!(a.system(), 0xF3C);
+        assert_eq!(a.rfss(), 0xAA);
+        assert_eq!(a.site(), 0x55);
+        assert_eq!(a.channel(), Channel::from_parts(3, 0x67E));
+        assert_eq!(a.services(), SystemServices::new(0x51));
+
+        let t = channel_params_update(0b0110, 170201250, 0x64, 0b010110100, 0x32);
+        let p = ChannelParamsUpdate::new(t.payload());
+        assert_eq!(p.id(), 0b0110);
+        assert_eq!(p.params(), ChannelParams::new(170201250, 0x64, 0b010110100, 0x32));
+
+        let t = secondary_control_channel(1, 2, Channel::from_parts(3, 1),
+                                          Channel::from_parts(3, 2),
+                                          SystemServices::new(0x51));
+        let c = SecondaryControlChannel::new(t);
+        assert_eq!(c.rfss(), 1);
+        assert_eq!(c.site(), 2);
+        assert_eq!(c.tx_channel(), Channel::from_parts(3, 1));
+        assert_eq!(c.rx_channel(), Channel::from_parts(3, 2));
+        assert_eq!(c.services(), SystemServices::new(0x51));
+
+        let t = protection_param_broadcast(CryptoAlgorithm::Des, 0x1234);
+        let b = ProtectionParamBroadcast::new(t);
+        assert_eq!(b.crypto_alg(), CryptoAlgorithm::Des);
+        assert_eq!(b.crypto_key(), 0x1234);
+
+        let t = protection_param_update(CryptoAlgorithm::Aes, 0x4321, 0xABCDEF);
+        let u = ProtectionParamUpdate::new(t);
+        assert_eq!(u.crypto_alg(), CryptoAlgorithm::Aes);
+        assert_eq!(u.crypto_key(), 0x4321);
+        assert_eq!(u.dest_unit(), 0xABCDEF);
+    }
+
+    #[test]
+    fn test_mfg() {
+        let groups = [TalkGroup::Other(0x11), TalkGroup::Other(0x22), TalkGroup::Nobody];
+        let t = group_regroup(MfgOpcode::Harris(HarrisOpcode::RegroupAdd),
+                              TalkGroup::Other(0x1234), groups);
+        assert!(t.crc_valid());
+        assert_eq!(t.mfg(), MFG_HARRIS);
+        assert_eq!(t.opcode(), None);
+        assert_eq!(t.mfg_opcode(), Some(MfgOpcode::Harris(HarrisOpcode::RegroupAdd)));
+        let r = GroupRegroup::new(t.payload());
+        assert_eq!(r.supergroup(), TalkGroup::Other(0x1234));
+        assert_eq!(r.groups(), groups);
+
+        let t = explicit_group_voice_grant(ServiceOptions::new(0b10000010),
+                                           Channel::from_parts(3, 1),
+                                           Channel::from_parts(3, 0x61),
+                                           TalkGroup::Other(0x1234));
+        assert_eq!(t.mfg(), MFG_MOTOROLA);
+        assert_eq!(t.mfg_opcode(),
+                   Some(MfgOpcode::Motorola(MotorolaOpcode::ExplicitGroupVoiceGrant)));
+        let g = ExplicitGroupVoiceGrant::new(t.payload());
+        assert!(g.opts().emergency());
+        assert_eq!(g.tx_channel(), Channel::from_parts(3, 1));
+        assert_eq!(g.rx_channel(), Channel::from_parts(3, 0x61));
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x1234));
+    }
+}
@@ -3,6 +3,7 @@
 use util::{slice_u16, slice_u24, slice_u32};
 
 /// Options that can be requested/granted by a service.
+#[derive(Copy, Clone)]
 pub struct ServiceOptions(u8);
 
 impl ServiceOptions {
@@ -21,6 +22,9 @@ impl ServiceOptions {
     pub fn packet_switched(&self) -> bool { self.0 >> 4 & 1 == 1 }
     /// Priority assigned to service, with 1 as lowest and 7 as highest.
     pub fn prio(&self) -> u8 { self.0 & 0x7 }
+
+    /// Convert the options to their byte representation.
+    pub fn to_bits(self) -> u8 { self.0 }
 }
 
 /// Uniquely identifies a channel within a site.
@@ -31,10 +35,21 @@ impl Channel {
     /// Create a new `Channel` from the given 16 bits.
     pub fn new(bytes: &[u8]) -> Channel { Channel(slice_u16(bytes)) }
 
+    /// Create a new `Channel` from the given channel ID and channel number.
+    pub fn from_parts(id: u8, number: u16) -> Channel {
+        assert!(id >> 4 == 0);
+        assert!(number >> 12 == 0);
+
+        Channel((id as u16) << 12 | number)
+    }
+
     /// Channel ID whose parameters to use.
     pub fn id(&self) -> u8 { (self.0 >> 12) as u8 }
     /// Individual channel number within the channel.
     pub fn number(&self) -> u16 { self.0 & 0xFFF }
+
+    /// Convert the channel to its 16-bit representation.
+    pub fn to_bits(self) -> u16 { self.0 }
 }
 
 /// Identifies which group a message belongs to.
@@ -73,6 +88,18 @@ impl TalkGroup {
             _ => Other(bits),
         }
     }
+
+    /// Convert the talkgroup to its 16-bit representation.
+    pub fn to_bits(self) -> u16 {
+        use self::TalkGroup::*;
+
+        match self {
+            Nobody => 0x0000,
+            Default => 0x0001,
+            Everbody => 0xFFFF,
+            Other(bits) => bits,
+        }
+    }
 }
 
 /// Supported services of a control channel.
@@ -98,6 +125,9 @@ impl SystemServices {
     pub fn has_registration(&self) -> bool { self.0 & 0x40 != 0 }
     /// Supports authentication requests.
     pub fn has_auth(&self) -> bool { self.0 & 0x80 != 0 }
+
+    /// Convert the services to their byte representation.
+    pub fn to_bits(self) -> u8 { self.0 }
 }
 
 /// Maps channel identifiers (maximum 16 per control channel) to their tuning parameters.
@@ -156,6 +186,7 @@ impl ChannelParams {
 }
 
 /// Options for a P25 site.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub struct SiteOptions(u8);
 
 impl SiteOptions {
@@ -175,6 +206,9 @@ impl SiteOptions {
     /// Whether site has active network connection with RFSS controller and can
     /// communicate with other sites.
     pub fn networked(&self) -> bool { self.0 & 1 != 0 }
+
+    /// Convert the options to their 4-bit representation.
+    pub fn to_bits(self) -> u8 { self.0 }
 }
 
 /// Updates subscribers about new or ongoing talkgroup conversations.
@@ -352,6 +386,151 @@ impl RegResponse {
             _ => unreachable!(),
         }
     }
+
+    /// Convert the response to its 2-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::RegResponse::*;
+
+        match self {
+            Accept => 0b00,
+            Fail => 0b01,
+            Deny => 0b10,
+            Refuse => 0b11,
+        }
+    }
+}
+
+/// Reason a service request was denied.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum DenyReason {
+    /// Requesting unit isn't valid.
+    InvalidSource,
+    /// Requesting unit isn't authorized for the service.
+    UnauthorizedSource,
+    /// Target unit isn't valid.
+    InvalidTarget,
+    /// Target unit isn't authorized for the service.
+    UnauthorizedTarget,
+    /// Target unit refused the call.
+    TargetRefused,
+    /// Target talkgroup isn't valid.
+    InvalidGroup,
+    /// Target talkgroup isn't authorized for the service.
+    UnauthorizedGroup,
+    /// Dialed digits are invalid.
+    InvalidDialing,
+    /// Telephone number isn't authorized.
+    UnauthorizedPhone,
+    /// PSTN address isn't valid.
+    InvalidPstn,
+    /// Call timer has expired.
+    CallTimeout,
+    /// Call was terminated by the landline.
+    LandlineTerminated,
+    /// Call was terminated by the subscriber unit.
+    UnitTerminated,
+    /// Call was preempted by a higher priority call.
+    CallPreempted,
+    /// Requesting unit isn't allowed to access the site.
+    SiteAccessDenied,
+    /// Requested service isn't supported by the system.
+    Unsupported,
+    /// Reserved or system-defined reason.
+    Other(u8),
+}
+
+impl DenyReason {
+    /// Parse a deny reason from the given byte.
+    pub fn from_bits(bits: u8) -> DenyReason {
+        use self::DenyReason::*;
+
+        match bits {
+            0x10 => InvalidSource,
+            0x11 => UnauthorizedSource,
+            0x20 => InvalidTarget,
+            0x21 => UnauthorizedTarget,
+            0x2F => TargetRefused,
+            0x30 => InvalidGroup,
+            0x31 => UnauthorizedGroup,
+            0x40 => InvalidDialing,
+            0x41 => UnauthorizedPhone,
+            0x42 => InvalidPstn,
+            0x50 => CallTimeout,
+            0x51 => LandlineTerminated,
+            0x52 => UnitTerminated,
+            0x5F => CallPreempted,
+            0x60 => SiteAccessDenied,
+            0xFF => Unsupported,
+            b => Other(b),
+        }
+    }
+
+    /// Convert the reason to its byte representation.
+    pub fn to_bits(self) -> u8 {
+        use self::DenyReason::*;
+
+        match self {
+            InvalidSource => 0x10,
+            UnauthorizedSource => 0x11,
+            InvalidTarget => 0x20,
+            UnauthorizedTarget => 0x21,
+            TargetRefused => 0x2F,
+            InvalidGroup => 0x30,
+            UnauthorizedGroup => 0x31,
+            InvalidDialing => 0x40,
+            UnauthorizedPhone => 0x41,
+            InvalidPstn => 0x42,
+            CallTimeout => 0x50,
+            LandlineTerminated => 0x51,
+            UnitTerminated => 0x52,
+            CallPreempted => 0x5F,
+            SiteAccessDenied => 0x60,
+            Unsupported => 0xFF,
+            Other(b) => b,
+        }
+    }
+}
+
+/// Operation to perform on a unit's roaming address stack.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum RoamingStackOp {
+    /// Clear all entries from the stack.
+    Clear,
+    /// Write the given address to the stack.
+    Write,
+    /// Delete the given address from the stack.
+    Delete,
+    /// Report the stack contents back to the system.
+    Read,
+}
+
+impl RoamingStackOp {
+    /// Try to parse a stack operation from the given byte.
+    pub fn from_bits(bits: u8) -> Option<RoamingStackOp> {
+        use self::RoamingStackOp::*;
+
+        match bits {
+            0x00 => Some(Clear),
+            0x01 => Some(Write),
+            0x02 => Some(Delete),
+            0x03 => Some(Read),
+            _ => None,
+        }
+    }
+
+    /// Convert the operation to its byte representation.
+    pub fn to_bits(self) -> u8 {
+        use self::RoamingStackOp::*;
+
+        match self {
+            Clear => 0x00,
+            Write => 0x01,
+            Delete => 0x02,
+            Read => 0x03,
+        }
+    }
 }
 
 /// Request for a target unit to call a source unit.
@@ -0,0 +1,437 @@
+//! Follow talkgroup calls from a control channel to their traffic channels.
+//!
+//! The tracker listens to a control channel for voice grants and updates, chooses which
+//! talkgroup to follow based on a priority list and lockouts, and retunes the receiver
+//! through a user-supplied `Tuner`. While on a traffic channel, it returns to the control
+//! channel when the call is terminated or no traffic has been heard for the hang time,
+//! and moves to a higher priority call if link control announces one.
+//!
+//! Timestamps are supplied by the caller and may be in any monotonic unit, such as
+//! sample counts or milliseconds.
+
+use message::receiver::MessageEvent;
+use trunking::fields::{
+    Channel,
+    ChannelParamsMap,
+    ChannelParamsUpdate,
+    GroupTrafficUpdate,
+    TalkGroup,
+};
+use trunking::tsbk::{GroupVoiceGrant, TsbkOpcode};
+use voice::control::{GroupVoiceTraffic, LinkControlOpcode};
+
+/// Receiver frontend that can be retuned by the tracker.
+pub trait Tuner {
+    /// Tune the receiver to the given frequency in Hz.
+    fn tune(&mut self, freq: u32);
+}
+
+/// Traffic channel call currently being followed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Followed {
+    /// Talkgroup of the call.
+    pub talkgroup: TalkGroup,
+    /// Traffic channel of the call.
+    pub channel: Channel,
+    /// Receive frequency of the traffic channel in Hz.
+    pub freq: u32,
+    /// Priority of the talkgroup, with lower values taking precedence.
+    pub prio: usize,
+    /// Time traffic was last heard on the channel.
+    last: u64,
+}
+
+/// Follows talkgroup calls between a control channel and traffic channels.
+pub struct TrunkTracker<T: Tuner> {
+    /// Frontend used for retuning.
+    tuner: T,
+    /// Receive frequency of the control channel in Hz.
+    control: u32,
+    /// Channel identifier tables, learned from the control channel.
+    params: ChannelParamsMap,
+    /// Followed talkgroups, in order of decreasing priority.
+    prios: Vec<TalkGroup>,
+    /// Talkgroups that are never followed.
+    lockouts: Vec<TalkGroup>,
+    /// Whether talkgroups not in the priority list are followed, at the lowest priority.
+    unlisted: bool,
+    /// Time without traffic after which a traffic channel is released.
+    hang: u64,
+    /// Call currently being followed, or `None` if on the control channel.
+    cur: Option<Followed>,
+}
+
+impl<T: Tuner> TrunkTracker<T> {
+    /// Create a new `TrunkTracker` that uses the given tuner, starting on the control
+    /// channel at the given frequency in Hz and releasing traffic channels after `hang`
+    /// time units without traffic.
+    pub fn new(mut tuner: T, control: u32, hang: u64) -> TrunkTracker<T> {
+        tuner.tune(control);
+
+        TrunkTracker {
+            tuner: tuner,
+            control: control,
+            params: ChannelParamsMap::default(),
+            prios: vec![],
+            lockouts: vec![],
+            unlisted: false,
+            hang: hang,
+            cur: None,
+        }
+    }
+
+    /// Tuner used by the tracker.
+    pub fn tuner(&self) -> &T { &self.tuner }
+
+    /// Call currently being followed, or `None` if on the control channel.
+    pub fn following(&self) -> Option<&Followed> { self.cur.as_ref() }
+
+    /// Set the talkgroups to follow, in order of decreasing priority.
+    pub fn set_priorities(&mut self, prios: Vec<TalkGroup>) { self.prios = prios; }
+
+    /// Set whether talkgroups not in the priority list are followed, at a priority
+    /// below all listed talkgroups.
+    pub fn follow_unlisted(&mut self, unlisted: bool) { self.unlisted = unlisted; }
+
+    /// Never follow the given talkgroup, releasing its traffic channel if it's currently
+    /// being followed.
+    pub fn lockout(&mut self, tg: TalkGroup) {
+        if !self.lockouts.contains(&tg) {
+            self.lockouts.push(tg);
+        }
+
+        if self.cur.map_or(false, |c| c.talkgroup == tg) {
+            self.release();
+        }
+    }
+
+    /// Remove the lockout of the given talkgroup.
+    pub fn unlock(&mut self, tg: TalkGroup) {
+        self.lockouts.retain(|&t| t != tg);
+    }
+
+    /// Priority of the given talkgroup, with lower values taking precedence, or `None`
+    /// if it shouldn't be followed.
+    ///
+    /// The null talkgroup marks an empty slot in an update, so it's never followed.
+    pub fn priority(&self, tg: TalkGroup) -> Option<usize> {
+        if tg == TalkGroup::Nobody || self.lockouts.contains(&tg) {
+            return None;
+        }
+
+        match self.prios.iter().position(|&t| t == tg) {
+            Some(idx) => Some(idx),
+            None if self.unlisted => Some(self.prios.len()),
+            None => None,
+        }
+    }
+
+    /// Handle the given receiver event received at the given time.
+    pub fn feed(&mut self, time: u64, event: &MessageEvent) {
+        self.poll(time);
+
+        match self.cur {
+            None => self.feed_control(time, event),
+            Some(_) => self.feed_traffic(time, event),
+        }
+    }
+
+    /// Release the current traffic channel if no traffic has been heard within the hang
+    /// time as of the given time.
+    pub fn poll(&mut self, time: u64) {
+        let expired = match self.cur {
+            Some(c) => time.saturating_sub(c.last) >= self.hang,
+            None => false,
+        };
+
+        if expired {
+            self.release();
+        }
+    }
+
+    /// Return to the control channel.
+    pub fn release(&mut self) {
+        if self.cur.take().is_some() {
+            self.tuner.tune(self.control);
+        }
+    }
+
+    /// Handle an event received on the control channel.
+    fn feed_control(&mut self, time: u64, event: &MessageEvent) {
+        let tsbk = match *event {
+            MessageEvent::TrunkingControl(tsbk) => tsbk,
+            _ => return,
+        };
+
+        if tsbk.protected() {
+            return;
+        }
+
+        match tsbk.opcode() {
+            Some(TsbkOpcode::ChannelParamsUpdate) =>
+                self.params.update(&ChannelParamsUpdate::new(tsbk.payload())),
+            Some(TsbkOpcode::GroupVoiceGrant) => {
+                let dec = GroupVoiceGrant::new(tsbk);
+                self.consider(time, dec.talkgroup(), dec.channel());
+            },
+            Some(TsbkOpcode::GroupVoiceUpdate) => {
+                let dec = GroupTrafficUpdate::new(tsbk.payload());
+
+                for &(ch, tg) in dec.updates().iter() {
+                    self.consider(time, tg, ch);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Handle an event received on a traffic channel.
+    fn feed_traffic(&mut self, time: u64, event: &MessageEvent) {
+        match *event {
+            MessageEvent::PacketNID(_) | MessageEvent::VoiceFrame(_) => self.touch(time),
+            MessageEvent::VoiceTerm(_) => self.release(),
+            MessageEvent::LinkControl(lc) => {
+                if lc.protected() {
+                    return;
+                }
+
+                match lc.opcode() {
+                    Some(LinkControlOpcode::GroupVoiceTraffic) => {
+                        let tg = GroupVoiceTraffic::new(lc).talkgroup();
+
+                        if self.priority(tg).is_none() {
+                            self.release();
+                        } else {
+                            self.touch(time);
+                        }
+                    },
+                    Some(LinkControlOpcode::GroupVoiceUpdate) => {
+                        let dec = GroupTrafficUpdate::new(lc.payload());
+
+                        for &(ch, tg) in dec.updates().iter() {
+                            self.consider(time, tg, ch);
+                        }
+                    },
+                    Some(LinkControlOpcode::ChannelParamsUpdate) =>
+                        self.params.update(&ChannelParamsUpdate::new(lc.payload())),
+                    Some(LinkControlOpcode::CallTermination) => self.release(),
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Follow the given talkgroup call on the given channel if it takes precedence over
+    /// the current call.
+    fn consider(&mut self, time: u64, tg: TalkGroup, ch: Channel) {
+        let prio = match self.priority(tg) {
+            Some(prio) => prio,
+            None => return,
+        };
+
+        let freq = match self.params.lookup(ch.id()) {
+            Some(p) => p.rx_freq(ch.number()),
+            None => return,
+        };
+
+        if let Some(c) = self.cur {
+            if c.talkgroup == tg || prio >= c.prio {
+                return;
+            }
+        }
+
+        self.tuner.tune(freq);
+
+        self.cur = Some(Followed {
+            talkgroup: tg,
+            channel: ch,
+            freq: freq,
+            prio: prio,
+            last: time,
+        });
+    }
+
+    /// Record that traffic was heard on the current channel.
+    fn touch(&mut self, time: u64) {
+        if let Some(c) = self.cur.as_mut() {
+            c.last = time;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use message::receiver::MessageEvent;
+    use trunking::build::{channel_params_update, group_traffic_update,
+                          group_voice_grant};
+    use trunking::fields::{Channel, ServiceOptions, TalkGroup};
+    use trunking::tsbk::TsbkOpcode;
+    use voice::control::{group_voice_traffic, LinkControlBuilder, LinkControlOpcode};
+    use voice::frame::VoiceFrame;
+
+    struct MockTuner(Vec<u32>);
+
+    impl Tuner for MockTuner {
+        fn tune(&mut self, freq: u32) { self.0.push(freq); }
+    }
+
+    const CONTROL: u32 = 851_000_000;
+
+    fn tracker() -> TrunkTracker<MockTuner> {
+        let mut t = TrunkTracker::new(MockTuner(vec![]), CONTROL, 100);
+
+        t.set_priorities(vec![TalkGroup::Other(100), TalkGroup::Other(200)]);
+        t.feed(0, &MessageEvent::TrunkingControl(
+            channel_params_update(1, 170_200_000, 0x64, 0x0, 0x32)));
+
+        t
+    }
+
+    fn grant(tg: u16, ch: u16) -> MessageEvent {
+        MessageEvent::TrunkingControl(group_voice_grant(ServiceOptions::new(0),
+            Channel::from_parts(1, ch), TalkGroup::Other(tg), 0x123456))
+    }
+
+    fn frame() -> MessageEvent {
+        MessageEvent::VoiceFrame(VoiceFrame {
+            chunks: [0; 8],
+            errors: [0; 7],
+        })
+    }
+
+    fn freq(ch: u16) -> u32 { CONTROL + ch as u32 * 6250 }
+
+    #[test]
+    fn test_follow() {
+        let mut t = tracker();
+        assert_eq!(t.tuner().0, vec![CONTROL]);
+
+        // Unlisted talkgroups aren't followed.
+        t.feed(1, &grant(300, 10));
+        assert!(t.following().is_none());
+
+        t.feed(2, &grant(200, 10));
+        let c = *t.following().unwrap();
+        assert_eq!(c.talkgroup, TalkGroup::Other(200));
+        assert_eq!(c.freq, freq(10));
+        assert_eq!(c.prio, 1);
+        assert_eq!(t.tuner().0, vec![CONTROL, freq(10)]);
+
+        // Traffic keeps the channel alive past the hang time.
+        t.feed(90, &frame());
+        t.feed(180, &frame());
+        assert!(t.following().is_some());
+
+        let lc = LinkControlBuilder::new(LinkControlOpcode::CallTermination).build();
+        t.feed(181, &MessageEvent::VoiceTerm(lc));
+        assert!(t.following().is_none());
+        assert_eq!(t.tuner().0, vec![CONTROL, freq(10), CONTROL]);
+
+        t.feed(182, &grant(100, 11));
+        t.feed(200, &frame());
+        t.poll(299);
+        assert!(t.following().is_some());
+        t.poll(300);
+        assert!(t.following().is_none());
+
+        t.feed(301, &grant(100, 12));
+        t.feed(302, &MessageEvent::LinkControl(
+            LinkControlBuilder::new(LinkControlOpcode::CallTermination).build()));
+        assert!(t.following().is_none());
+
+        assert_eq!(t.tuner().0, vec![
+            CONTROL, freq(10), CONTROL, freq(11), CONTROL, freq(12), CONTROL,
+        ]);
+    }
+
+    #[test]
+    fn test_preempt() {
+        let mut t = tracker();
+
+        t.feed(1, &grant(200, 10));
+
+        // Updates for the same or lower priority talkgroups are ignored.
+        let lc = LinkControlBuilder::new(LinkControlOpcode::GroupVoiceUpdate)
+            .payload(&[0x10, 0x14, 0x00, 200, 0x10, 0x15, 0x01, 0x2C])
+            .build();
+        t.feed(2, &MessageEvent::LinkControl(lc));
+        assert_eq!(t.following().unwrap().channel, Channel::from_parts(1, 10));
+
+        let lc = LinkControlBuilder::new(LinkControlOpcode::GroupVoiceUpdate)
+            .payload(&[0x10, 0x14, 0x00, 200, 0x10, 0x16, 0x00, 100])
+            .build();
+        t.feed(3, &MessageEvent::LinkControl(lc));
+
+        let c = *t.following().unwrap();
+        assert_eq!(c.talkgroup, TalkGroup::Other(100));
+        assert_eq!(c.channel, Channel::from_parts(1, 0x16));
+        assert_eq!(t.tuner().0, vec![CONTROL, freq(10), freq(0x16)]);
+
+        // Control channel updates are also considered.
+        t.release();
+        t.feed(4, &MessageEvent::TrunkingControl(group_traffic_update(
+            TsbkOpcode::GroupVoiceUpdate, [
+                (Channel::from_parts(1, 20), TalkGroup::Other(200)),
+                (Channel::from_parts(1, 21), TalkGroup::Other(100)),
+            ])));
+        assert_eq!(t.following().unwrap().talkgroup, TalkGroup::Other(100));
+    }
+
+    #[test]
+    fn test_lockout() {
+        let mut t = tracker();
+
+        t.follow_unlisted(true);
+        assert_eq!(t.priority(TalkGroup::Other(300)), Some(2));
+
+        t.lockout(TalkGroup::Other(100));
+        assert_eq!(t.priority(TalkGroup::Other(100)), None);
+        t.feed(1, &grant(100, 10));
+        assert!(t.following().is_none());
+
+        t.feed(2, &grant(300, 11));
+        assert_eq!(t.following().unwrap().talkgroup, TalkGroup::Other(300));
+
+        // Locking out the current talkgroup releases the channel.
+        t.lockout(TalkGroup::Other(300));
+        assert!(t.following().is_none());
+
+        t.unlock(TalkGroup::Other(100));
+        t.feed(3, &grant(100, 10));
+        assert_eq!(t.following().unwrap().talkgroup, TalkGroup::Other(100));
+
+        // Traffic for a locked out talkgroup on the channel releases it.
+        t.lockout(TalkGroup::Other(300));
+        t.feed(4, &MessageEvent::LinkControl(group_voice_traffic(0,
+            ServiceOptions::new(0), TalkGroup::Other(300), 0x123456)));
+        assert!(t.following().is_none());
+    }
+
+    #[test]
+    fn test_empty_slot() {
+        let mut t = tracker();
+
+        t.follow_unlisted(true);
+        t.feed(1, &MessageEvent::TrunkingControl(
+            channel_params_update(0, 170_200_000, 0x64, 0x0, 0x32)));
+        assert_eq!(t.priority(TalkGroup::Nobody), None);
+
+        t.feed(2, &MessageEvent::TrunkingControl(group_traffic_update(
+            TsbkOpcode::GroupVoiceUpdate, [
+                (Channel::new(&[0, 0]), TalkGroup::Nobody),
+                (Channel::new(&[0, 0]), TalkGroup::Nobody),
+            ])));
+        assert!(t.following().is_none());
+        assert_eq!(t.tuner().0, vec![CONTROL]);
+
+        t.feed(3, &MessageEvent::TrunkingControl(group_traffic_update(
+            TsbkOpcode::GroupVoiceUpdate, [
+                (Channel::new(&[0, 0]), TalkGroup::Nobody),
+                (Channel::from_parts(1, 21), TalkGroup::Other(300)),
+            ])));
+        assert_eq!(t.following().unwrap().talkgroup, TalkGroup::Other(300));
+        assert_eq!(t.tuner().0, vec![CONTROL, freq(21)]);
+    }
+}
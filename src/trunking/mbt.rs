@@ -0,0 +1,375 @@
+//! Decode Multiple Block Trunking (MBT) packets, which carry trunking messages in the
+//! alternative and extended formats that are too large for a single TSBK.
+//!
+//! An MBT is received as a data packet with a trunking header, so the header and block
+//! decoding is handled by `data::receiver`. The header carries the opcode along with some
+//! of the message fields, and the remaining fields are carried in the data blocks.
+
+use data::{DataHeader, DataPacket, TrunkingFields};
+use trunking::fields::{Channel, TalkGroup, ServiceOptions, SiteOptions, SystemServices};
+use trunking::mfg::Manufacturer;
+use trunking::tsbk::TsbkOpcode;
+use util::{slice_u16, slice_u24, slice_u32};
+
+/// A Multiple Block Trunking packet.
+pub struct MbtFields {
+    /// Header fields.
+    header: TrunkingFields,
+    /// Bytes carried in the data blocks, with the packet checksum removed.
+    data: Vec<u8>,
+}
+
+impl MbtFields {
+    /// Create a new `MbtFields` from the given header fields and data block bytes.
+    pub fn new(header: TrunkingFields, data: Vec<u8>) -> MbtFields {
+        MbtFields {
+            header: header,
+            data: data,
+        }
+    }
+
+    /// Interpret the given data packet as an MBT, if it has a trunking header.
+    pub fn from_packet(pkt: DataPacket) -> Option<MbtFields> {
+        match pkt.header {
+            DataHeader::Trunking(h) => Some(MbtFields::new(h, pkt.data)),
+            _ => None,
+        }
+    }
+
+    /// Header fields of the packet.
+    pub fn header(&self) -> &TrunkingFields { &self.header }
+    /// Type of message contained in the packet, or `None` if the packet has a
+    /// manufacturer-specific format.
+    pub fn opcode(&self) -> Option<TsbkOpcode> {
+        if Manufacturer::from_bits(self.mfg()).is_standard() {
+            TsbkOpcode::from_bits(self.header.opcode.0)
+        } else {
+            None
+        }
+    }
+    /// Manufacturer ID, which determines if the packet is standardized.
+    pub fn mfg(&self) -> u8 { self.header.mfg.0 }
+    /// Whether the packet is an outbound message.
+    pub fn is_outbound(&self) -> bool { self.header.preamble.is_outbound() }
+    /// Bytes carried in the data blocks.
+    pub fn data(&self) -> &[u8] { &self.data[..] }
+
+    /// Address field of the header, whose meaning depends on the opcode.
+    fn addr(&self) -> u32 { self.header.addr.0 }
+    /// Opcode-specific fields of the header.
+    fn args(&self) -> [u8; 2] { self.header.args }
+
+    /// Get the data bytes if there are at least the given amount.
+    fn data_len(&self, len: usize) -> Option<&[u8]> {
+        if self.data.len() >= len {
+            Some(&self.data[..])
+        } else {
+            None
+        }
+    }
+}
+
+/// Indicates a talkgroup has been granted a voice traffic channel, with explicit transmit
+/// and receive channels.
+pub struct GroupVoiceGrantExplicit<'a>(&'a MbtFields, &'a [u8]);
+
+impl<'a> GroupVoiceGrantExplicit<'a> {
+    /// Create a new `GroupVoiceGrantExplicit` decoder from the base MBT decoder, if it
+    /// carries enough data.
+    pub fn new(mbt: &'a MbtFields) -> Option<Self> {
+        mbt.data_len(6).map(|data| GroupVoiceGrantExplicit(mbt, data))
+    }
+
+    /// Options requested/granted for the traffic channel.
+    pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0.args()[0]) }
+    /// Unit that initiated the conversation.
+    pub fn src_unit(&self) -> u32 { self.0.addr() }
+    /// Parameters for tuning to the traffic channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.1[0...1]) }
+    /// Parameters for tuning to the traffic channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.1[2...3]) }
+    /// Talkgroup for the conversation.
+    pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.1[4...5]) }
+}
+
+/// Indicates a pair of units have been granted a voice traffic channel, with the fully
+/// qualified ID of the originating unit.
+pub struct UnitVoiceGrantExtended<'a>(&'a MbtFields, &'a [u8]);
+
+impl<'a> UnitVoiceGrantExtended<'a> {
+    /// Create a new `UnitVoiceGrantExtended` decoder from the base MBT decoder, if it
+    /// carries enough data.
+    pub fn new(mbt: &'a MbtFields) -> Option<Self> {
+        mbt.data_len(11).map(|data| UnitVoiceGrantExtended(mbt, data))
+    }
+
+    /// Options requested/granted for the traffic channel.
+    pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0.args()[0]) }
+    /// Destination unit of the call.
+    pub fn dest_unit(&self) -> u32 { self.0.addr() }
+    /// WACN ID of the originating unit.
+    pub fn src_wacn(&self) -> u32 { slice_u32(&self.1[0...3]) >> 12 }
+    /// System ID of the originating unit within its WACN.
+    pub fn src_system(&self) -> u16 { slice_u16(&self.1[2...3]) & 0xFFF }
+    /// Originating unit of the call.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.1[4...6]) }
+    /// Parameters for tuning to the traffic channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.1[7...8]) }
+    /// Parameters for tuning to the traffic channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.1[9...10]) }
+}
+
+/// Advertisement of an adjacent/nearby site, with explicit transmit and receive channels
+/// and WACN.
+pub struct AdjacentSiteExtended<'a>(&'a MbtFields, &'a [u8]);
+
+impl<'a> AdjacentSiteExtended<'a> {
+    /// Create a new `AdjacentSiteExtended` decoder from the base MBT decoder, if it
+    /// carries enough data.
+    pub fn new(mbt: &'a MbtFields) -> Option<Self> {
+        mbt.data_len(8).map(|data| AdjacentSiteExtended(mbt, data))
+    }
+
+    /// Location registration area of adjacent site.
+    pub fn area(&self) -> u8 { (self.0.addr() >> 16) as u8 }
+    /// Description of adjacent site.
+    pub fn opts(&self) -> SiteOptions {
+        SiteOptions::new((self.0.addr() >> 12) as u8 & 0xF)
+    }
+    /// System ID of adjacent site within WACN.
+    pub fn system(&self) -> u16 { self.0.addr() as u16 & 0xFFF }
+    /// RF Subsystem ID of adjacent site within the System.
+    pub fn rfss(&self) -> u8 { self.0.args()[0] }
+    /// Site ID of adjacent site within the RFSS.
+    pub fn site(&self) -> u8 { self.0.args()[1] }
+    /// Parameters for tuning to the control channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.1[0...1]) }
+    /// Parameters for tuning to the control channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.1[2...3]) }
+    /// WACN ID of adjacent site.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.1[4...6]) >> 4 }
+    /// Services supported by the adjacent site.
+    pub fn services(&self) -> SystemServices { SystemServices::new(self.1[7]) }
+}
+
+/// Site and RFSS information of current control channel, with explicit transmit and
+/// receive channels.
+pub struct RfssStatusExtended<'a>(&'a MbtFields, &'a [u8]);
+
+impl<'a> RfssStatusExtended<'a> {
+    /// Create a new `RfssStatusExtended` decoder from the base MBT decoder, if it
+    /// carries enough data.
+    pub fn new(mbt: &'a MbtFields) -> Option<Self> {
+        mbt.data_len(7).map(|data| RfssStatusExtended(mbt, data))
+    }
+
+    /// Location registration area of current site.
+    pub fn area(&self) -> u8 { (self.0.addr() >> 16) as u8 }
+    /// Whether the site is networked with the RFSS controller.
+    pub fn networked(&self) -> bool { self.0.addr() & 0x1000 != 0 }
+    /// System ID of current site within WACN.
+    pub fn system(&self) -> u16 { self.0.addr() as u16 & 0xFFF }
+    /// RF Subsystem ID of current site within System.
+    pub fn rfss(&self) -> u8 { self.1[0] }
+    /// Site ID of current site within RFSS.
+    pub fn site(&self) -> u8 { self.1[1] }
+    /// Parameters for tuning to the control channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.1[2...3]) }
+    /// Parameters for tuning to the control channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.1[4...5]) }
+    /// Services supported by the current site.
+    pub fn services(&self) -> SystemServices { SystemServices::new(self.1[6]) }
+}
+
+/// WACN and System ID information of current control channel, with explicit transmit and
+/// receive channels.
+pub struct NetworkStatusExtended<'a>(&'a MbtFields, &'a [u8]);
+
+impl<'a> NetworkStatusExtended<'a> {
+    /// Create a new `NetworkStatusExtended` decoder from the base MBT decoder, if it
+    /// carries enough data.
+    pub fn new(mbt: &'a MbtFields) -> Option<Self> {
+        mbt.data_len(8).map(|data| NetworkStatusExtended(mbt, data))
+    }
+
+    /// Location registration area of site.
+    pub fn area(&self) -> u8 { (self.0.addr() >> 16) as u8 }
+    /// System ID of site within WACN.
+    pub fn system(&self) -> u16 { self.0.addr() as u16 & 0xFFF }
+    /// WACN ID within the communications network.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.1[0...2]) >> 4 }
+    /// Parameters for tuning to the control channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.1[3...4]) }
+    /// Parameters for tuning to the control channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.1[5...6]) }
+    /// Services supported by the current site.
+    pub fn services(&self) -> SystemServices { SystemServices::new(self.1[7]) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use data::fields;
+    use data::header::*;
+    use trunking::fields::TalkGroup;
+    use trunking::tsbk::TsbkOpcode;
+
+    fn mbt(opcode: u8, addr: u32, args: [u8; 2], data: &[u8]) -> MbtFields {
+        MbtFields::new(TrunkingFields {
+            preamble: TrunkingPreamble::outbound(),
+            sap: ServiceAccessPoint(fields::ServiceAccessPoint::TrunkingControl),
+            mfg: Manufacturer(0),
+            addr: LogicalLink(addr),
+            blocks: BlockCount {
+                full_pkt: true,
+                count: 1,
+            },
+            opcode: TrunkingOpcode(opcode),
+            args: args,
+        }, data.to_vec())
+    }
+
+    #[test]
+    fn test_group_voice_grant() {
+        let m = mbt(0b000000, 0xABCDEF, [0b10000011, 0], &[
+            0b00011000,
+            0b00000001,
+            0b00101000,
+            0b00000010,
+            0x12,
+            0x34,
+            0x00,
+            0x00,
+        ]);
+
+        assert_eq!(m.opcode(), Some(TsbkOpcode::GroupVoiceGrant));
+        assert!(m.is_outbound());
+
+        let g = GroupVoiceGrantExplicit::new(&m).unwrap();
+        assert!(g.opts().emergency());
+        assert_eq!(g.opts().prio(), 3);
+        assert_eq!(g.src_unit(), 0xABCDEF);
+        assert_eq!(g.tx_channel().id(), 1);
+        assert_eq!(g.tx_channel().number(), 0b100000000001);
+        assert_eq!(g.rx_channel().id(), 2);
+        assert_eq!(g.rx_channel().number(), 0b100000000010);
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x1234));
+
+        let m = mbt(0b000000, 0xABCDEF, [0, 0], &[0; 4]);
+        assert!(GroupVoiceGrantExplicit::new(&m).is_none());
+    }
+
+    #[test]
+    fn test_unit_voice_grant() {
+        let m = mbt(0b000100, 0x123456, [0b01000000, 0], &[
+            0xBE,
+            0xE0,
+            0x02,
+            0x9A,
+            0xAB,
+            0xCD,
+            0xEF,
+            0x10,
+            0x05,
+            0x10,
+            0x06,
+            0x00,
+        ]);
+
+        assert_eq!(m.opcode(), Some(TsbkOpcode::UnitVoiceGrant));
+
+        let g = UnitVoiceGrantExtended::new(&m).unwrap();
+        assert!(g.opts().protected());
+        assert_eq!(g.dest_unit(), 0x123456);
+        assert_eq!(g.src_wacn(), 0xBEE00);
+        assert_eq!(g.src_system(), 0x29A);
+        assert_eq!(g.src_unit(), 0xABCDEF);
+        assert_eq!(g.tx_channel().id(), 1);
+        assert_eq!(g.tx_channel().number(), 5);
+        assert_eq!(g.rx_channel().id(), 1);
+        assert_eq!(g.rx_channel().number(), 6);
+    }
+
+    #[test]
+    fn test_adjacent_site() {
+        let m = mbt(0b111100, 0xCCB3C4, [0xAA, 0x55], &[
+            0x30,
+            0x01,
+            0x30,
+            0x02,
+            0xBE,
+            0xE0,
+            0x00,
+            0b01010001,
+        ]);
+
+        assert_eq!(m.opcode(), Some(TsbkOpcode::AdjacentSite));
+
+        let a = AdjacentSiteExtended::new(&m).unwrap();
+        assert_eq!(a.area(), 0xCC);
+        assert!(a.opts().conventional());
+        assert!(!a.opts().failing());
+        assert!(a.opts().current());
+        assert!(a.opts().networked());
+        assert_eq!(a.system(), 0x3C4);
+        assert_eq!(a.rfss(), 0xAA);
+        assert_eq!(a.site(), 0x55);
+        assert_eq!(a.tx_channel().number(), 1);
+        assert_eq!(a.rx_channel().number(), 2);
+        assert_eq!(a.wacn(), 0xBEE00);
+        assert!(a.services().is_composite());
+        assert!(a.services().has_data());
+        assert!(a.services().has_registration());
+    }
+
+    #[test]
+    fn test_rfss_status() {
+        let m = mbt(0b111010, 0x0513C4, [0, 0], &[
+            0x01,
+            0x02,
+            0x30,
+            0x01,
+            0x30,
+            0x02,
+            0b00110000,
+            0x00,
+        ]);
+
+        assert_eq!(m.opcode(), Some(TsbkOpcode::RfssStatusBroadcast));
+
+        let r = RfssStatusExtended::new(&m).unwrap();
+        assert_eq!(r.area(), 0x05);
+        assert!(r.networked());
+        assert_eq!(r.system(), 0x3C4);
+        assert_eq!(r.rfss(), 1);
+        assert_eq!(r.site(), 2);
+        assert_eq!(r.tx_channel().id(), 3);
+        assert_eq!(r.rx_channel().number(), 2);
+        assert!(r.services().has_data());
+        assert!(r.services().has_voice());
+    }
+
+    #[test]
+    fn test_network_status() {
+        let m = mbt(0b111011, 0x0503C4, [0, 0], &[
+            0xBE,
+            0xE0,
+            0x00,
+            0x30,
+            0x01,
+            0x30,
+            0x02,
+            0b00100000,
+        ]);
+
+        assert_eq!(m.opcode(), Some(TsbkOpcode::NetworkStatusBroadcast));
+
+        let n = NetworkStatusExtended::new(&m).unwrap();
+        assert_eq!(n.area(), 0x05);
+        assert_eq!(n.system(), 0x3C4);
+        assert_eq!(n.wacn(), 0xBEE00);
+        assert_eq!(n.tx_channel().number(), 1);
+        assert_eq!(n.rx_channel().number(), 2);
+        assert!(n.services().has_voice());
+    }
+}
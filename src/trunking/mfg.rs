@@ -0,0 +1,280 @@
+//! Decode manufacturer-specific extensions to trunking and link control messages.
+//!
+//! Packets with a non-standard manufacturer ID (MFID) reuse the opcode field with
+//! manufacturer-defined semantics, so their payloads must be interpreted based on the
+//! combination of MFID and opcode.
+//!
+//! Note that some extended messages share the layout of a standard message. Motorola
+//! patch group voice grants and updates can be decoded with `tsbk::GroupVoiceGrant` and
+//! `fields::GroupTrafficUpdate`, and Motorola patch group voice traffic link control can
+//! be decoded with `control::GroupVoiceTraffic`. In each case the talkgroup field carries
+//! the patch supergroup.
+
+use trunking::fields::{Channel, TalkGroup, ServiceOptions};
+
+/// MFID of standard messages.
+pub const MFG_STANDARD: u8 = 0x00;
+/// Alternative MFID of standard messages.
+pub const MFG_STANDARD_ALT: u8 = 0x01;
+/// MFID of Motorola extended messages.
+pub const MFG_MOTOROLA: u8 = 0x90;
+/// MFID of Harris extended messages.
+pub const MFG_HARRIS: u8 = 0xA4;
+
+/// Manufacturer that defines the format of a message.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "ser", derive(Serialize))]
+pub enum Manufacturer {
+    /// Standard P25 format.
+    Standard,
+    /// Motorola extended format.
+    Motorola,
+    /// Harris extended format.
+    Harris,
+    /// Format of some other manufacturer.
+    Other(u8),
+}
+
+impl Manufacturer {
+    /// Parse a manufacturer from the given MFID byte.
+    pub fn from_bits(bits: u8) -> Manufacturer {
+        use self::Manufacturer::*;
+
+        match bits {
+            MFG_STANDARD | MFG_STANDARD_ALT => Standard,
+            MFG_MOTOROLA => Motorola,
+            MFG_HARRIS => Harris,
+            b => Other(b),
+        }
+    }
+
+    /// Whether the message has the standard format.
+    pub fn is_standard(&self) -> bool { *self == Manufacturer::Standard }
+}
+
+/// Type of a Motorola extended TSBK payload.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MotorolaOpcode {
+    PatchGroupAdd,
+    PatchGroupDelete,
+    PatchGroupVoiceGrant,
+    PatchGroupVoiceUpdate,
+    ExplicitGroupVoiceGrant,
+}
+
+impl MotorolaOpcode {
+    /// Try to parse an opcode from the given 6 bits.
+    pub fn from_bits(bits: u8) -> Option<MotorolaOpcode> {
+        use self::MotorolaOpcode::*;
+
+        assert!(bits >> 6 == 0);
+
+        match bits {
+            0b000000 => Some(PatchGroupAdd),
+            0b000001 => Some(PatchGroupDelete),
+            0b000010 => Some(PatchGroupVoiceGrant),
+            0b000011 => Some(PatchGroupVoiceUpdate),
+            0b001001 => Some(ExplicitGroupVoiceGrant),
+            _ => None,
+        }
+    }
+
+    /// Convert the opcode to its 6-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::MotorolaOpcode::*;
+
+        match self {
+            PatchGroupAdd => 0b000000,
+            PatchGroupDelete => 0b000001,
+            PatchGroupVoiceGrant => 0b000010,
+            PatchGroupVoiceUpdate => 0b000011,
+            ExplicitGroupVoiceGrant => 0b001001,
+        }
+    }
+}
+
+/// Type of a Harris extended TSBK payload.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum HarrisOpcode {
+    RegroupAdd,
+    RegroupDelete,
+}
+
+impl HarrisOpcode {
+    /// Try to parse an opcode from the given 6 bits.
+    pub fn from_bits(bits: u8) -> Option<HarrisOpcode> {
+        use self::HarrisOpcode::*;
+
+        assert!(bits >> 6 == 0);
+
+        match bits {
+            0b110000 => Some(RegroupAdd),
+            0b110001 => Some(RegroupDelete),
+            _ => None,
+        }
+    }
+
+    /// Convert the opcode to its 6-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::HarrisOpcode::*;
+
+        match self {
+            RegroupAdd => 0b110000,
+            RegroupDelete => 0b110001,
+        }
+    }
+}
+
+/// Type of a manufacturer extended TSBK payload.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MfgOpcode {
+    Motorola(MotorolaOpcode),
+    Harris(HarrisOpcode),
+}
+
+impl MfgOpcode {
+    /// Try to parse an opcode from the given MFID and 6 opcode bits.
+    pub fn from_bits(mfg: u8, bits: u8) -> Option<MfgOpcode> {
+        match Manufacturer::from_bits(mfg) {
+            Manufacturer::Motorola =>
+                MotorolaOpcode::from_bits(bits).map(MfgOpcode::Motorola),
+            Manufacturer::Harris =>
+                HarrisOpcode::from_bits(bits).map(MfgOpcode::Harris),
+            _ => None,
+        }
+    }
+
+    /// Convert the opcode to its MFID and 6-bit opcode representation.
+    pub fn to_bits(self) -> (u8, u8) {
+        match self {
+            MfgOpcode::Motorola(op) => (MFG_MOTOROLA, op.to_bits()),
+            MfgOpcode::Harris(op) => (MFG_HARRIS, op.to_bits()),
+        }
+    }
+}
+
+/// Type of a Motorola extended link control payload.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MotorolaLcOpcode {
+    PatchGroupVoiceTraffic,
+}
+
+impl MotorolaLcOpcode {
+    /// Try to parse an opcode from the given 6 bits.
+    pub fn from_bits(bits: u8) -> Option<MotorolaLcOpcode> {
+        assert!(bits >> 6 == 0);
+
+        match bits {
+            0b000000 => Some(MotorolaLcOpcode::PatchGroupVoiceTraffic),
+            _ => None,
+        }
+    }
+}
+
+/// Adds or removes talkgroups from a supergroup, which combines them into a single
+/// conversation.
+///
+/// Note that this can be used for Motorola `PatchGroupAdd`/`PatchGroupDelete` and Harris
+/// `RegroupAdd`/`RegroupDelete` packets.
+pub struct GroupRegroup<'a>(&'a [u8]);
+
+impl<'a> GroupRegroup<'a> {
+    /// Create a new `GroupRegroup` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { GroupRegroup(payload) }
+
+    /// Supergroup being modified.
+    pub fn supergroup(&self) -> TalkGroup { TalkGroup::new(&self.0[0...1]) }
+
+    /// Talkgroups being added to or removed from the supergroup. Unused entries may
+    /// repeat a previous talkgroup or contain `TalkGroup::Nobody`.
+    pub fn groups(&self) -> [TalkGroup; 3] {
+        [
+            TalkGroup::new(&self.0[2...3]),
+            TalkGroup::new(&self.0[4...5]),
+            TalkGroup::new(&self.0[6...7]),
+        ]
+    }
+}
+
+/// Indicates a talkgroup has been granted a voice traffic channel, with explicit transmit
+/// and receive channels.
+pub struct ExplicitGroupVoiceGrant<'a>(&'a [u8]);
+
+impl<'a> ExplicitGroupVoiceGrant<'a> {
+    /// Create a new `ExplicitGroupVoiceGrant` decoder from the given payload bytes.
+    pub fn new(payload: &'a [u8]) -> Self { ExplicitGroupVoiceGrant(payload) }
+
+    /// Options requested/granted for the traffic channel.
+    pub fn opts(&self) -> ServiceOptions { ServiceOptions::new(self.0[0]) }
+    /// Parameters for tuning to the traffic channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.0[1...2]) }
+    /// Parameters for tuning to the traffic channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.0[3...4]) }
+    /// Talkgroup for the conversation.
+    pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.0[5...6]) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::fields::TalkGroup;
+
+    #[test]
+    fn test_manufacturer() {
+        assert_eq!(Manufacturer::from_bits(0x00), Manufacturer::Standard);
+        assert_eq!(Manufacturer::from_bits(0x01), Manufacturer::Standard);
+        assert_eq!(Manufacturer::from_bits(0x90), Manufacturer::Motorola);
+        assert_eq!(Manufacturer::from_bits(0xA4), Manufacturer::Harris);
+        assert_eq!(Manufacturer::from_bits(0xFF), Manufacturer::Other(0xFF));
+        assert!(Manufacturer::from_bits(0x01).is_standard());
+        assert!(!Manufacturer::from_bits(0x90).is_standard());
+    }
+
+    #[test]
+    fn test_mfg_opcode() {
+        assert_eq!(MfgOpcode::from_bits(0x90, 0b000000),
+                   Some(MfgOpcode::Motorola(MotorolaOpcode::PatchGroupAdd)));
+        assert_eq!(MfgOpcode::from_bits(0x90, 0b001001),
+                   Some(MfgOpcode::Motorola(MotorolaOpcode::ExplicitGroupVoiceGrant)));
+        assert_eq!(MfgOpcode::from_bits(0xA4, 0b110001),
+                   Some(MfgOpcode::Harris(HarrisOpcode::RegroupDelete)));
+        assert_eq!(MfgOpcode::from_bits(0xA4, 0b000000), None);
+        assert_eq!(MfgOpcode::from_bits(0x00, 0b000000), None);
+    }
+
+    #[test]
+    fn test_group_regroup() {
+        let r = GroupRegroup::new(&[
+            0x12, 0x34,
+            0x00, 0x01,
+            0xAB, 0xCD,
+            0x00, 0x00,
+        ]);
+
+        assert_eq!(r.supergroup(), TalkGroup::Other(0x1234));
+        assert_eq!(r.groups(), [
+            TalkGroup::Default,
+            TalkGroup::Other(0xABCD),
+            TalkGroup::Nobody,
+        ]);
+    }
+
+    #[test]
+    fn test_explicit_grant() {
+        let g = ExplicitGroupVoiceGrant::new(&[
+            0b10000010,
+            0b00110000, 0b00000001,
+            0b00110000, 0b01100001,
+            0x12, 0x34,
+            0x00,
+        ]);
+
+        assert!(g.opts().emergency());
+        assert_eq!(g.opts().prio(), 2);
+        assert_eq!(g.tx_channel().id(), 3);
+        assert_eq!(g.tx_channel().number(), 1);
+        assert_eq!(g.rx_channel().id(), 3);
+        assert_eq!(g.rx_channel().number(), 0x61);
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x1234));
+    }
+}
@@ -1,4 +1,10 @@
 //! Trunking control message decoding.
 
+pub mod affil;
+pub mod build;
 pub mod fields;
+pub mod follow;
+pub mod mbt;
+pub mod mfg;
+pub mod system;
 pub mod tsbk;
@@ -0,0 +1,378 @@
+//! Track the state of a trunking system from its control channel.
+//!
+//! Broadcasts from the control channel are accumulated into a single snapshot of the
+//! network and site identity, neighboring sites, alternate control channels, channel
+//! identifier tables, and currently active talkgroup grants.
+
+use trunking::fields::{
+    AdjacentSite,
+    AltControlChannel,
+    Channel,
+    ChannelParamsMap,
+    ChannelParamsUpdate,
+    GroupTrafficUpdate,
+    NetworkStatusBroadcast,
+    RfssStatusBroadcast,
+    SiteOptions,
+    SystemServices,
+    TalkGroup,
+};
+use trunking::tsbk::{GroupVoiceGrant, TsbkFields, TsbkOpcode};
+
+/// Identity and capabilities of a site.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Site {
+    /// Location registration area of the site.
+    pub area: u8,
+    /// System ID of the site within its WACN.
+    pub system: u16,
+    /// RF Subsystem ID of the site within its system.
+    pub rfss: u8,
+    /// Site ID within its RFSS.
+    pub site: u8,
+    /// Control channel of the site.
+    pub channel: Channel,
+    /// Services supported by the site.
+    pub services: SystemServices,
+    /// Description of the site, only advertised for adjacent sites.
+    pub opts: Option<SiteOptions>,
+}
+
+impl Site {
+    /// Check whether the given site has the same identity as this one.
+    fn same_site(&self, other: &Site) -> bool {
+        self.system == other.system && self.rfss == other.rfss && self.site == other.site
+    }
+}
+
+/// Talkgroup currently granted a traffic channel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Grant {
+    /// Talkgroup of the traffic.
+    pub talkgroup: TalkGroup,
+    /// Traffic channel of the talkgroup.
+    pub channel: Channel,
+    /// Unit that requested the grant, if an explicit grant was seen.
+    pub src_unit: Option<u32>,
+    /// Whether the grant was for an emergency call.
+    pub emergency: bool,
+    /// Time the grant was first seen.
+    pub start: u64,
+    /// Time the grant was last seen or updated.
+    pub last: u64,
+}
+
+/// Accumulated state of a trunking system.
+pub struct SystemState {
+    /// WACN ID of the network.
+    wacn: Option<u32>,
+    /// System ID within the WACN, from the network or RFSS status broadcast.
+    system: Option<u16>,
+    /// Current site, from the RFSS status broadcast.
+    site: Option<Site>,
+    /// Adjacent sites, in the order first seen.
+    neighbors: Vec<Site>,
+    /// Alternate control channels for the current site.
+    alts: Vec<(Channel, SystemServices)>,
+    /// Channel identifier tables.
+    params: ChannelParamsMap,
+    /// Currently active grants.
+    grants: Vec<Grant>,
+    /// Time after which a grant that hasn't been updated is considered inactive.
+    timeout: u64,
+}
+
+impl SystemState {
+    /// Create a new `SystemState` with no information, which expires grants after
+    /// `timeout` time units without an update.
+    pub fn new(timeout: u64) -> SystemState {
+        SystemState {
+            wacn: None,
+            system: None,
+            site: None,
+            neighbors: vec![],
+            alts: vec![],
+            params: ChannelParamsMap::default(),
+            grants: vec![],
+            timeout: timeout,
+        }
+    }
+
+    /// WACN ID of the network, if known.
+    pub fn wacn(&self) -> Option<u32> { self.wacn }
+    /// System ID within the WACN, if known.
+    pub fn system(&self) -> Option<u16> { self.system }
+    /// Current site, if known.
+    pub fn site(&self) -> Option<&Site> { self.site.as_ref() }
+    /// Adjacent sites advertised by the current site.
+    pub fn neighbors(&self) -> &[Site] { &self.neighbors[..] }
+    /// Alternate control channels of the current site.
+    pub fn alt_channels(&self) -> &[(Channel, SystemServices)] { &self.alts[..] }
+    /// Channel identifier tables.
+    pub fn params(&self) -> &ChannelParamsMap { &self.params }
+    /// Currently active grants.
+    pub fn grants(&self) -> &[Grant] { &self.grants[..] }
+
+    /// Find the active grant for the given talkgroup.
+    pub fn grant(&self, tg: TalkGroup) -> Option<&Grant> {
+        self.grants.iter().find(|g| g.talkgroup == tg)
+    }
+
+    /// Resolve the given channel to its receive frequency in Hz, if its identifier table
+    /// is known.
+    pub fn rx_freq(&self, ch: Channel) -> Option<u32> {
+        self.params.lookup(ch.id()).map(|p| p.rx_freq(ch.number()))
+    }
+
+    /// Update the state with the given TSBK received at the given time.
+    pub fn feed(&mut self, time: u64, tsbk: &TsbkFields) {
+        self.expire(time);
+
+        if tsbk.protected() {
+            return;
+        }
+
+        let opcode = match tsbk.opcode() {
+            Some(opcode) => opcode,
+            None => return,
+        };
+
+        match opcode {
+            TsbkOpcode::NetworkStatusBroadcast => {
+                let dec = NetworkStatusBroadcast::new(tsbk.payload());
+                self.wacn = Some(dec.wacn());
+                self.system = Some(dec.system());
+            },
+            TsbkOpcode::RfssStatusBroadcast => {
+                let dec = RfssStatusBroadcast::new(tsbk.payload());
+
+                let site = Site {
+                    area: dec.area(),
+                    system: dec.system(),
+                    rfss: dec.rfss(),
+                    site: dec.site(),
+                    channel: dec.channel(),
+                    services: dec.services(),
+                    opts: None,
+                };
+
+                // Alternate channels belong to the previous site.
+                if self.site.map_or(false, |s| !s.same_site(&site)) {
+                    self.alts.clear();
+                }
+
+                self.system = Some(site.system);
+                self.site = Some(site);
+            },
+            TsbkOpcode::AdjacentSite => {
+                let dec = AdjacentSite::new(tsbk.payload());
+
+                let site = Site {
+                    area: dec.area(),
+                    system: dec.system(),
+                    rfss: dec.rfss(),
+                    site: dec.site(),
+                    channel: dec.channel(),
+                    services: dec.services(),
+                    opts: Some(dec.opts()),
+                };
+
+                match self.neighbors.iter().position(|s| s.same_site(&site)) {
+                    Some(idx) => self.neighbors[idx] = site,
+                    None => self.neighbors.push(site),
+                }
+            },
+            TsbkOpcode::AltControlChannel => {
+                let dec = AltControlChannel::new(tsbk.payload());
+
+                for &(ch, services) in dec.alts().iter() {
+                    // An unused slot is filled with a null channel.
+                    if ch.to_bits() == 0 {
+                        continue;
+                    }
+
+                    match self.alts.iter().position(|&(c, _)| c == ch) {
+                        Some(idx) => self.alts[idx].1 = services,
+                        None => self.alts.push((ch, services)),
+                    }
+                }
+            },
+            TsbkOpcode::ChannelParamsUpdate =>
+                self.params.update(&ChannelParamsUpdate::new(tsbk.payload())),
+            TsbkOpcode::GroupVoiceGrant => {
+                let dec = GroupVoiceGrant::new(*tsbk);
+                let grant = self.update_grant(time, dec.talkgroup(), dec.channel());
+
+                grant.src_unit = Some(dec.src_unit());
+                grant.emergency = dec.opts().emergency();
+            },
+            TsbkOpcode::GroupVoiceUpdate => {
+                let dec = GroupTrafficUpdate::new(tsbk.payload());
+
+                for &(ch, tg) in dec.updates().iter() {
+                    if tg == TalkGroup::Nobody {
+                        continue;
+                    }
+
+                    self.update_grant(time, tg, ch);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    /// Remove grants that haven't been updated within the timeout as of the given time.
+    pub fn expire(&mut self, time: u64) {
+        let timeout = self.timeout;
+        self.grants.retain(|g| time.saturating_sub(g.last) < timeout);
+    }
+
+    /// Record that the given talkgroup is active on the given channel, returning its
+    /// grant.
+    fn update_grant(&mut self, time: u64, tg: TalkGroup, ch: Channel) -> &mut Grant {
+        let idx = match self.grants.iter().position(|g| g.talkgroup == tg) {
+            Some(idx) => idx,
+            None => {
+                self.grants.push(Grant {
+                    talkgroup: tg,
+                    channel: ch,
+                    src_unit: None,
+                    emergency: false,
+                    start: time,
+                    last: time,
+                });
+
+                self.grants.len() - 1
+            },
+        };
+
+        let grant = &mut self.grants[idx];
+
+        // A talkgroup moved to a new channel is a new call.
+        if grant.channel != ch {
+            grant.channel = ch;
+            grant.src_unit = None;
+            grant.emergency = false;
+            grant.start = time;
+        }
+
+        grant.last = time;
+        grant
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use trunking::build::*;
+    use trunking::fields::{Channel, ServiceOptions, SiteOptions, SystemServices,
+                           TalkGroup};
+    use trunking::tsbk::TsbkOpcode;
+
+    #[test]
+    fn test_site() {
+        let mut s = SystemState::new(100);
+        let services = SystemServices::new(0xF0);
+
+        assert!(s.wacn().is_none());
+        assert!(s.system().is_none());
+        assert!(s.site().is_none());
+
+        // The network identity is known before any RFSS status broadcast.
+        s.feed(0, &network_status_broadcast(1, 0xBEE00, 0x123,
+                                            Channel::from_parts(1, 10), services));
+        assert_eq!(s.wacn(), Some(0xBEE00));
+        assert_eq!(s.system(), Some(0x123));
+        assert!(s.site().is_none());
+
+        s.feed(1, &rfss_status_broadcast(1, true, 0x123, 2, 3,
+                                         Channel::from_parts(1, 10), services));
+        let site = *s.site().unwrap();
+        assert_eq!(site.system, 0x123);
+        assert_eq!(site.rfss, 2);
+        assert_eq!(site.site, 3);
+        assert_eq!(site.channel, Channel::from_parts(1, 10));
+        assert!(site.opts.is_none());
+
+        s.feed(2, &adjacent_site(1, SiteOptions::new(0b0011), 0x123, 2, 4,
+                                 Channel::from_parts(1, 20), services));
+        s.feed(3, &adjacent_site(1, SiteOptions::new(0b0011), 0x123, 2, 5,
+                                 Channel::from_parts(1, 30), services));
+        s.feed(4, &adjacent_site(1, SiteOptions::new(0b0100), 0x123, 2, 4,
+                                 Channel::from_parts(1, 21), services));
+        assert_eq!(s.neighbors().len(), 2);
+        assert_eq!(s.neighbors()[0].site, 4);
+        assert_eq!(s.neighbors()[0].channel, Channel::from_parts(1, 21));
+        assert!(s.neighbors()[0].opts.unwrap().failing());
+        assert_eq!(s.neighbors()[1].site, 5);
+
+        s.feed(5, &alt_control_channel(2, 3, [
+            (Channel::from_parts(1, 11), services),
+            (Channel::new(&[0, 0]), SystemServices::new(0)),
+        ]));
+        s.feed(6, &alt_control_channel(2, 3, [
+            (Channel::from_parts(1, 12), services),
+            (Channel::from_parts(1, 11), services),
+        ]));
+        assert_eq!(s.alt_channels().len(), 2);
+        assert_eq!(s.alt_channels()[0].0, Channel::from_parts(1, 11));
+        assert_eq!(s.alt_channels()[1].0, Channel::from_parts(1, 12));
+
+        // Moving to another site discards its alternate channels.
+        s.feed(7, &rfss_status_broadcast(1, true, 0x123, 2, 4,
+                                         Channel::from_parts(1, 21), services));
+        assert_eq!(s.site().unwrap().site, 4);
+        assert!(s.alt_channels().is_empty());
+    }
+
+    #[test]
+    fn test_grants() {
+        let mut s = SystemState::new(100);
+
+        assert!(s.rx_freq(Channel::from_parts(1, 10)).is_none());
+        s.feed(0, &channel_params_update(1, 170_200_000, 0x64, 0x0, 0x32));
+        assert_eq!(s.rx_freq(Channel::from_parts(1, 10)), Some(851_062_500));
+
+        s.feed(10, &group_voice_grant(ServiceOptions::new(0x80),
+                                      Channel::from_parts(1, 10),
+                                      TalkGroup::Other(0x1234), 0xABCDEF));
+        let g = *s.grant(TalkGroup::Other(0x1234)).unwrap();
+        assert_eq!(g.channel, Channel::from_parts(1, 10));
+        assert_eq!(g.src_unit, Some(0xABCDEF));
+        assert!(g.emergency);
+        assert_eq!(g.start, 10);
+
+        s.feed(50, &group_traffic_update(TsbkOpcode::GroupVoiceUpdate, [
+            (Channel::from_parts(1, 10), TalkGroup::Other(0x1234)),
+            (Channel::from_parts(1, 11), TalkGroup::Other(0x5678)),
+        ]));
+        assert_eq!(s.grants().len(), 2);
+
+        let g = *s.grant(TalkGroup::Other(0x1234)).unwrap();
+        assert_eq!(g.src_unit, Some(0xABCDEF));
+        assert_eq!(g.start, 10);
+        assert_eq!(g.last, 50);
+
+        let g = *s.grant(TalkGroup::Other(0x5678)).unwrap();
+        assert_eq!(g.src_unit, None);
+        assert_eq!(s.rx_freq(g.channel), Some(851_068_750));
+
+        // Moving channels starts a new call.
+        s.feed(60, &group_traffic_update(TsbkOpcode::GroupVoiceUpdate, [
+            (Channel::from_parts(1, 12), TalkGroup::Other(0x1234)),
+            (Channel::new(&[0, 0]), TalkGroup::Nobody),
+        ]));
+        let g = *s.grant(TalkGroup::Other(0x1234)).unwrap();
+        assert_eq!(g.channel, Channel::from_parts(1, 12));
+        assert_eq!(g.src_unit, None);
+        assert_eq!(g.start, 60);
+        assert_eq!(s.grants().len(), 2);
+
+        s.expire(150);
+        assert_eq!(s.grants().len(), 1);
+        assert!(s.grant(TalkGroup::Other(0x5678)).is_none());
+
+        s.expire(160);
+        assert!(s.grants().is_empty());
+    }
+}
@@ -2,15 +2,28 @@
 
 use collect_slice::CollectSlice;
 
-use bits::{Dibit, DibitBytes};
+use baseband::sync::SYNC_GENERATOR;
+use bits::{Dibit, Dibits, DibitBytes, SoftDibit};
 use buffer::{Buffer, DataPayloadStorage};
 use coding::trellis;
 use consts::{TSBK_DIBITS, TSBK_BYTES};
-use data::{crc, interleave};
+use data::{coder, crc, interleave};
 use error::{Result, P25Error};
-use util::{slice_u16, slice_u24};
-
-use trunking::fields::{Channel, TalkGroup, ServiceOptions, RegResponse};
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::{StatusCode, StatusInterleaver};
+use util::{slice_u16, slice_u24, write_u16};
+
+use trunking::fields::{
+    Channel,
+    DenyReason,
+    RegResponse,
+    RoamingStackOp,
+    ServiceOptions,
+    SystemServices,
+    TalkGroup,
+};
+use trunking::mfg::{Manufacturer, MfgOpcode, MFG_STANDARD};
+use voice::crypto::CryptoAlgorithm;
 
 /// State machine for receiving a TSBK packet.
 ///
@@ -18,11 +31,14 @@ use trunking::fields::{Channel, TalkGroup, ServiceOptions, RegResponse};
 ///
 /// 1. Buffer dibits until a full packet's worth are available
 /// 2. Descramble symbols using the same deinterleaver as data packets
-/// 3. Decode 1/2-rate convolutional code and attempt to correct any errors
+/// 3. Decode 1/2-rate convolutional code and attempt to correct any errors, using soft
+///    decisions if every symbol in the packet was soft
 /// 4. Group dibits into a buffer of bytes for further interpretation
 pub struct TsbkReceiver {
     /// Current buffered dibits.
     dibits: Buffer<DataPayloadStorage>,
+    /// Whether every buffered dibit was soft.
+    soft: bool,
 }
 
 impl TsbkReceiver {
@@ -30,6 +46,7 @@ impl TsbkReceiver {
     pub fn new() -> TsbkReceiver {
         TsbkReceiver {
             dibits: Buffer::new(DataPayloadStorage::new()),
+            soft: true,
         }
     }
 
@@ -37,23 +54,39 @@ impl TsbkReceiver {
     /// `Some(Ok(pkt))` if a packet was successfully received, `Some(Err(err))` if an
     /// error occurred, and `None` in the case of no event.
     pub fn feed(&mut self, dibit: Dibit) -> Option<Result<TsbkFields>> {
-        let (count, dibits) = {
-            let buf = match self.dibits.feed(dibit) {
-                Some(buf) => buf,
-                None => return None,
-            };
-
-            let mut dibits = [Dibit::default(); TSBK_DIBITS];
-            let count = trellis::DibitDecoder::new(interleave::Deinterleaver::new(buf))
-                .filter_map(|x| x.ok())
-                .collect_slice_exhaust(&mut dibits[..]);
-
-            (count, dibits)
+        self.soft = false;
+        self.feed_symbol(SoftDibit::ideal(dibit))
+    }
+
+    /// Feed in a soft baseband symbol, using the reliability of each symbol to correct
+    /// more errors in the convolutional code. The return value has the same meaning as
+    /// for `feed`.
+    pub fn feed_soft(&mut self, s: SoftDibit) -> Option<Result<TsbkFields>> {
+        self.feed_symbol(s)
+    }
+
+    /// Buffer the given symbol and decode the packet once it's complete.
+    fn feed_symbol(&mut self, s: SoftDibit) -> Option<Result<TsbkFields>> {
+        let buf = match self.dibits.feed(s) {
+            Some(buf) => *buf,
+            None => return None,
         };
 
-        if count != dibits.len() {
-            return Some(Err(P25Error::ViterbiUnrecoverable));
-        }
+        let soft = self.soft;
+        self.soft = true;
+
+        let deint = interleave::Deinterleaver::new(&buf);
+
+        let decoded = if soft {
+            decode(deint)
+        } else {
+            decode(deint.map(|s| s.hard()))
+        };
+
+        let dibits = match decoded {
+            Some(dibits) => dibits,
+            None => return Some(Err(P25Error::ViterbiUnrecoverable)),
+        };
 
         let mut bytes = [0; TSBK_BYTES];
         DibitBytes::new(dibits.iter().cloned()).collect_slice_checked(&mut bytes[..]);
@@ -62,6 +95,23 @@ impl TsbkReceiver {
     }
 }
 
+/// Decode the given deinterleaved hard or soft dibits, returning `None` if they couldn't
+/// be corrected.
+fn decode<T>(src: T) -> Option<[Dibit; TSBK_DIBITS]> where
+    T: Iterator, T::Item: trellis::TrellisSymbol
+{
+    let mut dibits = [Dibit::default(); TSBK_DIBITS];
+    let count = trellis::DibitDecoder::new(src)
+        .filter_map(|x| x.ok())
+        .collect_slice_exhaust(&mut dibits[..]);
+
+    if count == dibits.len() {
+        Some(dibits)
+    } else {
+        None
+    }
+}
+
 /// Type of a TSBK payload.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum TsbkOpcode {
@@ -93,6 +143,7 @@ pub enum TsbkOpcode {
     UnitRegCommand,
     UnitAuthCommand,
     UnitDeregAck,
+    SecondaryControlChannel,
     RoamingAddrCommand,
     RoamingAddrUpdate,
     SystemServiceBroadcast,
@@ -152,7 +203,7 @@ impl TsbkOpcode {
             0b100111 => Some(DenyResponse),
 
             0b101000 => Some(GroupAffiliationResponse),
-            0b101001 => Some(Reserved),
+            0b101001 => Some(SecondaryControlChannel),
             0b101010 => Some(GroupAffiliationQuery),
             0b101011 => Some(LocRegResponse),
             0b101100 => Some(UnitRegResponse),
@@ -176,6 +227,55 @@ impl TsbkOpcode {
             _ => None,
         }
     }
+
+    /// Convert the opcode to its 6-bit representation. Panics if the opcode is
+    /// `Reserved`.
+    pub fn to_bits(self) -> u8 {
+        use self::TsbkOpcode::*;
+
+        match self {
+            GroupVoiceGrant => 0b000000,
+            GroupVoiceUpdate => 0b000010,
+            GroupVoiceUpdateExplicit => 0b000011,
+            UnitVoiceGrant => 0b000100,
+            UnitCallRequest => 0b000101,
+            UnitVoiceUpdate => 0b000110,
+            PhoneGrant => 0b001000,
+            PhoneAlert => 0b001010,
+            UnitDataGrant => 0b010000,
+            GroupDataGrant => 0b010001,
+            GroupDataUpdate => 0b010010,
+            GroupDataUpdateExplicit => 0b010011,
+            UnitStatusUpdate => 0b011000,
+            UnitStatusQuery => 0b011010,
+            UnitShortMessage => 0b011100,
+            UnitMonitor => 0b011101,
+            UnitCallAlert => 0b011111,
+            AckResponse => 0b100000,
+            QueuedResponse => 0b100001,
+            ExtendedFunctionResponse => 0b100100,
+            DenyResponse => 0b100111,
+            GroupAffiliationResponse => 0b101000,
+            SecondaryControlChannel => 0b101001,
+            GroupAffiliationQuery => 0b101010,
+            LocRegResponse => 0b101011,
+            UnitRegResponse => 0b101100,
+            UnitRegCommand => 0b101101,
+            UnitAuthCommand => 0b101110,
+            UnitDeregAck => 0b101111,
+            RoamingAddrCommand => 0b110110,
+            RoamingAddrUpdate => 0b110111,
+            SystemServiceBroadcast => 0b111000,
+            AltControlChannel => 0b111001,
+            RfssStatusBroadcast => 0b111010,
+            NetworkStatusBroadcast => 0b111011,
+            AdjacentSite => 0b111100,
+            ChannelParamsUpdate => 0b111101,
+            ProtectionParamBroadcast => 0b111110,
+            ProtectionParamUpdate => 0b111111,
+            Reserved => panic!("reserved opcode can't be encoded"),
+        }
+    }
 }
 
 /// Buffer of bytes that represents a TSBK packet.
@@ -193,8 +293,22 @@ impl TsbkFields {
     pub fn is_tail(&self) -> bool { self.0[0] >> 7 == 1 }
     /// Whether the packet is encrypted.
     pub fn protected(&self) -> bool { self.0[0] >> 6 & 1 == 1 }
-    /// Type of data contained in the payload.
-    pub fn opcode(&self) -> Option<TsbkOpcode> { TsbkOpcode::from_bits(self.0[0] & 0x3F) }
+    /// Type of data contained in the payload, or `None` if the packet has a
+    /// manufacturer-specific format.
+    pub fn opcode(&self) -> Option<TsbkOpcode> {
+        if Manufacturer::from_bits(self.mfg()).is_standard() {
+            TsbkOpcode::from_bits(self.0[0] & 0x3F)
+        } else {
+            None
+        }
+    }
+
+    /// Type of data contained in the payload, if the packet has a recognized
+    /// manufacturer-specific format.
+    pub fn mfg_opcode(&self) -> Option<MfgOpcode> {
+        MfgOpcode::from_bits(self.mfg(), self.0[0] & 0x3F)
+    }
+
     /// Manufacturer ID, which determines if the packet is standardized.
     pub fn mfg(&self) -> u8 { self.0[1] }
     /// Transmitted CRC.
@@ -214,6 +328,117 @@ impl TsbkFields {
 
     /// Bytes that make up the payload of the packet.
     pub fn payload(&self) -> &[u8] { &self.0[2...9] }
+
+    /// Raw bytes of the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
+
+    /// Copy the packet with the tail bit set to the given value, updating the CRC.
+    pub fn with_tail(self, tail: bool) -> TsbkFields {
+        let mut buf = self.0;
+
+        buf[0] = (tail as u8) << 7 | buf[0] & 0x7F;
+
+        TsbkBuilder(buf).build()
+    }
+}
+
+/// Builds a TSBK packet from its fields.
+///
+/// The packet is initially marked as the tail of the TSBK group, unencrypted, and with a
+/// zeroed payload.
+pub struct TsbkBuilder(Buf);
+
+impl TsbkBuilder {
+    /// Create a new `TsbkBuilder` for a standard packet with the given opcode.
+    pub fn new(opcode: TsbkOpcode) -> TsbkBuilder {
+        TsbkBuilder::mfg(opcode.to_bits(), MFG_STANDARD)
+    }
+
+    /// Create a new `TsbkBuilder` for a packet with the given 6-bit opcode and
+    /// manufacturer ID, which can be used for manufacturer-specific packets.
+    pub fn mfg(opcode: u8, mfg: u8) -> TsbkBuilder {
+        assert!(opcode >> 6 == 0);
+
+        let mut buf = [0; TSBK_BYTES];
+
+        buf[0] = 1 << 7 | opcode;
+        buf[1] = mfg;
+
+        TsbkBuilder(buf)
+    }
+
+    /// Set whether the packet is the last one in the TSBK group.
+    pub fn tail(mut self, tail: bool) -> Self {
+        self.0[0] = (tail as u8) << 7 | self.0[0] & 0x7F;
+        self
+    }
+
+    /// Set whether the packet is encrypted.
+    pub fn protected(mut self, protected: bool) -> Self {
+        self.0[0] = (protected as u8) << 6 | self.0[0] & 0xBF;
+        self
+    }
+
+    /// Set the 8 payload bytes of the packet.
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        (&mut self.0[2...9]).copy_from_slice(payload);
+        self
+    }
+
+    /// Calculate the CRC and construct the packet.
+    pub fn build(self) -> TsbkFields {
+        let mut tsbk = TsbkFields(self.0);
+        let crc = tsbk.calc_crc();
+
+        write_u16(&mut tsbk.0[10..], crc);
+
+        tsbk
+    }
+}
+
+/// Generates the symbols of a TSBK burst.
+///
+/// Each burst is made up of the frame sync and NID, followed by 1 to 3 coded and
+/// interleaved TSBK packets, with status symbols interleaved throughout. The tail bit of
+/// each packet is set so only the final packet is marked as the tail.
+pub struct TsbkTransmitter {
+    /// NID transmitted at the start of each burst.
+    nid: NetworkId,
+    /// Status symbol to interleave.
+    status: StatusCode,
+}
+
+impl TsbkTransmitter {
+    /// Create a new `TsbkTransmitter` using the given NAC and status symbol.
+    pub fn new(nac: NetworkAccessCode, status: StatusCode) -> TsbkTransmitter {
+        TsbkTransmitter {
+            nid: NetworkId::new(nac, DataUnit::TrunkingSignaling),
+            status: status,
+        }
+    }
+
+    /// Update the status symbol interleaved into following bursts.
+    pub fn update_status(&mut self, status: StatusCode) { self.status = status; }
+
+    /// Generate the dibit symbols of a burst containing the given packets.
+    pub fn burst(&self, tsbks: &[TsbkFields]) -> Vec<Dibit> {
+        assert!(tsbks.len() >= 1 && tsbks.len() <= 3);
+
+        let mut dibits = vec![];
+
+        dibits.extend(Dibits::new(SYNC_GENERATOR.iter().cloned()));
+        dibits.extend(Dibits::new(self.nid.encode().iter().cloned()));
+
+        for (i, tsbk) in tsbks.iter().enumerate() {
+            let tsbk = tsbk.with_tail(i == tsbks.len() - 1);
+
+            dibits.extend(interleave::Interleaver::new(coder::DibitCoder::new()
+                .feed_bytes(tsbk.0.iter().cloned())
+                .finish()));
+        }
+
+        StatusInterleaver::new(dibits.into_iter(), self.status).collect()
+    }
 }
 
 /// Response given to a location registration request.
@@ -341,10 +566,300 @@ impl GroupDataGrant {
     pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
 }
 
+/// Acknowledgement of a service request or other message.
+pub struct AckResponse(Buf);
+
+impl AckResponse {
+    /// Create a new `AckResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { AckResponse(tsbk.0) }
+
+    /// Whether the additional information fields are valid.
+    pub fn has_info(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Whether the additional information contains a WACN and System ID (true) or a
+    /// source unit (false).
+    pub fn extended(&self) -> bool { self.0[2] >> 6 & 1 == 1 }
+    /// Type of service being acknowledged.
+    pub fn service(&self) -> Option<TsbkOpcode> {
+        TsbkOpcode::from_bits(self.0[2] & 0x3F)
+    }
+
+    /// WACN ID of target unit, valid for extended acknowledgements.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[3...5]) >> 4 }
+    /// System ID of target unit, valid for extended acknowledgements.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[5...6]) & 0xFFF }
+    /// Unit that sent the acknowledged message, valid for non-extended
+    /// acknowledgements.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit being acknowledged.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Indicates a service request has been queued until resources are available.
+pub struct QueuedResponse(Buf);
+
+impl QueuedResponse {
+    /// Create a new `QueuedResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { QueuedResponse(tsbk.0) }
+
+    /// Whether the additional information field is valid.
+    pub fn has_info(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Type of service that was queued.
+    pub fn service(&self) -> Option<TsbkOpcode> {
+        TsbkOpcode::from_bits(self.0[2] & 0x3F)
+    }
+
+    /// Reason the request was queued.
+    pub fn reason(&self) -> u8 { self.0[3] }
+    /// Additional service-specific information, such as a target address.
+    pub fn info(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit that made the request.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Indicates a service request has been denied.
+pub struct DenyResponse(Buf);
+
+impl DenyResponse {
+    /// Create a new `DenyResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { DenyResponse(tsbk.0) }
+
+    /// Whether the additional information field is valid.
+    pub fn has_info(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Type of service that was denied.
+    pub fn service(&self) -> Option<TsbkOpcode> {
+        TsbkOpcode::from_bits(self.0[2] & 0x3F)
+    }
+
+    /// Reason the request was denied.
+    pub fn reason(&self) -> DenyReason { DenyReason::from_bits(self.0[3]) }
+    /// Additional service-specific information, such as a target address.
+    pub fn info(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit that made the request.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Response to an extended function command, such as a radio check or inhibit.
+pub struct ExtendedFunctionResponse(Buf);
+
+impl ExtendedFunctionResponse {
+    /// Create a new `ExtendedFunctionResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { ExtendedFunctionResponse(tsbk.0) }
+
+    /// Class of the extended function.
+    pub fn class(&self) -> u8 { self.0[2] }
+    /// Operand of the extended function within the class.
+    pub fn operand(&self) -> u8 { self.0[3] }
+    /// Function-specific argument, typically the address of the commanding unit.
+    pub fn args(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit that performed the function.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Response given to a talkgroup affiliation request.
+pub struct GroupAffiliationResponse(Buf);
+
+impl GroupAffiliationResponse {
+    /// Create a new `GroupAffiliationResponse` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { GroupAffiliationResponse(tsbk.0) }
+
+    /// Whether the affiliation is valid system-wide (true) or only at the current
+    /// site (false).
+    pub fn global(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// System response to the affiliation request.
+    pub fn response(&self) -> RegResponse { RegResponse::from_bits(self.0[2] & 0b11) }
+    /// Announcement group that contains the affiliated talkgroup.
+    pub fn announce_group(&self) -> TalkGroup { TalkGroup::new(&self.0[3...4]) }
+    /// Talkgroup the unit requested affiliation with.
+    pub fn talkgroup(&self) -> TalkGroup { TalkGroup::new(&self.0[5...6]) }
+    /// Unit that requested affiliation.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Status update sent from one unit to another.
+pub struct UnitStatusUpdate(Buf);
+
+impl UnitStatusUpdate {
+    /// Create a new `UnitStatusUpdate` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitStatusUpdate(tsbk.0) }
+
+    /// User-defined status, such as a dispatch code.
+    pub fn user_status(&self) -> u8 { self.0[2] }
+    /// Unit-defined status, such as the radio's operating state.
+    pub fn unit_status(&self) -> u8 { self.0[3] }
+    /// Unit receiving the status.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit sending the status.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Request for a unit to report its status.
+pub struct UnitStatusQuery(Buf);
+
+impl UnitStatusQuery {
+    /// Create a new `UnitStatusQuery` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitStatusQuery(tsbk.0) }
+
+    /// Unit being queried.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit requesting the status.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Short predefined message sent from one unit to another.
+pub struct UnitShortMessage(Buf);
+
+impl UnitShortMessage {
+    /// Create a new `UnitShortMessage` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitShortMessage(tsbk.0) }
+
+    /// User-defined message code.
+    pub fn message(&self) -> u16 { slice_u16(&self.0[2...3]) }
+    /// Unit receiving the message.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit sending the message.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Command for a unit to key up so its audio can be monitored.
+pub struct UnitMonitor(Buf);
+
+impl UnitMonitor {
+    /// Create a new `UnitMonitor` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitMonitor(tsbk.0) }
+
+    /// Whether the unit should transmit without any indication to its user.
+    pub fn silent(&self) -> bool { self.0[3] >> 7 == 1 }
+    /// Multiplier applied to the system transmit time to determine how long the unit
+    /// should transmit.
+    pub fn tx_mult(&self) -> u8 { self.0[3] & 0b11 }
+    /// Unit being monitored.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[4...6]) }
+    /// Unit requesting the monitor.
+    pub fn src_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Command for a unit to authenticate with the system.
+pub struct UnitAuthCommand(Buf);
+
+impl UnitAuthCommand {
+    /// Create a new `UnitAuthCommand` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { UnitAuthCommand(tsbk.0) }
+
+    /// WACN ID of target unit.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[2...4]) >> 4 }
+    /// System ID of target unit within WACN.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[4...5]) & 0xFFF }
+    /// ID of target unit which, along with the WACN and System ID, uniquely
+    /// identifies the unit.
+    pub fn dest_id(&self) -> u32 { slice_u24(&self.0[6...8]) }
+}
+
+/// Command for a unit to modify its roaming address stack, which lists the systems the
+/// unit may roam to.
+pub struct RoamingAddrCommand(Buf);
+
+impl RoamingAddrCommand {
+    /// Create a new `RoamingAddrCommand` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { RoamingAddrCommand(tsbk.0) }
+
+    /// Operation to perform on the stack.
+    pub fn op(&self) -> Option<RoamingStackOp> { RoamingStackOp::from_bits(self.0[2]) }
+    /// WACN ID of the roaming address.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[3...5]) >> 4 }
+    /// System ID of the roaming address.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[5...6]) & 0xFFF }
+    /// Unit being commanded.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Entry of a unit's roaming address stack, sent to the unit as part of a sequence.
+pub struct RoamingAddrUpdate(Buf);
+
+impl RoamingAddrUpdate {
+    /// Create a new `RoamingAddrUpdate` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { RoamingAddrUpdate(tsbk.0) }
+
+    /// Whether this is the last update in the sequence.
+    pub fn is_last(&self) -> bool { self.0[2] >> 7 == 1 }
+    /// Sequence number of the update.
+    pub fn seq(&self) -> u8 { self.0[2] & 0xF }
+    /// WACN ID of the roaming address.
+    pub fn wacn(&self) -> u32 { slice_u24(&self.0[3...5]) >> 4 }
+    /// System ID of the roaming address.
+    pub fn system(&self) -> u16 { slice_u16(&self.0[5...6]) & 0xFFF }
+    /// Unit being updated.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Advertisement of the services available and supported by the current system.
+pub struct SystemServiceBroadcast(Buf);
+
+impl SystemServiceBroadcast {
+    /// Create a new `SystemServiceBroadcast` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { SystemServiceBroadcast(tsbk.0) }
+
+    /// Minimum priority a service request must have to be processed.
+    pub fn prio(&self) -> u8 { self.0[2] & 0xF }
+    /// Bitmap of services currently available on the system.
+    pub fn available(&self) -> u32 { slice_u24(&self.0[3...5]) }
+    /// Bitmap of services supported by the system.
+    pub fn supported(&self) -> u32 { slice_u24(&self.0[6...8]) }
+}
+
+/// Advertisement of the encryption parameters used on the current system.
+pub struct ProtectionParamBroadcast(Buf);
+
+impl ProtectionParamBroadcast {
+    /// Create a new `ProtectionParamBroadcast` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { ProtectionParamBroadcast(tsbk.0) }
+
+    /// Encryption algorithm in use.
+    pub fn crypto_alg(&self) -> CryptoAlgorithm { CryptoAlgorithm::from_bits(self.0[3]) }
+    /// Encryption key in use.
+    pub fn crypto_key(&self) -> u16 { slice_u16(&self.0[4...5]) }
+}
+
+/// Update of the encryption parameters a unit should use.
+pub struct ProtectionParamUpdate(Buf);
+
+impl ProtectionParamUpdate {
+    /// Create a new `ProtectionParamUpdate` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { ProtectionParamUpdate(tsbk.0) }
+
+    /// Encryption algorithm the unit should use.
+    pub fn crypto_alg(&self) -> CryptoAlgorithm { CryptoAlgorithm::from_bits(self.0[3]) }
+    /// Encryption key the unit should use.
+    pub fn crypto_key(&self) -> u16 { slice_u16(&self.0[4...5]) }
+    /// Unit being updated.
+    pub fn dest_unit(&self) -> u32 { slice_u24(&self.0[7...9]) }
+}
+
+/// Advertisement of a secondary control channel for the current site, with explicit
+/// transmit and receive channels.
+pub struct SecondaryControlChannel(Buf);
+
+impl SecondaryControlChannel {
+    /// Create a new `SecondaryControlChannel` decoder from the base TSBK decoder.
+    pub fn new(tsbk: TsbkFields) -> Self { SecondaryControlChannel(tsbk.0) }
+
+    /// RF Subsystem ID of current site within System.
+    pub fn rfss(&self) -> u8 { self.0[2] }
+    /// Site ID of current site within RFSS.
+    pub fn site(&self) -> u8 { self.0[3] }
+    /// Parameters for tuning to the control channel transmit frequency.
+    pub fn tx_channel(&self) -> Channel { Channel::new(&self.0[4...5]) }
+    /// Parameters for tuning to the control channel receive frequency.
+    pub fn rx_channel(&self) -> Channel { Channel::new(&self.0[6...7]) }
+    /// Services supported by the control channel.
+    pub fn services(&self) -> SystemServices { SystemServices::new(self.0[8]) }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
     use trunking::fields::*;
+    use trunking::mfg::*;
+    use voice::crypto::CryptoAlgorithm;
 
     #[test]
     fn test_tsbk_fields() {
@@ -382,6 +897,52 @@ mod test {
         ]);
     }
 
+    #[test]
+    fn test_mfg() {
+        let t = TsbkFields::new([
+            0b10000000,
+            0b10010000,
+            0x12,
+            0x34,
+            0x00,
+            0x01,
+            0xAB,
+            0xCD,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ]);
+
+        assert_eq!(t.mfg(), 0x90);
+        assert_eq!(t.opcode(), None);
+        assert_eq!(t.mfg_opcode(),
+                   Some(MfgOpcode::Motorola(MotorolaOpcode::PatchGroupAdd)));
+
+        let r = GroupRegroup::new(t.payload());
+        assert_eq!(r.supergroup(), TalkGroup::Other(0x1234));
+        assert_eq!(r.groups()[0], TalkGroup::Default);
+        assert_eq!(r.groups()[1], TalkGroup::Other(0xABCD));
+
+        let t = TsbkFields::new([
+            0b10000000,
+            0b00000000,
+            0x12,
+            0x34,
+            0x00,
+            0x01,
+            0xAB,
+            0xCD,
+            0x00,
+            0x00,
+            0x00,
+            0x00,
+        ]);
+
+        assert_eq!(t.opcode(), Some(TsbkOpcode::GroupVoiceGrant));
+        assert_eq!(t.mfg_opcode(), None);
+    }
+
     #[test]
     fn test_adjacent_site() {
         let t = TsbkFields::new([
@@ -709,7 +1270,7 @@ mod test {
     fn test_group_voice_grant() {
         let t = TsbkFields::new([
             0b00000000,
-            0b11111111,
+            0b00000000,
             0b10100011,
             0b11100101,
             0b11001100,
@@ -739,7 +1300,7 @@ mod test {
     fn test_unit_traffic_channel() {
         let t = TsbkFields::new([
             0b00000100,
-            0b11111111,
+            0b00000000,
             0b11001110,
             0b10101010,
             0b11100111,
@@ -846,4 +1407,456 @@ mod test {
         assert_eq!(g.talkgroup(), TalkGroup::Other(0b1111000010001001));
         assert_eq!(g.src_unit(), 0b111000110100010011101010);
     }
+
+    #[test]
+    fn test_ack_response() {
+        let t = TsbkFields::new([
+            0b00100000,
+            0b00000000,
+            0b11000101,
+            0b11111100,
+            0b00001010,
+            0b10110011,
+            0b11001100,
+            0b00010010,
+            0b00110100,
+            0b01010110,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::AckResponse));
+        let a = AckResponse::new(t);
+        assert!(a.has_info());
+        assert!(a.extended());
+        assert_eq!(a.service(), Some(TsbkOpcode::UnitCallRequest));
+        assert_eq!(a.wacn(), 0b11111100000010101011);
+        assert_eq!(a.system(), 0b001111001100);
+        assert_eq!(a.dest_unit(), 0x123456);
+    }
+
+    #[test]
+    fn test_deny_response() {
+        let t = TsbkFields::new([
+            0b00100111,
+            0b00000000,
+            0b10000000,
+            0x31,
+            0x00,
+            0xAB,
+            0xCD,
+            0x12,
+            0x34,
+            0x56,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::DenyResponse));
+        let d = DenyResponse::new(t);
+        assert!(d.has_info());
+        assert_eq!(d.service(), Some(TsbkOpcode::GroupVoiceGrant));
+        assert_eq!(d.reason(), DenyReason::UnauthorizedGroup);
+        assert_eq!(d.info(), 0x00ABCD);
+        assert_eq!(d.dest_unit(), 0x123456);
+        assert_eq!(DenyReason::from_bits(0xF3), DenyReason::Other(0xF3));
+    }
+
+    #[test]
+    fn test_queued_response() {
+        let t = TsbkFields::new([
+            0b00100001,
+            0b00000000,
+            0b00000100,
+            0x2F,
+            0x65,
+            0x43,
+            0x21,
+            0x12,
+            0x34,
+            0x56,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::QueuedResponse));
+        let q = QueuedResponse::new(t);
+        assert!(!q.has_info());
+        assert_eq!(q.service(), Some(TsbkOpcode::UnitVoiceGrant));
+        assert_eq!(q.reason(), 0x2F);
+        assert_eq!(q.info(), 0x654321);
+        assert_eq!(q.dest_unit(), 0x123456);
+    }
+
+    #[test]
+    fn test_extended_function_response() {
+        let t = TsbkFields::new([
+            0b00100100,
+            0b00000000,
+            0x00,
+            0x7E,
+            0xFF,
+            0xFF,
+            0xFD,
+            0x12,
+            0x34,
+            0x56,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::ExtendedFunctionResponse));
+        let e = ExtendedFunctionResponse::new(t);
+        assert_eq!(e.class(), 0x00);
+        assert_eq!(e.operand(), 0x7E);
+        assert_eq!(e.args(), 0xFFFFFD);
+        assert_eq!(e.src_unit(), 0x123456);
+    }
+
+    #[test]
+    fn test_group_affiliation_response() {
+        let t = TsbkFields::new([
+            0b00101000,
+            0b00000000,
+            0b10000010,
+            0x00,
+            0x01,
+            0x12,
+            0x34,
+            0xAB,
+            0xCD,
+            0xEF,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::GroupAffiliationResponse));
+        let g = GroupAffiliationResponse::new(t);
+        assert!(g.global());
+        assert_eq!(g.response(), RegResponse::Deny);
+        assert_eq!(g.announce_group(), TalkGroup::Default);
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x1234));
+        assert_eq!(g.dest_unit(), 0xABCDEF);
+    }
+
+    #[test]
+    fn test_unit_status() {
+        let t = TsbkFields::new([
+            0b00011000,
+            0b00000000,
+            0x12,
+            0x34,
+            0xAB,
+            0xCD,
+            0xEF,
+            0x65,
+            0x43,
+            0x21,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitStatusUpdate));
+        let u = UnitStatusUpdate::new(t);
+        assert_eq!(u.user_status(), 0x12);
+        assert_eq!(u.unit_status(), 0x34);
+        assert_eq!(u.dest_unit(), 0xABCDEF);
+        assert_eq!(u.src_unit(), 0x654321);
+
+        let q = UnitStatusQuery::new(t);
+        assert_eq!(q.dest_unit(), 0xABCDEF);
+        assert_eq!(q.src_unit(), 0x654321);
+
+        let m = UnitShortMessage::new(t);
+        assert_eq!(m.message(), 0x1234);
+        assert_eq!(m.dest_unit(), 0xABCDEF);
+        assert_eq!(m.src_unit(), 0x654321);
+    }
+
+    #[test]
+    fn test_unit_monitor() {
+        let t = TsbkFields::new([
+            0b00011101,
+            0b00000000,
+            0x00,
+            0b10000011,
+            0xAB,
+            0xCD,
+            0xEF,
+            0x65,
+            0x43,
+            0x21,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitMonitor));
+        let m = UnitMonitor::new(t);
+        assert!(m.silent());
+        assert_eq!(m.tx_mult(), 3);
+        assert_eq!(m.dest_unit(), 0xABCDEF);
+        assert_eq!(m.src_unit(), 0x654321);
+    }
+
+    #[test]
+    fn test_unit_auth_command() {
+        let t = TsbkFields::new([
+            0b00101110,
+            0b00000000,
+            0xBE,
+            0xE0,
+            0x02,
+            0x9A,
+            0x12,
+            0x34,
+            0x56,
+            0x00,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitAuthCommand));
+        let a = UnitAuthCommand::new(t);
+        assert_eq!(a.wacn(), 0xBEE00);
+        assert_eq!(a.system(), 0x29A);
+        assert_eq!(a.dest_id(), 0x123456);
+    }
+
+    #[test]
+    fn test_roaming_addr() {
+        let t = TsbkFields::new([
+            0b00110110,
+            0b00000000,
+            0x01,
+            0xBE,
+            0xE0,
+            0x02,
+            0x9A,
+            0x12,
+            0x34,
+            0x56,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::RoamingAddrCommand));
+        let c = RoamingAddrCommand::new(t);
+        assert_eq!(c.op(), Some(RoamingStackOp::Write));
+        assert_eq!(c.wacn(), 0xBEE00);
+        assert_eq!(c.system(), 0x29A);
+        assert_eq!(c.dest_unit(), 0x123456);
+
+        let t = TsbkFields::new([
+            0b00110111,
+            0b00000000,
+            0b10000101,
+            0xBE,
+            0xE0,
+            0x02,
+            0x9A,
+            0x12,
+            0x34,
+            0x56,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::RoamingAddrUpdate));
+        let u = RoamingAddrUpdate::new(t);
+        assert!(u.is_last());
+        assert_eq!(u.seq(), 5);
+        assert_eq!(u.wacn(), 0xBEE00);
+        assert_eq!(u.system(), 0x29A);
+        assert_eq!(u.dest_unit(), 0x123456);
+    }
+
+    #[test]
+    fn test_system_service_broadcast() {
+        let t = TsbkFields::new([
+            0b00111000,
+            0b00000000,
+            0b00000011,
+            0x12,
+            0x34,
+            0x56,
+            0xAB,
+            0xCD,
+            0xEF,
+            0x00,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::SystemServiceBroadcast));
+        let b = SystemServiceBroadcast::new(t);
+        assert_eq!(b.prio(), 3);
+        assert_eq!(b.available(), 0x123456);
+        assert_eq!(b.supported(), 0xABCDEF);
+    }
+
+    #[test]
+    fn test_protection_params() {
+        let t = TsbkFields::new([
+            0b00111111,
+            0b00000000,
+            0x00,
+            0x84,
+            0x12,
+            0x34,
+            0x00,
+            0xAB,
+            0xCD,
+            0xEF,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::ProtectionParamUpdate));
+        let u = ProtectionParamUpdate::new(t);
+        assert_eq!(u.crypto_alg(), CryptoAlgorithm::Aes);
+        assert_eq!(u.crypto_key(), 0x1234);
+        assert_eq!(u.dest_unit(), 0xABCDEF);
+
+        let b = ProtectionParamBroadcast::new(t);
+        assert_eq!(b.crypto_alg(), CryptoAlgorithm::Aes);
+        assert_eq!(b.crypto_key(), 0x1234);
+    }
+
+    #[test]
+    fn test_secondary_control_channel() {
+        let t = TsbkFields::new([
+            0b00101001,
+            0b00000000,
+            0x01,
+            0x02,
+            0b00110000,
+            0b00000001,
+            0b00110000,
+            0b00000010,
+            0b01010001,
+            0x00,
+            0b00000000,
+            0b00000000,
+        ]);
+        assert_eq!(t.opcode(), Some(TsbkOpcode::SecondaryControlChannel));
+        let c = SecondaryControlChannel::new(t);
+        assert_eq!(c.rfss(), 1);
+        assert_eq!(c.site(), 2);
+        assert_eq!(c.tx_channel().id(), 3);
+        assert_eq!(c.tx_channel().number(), 1);
+        assert_eq!(c.rx_channel().number(), 2);
+        assert!(c.services().is_composite());
+        assert!(c.services().has_registration());
+    }
+
+    #[test]
+    fn test_builder() {
+        let t = TsbkBuilder::new(TsbkOpcode::UnitCallAlert)
+            .tail(false)
+            .protected(true)
+            .payload(&[1, 2, 3, 4, 5, 6, 7, 8])
+            .build();
+
+        assert!(!t.is_tail());
+        assert!(t.protected());
+        assert_eq!(t.opcode(), Some(TsbkOpcode::UnitCallAlert));
+        assert_eq!(t.mfg(), MFG_STANDARD);
+        assert_eq!(t.payload(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(t.crc_valid());
+
+        let t = t.with_tail(true);
+        assert!(t.is_tail());
+        assert!(t.protected());
+        assert!(t.crc_valid());
+
+        let t = TsbkBuilder::mfg(0b110000, MFG_HARRIS).build();
+        assert_eq!(t.mfg(), MFG_HARRIS);
+        assert_eq!(t.opcode(), None);
+        assert_eq!(t.mfg_opcode(), Some(MfgOpcode::Harris(HarrisOpcode::RegroupAdd)));
+        assert!(t.crc_valid());
+    }
+
+    #[test]
+    fn test_transmitter() {
+        use message::status::{StatusDeinterleaver, StreamSymbol};
+
+        let tx = TsbkTransmitter::new(NetworkAccessCode::Default,
+                                      StatusCode::InboundIdle);
+        let tsbks = [
+            TsbkBuilder::new(TsbkOpcode::UnitCallAlert).payload(&[1; 8]).build(),
+            TsbkBuilder::new(TsbkOpcode::PhoneAlert).payload(&[2; 8]).build(),
+            TsbkBuilder::new(TsbkOpcode::AckResponse).payload(&[3; 8]).build(),
+        ];
+
+        let dibits = tx.burst(&tsbks[..]);
+        assert_eq!(dibits.len(), 24 + 32 + 3 * 98 + 10);
+
+        let mut status = StatusDeinterleaver::new();
+        let mut recv = TsbkReceiver::new();
+        let mut packets = vec![];
+
+        let data = dibits[24..].iter().filter_map(|&d| match status.feed(d) {
+            StreamSymbol::Status(s) => {
+                assert_eq!(s, StatusCode::InboundIdle);
+                None
+            },
+            StreamSymbol::Data(d) => Some(d),
+        }).collect::<Vec<Dibit>>();
+
+        for &d in &data[32..] {
+            match recv.feed(d) {
+                Some(Ok(t)) => packets.push(t),
+                Some(Err(_)) => panic!(),
+                None => {},
+            }
+        }
+
+        assert_eq!(packets.len(), 3);
+        assert!(!packets[0].is_tail());
+        assert!(!packets[1].is_tail());
+        assert!(packets[2].is_tail());
+
+        for (t, orig) in packets.iter().zip(tsbks.iter()) {
+            assert!(t.crc_valid());
+            assert_eq!(t.opcode(), orig.opcode());
+            assert_eq!(t.payload(), orig.payload());
+        }
+    }
+
+    #[test]
+    fn test_receive_soft() {
+        use consts::CODING_DIBITS;
+
+        let tsbk = TsbkBuilder::new(TsbkOpcode::UnitCallAlert).payload(&[7; 8]).build();
+        let dibits = interleave::Interleaver::new(coder::DibitCoder::new()
+            .feed_bytes(tsbk.0.iter().cloned())
+            .finish()).collect::<Vec<Dibit>>();
+
+        // Find where each coded dibit lands in the interleaved packet.
+        let mut pos = [SoftDibit::default(); CODING_DIBITS];
+
+        for (i, p) in pos.iter_mut().enumerate() {
+            *p = SoftDibit::new(i as f32);
+        }
+
+        let order = interleave::Deinterleaver::new(&pos)
+            .map(|s| s.value() as usize)
+            .collect::<Vec<usize>>();
+
+        // Push a burst of coded symbols just over the threshold toward zero, so their
+        // hard decisions are wrong but they're still closest to the transmitted symbol.
+        let mut soft = dibits.iter().map(|&d| SoftDibit::ideal(d)).collect::<Vec<_>>();
+
+        for &i in &order[4..10] {
+            let v = soft[i].value();
+            soft[i] = SoftDibit::new(if v > 0.0 { v - 1.1 } else { v + 1.1 });
+        }
+
+        let mut recv = TsbkReceiver::new();
+        let (last, rest) = soft.split_last().unwrap();
+
+        for &s in rest {
+            assert!(recv.feed(s.hard()).is_none());
+        }
+
+        match recv.feed(last.hard()).unwrap() {
+            Ok(t) => assert!(t.0 != tsbk.0),
+            Err(_) => {},
+        }
+
+        for &s in rest {
+            assert!(recv.feed_soft(s).is_none());
+        }
+
+        let t = recv.feed_soft(*last).unwrap().unwrap();
+        assert_eq!(t.0, tsbk.0);
+    }
 }
@@ -26,6 +26,26 @@ pub fn slice_u32(bytes: &[u8]) -> u32 {
     (slice_u16(bytes) as u32) << 16 | slice_u16(&bytes[2..]) as u32
 }
 
+/// Write the given 16 bits into the given bytes (in P25 big endian format.)
+pub fn write_u16(bytes: &mut [u8], bits: u16) {
+    bytes[0] = (bits >> 8) as u8;
+    bytes[1] = bits as u8;
+}
+
+/// Write the given 24 bits into the given bytes (in P25 big endian format.)
+pub fn write_u24(bytes: &mut [u8], bits: u32) {
+    assert!(bits >> 24 == 0);
+
+    write_u16(bytes, (bits >> 8) as u16);
+    bytes[2] = bits as u8;
+}
+
+/// Write the given 32 bits into the given bytes (in P25 big endian format.)
+pub fn write_u32(bytes: &mut [u8], bits: u32) {
+    write_u16(bytes, (bits >> 16) as u16);
+    write_u16(&mut bytes[2..], bits as u16);
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -54,4 +74,18 @@ mod test {
         assert_eq!(slice_u32(&[0xDE, 0xAD, 0xBE, 0xEF]), 0xDEADBEEF);
         assert_eq!(slice_u32(&[0xDE, 0xAD, 0xBE, 0xEF, 0x12]), 0xDEADBEEF);
     }
+
+    #[test]
+    fn test_write() {
+        let mut buf = [0; 5];
+
+        write_u16(&mut buf[..], 0xDEAD);
+        assert_eq!(buf, [0xDE, 0xAD, 0, 0, 0]);
+
+        write_u24(&mut buf[1..], 0xABCDEF);
+        assert_eq!(buf, [0xDE, 0xAB, 0xCD, 0xEF, 0]);
+
+        write_u32(&mut buf[1..], 0x12345678);
+        assert_eq!(buf, [0xDE, 0x12, 0x34, 0x56, 0x78]);
+    }
 }
@@ -0,0 +1,508 @@
+//! Assemble received voice events into calls.
+//!
+//! Voice headers, link control, crypto control, and voice frames are correlated into a
+//! single `Call` record that's reported as it starts, as its metadata is learned, and
+//! when it ends. A call ends with a voice terminator, when link control announces a
+//! different call, or when nothing has been heard for the configured timeout.
+//!
+//! Timestamps are supplied by the caller and may be in any monotonic unit, such as
+//! sample counts or milliseconds.
+
+use message::nid::DataUnit;
+use message::receiver::MessageEvent;
+use trunking::fields::TalkGroup;
+use trunking::mfg::MotorolaLcOpcode;
+use voice::control::{LinkControlFields, LinkControlOpcode, GroupVoiceTraffic,
+                     UnitVoiceTraffic};
+use voice::crypto::CryptoAlgorithm;
+
+/// Destination of a voice call.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CallTarget {
+    /// Call to all units in a talkgroup.
+    Group(TalkGroup),
+    /// Call to a single unit.
+    Unit(u32),
+}
+
+/// A voice call, as assembled from received events.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Call {
+    /// Sequence number of the call, unique within an assembler.
+    pub id: u32,
+    /// Destination of the call, if known.
+    pub target: Option<CallTarget>,
+    /// Unit transmitting the call, if known.
+    pub src_unit: Option<u32>,
+    /// Whether the call was marked as an emergency.
+    pub emergency: bool,
+    /// Encryption algorithm of the call, if known.
+    pub crypto: Option<CryptoAlgorithm>,
+    /// Time the call started.
+    pub start: u64,
+    /// Time the call was last heard, or the time it ended.
+    pub end: u64,
+    /// Number of voice frames received.
+    pub frames: u32,
+    /// Whether the call ended with a voice terminator rather than a timeout or another
+    /// call.
+    pub terminated: bool,
+}
+
+impl Call {
+    /// Create a new call with no metadata starting at the given time.
+    fn new(id: u32, time: u64) -> Call {
+        Call {
+            id: id,
+            target: None,
+            src_unit: None,
+            emergency: false,
+            crypto: None,
+            start: time,
+            end: time,
+            frames: 0,
+            terminated: false,
+        }
+    }
+
+    /// Whether the call is encrypted, if known.
+    pub fn encrypted(&self) -> Option<bool> {
+        self.crypto.map(|alg| alg != CryptoAlgorithm::Unencrypted)
+    }
+
+    /// Duration of the call so far.
+    pub fn duration(&self) -> u64 { self.end - self.start }
+
+    /// Check whether the metadata of the given call matches this one.
+    fn same_info(&self, other: &Call) -> bool {
+        self.target == other.target &&
+            self.src_unit == other.src_unit &&
+            self.emergency == other.emergency &&
+            self.crypto == other.crypto
+    }
+}
+
+/// Events emitted by the call assembler.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CallEvent {
+    /// A new call was heard.
+    CallStarted(Call),
+    /// Metadata of the current call changed.
+    CallUpdated(Call),
+    /// The current call ended.
+    CallEnded(Call),
+}
+
+/// Call metadata decoded from a link control word.
+struct CallInfo {
+    target: CallTarget,
+    src_unit: u32,
+    emergency: bool,
+}
+
+impl CallInfo {
+    /// Decode call metadata from the given link control word, if it describes a voice
+    /// call.
+    fn new(lc: LinkControlFields) -> Option<CallInfo> {
+        match (lc.opcode(), lc.mfg_opcode()) {
+            (Some(LinkControlOpcode::GroupVoiceTraffic), _) |
+            (_, Some(MotorolaLcOpcode::PatchGroupVoiceTraffic)) => {
+                let dec = GroupVoiceTraffic::new(lc);
+
+                Some(CallInfo {
+                    target: CallTarget::Group(dec.talkgroup()),
+                    src_unit: dec.src_unit(),
+                    emergency: dec.opts().emergency(),
+                })
+            },
+            (Some(LinkControlOpcode::UnitVoiceTraffic), _) => {
+                let dec = UnitVoiceTraffic::new(lc);
+
+                Some(CallInfo {
+                    target: CallTarget::Unit(dec.dest_unit()),
+                    src_unit: dec.src_unit(),
+                    emergency: dec.opts().emergency(),
+                })
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Correlates receiver events into calls.
+pub struct CallAssembler {
+    /// Time after which a call with no activity is ended.
+    timeout: u64,
+    /// Call currently in progress.
+    call: Option<Call>,
+    /// Sequence number for the next call.
+    next_id: u32,
+}
+
+impl CallAssembler {
+    /// Create a new `CallAssembler` that ends calls after `timeout` time units of
+    /// inactivity.
+    pub fn new(timeout: u64) -> CallAssembler {
+        CallAssembler {
+            timeout: timeout,
+            call: None,
+            next_id: 0,
+        }
+    }
+
+    /// Call currently in progress, if any.
+    pub fn call(&self) -> Option<&Call> { self.call.as_ref() }
+
+    /// Handle the given receiver event received at the given time, returning any
+    /// resulting call events in order.
+    pub fn feed(&mut self, time: u64, event: &MessageEvent) -> Vec<CallEvent> {
+        let mut events = self.poll(time);
+
+        match *event {
+            MessageEvent::VoiceHeader(ref h) => {
+                // A header always begins a new transmission.
+                if let Some(e) = self.end_call(false) {
+                    events.push(e);
+                }
+
+                let (tg, alg) = (h.talk_group(), h.crypto_alg());
+
+                self.update(time, &mut events, |c| {
+                    // Unit-to-unit calls carry the null talkgroup in the header, so
+                    // their destination is left for link control to fill in.
+                    if tg != TalkGroup::Nobody {
+                        c.target = Some(CallTarget::Group(tg));
+                    }
+
+                    c.crypto = Some(alg);
+                });
+            },
+            MessageEvent::PacketNID(nid) => match nid.data_unit {
+                DataUnit::VoiceLCFrameGroup | DataUnit::VoiceCCFrameGroup =>
+                    self.update(time, &mut events, |_| {}),
+                DataUnit::VoiceSimpleTerminator | DataUnit::VoiceLCTerminator => {
+                    if let Some(c) = self.call.as_mut() {
+                        c.end = time;
+                    }
+
+                    if let Some(e) = self.end_call(true) {
+                        events.push(e);
+                    }
+                },
+                _ => {},
+            },
+            MessageEvent::LinkControl(lc) => {
+                let info = match CallInfo::new(lc) {
+                    Some(info) => info,
+                    None => return events,
+                };
+
+                // Link control for a different destination means the terminator of
+                // the previous call was missed.
+                let other = match self.call {
+                    Some(ref c) => c.target.map_or(false, |t| t != info.target),
+                    None => false,
+                };
+
+                if other {
+                    if let Some(e) = self.end_call(false) {
+                        events.push(e);
+                    }
+                }
+
+                self.update(time, &mut events, |c| {
+                    c.target = Some(info.target);
+                    c.src_unit = Some(info.src_unit);
+                    c.emergency = info.emergency;
+                });
+            },
+            MessageEvent::CryptoControl(ref cc) => {
+                let alg = cc.alg();
+                self.update(time, &mut events, |c| c.crypto = Some(alg));
+            },
+            MessageEvent::VoiceFrame(_) =>
+                self.update(time, &mut events, |c| c.frames += 1),
+            MessageEvent::LowSpeedDataFragment(_) => {
+                if let Some(c) = self.call.as_mut() {
+                    c.end = time;
+                }
+            },
+            _ => {},
+        }
+
+        events
+    }
+
+    /// Check the current call for inactivity at the given time, returning an ended
+    /// event if it timed out.
+    pub fn poll(&mut self, time: u64) -> Vec<CallEvent> {
+        let expired = match self.call {
+            Some(ref c) => time.saturating_sub(c.end) >= self.timeout,
+            None => false,
+        };
+
+        if !expired {
+            return vec![];
+        }
+
+        self.end_call(false).into_iter().collect()
+    }
+
+    /// End the current call, if any, returning its ended event.
+    fn end_call(&mut self, terminated: bool) -> Option<CallEvent> {
+        self.call.take().map(|mut c| {
+            c.terminated = terminated;
+            CallEvent::CallEnded(c)
+        })
+    }
+
+    /// Apply the given modification to the current call, starting a new call if none is
+    /// in progress, and emit the appropriate event.
+    fn update<F: FnOnce(&mut Call)>(&mut self, time: u64, events: &mut Vec<CallEvent>,
+                                    f: F)
+    {
+        let started = self.call.is_none();
+
+        if started {
+            self.call = Some(Call::new(self.next_id, time));
+            self.next_id = self.next_id.wrapping_add(1);
+        }
+
+        let call = self.call.as_mut().unwrap();
+        let prev = *call;
+
+        f(call);
+        call.end = time;
+
+        if started {
+            events.push(CallEvent::CallStarted(*call));
+        } else if !call.same_info(&prev) {
+            events.push(CallEvent::CallUpdated(*call));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+    use message::receiver::MessageEvent;
+    use trunking::fields::{ServiceOptions, TalkGroup};
+    use voice::control::{group_voice_traffic, unit_voice_traffic};
+    use voice::crypto::{CryptoAlgorithm, CryptoControlFields};
+    use voice::frame::VoiceFrame;
+    use voice::header::VoiceHeaderFields;
+
+    fn nid(du: DataUnit) -> MessageEvent {
+        MessageEvent::PacketNID(NetworkId::new(NetworkAccessCode::Default, du))
+    }
+
+    fn frame() -> MessageEvent {
+        MessageEvent::VoiceFrame(VoiceFrame {
+            chunks: [0; 8],
+            errors: [0; 7],
+        })
+    }
+
+    fn group(tg: u16, src: u32, emergency: bool) -> MessageEvent {
+        let opts = ServiceOptions::new(if emergency { 0x80 } else { 0 });
+        MessageEvent::LinkControl(group_voice_traffic(0, opts, TalkGroup::Other(tg), src))
+    }
+
+    #[test]
+    fn test_call() {
+        let mut a = CallAssembler::new(100);
+
+        let mut header = [0; 15];
+        header[10] = 0x80;
+        header[13] = 0x12;
+        header[14] = 0x34;
+
+        let e = a.feed(0, &MessageEvent::VoiceHeader(VoiceHeaderFields::new(header)));
+        assert_eq!(e.len(), 1);
+        let c = match e[0] {
+            CallEvent::CallStarted(c) => c,
+            _ => panic!(),
+        };
+        assert_eq!(c.id, 0);
+        assert_eq!(c.target, Some(CallTarget::Group(TalkGroup::Other(0x1234))));
+        assert_eq!(c.crypto, Some(CryptoAlgorithm::Unencrypted));
+        assert_eq!(c.encrypted(), Some(false));
+        assert_eq!(c.src_unit, None);
+
+        assert!(a.feed(1, &nid(DataUnit::VoiceLCFrameGroup)).is_empty());
+        assert!(a.feed(2, &frame()).is_empty());
+        assert!(a.feed(3, &frame()).is_empty());
+
+        let e = a.feed(4, &group(0x1234, 0xABCDEF, false));
+        assert_eq!(e.len(), 1);
+        let c = match e[0] {
+            CallEvent::CallUpdated(c) => c,
+            _ => panic!(),
+        };
+        assert_eq!(c.src_unit, Some(0xABCDEF));
+        assert!(!c.emergency);
+
+        // Repeated metadata isn't reported.
+        assert!(a.feed(5, &group(0x1234, 0xABCDEF, false)).is_empty());
+
+        let e = a.feed(6, &group(0x1234, 0xABCDEF, true));
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallUpdated(c) => assert!(c.emergency),
+            _ => panic!(),
+        }
+
+        let mut cc = [0; 12];
+        cc[9] = 0x84;
+
+        let e = a.feed(7, &MessageEvent::CryptoControl(CryptoControlFields::new(cc)));
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallUpdated(c) => {
+                assert_eq!(c.crypto, Some(CryptoAlgorithm::Aes));
+                assert_eq!(c.encrypted(), Some(true));
+            },
+            _ => panic!(),
+        }
+
+        assert!(a.feed(8, &frame()).is_empty());
+        assert_eq!(a.call().unwrap().frames, 3);
+
+        let e = a.feed(20, &nid(DataUnit::VoiceSimpleTerminator));
+        assert_eq!(e.len(), 1);
+        let c = match e[0] {
+            CallEvent::CallEnded(c) => c,
+            _ => panic!(),
+        };
+        assert_eq!(c.frames, 3);
+        assert_eq!(c.start, 0);
+        assert_eq!(c.end, 20);
+        assert_eq!(c.duration(), 20);
+        assert!(c.terminated);
+        assert!(a.call().is_none());
+
+        assert!(a.feed(21, &nid(DataUnit::VoiceLCTerminator)).is_empty());
+    }
+
+    #[test]
+    fn test_timeout() {
+        let mut a = CallAssembler::new(100);
+
+        let e = a.feed(10, &frame());
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallStarted(c) => {
+                assert_eq!(c.target, None);
+                assert_eq!(c.frames, 1);
+            },
+            _ => panic!(),
+        }
+
+        assert!(a.poll(109).is_empty());
+        assert!(a.feed(50, &MessageEvent::LowSpeedDataFragment(0)).is_empty());
+        assert!(a.poll(149).is_empty());
+
+        let e = a.poll(150);
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallEnded(c) => {
+                assert_eq!(c.end, 50);
+                assert!(!c.terminated);
+            },
+            _ => panic!(),
+        }
+
+        assert!(a.poll(1000).is_empty());
+
+        // Timeouts are also checked when feeding events.
+        a.feed(1000, &frame());
+        let e = a.feed(2000, &frame());
+        assert_eq!(e.len(), 2);
+        match (e[0], e[1]) {
+            (CallEvent::CallEnded(c), CallEvent::CallStarted(d)) => {
+                assert_eq!(c.id, 1);
+                assert_eq!(d.id, 2);
+                assert_eq!(d.start, 2000);
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_new_target() {
+        let mut a = CallAssembler::new(100);
+
+        a.feed(0, &nid(DataUnit::VoiceLCFrameGroup));
+        a.feed(1, &group(0x1111, 1, false));
+        a.feed(2, &frame());
+
+        let lc = unit_voice_traffic(0, ServiceOptions::new(0), 0x123456, 2);
+        let e = a.feed(3, &MessageEvent::LinkControl(lc));
+
+        assert_eq!(e.len(), 2);
+        match (e[0], e[1]) {
+            (CallEvent::CallEnded(c), CallEvent::CallStarted(d)) => {
+                assert_eq!(c.target, Some(CallTarget::Group(TalkGroup::Other(0x1111))));
+                assert_eq!(c.frames, 1);
+                assert!(!c.terminated);
+                assert_eq!(d.target, Some(CallTarget::Unit(0x123456)));
+                assert_eq!(d.src_unit, Some(2));
+                assert_eq!(d.frames, 0);
+            },
+            _ => panic!(),
+        }
+
+        // A header always starts a new call.
+        let e = a.feed(4, &MessageEvent::VoiceHeader(VoiceHeaderFields::new([0; 15])));
+        assert_eq!(e.len(), 2);
+        match (e[0], e[1]) {
+            (CallEvent::CallEnded(_), CallEvent::CallStarted(_)) => {},
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_unit_call() {
+        let mut a = CallAssembler::new(100);
+
+        let e = a.feed(0, &MessageEvent::VoiceHeader(VoiceHeaderFields::new([0; 15])));
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallStarted(c) => {
+                assert_eq!(c.id, 0);
+                assert_eq!(c.target, None);
+            },
+            _ => panic!(),
+        }
+
+        a.feed(1, &nid(DataUnit::VoiceLCFrameGroup));
+        a.feed(2, &frame());
+
+        // The first link control fills in the destination of the same call.
+        let lc = unit_voice_traffic(0, ServiceOptions::new(0), 0x123456, 2);
+        let e = a.feed(3, &MessageEvent::LinkControl(lc));
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallUpdated(c) => {
+                assert_eq!(c.id, 0);
+                assert_eq!(c.target, Some(CallTarget::Unit(0x123456)));
+                assert_eq!(c.src_unit, Some(2));
+                assert_eq!(c.frames, 1);
+            },
+            _ => panic!(),
+        }
+
+        assert!(a.feed(4, &MessageEvent::LinkControl(lc)).is_empty());
+
+        let e = a.feed(5, &nid(DataUnit::VoiceLCTerminator));
+        assert_eq!(e.len(), 1);
+        match e[0] {
+            CallEvent::CallEnded(c) => {
+                assert_eq!(c.id, 0);
+                assert!(c.terminated);
+            },
+            _ => panic!(),
+        }
+    }
+}
@@ -1,9 +1,10 @@
 //! Decode Link Control (LC) packets and payloads.
 
 use consts::LINK_CONTROL_BYTES;
-use util::{slice_u16, slice_u24};
+use util::{slice_u16, slice_u24, write_u16, write_u24};
 
 use trunking::fields::{TalkGroup, ServiceOptions};
+use trunking::mfg::{Manufacturer, MotorolaLcOpcode, MFG_MOTOROLA};
 
 /// Buffer of bytes that represents a link control packet.
 pub type Buf = [u8; LINK_CONTROL_BYTES];
@@ -80,6 +81,42 @@ impl LinkControlOpcode {
             _ => None,
         }
     }
+
+    /// Convert the opcode to its 6-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::LinkControlOpcode::*;
+
+        match self {
+            GroupVoiceTraffic => 0b000000,
+            GroupVoiceUpdate => 0b000010,
+            UnitVoiceTraffic => 0b000011,
+            GroupVoiceUpdateExplicit => 0b000100,
+            UnitCallRequest => 0b000101,
+            PhoneTraffic => 0b000110,
+            PhoneAlert => 0b000111,
+            CallTermination => 0b001111,
+            GroupAffiliationQuery => 0b010000,
+            UnitRegistrationRequest => 0b010001,
+            UnitAuthenticationRequst => 0b010010,
+            UnitStatusRequest => 0b010011,
+            SystemServiceBroadcast => 0b100000,
+            AltControlChannel => 0b100001,
+            AdjacentSite => 0b100010,
+            RfssStatusBroadcast => 0b100011,
+            NetworkStatusBroadcast => 0b100100,
+            UnitStatusUpdate => 0b010100,
+            UnitShortMessage => 0b010101,
+            UnitCallAlert => 0b010110,
+            ExtendedFunction => 0b010111,
+            ChannelParamsUpdate => 0b011000,
+            ProtectionParamBroadcast => 0b100101,
+            AltControlChannelExplicit => 0b100110,
+            AdjacentSiteExplicit => 0b100111,
+            ChannelParamsExplicit => 0b011001,
+            RfssStatusExplicit => 0b101000,
+            NetworkStatusExplicit => 0b101001,
+        }
+    }
 }
 
 /// Base link control decoder, common to all packets.
@@ -93,13 +130,137 @@ impl LinkControlFields {
     /// Whether the packet is encrypted.
     pub fn protected(&self) -> bool { self.0[0] >> 7 == 1 }
 
-    /// Type of data contained in the payload.
+    /// Whether the packet has an explicit manufacturer ID in the first payload byte.
+    fn explicit_mfg(&self) -> bool { self.0[0] >> 6 & 1 == 0 }
+
+    /// Manufacturer ID of the packet if it has a manufacturer-specific format.
+    ///
+    /// Standard formats that carry no MFID use the first payload byte for other fields,
+    /// so only packets with an opcode that is nonstandard or whose standard format
+    /// carries an MFID are checked. Any MFID other than the standard ones marks the
+    /// packet as manufacturer-specific.
+    fn ext_mfg(&self) -> Option<u8> {
+        if !self.explicit_mfg() {
+            return None;
+        }
+
+        match LinkControlOpcode::from_bits(self.0[0] & 0x3F) {
+            Some(LinkControlOpcode::GroupVoiceTraffic) |
+            Some(LinkControlOpcode::UnitVoiceTraffic) |
+            None => {},
+            Some(_) => return None,
+        }
+
+        if Manufacturer::from_bits(self.0[1]).is_standard() {
+            None
+        } else {
+            Some(self.0[1])
+        }
+    }
+
+    /// Type of data contained in the payload, or `None` if the packet has a
+    /// manufacturer-specific format.
     pub fn opcode(&self) -> Option<LinkControlOpcode> {
-        LinkControlOpcode::from_bits(self.0[0] & 0x3F)
+        match self.ext_mfg() {
+            Some(_) => None,
+            None => LinkControlOpcode::from_bits(self.0[0] & 0x3F),
+        }
+    }
+
+    /// Type of data contained in the payload, if the packet has a recognized Motorola
+    /// format.
+    pub fn mfg_opcode(&self) -> Option<MotorolaLcOpcode> {
+        match self.ext_mfg() {
+            Some(MFG_MOTOROLA) => MotorolaLcOpcode::from_bits(self.0[0] & 0x3F),
+            _ => None,
+        }
     }
 
     /// Bytes that make up the payload.
     pub fn payload(&self) -> &[u8] { &self.0[1...8] }
+
+    /// Raw bytes of the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
+}
+
+/// Builds a link control packet from its fields.
+///
+/// The packet is initially unencrypted with a zeroed payload.
+pub struct LinkControlBuilder(Buf);
+
+impl LinkControlBuilder {
+    /// Create a new `LinkControlBuilder` for a packet with the given opcode.
+    pub fn new(opcode: LinkControlOpcode) -> LinkControlBuilder {
+        LinkControlBuilder::raw(opcode.to_bits())
+    }
+
+    /// Create a new `LinkControlBuilder` for a packet with the given 6-bit opcode, which
+    /// can be used for manufacturer-specific packets.
+    pub fn raw(opcode: u8) -> LinkControlBuilder {
+        assert!(opcode >> 6 == 0);
+
+        let mut buf = [0; LINK_CONTROL_BYTES];
+        buf[0] = opcode;
+
+        LinkControlBuilder(buf)
+    }
+
+    /// Set whether the packet is encrypted.
+    pub fn protected(mut self, protected: bool) -> Self {
+        self.0[0] = (protected as u8) << 7 | self.0[0] & 0x7F;
+        self
+    }
+
+    /// Set the 8 payload bytes of the packet.
+    pub fn payload(mut self, payload: &[u8]) -> Self {
+        (&mut self.0[1...8]).copy_from_slice(payload);
+        self
+    }
+
+    /// Construct the packet.
+    pub fn build(self) -> LinkControlFields { LinkControlFields(self.0) }
+}
+
+/// Build a `GroupVoiceTraffic` packet with the given manufacturer ID.
+pub fn group_voice_traffic(mfg: u8, opts: ServiceOptions, talkgroup: TalkGroup,
+                           src_unit: u32)
+    -> LinkControlFields
+{
+    let mut p = [0; 8];
+
+    p[0] = mfg;
+    p[1] = opts.to_bits();
+    write_u16(&mut p[3..], talkgroup.to_bits());
+    write_u24(&mut p[5..], src_unit);
+
+    LinkControlBuilder::new(LinkControlOpcode::GroupVoiceTraffic).payload(&p[..]).build()
+}
+
+/// Build a `UnitVoiceTraffic` packet with the given manufacturer ID.
+pub fn unit_voice_traffic(mfg: u8, opts: ServiceOptions, dest_unit: u32, src_unit: u32)
+    -> LinkControlFields
+{
+    let mut p = [0; 8];
+
+    p[0] = mfg;
+    p[1] = opts.to_bits();
+    write_u24(&mut p[2..], dest_unit);
+    write_u24(&mut p[5..], src_unit);
+
+    LinkControlBuilder::new(LinkControlOpcode::UnitVoiceTraffic).payload(&p[..]).build()
+}
+
+/// Build a `PhoneTraffic` packet.
+pub fn phone_traffic(opts: ServiceOptions, call_timer: u16, unit: u32)
+    -> LinkControlFields
+{
+    let mut p = [0; 8];
+
+    p[1] = opts.to_bits();
+    write_u16(&mut p[3..], call_timer);
+    write_u24(&mut p[5..], unit);
+
+    LinkControlBuilder::new(LinkControlOpcode::PhoneTraffic).payload(&p[..]).build()
 }
 
 /// Identity of unit transmitting on the current talkgroup traffic channel.
@@ -178,6 +339,65 @@ mod test {
         ]);
     }
 
+    #[test]
+    fn test_mfg() {
+        let lc = LinkControlFields::new([
+            0b00000000,
+            0b10010000,
+            0b10000000, 0b00000000,
+            0x12, 0x34,
+            0xDE, 0xAD, 0xBE,
+        ]);
+
+        assert_eq!(lc.opcode(), None);
+        assert_eq!(lc.mfg_opcode(), Some(MotorolaLcOpcode::PatchGroupVoiceTraffic));
+
+        let dec = GroupVoiceTraffic::new(lc);
+        assert_eq!(dec.mfg(), 0x90);
+        assert!(dec.opts().emergency());
+        assert_eq!(dec.talkgroup(), TalkGroup::Other(0x1234));
+        assert_eq!(dec.src_unit(), 0xDEADBE);
+
+        let lc = LinkControlFields::new([
+            0b01000000,
+            0b10010000,
+            0b10000000, 0b00000000,
+            0x12, 0x34,
+            0xDE, 0xAD, 0xBE,
+        ]);
+
+        assert_eq!(lc.opcode(), Some(LinkControlOpcode::GroupVoiceTraffic));
+        assert_eq!(lc.mfg_opcode(), None);
+
+        // Unrecognized manufacturers don't use standard opcodes either.
+        let lc = LinkControlFields::new([
+            0b00000000,
+            0x68,
+            0b10000000, 0b00000000,
+            0x12, 0x34,
+            0xDE, 0xAD, 0xBE,
+        ]);
+
+        assert_eq!(lc.opcode(), None);
+        assert_eq!(lc.mfg_opcode(), None);
+
+        let lc = unit_voice_traffic(0x68, ServiceOptions::new(0), 0x123456, 0xDEADBE);
+        assert_eq!(lc.opcode(), None);
+
+        for &mfg in [0x00, 0x01].iter() {
+            let lc = LinkControlFields::new([
+                0b00000000,
+                mfg,
+                0b10000000, 0b00000000,
+                0x12, 0x34,
+                0xDE, 0xAD, 0xBE,
+            ]);
+
+            assert_eq!(lc.opcode(), Some(LinkControlOpcode::GroupVoiceTraffic));
+            assert_eq!(lc.mfg_opcode(), None);
+        }
+    }
+
     #[test]
     fn test_adjacent_site() {
         let lc = LinkControlFields::new([
@@ -474,4 +694,51 @@ mod test {
         assert_eq!(p.call_timer(), 0b1000000000000010);
         assert_eq!(p.unit(), 0b111100000011001111100010);
     }
+
+    #[test]
+    fn test_builder() {
+        let lc = LinkControlBuilder::new(LinkControlOpcode::UnitCallAlert)
+            .protected(true)
+            .payload(&[1, 2, 3, 4, 5, 6, 7, 8])
+            .build();
+        assert!(lc.protected());
+        assert_eq!(lc.opcode(), Some(LinkControlOpcode::UnitCallAlert));
+        assert_eq!(lc.payload(), &[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        for bits in 0..64 {
+            if let Some(op) = LinkControlOpcode::from_bits(bits) {
+                assert_eq!(op.to_bits(), bits);
+            }
+        }
+
+        let lc = group_voice_traffic(0, ServiceOptions::new(0b10000101),
+                                     TalkGroup::Other(0x1234), 0xABCDEF);
+        assert_eq!(lc.opcode(), Some(LinkControlOpcode::GroupVoiceTraffic));
+        let g = GroupVoiceTraffic::new(lc);
+        assert_eq!(g.mfg(), 0);
+        assert!(g.opts().emergency());
+        assert_eq!(g.opts().prio(), 5);
+        assert_eq!(g.talkgroup(), TalkGroup::Other(0x1234));
+        assert_eq!(g.src_unit(), 0xABCDEF);
+
+        let lc = group_voice_traffic(MFG_MOTOROLA, ServiceOptions::new(0),
+                                     TalkGroup::Other(0x4321), 0x123456);
+        assert_eq!(lc.opcode(), None);
+        assert_eq!(lc.mfg_opcode(), Some(MotorolaLcOpcode::PatchGroupVoiceTraffic));
+
+        let lc = unit_voice_traffic(0, ServiceOptions::new(0b01000000), 0x123456,
+                                    0xABCDEF);
+        assert_eq!(lc.opcode(), Some(LinkControlOpcode::UnitVoiceTraffic));
+        let u = UnitVoiceTraffic::new(lc);
+        assert!(u.opts().protected());
+        assert_eq!(u.dest_unit(), 0x123456);
+        assert_eq!(u.src_unit(), 0xABCDEF);
+
+        let lc = phone_traffic(ServiceOptions::new(0b00000011), 900, 0x654321);
+        assert_eq!(lc.opcode(), Some(LinkControlOpcode::PhoneTraffic));
+        let p = PhoneTraffic::new(lc);
+        assert_eq!(p.opts().prio(), 3);
+        assert_eq!(p.call_timer(), 900);
+        assert_eq!(p.unit(), 0x654321);
+    }
 }
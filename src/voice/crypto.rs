@@ -19,6 +19,9 @@ impl CryptoControlFields {
     pub fn alg(&self) -> CryptoAlgorithm { CryptoAlgorithm::from_bits(self.0[9]) }
     /// Encryption key to use.
     pub fn key(&self) -> u16 { slice_u16(&self.0[10..]) }
+
+    /// Raw bytes of the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
 }
 
 /// Type of cryptographic algorithm.
@@ -57,6 +60,25 @@ impl CryptoAlgorithm {
             b => Other(b),
         }
     }
+
+    /// Convert the algorithm to its 8-bit representation.
+    pub fn to_bits(self) -> u8 {
+        use self::CryptoAlgorithm::*;
+
+        match self {
+            Accordion => 0x00,
+            BatonEven => 0x01,
+            Firefly => 0x02,
+            Mayfly => 0x03,
+            Saville => 0x04,
+            BatonOdd => 0x41,
+            Unencrypted => 0x80,
+            Des => 0x81,
+            TripleDes => 0x83,
+            Aes => 0x84,
+            Other(b) => b,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,6 +1,7 @@
-//! Descramble/Deinterleave the dibits in a received voice frame.
+//! Descramble/Deinterleave the dibits in a received voice frame, and the inverse
+//! scramble/interleave for transmitted voice frames.
 
-use bits::Dibit;
+use bits::{Dibit, SoftDibit};
 use consts;
 
 /// Descramble a portion of the given voice frame dibits into the PN-scrambled, coded
@@ -9,6 +10,20 @@ pub fn descramble(dibits: &[Dibit; consts::FRAME_DIBITS], idx: usize) -> u32 {
     DESCRAMBLERS[idx].descramble(dibits)
 }
 
+/// Scramble the given PN-scrambled, coded chunk `u_{idx}` into its portion of the given
+/// voice frame dibits.
+pub fn scramble(dibits: &mut [Dibit; consts::FRAME_DIBITS], idx: usize, chunk: u32) {
+    DESCRAMBLERS[idx].scramble(dibits, chunk)
+}
+
+/// Fill in the reliability of each bit of the PN-scrambled, coded chunk `u_{idx}`, from
+/// MSB to LSB, from the given soft voice frame symbols.
+pub fn reliabilities(soft: &[SoftDibit; consts::FRAME_DIBITS], idx: usize,
+                     rel: &mut [f32])
+{
+    DESCRAMBLERS[idx].reliabilities(soft, rel)
+}
+
 /// Set of descramblers for each associated chunk `u_0`, ..., `u_7`.
 const DESCRAMBLERS: [VoiceFrameDescrambler; 8] = [
     VoiceFrameDescrambler(&[
@@ -59,6 +74,37 @@ impl VoiceFrameDescrambler {
             })
         })
     }
+
+    /// Fill in the reliability of each chunk bit, in the order they're descrambled.
+    pub fn reliabilities(&self, soft: &[SoftDibit; consts::FRAME_DIBITS],
+                         rel: &mut [f32])
+    {
+        assert!(rel.len() == self.0.iter().fold(0, |sum, zz| sum + zz.remain));
+
+        for (n, (idx, hi)) in self.0.iter().flat_map(|&zz| zz).enumerate() {
+            rel[n] = if hi {
+                soft[idx].hi_reliability()
+            } else {
+                soft[idx].lo_reliability()
+            };
+        }
+    }
+
+    /// Scramble the given chunk into the given dibits.
+    pub fn scramble(&self, dibits: &mut [Dibit; consts::FRAME_DIBITS], chunk: u32) {
+        // Chunk bits are consumed MSB-first, in the same order they're descrambled.
+        let bits = self.0.iter().fold(0, |sum, zz| sum + zz.remain);
+
+        for (n, (idx, hi)) in self.0.iter().flat_map(|&zz| zz).enumerate() {
+            let bit = (chunk >> (bits - n - 1) & 1) as u8;
+
+            dibits[idx] = if hi {
+                Dibit::new(bit << 1 | dibits[idx].lo())
+            } else {
+                Dibit::new(dibits[idx].hi() << 1 | bit)
+            };
+        }
+    }
 }
 
 /// Walks the zigzagging interleave schedule used for voice frames.
@@ -117,7 +163,8 @@ impl Iterator for ZigZag {
 
 #[cfg(test)]
 mod test {
-    use super::DESCRAMBLERS;
+    use super::*;
+    use bits::Dibit;
     use consts;
 
     #[test]
@@ -136,4 +183,28 @@ mod test {
             assert_eq!(v, 2);
         }
     }
+
+    #[test]
+    fn test_scramble() {
+        let chunks = [
+            0x5A5A5A,
+            0x0F0F0F,
+            0x7FFFFF,
+            0x123456,
+            0x5555,
+            0x7FFF,
+            0x2AAA,
+            0b1011001,
+        ];
+
+        let mut dibits = [Dibit::default(); consts::FRAME_DIBITS];
+
+        for (idx, &chunk) in chunks.iter().enumerate() {
+            scramble(&mut dibits, idx, chunk);
+        }
+
+        for (idx, &chunk) in chunks.iter().enumerate() {
+            assert_eq!(descramble(&dibits, idx), chunk);
+        }
+    }
 }
@@ -1,11 +1,12 @@
-//! Decode a voice frame into chunks suitable for IMBE.
+//! Decode a voice frame into chunks suitable for IMBE, and encode chunks into a voice
+//! frame.
 
-use bits::Dibit;
+use bits::{Dibit, SoftDibit};
 use coding::{golay, hamming};
 use consts;
 use error::Result;
 
-use voice::descramble::descramble;
+use voice::descramble::{descramble, reliabilities, scramble};
 use voice::rand;
 
 use error::P25Error::*;
@@ -23,11 +24,33 @@ impl VoiceFrame {
     /// dibits. Return `Ok(frame)` if the frame was successfully decoded, and `Err(err)`
     /// otherwise.
     pub fn new(dibits: &[Dibit; consts::FRAME_DIBITS]) -> Result<VoiceFrame> {
+        VoiceFrame::decode(dibits, None)
+    }
+
+    /// Try to decode a `VoiceFrame` from the given soft coded, PN-scrambled, interleaved
+    /// symbols, using the reliability of each bit to correct more errors in the Golay
+    /// and Hamming chunks. Return `Ok(frame)` if the frame was successfully decoded, and
+    /// `Err(err)` otherwise.
+    pub fn new_soft(soft: &[SoftDibit; consts::FRAME_DIBITS]) -> Result<VoiceFrame> {
+        let mut dibits = [Dibit::default(); consts::FRAME_DIBITS];
+
+        for (d, s) in dibits.iter_mut().zip(soft.iter()) {
+            *d = s.hard();
+        }
+
+        VoiceFrame::decode(&dibits, Some(soft))
+    }
+
+    /// Decode the given dibits, soft-decoding the FEC chunks if the corresponding soft
+    /// symbols are given.
+    fn decode(dibits: &[Dibit; consts::FRAME_DIBITS], soft: Option<&Soft>)
+        -> Result<VoiceFrame>
+    {
         let mut chunks = [0; 8];
         let mut errors = [0; 7];
 
         // Decode u_0 to recover the PN seed.
-        let (init, err) = match golay::standard::decode(descramble(dibits, 0)) {
+        let (init, err) = match decode_golay(descramble(dibits, 0), soft, 0) {
             Some(x) => x,
             None => return Err(GolayUnrecoverable),
         };
@@ -41,7 +64,7 @@ impl VoiceFrame {
         for idx in 1...3 {
             let bits = descramble(dibits, idx) ^ prand.next_23();
 
-            let (data, err) = match golay::standard::decode(bits) {
+            let (data, err) = match decode_golay(bits, soft, idx) {
                 Some(x) => x,
                 None => return Err(GolayUnrecoverable),
             };
@@ -54,7 +77,7 @@ impl VoiceFrame {
         for idx in 4...6 {
             let bits = descramble(dibits, idx) ^ prand.next_15();
 
-            let (data, err) = match hamming::standard::decode(bits as u16) {
+            let (data, err) = match decode_hamming(bits as u16, soft, idx) {
                 Some(x) => x,
                 None => return Err(HammingUnrecoverable),
             };
@@ -70,4 +93,135 @@ impl VoiceFrame {
             errors: errors,
         })
     }
+
+    /// Encode the given IMBE chunks `u_0`, ..., `u_7` into coded, PN-scrambled,
+    /// interleaved dibits. This is the inverse of `VoiceFrame::new`.
+    pub fn encode(chunks: &[u32; 8]) -> [Dibit; consts::FRAME_DIBITS] {
+        let mut dibits = [Dibit::default(); consts::FRAME_DIBITS];
+
+        // Encode u_0, which is also the PN seed.
+        scramble(&mut dibits, 0, golay::standard::encode(chunks[0] as u16));
+
+        let mut prand = rand::PseudoRand::new(chunks[0] as u16);
+
+        // Encode "higher-priority" Golay chunks.
+        for idx in 1...3 {
+            let bits = golay::standard::encode(chunks[idx] as u16);
+            scramble(&mut dibits, idx, bits ^ prand.next_23());
+        }
+
+        // Encode "lower-priority" Hamming chunks.
+        for idx in 4...6 {
+            let bits = hamming::standard::encode(chunks[idx] as u16) as u32;
+            scramble(&mut dibits, idx, bits ^ prand.next_15());
+        }
+
+        assert!(chunks[7] >> 7 == 0);
+        scramble(&mut dibits, 7, chunks[7]);
+
+        dibits
+    }
+}
+
+/// Soft symbols of a voice frame.
+type Soft = [SoftDibit; consts::FRAME_DIBITS];
+
+/// Decode the given Golay-coded chunk `u_{idx}`, using the reliabilities from the given
+/// soft symbols if available.
+fn decode_golay(bits: u32, soft: Option<&Soft>, idx: usize) -> Option<(u16, usize)> {
+    match soft {
+        Some(soft) => {
+            let mut rel = [0.0; 23];
+            reliabilities(soft, idx, &mut rel[..]);
+
+            golay::standard::decode_soft(bits, &rel[..])
+        },
+        None => golay::standard::decode(bits),
+    }
+}
+
+/// Decode the given Hamming-coded chunk `u_{idx}`, using the reliabilities from the
+/// given soft symbols if available.
+fn decode_hamming(bits: u16, soft: Option<&Soft>, idx: usize) -> Option<(u16, usize)> {
+    match soft {
+        Some(soft) => {
+            let mut rel = [0.0; 15];
+            reliabilities(soft, idx, &mut rel[..]);
+
+            hamming::standard::decode_soft(bits, &rel[..])
+        },
+        None => hamming::standard::decode(bits),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let chunks = [0xABC, 0x123, 0xFFF, 0x000, 0x7FF, 0x2AA, 0x155, 0b1010011];
+        let dibits = VoiceFrame::encode(&chunks);
+        let f = VoiceFrame::new(&dibits).unwrap();
+
+        assert_eq!(f.chunks, chunks);
+        assert_eq!(f.errors, [0; 7]);
+
+        // Chunks with zero PN seed.
+        let chunks = [0, 1, 2, 3, 4, 5, 6, 7];
+        let f = VoiceFrame::new(&VoiceFrame::encode(&chunks)).unwrap();
+        assert_eq!(f.chunks, chunks);
+    }
+
+    #[test]
+    fn test_correct() {
+        let chunks = [0x5A5, 0xA5A, 0x0F0, 0xF0F, 0x400, 0x3FF, 0x001, 0b1111111];
+        let mut dibits = VoiceFrame::encode(&chunks);
+
+        // Flip both bits of the first symbol, which belongs to u_0 and u_1.
+        dibits[0] = Dibit::new(dibits[0].bits() ^ 0b11);
+
+        let f = VoiceFrame::new(&dibits).unwrap();
+        assert_eq!(f.chunks, chunks);
+    }
+
+    #[test]
+    fn test_soft() {
+        let chunks = [0x5A5, 0xA5A, 0x0F0, 0xF0F, 0x400, 0x3FF, 0x001, 0b1111111];
+        let dibits = VoiceFrame::encode(&chunks);
+        let mut symbols = [SoftDibit::default(); consts::FRAME_DIBITS];
+
+        for (s, &d) in symbols.iter_mut().zip(dibits.iter()) {
+            *s = SoftDibit::ideal(d);
+        }
+
+        let f = VoiceFrame::new_soft(&symbols).unwrap();
+        assert_eq!(f.chunks, chunks);
+        assert_eq!(f.errors, [0; 7]);
+
+        // Corrupt 4 bits of u_0, which are carried in the LSB of these symbols, with
+        // symbols that lie just across the decision threshold.
+        let mut hard = dibits;
+
+        for &idx in [3, 9, 15, 21].iter() {
+            hard[idx] = Dibit::new(dibits[idx].bits() ^ 0b01);
+
+            symbols[idx] = SoftDibit::new(match hard[idx].bits() {
+                0b01 => 2.1,
+                0b00 => 1.9,
+                0b10 => -1.9,
+                _ => -2.1,
+            });
+
+            assert_eq!(symbols[idx].hard(), hard[idx]);
+        }
+
+        match VoiceFrame::new(&hard) {
+            Ok(f) => assert!(f.chunks != chunks),
+            Err(_) => {},
+        }
+
+        let f = VoiceFrame::new_soft(&symbols).unwrap();
+        assert_eq!(f.chunks, chunks);
+    }
 }
@@ -7,8 +7,8 @@ use std;
 
 use collect_slice::CollectSlice;
 
-use bits::{Hexbit, HexbitBytes, Dibit};
-use coding::{cyclic, hamming, reed_solomon};
+use bits::{Hexbit, HexbitBytes, Dibit, SoftDibit};
+use coding::{chase, cyclic, hamming, reed_solomon};
 use error::{P25Error, Result};
 use voice::frame::VoiceFrame;
 use voice::{control, crypto};
@@ -25,6 +25,7 @@ use consts::{
     CRYPTO_CONTROL_BYTES,
     EXTRA_HEXBITS,
     EXTRA_PIECE_DIBITS,
+    FRAME_DIBITS,
     LINK_CONTROL_BYTES,
 };
 
@@ -109,10 +110,11 @@ impl<E: Extra> FrameGroupReceiver<E> {
         if let Done = self.state { true } else { false }
     }
 
-    /// Determine what action to take based on the given symbol.
-    fn handle(&mut self, dibit: Dibit) -> StateChange<E> {
+    /// Determine what action to take based on the given symbol and its soft value, if
+    /// available.
+    fn handle(&mut self, dibit: Dibit, soft: Option<SoftDibit>) -> StateChange<E> {
         match self.state {
-            DecodeVoiceFrame(ref mut decoder) => match decoder.feed(dibit) {
+            DecodeVoiceFrame(ref mut decoder) => match decoder.feed(dibit, soft) {
                 Some(Ok(vf)) => {
                     self.frame += 1;
 
@@ -127,7 +129,7 @@ impl<E: Extra> FrameGroupReceiver<E> {
                 Some(Err(e)) => Error(e),
                 None => NoChange,
             },
-            DecodeExtra => match self.extra.feed(dibit) {
+            DecodeExtra => match self.extra.feed(dibit, soft) {
                 Some(Ok(extra)) => EventChange(FrameGroupEvent::Extra(extra),
                                                State::decode_voice_frame()),
                 Some(Err(err)) => Error(err),
@@ -151,7 +153,21 @@ impl<E: Extra> FrameGroupReceiver<E> {
     /// if a nominal event occurred, `Some(Err(err))` if an error occurred, and `None` in
     /// the case of no event.
     pub fn feed(&mut self, dibit: Dibit) -> Option<Result<FrameGroupEvent<E>>> {
-        match self.handle(dibit) {
+        self.feed_symbol(dibit, None)
+    }
+
+    /// Feed in a soft baseband symbol, using the reliability of each bit to correct more
+    /// errors in the voice frame and extra packet inner codes. The return value has the
+    /// same meaning as for `feed`.
+    pub fn feed_soft(&mut self, s: SoftDibit) -> Option<Result<FrameGroupEvent<E>>> {
+        self.feed_symbol(s.hard(), Some(s))
+    }
+
+    /// Feed in a baseband symbol and its soft value, if available.
+    fn feed_symbol(&mut self, dibit: Dibit, soft: Option<SoftDibit>)
+        -> Option<Result<FrameGroupEvent<E>>>
+    {
+        match self.handle(dibit, soft) {
             EventChange(event, next) => {
                 self.state = next;
                 Some(Ok(event))
@@ -171,8 +187,9 @@ pub trait Extra {
     /// Base decoder for the packet.
     type Fields;
 
-    /// Decode the inner Reed Soloman code.
-    fn decode_rs(buf: &mut [Hexbit; EXTRA_HEXBITS]) -> Option<(&[Hexbit], usize)>;
+    /// Decode the inner Reed Soloman code, with erasures at the given hexbit indexes.
+    fn decode_rs<'a>(buf: &'a mut [Hexbit; EXTRA_HEXBITS], erasures: &[usize])
+        -> Option<(&'a [Hexbit], usize)>;
     /// Transform the given hexbits into a base packet decoder.
     fn decode_extra(buf: &[Hexbit]) -> Self::Fields;
 }
@@ -183,8 +200,10 @@ pub struct LinkControlExtra;
 impl Extra for LinkControlExtra {
     type Fields = control::LinkControlFields;
 
-    fn decode_rs(buf: &mut [Hexbit; EXTRA_HEXBITS]) -> Option<(&[Hexbit], usize)> {
-        reed_solomon::short::decode(buf)
+    fn decode_rs<'a>(buf: &'a mut [Hexbit; EXTRA_HEXBITS], erasures: &[usize])
+        -> Option<(&'a [Hexbit], usize)>
+    {
+        reed_solomon::short::decode_erasures(buf, erasures)
     }
 
     fn decode_extra(buf: &[Hexbit]) -> Self::Fields {
@@ -201,8 +220,10 @@ pub struct CryptoControlExtra;
 impl Extra for CryptoControlExtra {
     type Fields = crypto::CryptoControlFields;
 
-    fn decode_rs(buf: &mut [Hexbit; EXTRA_HEXBITS]) -> Option<(&[Hexbit], usize)> {
-        reed_solomon::medium::decode(buf)
+    fn decode_rs<'a>(buf: &'a mut [Hexbit; EXTRA_HEXBITS], erasures: &[usize])
+        -> Option<(&'a [Hexbit], usize)>
+    {
+        reed_solomon::medium::decode_erasures(buf, erasures)
     }
 
     fn decode_extra(buf: &[Hexbit]) -> Self::Fields {
@@ -217,6 +238,10 @@ impl Extra for CryptoControlExtra {
 struct VoiceFrameReceiver {
     /// Current buffered dibits.
     dibits: Buffer<VoiceFrameStorage>,
+    /// Soft symbols received into the current frame.
+    soft: [SoftDibit; FRAME_DIBITS],
+    /// Number of soft symbols received into the current frame.
+    nsoft: usize,
 }
 
 impl VoiceFrameReceiver {
@@ -224,15 +249,30 @@ impl VoiceFrameReceiver {
     pub fn new() -> VoiceFrameReceiver {
         VoiceFrameReceiver {
             dibits: Buffer::new(VoiceFrameStorage::new()),
+            soft: [SoftDibit::default(); FRAME_DIBITS],
+            nsoft: 0,
         }
     }
 
-    /// Feed in a baseband symbol, possibly resulting in a decoded voice frame. Return
-    /// `Some(Ok(frame))` if a voice frame was successfully decoded, `Some(Err(err))` if
-    /// an error occurred, and `None` in the case of no event.
-    pub fn feed(&mut self, dibit: Dibit) -> Option<Result<VoiceFrame>> {
+    /// Feed in a baseband symbol and its soft value, if available, possibly resulting in
+    /// a decoded voice frame. Return `Some(Ok(frame))` if a voice frame was successfully
+    /// decoded, `Some(Err(err))` if an error occurred, and `None` in the case of no
+    /// event.
+    pub fn feed(&mut self, dibit: Dibit, soft: Option<SoftDibit>)
+        -> Option<Result<VoiceFrame>>
+    {
+        if let Some(s) = soft {
+            self.soft[self.nsoft] = s;
+            self.nsoft += 1;
+        }
+
         match self.dibits.feed(dibit) {
-            Some(buf) => Some(VoiceFrame::new(buf)),
+            // Use soft decoding if every symbol in the frame was soft.
+            Some(buf) => Some(if self.nsoft == FRAME_DIBITS {
+                VoiceFrame::new_soft(&self.soft)
+            } else {
+                VoiceFrame::new(buf)
+            }),
             None => None,
         }
     }
@@ -245,6 +285,10 @@ struct ExtraReceiver<E: Extra> {
     dibits: Buffer<VoiceExtraWordStorage>,
     /// Current buffered hexbits.
     hexbits: Buffer<VoiceExtraStorage>,
+    /// Hexbits in the current packet whose Hamming code couldn't be decoded.
+    erasures: reed_solomon::Erasures,
+    /// Bit reliabilities for the current hexbit, if received from soft symbols.
+    rel: chase::Reliabilities,
     /// Number of dibits that have been received into the packet.
     dibit: usize,
 }
@@ -256,6 +300,8 @@ impl<E: Extra> ExtraReceiver<E> {
             extra: std::marker::PhantomData,
             dibits: Buffer::new(VoiceExtraWordStorage::new()),
             hexbits: Buffer::new(VoiceExtraStorage::new()),
+            erasures: reed_solomon::Erasures::new(),
+            rel: chase::Reliabilities::new(),
             dibit: 0,
         }
     }
@@ -263,21 +309,43 @@ impl<E: Extra> ExtraReceiver<E> {
     /// Whether the current piece of the packet is finished decoding.
     pub fn piece_done(&self) -> bool { self.dibit % EXTRA_PIECE_DIBITS == 0 }
 
-    /// Feed in a baseband symbol, possibly producing a decoded packet. Return
-    /// `Some(Ok(pkt))` if the packet was successfully decoded, `Some(Err(err))` if an
-    /// error occurred, and `None` in the case of no event.
-    pub fn feed(&mut self, dibit: Dibit) -> Option<Result<E::Fields>> {
+    /// Feed in a baseband symbol and its soft value, if available, possibly producing a
+    /// decoded packet. Return `Some(Ok(pkt))` if the packet was successfully decoded,
+    /// `Some(Err(err))` if an error occurred, and `None` in the case of no event.
+    pub fn feed(&mut self, dibit: Dibit, soft: Option<SoftDibit>)
+        -> Option<Result<E::Fields>>
+    {
         self.dibit += 1;
 
+        if let Some(s) = soft {
+            self.rel.feed(s);
+        }
+
         let buf = match self.dibits.feed(dibit) {
             Some(buf) => *buf as u16,
             None => return None,
         };
 
-        let bits = match hamming::shortened::decode(buf) {
-            Some((data, err)) => data,
-            // Let the following RS code attempt to fix these errors.
-            None => 0,
+        let rel = self.rel;
+        self.rel = chase::Reliabilities::new();
+
+        // Use soft decoding if every symbol in the word was soft.
+        let decoded = if rel.bits().len() == 10 {
+            hamming::shortened::decode_soft(buf, rel.bits())
+        } else {
+            hamming::shortened::decode(buf)
+        };
+
+        let bits = match decoded {
+            Some((data, err)) => {
+                self.erasures.feed(false);
+                data
+            },
+            // Let the following RS code correct this hexbit as an erasure.
+            None => {
+                self.erasures.feed(true);
+                0
+            },
         };
 
         let hexbits = match self.hexbits.feed(Hexbit::new(bits)) {
@@ -285,7 +353,10 @@ impl<E: Extra> ExtraReceiver<E> {
             None => return None,
         };
 
-        let data = match E::decode_rs(hexbits) {
+        let erasures = self.erasures;
+        self.erasures = reed_solomon::Erasures::new();
+
+        let data = match E::decode_rs(hexbits, erasures.positions()) {
             Some((data, err)) => data,
             None => return Some(Err(ReedSolomonUnrecoverable)),
         };
@@ -341,3 +412,96 @@ impl DataFragmentReceiver {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::{Dibit, Hexbit, Hexbits};
+    use coding::{hamming, reed_solomon};
+    use consts::{CRYPTO_CONTROL_BYTES, EXTRA_HEXBITS, LINK_CONTROL_BYTES};
+
+    /// Feed the given RS-coded extra hexbits through an `ExtraReceiver`, corrupting the
+    /// Hamming words at the given indexes beyond their correction capacity.
+    fn receive<E: Extra>(hexbits: &[Hexbit; EXTRA_HEXBITS], erased: &[usize])
+        -> Option<Result<E::Fields>>
+    {
+        let mut recv = ExtraReceiver::<E>::new();
+        let mut out = None;
+
+        for (i, h) in hexbits.iter().enumerate() {
+            let mut word = hamming::shortened::encode(h.bits());
+
+            if erased.contains(&i) {
+                word ^= 0b101;
+                assert!(hamming::shortened::decode(word).is_none());
+            }
+
+            for shift in (0..5).rev() {
+                let dibit = Dibit::new((word >> (shift * 2)) as u8 & 0b11);
+
+                if let Some(r) = recv.feed(dibit, None) {
+                    out = Some(r);
+                }
+            }
+        }
+
+        out
+    }
+
+    /// Replace the hexbits at the given indexes with zero.
+    fn zeroed(hexbits: &[Hexbit; EXTRA_HEXBITS], erased: &[usize])
+        -> [Hexbit; EXTRA_HEXBITS]
+    {
+        let mut buf = *hexbits;
+
+        for &i in erased.iter() {
+            buf[i] = Hexbit::new(0);
+        }
+
+        buf
+    }
+
+    #[test]
+    fn test_lc_erasures() {
+        let bytes: [u8; LINK_CONTROL_BYTES] = [
+            0b00000000, 0x00, 0b10000000, 0x00, 0x12, 0x34, 0xDE, 0xAD, 0xBE,
+        ];
+
+        let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+        Hexbits::new(bytes.iter().cloned()).collect_slice_checked(&mut hexbits[..12]);
+        reed_solomon::short::encode(&mut hexbits);
+
+        // More than the 6 errors the RS code can correct but within its 12 erasures.
+        let erased = [0, 3, 5, 7, 10, 12, 15, 18, 20, 23];
+
+        assert!(reed_solomon::short::decode(&mut zeroed(&hexbits, &erased))
+            .map_or(true, |(data, _)| data != &hexbits[..12]));
+
+        match receive::<LinkControlExtra>(&hexbits, &erased) {
+            Some(Ok(lc)) => assert_eq!(lc.bytes(), &bytes),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_cc_erasures() {
+        let bytes: [u8; CRYPTO_CONTROL_BYTES] = [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 0x84, 0xDE, 0xAD,
+        ];
+
+        let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+        Hexbits::new(bytes.iter().cloned()).collect_slice_checked(&mut hexbits[..16]);
+        reed_solomon::medium::encode(&mut hexbits);
+
+        // More than the 4 errors the RS code can correct but within its 8 erasures.
+        let erased = [1, 4, 8, 11, 15, 19, 22];
+
+        assert!(reed_solomon::medium::decode(&mut zeroed(&hexbits, &erased))
+            .map_or(true, |(data, _)| data != &hexbits[..16]));
+
+        match receive::<CryptoControlExtra>(&hexbits, &erased) {
+            Some(Ok(cc)) => assert_eq!(cc.bytes(), &bytes),
+            _ => panic!(),
+        }
+    }
+}
@@ -2,9 +2,9 @@
 
 use collect_slice::CollectSlice;
 
-use bits::{Dibit, Hexbit, HexbitBytes};
+use bits::{Dibit, Hexbit, HexbitBytes, SoftDibit};
 use buffer::{Buffer, VoiceHeaderWordStorage, VoiceHeaderStorage};
-use coding::{reed_solomon, golay};
+use coding::{chase, reed_solomon, golay};
 use consts::HEADER_BYTES;
 use error::Result;
 use trunking::fields::TalkGroup;
@@ -19,6 +19,10 @@ pub struct VoiceHeaderReceiver {
     dibits: Buffer<VoiceHeaderWordStorage>,
     /// Current buffered hexbits.
     hexbits: Buffer<VoiceHeaderStorage>,
+    /// Hexbits in the current packet whose Golay code couldn't be decoded.
+    erasures: reed_solomon::Erasures,
+    /// Bit reliabilities for the current hexbit, if received from soft symbols.
+    rel: chase::Reliabilities,
 }
 
 impl VoiceHeaderReceiver {
@@ -27,6 +31,8 @@ impl VoiceHeaderReceiver {
         VoiceHeaderReceiver {
             dibits: Buffer::new(VoiceHeaderWordStorage::new()),
             hexbits: Buffer::new(VoiceHeaderStorage::new()),
+            erasures: reed_solomon::Erasures::new(),
+            rel: chase::Reliabilities::new(),
         }
     }
 
@@ -39,10 +45,26 @@ impl VoiceHeaderReceiver {
             None => return None,
         };
 
-        let data = match golay::shortened::decode(buf) {
-            Some((data, err)) => data,
-            // Let the following RS code attempt to fix these errors.
-            None => 0,
+        let rel = self.rel;
+        self.rel = chase::Reliabilities::new();
+
+        // Use soft decoding if every symbol in the word was soft.
+        let decoded = if rel.bits().len() == 18 {
+            golay::shortened::decode_soft(buf, rel.bits())
+        } else {
+            golay::shortened::decode(buf)
+        };
+
+        let data = match decoded {
+            Some((data, err)) => {
+                self.erasures.feed(false);
+                data
+            },
+            // Let the following RS code correct this hexbit as an erasure.
+            None => {
+                self.erasures.feed(true);
+                0
+            },
         };
 
         let hexbits = match self.hexbits.feed(Hexbit::new(data)) {
@@ -50,7 +72,11 @@ impl VoiceHeaderReceiver {
             None => return None,
         };
 
-        let data = match reed_solomon::long::decode(hexbits) {
+        let erasures = self.erasures;
+        self.erasures = reed_solomon::Erasures::new();
+
+        let data = match reed_solomon::long::decode_erasures(hexbits,
+                                                             erasures.positions()) {
             Some((data, err)) => data,
             None => return Some(Err(ReedSolomonUnrecoverable)),
         };
@@ -61,6 +87,13 @@ impl VoiceHeaderReceiver {
 
         Some(Ok(VoiceHeaderFields::new(bytes)))
     }
+
+    /// Feed in a soft baseband symbol, using the reliability of each bit to correct more
+    /// errors in the Golay code. The return value has the same meaning as for `feed`.
+    pub fn feed_soft(&mut self, s: SoftDibit) -> Option<Result<VoiceHeaderFields>> {
+        self.rel.feed(s);
+        self.feed(s.hard())
+    }
 }
 
 /// Buffer of bytes that represents a voice header packet.
@@ -87,11 +120,18 @@ impl VoiceHeaderFields {
     pub fn talk_group(&self) -> TalkGroup {
         TalkGroup::from_bits(slice_u16(&self.0[13..]))
     }
+
+    /// Raw bytes of the packet.
+    pub fn bytes(&self) -> &Buf { &self.0 }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use collect_slice::CollectSlice;
+    use bits::{Dibit, Hexbit, Hexbits};
+    use coding::{golay, reed_solomon};
+    use consts::{HEADER_BYTES, HEADER_HEXBITS};
     use voice::crypto::CryptoAlgorithm::*;
     use trunking::fields::TalkGroup;
 
@@ -113,4 +153,53 @@ mod test {
         assert_eq!(h.crypto_key(), 0);
         assert_eq!(h.talk_group(), TalkGroup::Everbody);
     }
+
+    #[test]
+    fn test_receive_erasures() {
+        let bytes: [u8; HEADER_BYTES] = [
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 0x90, 0x80, 0x12, 0x34, 0xAB, 0xCD,
+        ];
+
+        let mut hexbits = [Hexbit::default(); HEADER_HEXBITS];
+        Hexbits::new(bytes.iter().cloned()).collect_slice_checked(&mut hexbits[..20]);
+        reed_solomon::long::encode(&mut hexbits);
+
+        // Corrupt 12 Golay words beyond their correction capacity, which is more than
+        // the 8 hexbit errors the RS code can correct but within its 16 erasures.
+        let erased: Vec<usize> = (0..12).map(|i| i * 3).collect();
+        let mut dibits = vec![];
+
+        for (i, h) in hexbits.iter().enumerate() {
+            let mut word = golay::shortened::encode(h.bits());
+
+            if erased.contains(&i) {
+                word ^= 0b1111;
+                assert!(golay::shortened::decode(word).is_none());
+            }
+
+            for shift in (0..9).rev() {
+                dibits.push(Dibit::new((word >> (shift * 2)) as u8 & 0b11));
+            }
+        }
+
+        // Treating the uncorrectable words as zero hexbits leaves too many errors.
+        let mut zeroed = hexbits;
+
+        for &i in erased.iter() {
+            zeroed[i] = Hexbit::new(0);
+        }
+
+        assert!(reed_solomon::long::decode(&mut zeroed)
+            .map_or(true, |(data, _)| data != &hexbits[..20]));
+
+        let mut recv = VoiceHeaderReceiver::new();
+        let headers: Vec<VoiceHeaderFields> = dibits.iter()
+            .filter_map(|&d| recv.feed(d))
+            .map(|h| h.unwrap())
+            .collect();
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].bytes(), &bytes);
+        assert_eq!(headers[0].talk_group(), TalkGroup::Other(0xABCD));
+    }
 }
@@ -0,0 +1,377 @@
+//! Unpack IMBE vocoder parameters from the prioritized chunks of a voice frame, and pack
+//! parameters back into chunks.
+//!
+//! Each voice frame carries 88 bits of quantized model parameters, known as `b_0`, ...,
+//! `b_{L+1}` in the standard:
+//!
+//! - `b_0`: 8-bit fundamental frequency (pitch) index
+//! - `b_1`: `K` voiced/unvoiced decisions, one for each frequency band
+//! - `b_2`: 6-bit gain index
+//! - `b_3`, ..., `b_7`: remaining entries of the gain vector, whose inverse DCT gives the
+//!   average spectral amplitude of each of six blocks of harmonics
+//! - `b_8`, ..., `b_{L+1}`: higher-order DCT coefficients of each block in turn
+//!
+//! The amplitude indexes share the `74 - K` bits left after the voicing decisions. The
+//! pitch index checks and the derivation of `ω_0`, `L`, and `K` follow the standard, but
+//! the split of those bits between indexes and their scan into `u_0`, ..., `u_7` are
+//! provisional until the standard's per-`L` allocation tables are available. The most
+//! significant bits of the pitch and gain indexes are carried in `u_0`, and the voicing
+//! decisions followed by the amplitude bits are spread across the chunks by bit plane.
+
+use std;
+
+use error::Result;
+use voice::frame::VoiceFrame;
+
+use error::P25Error::*;
+
+/// Maximum number of frequency bands with a voicing decision.
+pub const MAX_BANDS: usize = 12;
+/// Maximum number of spectral amplitude indexes.
+pub const MAX_AMPLITUDES: usize = 55;
+
+/// Largest valid pitch index. Indexes above this are reserved.
+const MAX_PITCH: u8 = 207;
+/// Number of bits in the prioritized stream of voicing and spectral amplitude bits.
+const STREAM_BITS: usize = 74;
+
+/// Fundamental frequency and harmonic structure derived from the `b_0` pitch index.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BaseParams {
+    /// Fundamental frequency in radians per sample, known as `ω_0` in the standard.
+    pub fundamental: f32,
+    /// Number of harmonics in the spectrum, known as `L`.
+    pub harmonics: usize,
+    /// Number of frequency bands with a voicing decision, known as `K`.
+    pub bands: usize,
+}
+
+impl BaseParams {
+    /// Derive the base parameters from the given pitch index. Return `Ok(params)` if the
+    /// index is valid and `Err(InvalidPitch)` if it's reserved.
+    pub fn new(pitch: u8) -> Result<BaseParams> {
+        if pitch > MAX_PITCH {
+            return Err(InvalidPitch);
+        }
+
+        let fundamental = 4.0 * std::f32::consts::PI / (pitch as f32 + 39.5);
+        let harmonics = (0.9254 * (std::f32::consts::PI / fundamental + 0.25).floor())
+            .floor() as usize;
+
+        let bands = if harmonics <= 36 {
+            (harmonics + 2) / 3
+        } else {
+            MAX_BANDS
+        };
+
+        Ok(BaseParams {
+            fundamental: fundamental,
+            harmonics: harmonics,
+            bands: bands,
+        })
+    }
+
+    /// Number of spectral amplitude indexes, `b_3`, ..., `b_{L+1}`.
+    pub fn amplitudes(&self) -> usize { self.harmonics - 1 }
+
+    /// Calculate the number of bits allocated to each spectral amplitude index.
+    ///
+    /// This is a provisional allocation, not the standard's table: the `74 - K`
+    /// available bits are dealt out one at a time to each index in order, starting again
+    /// at `b_3` until no bits remain, so lower-order indexes receive any extra bits.
+    pub fn amplitude_bits(&self) -> [u8; MAX_AMPLITUDES] {
+        let mut alloc = [0; MAX_AMPLITUDES];
+        let count = self.amplitudes();
+
+        for n in 0..STREAM_BITS - self.bands {
+            alloc[n % count] += 1;
+        }
+
+        alloc
+    }
+}
+
+/// Quantized IMBE model parameters carried by a voice frame.
+#[derive(Copy, Clone)]
+pub struct ImbeParams {
+    /// Pitch index, `b_0`.
+    pub pitch: u8,
+    /// Voicing decision for each frequency band, `b_1`. Only the first `K` entries are
+    /// used.
+    pub voiced: [bool; MAX_BANDS],
+    /// Gain index, `b_2`.
+    pub gain: u8,
+    /// Spectral amplitude indexes, `b_3`, ..., `b_{L+1}`: the gain vector followed by the
+    /// higher-order coefficients of each block. Only the first `L - 1` entries are used.
+    pub amplitudes: [u16; MAX_AMPLITUDES],
+}
+
+impl ImbeParams {
+    /// Unpack the parameters carried by the given voice frame. Return `Ok(params)` if the
+    /// parameters were successfully unpacked and `Err(err)` otherwise.
+    pub fn new(frame: &VoiceFrame) -> Result<ImbeParams> {
+        ImbeParams::from_chunks(&frame.chunks)
+    }
+
+    /// Unpack the parameters from the given prioritized chunks `u_0`, ..., `u_7`.
+    pub fn from_chunks(chunks: &[u32; 8]) -> Result<ImbeParams> {
+        let pitch = (chunks[0] >> 6 << 2 | chunks[7] >> 5) as u8;
+        let gain = ((chunks[0] >> 3 & 0b111) << 3 | chunks[7] >> 2 & 0b111) as u8;
+
+        let base = match BaseParams::new(pitch) {
+            Ok(b) => b,
+            Err(e) => return Err(e),
+        };
+
+        let mut stream = BitReader::new(chunks);
+
+        let mut voiced = [false; MAX_BANDS];
+
+        for v in voiced.iter_mut().take(base.bands) {
+            *v = stream.next() == 1;
+        }
+
+        let mut amplitudes = [0; MAX_AMPLITUDES];
+        let alloc = base.amplitude_bits();
+
+        for plane in (0..alloc[0]).rev() {
+            for (a, &bits) in amplitudes.iter_mut().zip(alloc.iter()) {
+                if bits > plane {
+                    *a |= (stream.next() as u16) << plane;
+                }
+            }
+        }
+
+        Ok(ImbeParams {
+            pitch: pitch,
+            voiced: voiced,
+            gain: gain,
+            amplitudes: amplitudes,
+        })
+    }
+
+    /// Base parameters derived from the pitch index.
+    pub fn base(&self) -> Result<BaseParams> { BaseParams::new(self.pitch) }
+
+    /// Pack the parameters into prioritized chunks `u_0`, ..., `u_7`, suitable for
+    /// `VoiceFrame::encode`.
+    ///
+    /// Panics if the pitch index is reserved or a parameter doesn't fit in its allocated
+    /// bits.
+    pub fn to_chunks(&self) -> [u32; 8] {
+        let base = self.base().expect("reserved pitch index");
+
+        assert!(self.gain >> 6 == 0);
+
+        let mut chunks = [0; 8];
+
+        chunks[0] = (self.pitch as u32 >> 2) << 6 | (self.gain as u32 >> 3) << 3;
+        chunks[7] = (self.pitch as u32 & 0b11) << 5 | (self.gain as u32 & 0b111) << 2;
+
+        {
+            let mut stream = BitWriter::new(&mut chunks);
+
+            for &v in self.voiced.iter().take(base.bands) {
+                stream.push(v as u32);
+            }
+
+            let alloc = base.amplitude_bits();
+
+            for (&a, &bits) in self.amplitudes.iter().zip(alloc.iter()) {
+                assert!(a as u32 >> bits == 0);
+            }
+
+            for plane in (0..alloc[0]).rev() {
+                for (&a, &bits) in self.amplitudes.iter().zip(alloc.iter()) {
+                    if bits > plane {
+                        stream.push((a >> plane & 1) as u32);
+                    }
+                }
+            }
+
+            assert!(stream.pos == STREAM_BITS);
+        }
+
+        chunks
+    }
+}
+
+/// Position of the given bit in the prioritized stream, as a chunk index and bit shift
+/// within the chunk.
+fn stream_pos(bit: usize) -> (usize, usize) {
+    assert!(bit < STREAM_BITS);
+
+    match bit {
+        // Low 3 bits of u_0.
+        0...2 => (0, 2 - bit),
+        // All 12 bits of u_1, ..., u_3.
+        3...38 => (1 + (bit - 3) / 12, 11 - (bit - 3) % 12),
+        // All 11 bits of u_4, ..., u_6.
+        39...71 => (4 + (bit - 39) / 11, 10 - (bit - 39) % 11),
+        // Low 2 bits of u_7.
+        _ => (7, 73 - bit),
+    }
+}
+
+/// Reads the prioritized bit stream out of a set of chunks.
+struct BitReader<'a> {
+    /// Source chunks.
+    chunks: &'a [u32; 8],
+    /// Current position in the stream.
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    /// Create a new `BitReader` at the start of the stream in the given chunks.
+    pub fn new(chunks: &'a [u32; 8]) -> Self {
+        BitReader {
+            chunks: chunks,
+            pos: 0,
+        }
+    }
+
+    /// Read the next bit in the stream.
+    pub fn next(&mut self) -> u32 {
+        let (idx, shift) = stream_pos(self.pos);
+        self.pos += 1;
+
+        self.chunks[idx] >> shift & 1
+    }
+}
+
+/// Writes the prioritized bit stream into a set of chunks.
+struct BitWriter<'a> {
+    /// Destination chunks.
+    chunks: &'a mut [u32; 8],
+    /// Current position in the stream.
+    pos: usize,
+}
+
+impl<'a> BitWriter<'a> {
+    /// Create a new `BitWriter` at the start of the stream in the given chunks.
+    pub fn new(chunks: &'a mut [u32; 8]) -> Self {
+        BitWriter {
+            chunks: chunks,
+            pos: 0,
+        }
+    }
+
+    /// Write the given bit as the next bit in the stream.
+    pub fn push(&mut self, bit: u32) {
+        let (idx, shift) = stream_pos(self.pos);
+        self.pos += 1;
+
+        self.chunks[idx] |= bit << shift;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::P25Error;
+    use voice::frame::VoiceFrame;
+
+    #[test]
+    fn test_base_params() {
+        let b = BaseParams::new(0).unwrap();
+        assert_eq!(b.harmonics, 9);
+        assert_eq!(b.bands, 3);
+        assert_eq!(b.amplitudes(), 8);
+
+        let b = BaseParams::new(207).unwrap();
+        assert_eq!(b.harmonics, 56);
+        assert_eq!(b.bands, 12);
+        assert_eq!(b.amplitudes(), 55);
+
+        for pitch in 0...207 {
+            let b = BaseParams::new(pitch).unwrap();
+
+            assert!(b.harmonics >= 9 && b.harmonics <= 56);
+            assert!(b.bands >= 3 && b.bands <= 12);
+
+            let alloc = b.amplitude_bits();
+            let total = alloc.iter().fold(0, |s, &b| s + b as usize);
+
+            assert_eq!(total + b.bands, STREAM_BITS);
+            assert!(alloc[..b.amplitudes()].iter().all(|&b| b > 0));
+            assert!(alloc[b.amplitudes()..].iter().all(|&b| b == 0));
+        }
+
+        assert_eq!(BaseParams::new(208), Err(P25Error::InvalidPitch));
+        assert_eq!(BaseParams::new(255), Err(P25Error::InvalidPitch));
+    }
+
+    #[test]
+    fn test_stream_pos() {
+        let mut seen = [0u32; 8];
+
+        for bit in 0..STREAM_BITS {
+            let (idx, shift) = stream_pos(bit);
+            assert_eq!(seen[idx] >> shift & 1, 0);
+            seen[idx] |= 1 << shift;
+        }
+
+        assert_eq!(seen, [0b111, 0xFFF, 0xFFF, 0xFFF, 0x7FF, 0x7FF, 0x7FF, 0b11]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for &pitch in [0, 1, 50, 100, 150, 206, 207].iter() {
+            let base = BaseParams::new(pitch).unwrap();
+            let alloc = base.amplitude_bits();
+
+            let mut p = ImbeParams {
+                pitch: pitch,
+                voiced: [false; MAX_BANDS],
+                gain: 0b101101,
+                amplitudes: [0; MAX_AMPLITUDES],
+            };
+
+            for (i, v) in p.voiced.iter_mut().take(base.bands).enumerate() {
+                *v = i % 3 != 1;
+            }
+
+            for (i, a) in p.amplitudes.iter_mut().take(base.amplitudes()).enumerate() {
+                *a = (0xA5A5 ^ i as u16 * 7) & ((1 << alloc[i]) - 1);
+            }
+
+            let chunks = p.to_chunks();
+
+            assert!(chunks[0] >> 12 == 0);
+            assert!(chunks[4] >> 11 == 0);
+            assert!(chunks[7] >> 7 == 0);
+
+            let f = VoiceFrame::new(&VoiceFrame::encode(&chunks)).unwrap();
+            let q = ImbeParams::new(&f).unwrap();
+
+            assert_eq!(q.pitch, p.pitch);
+            assert_eq!(q.gain, p.gain);
+            assert_eq!(q.voiced, p.voiced);
+            assert_eq!(&q.amplitudes[..], &p.amplitudes[..]);
+        }
+    }
+
+    #[test]
+    fn test_invalid_pitch() {
+        // Pitch index 0b11010000 = 208.
+        let chunks = [0b110100 << 6, 0, 0, 0, 0, 0, 0, 0];
+        assert_eq!(ImbeParams::from_chunks(&chunks).err(), Some(P25Error::InvalidPitch));
+    }
+
+    #[test]
+    fn test_priority() {
+        let p = ImbeParams {
+            pitch: 0b11001011,
+            voiced: [true; MAX_BANDS],
+            gain: 0b110011,
+            amplitudes: [0; MAX_AMPLITUDES],
+        };
+
+        let chunks = p.to_chunks();
+
+        // Pitch and gain MSBs followed by the first voicing decisions.
+        assert_eq!(chunks[0], 0b110010_110_111);
+        // Pitch and gain LSBs.
+        assert_eq!(chunks[7] >> 2, 0b11_011);
+    }
+}
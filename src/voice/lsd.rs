@@ -0,0 +1,202 @@
+//! Reassemble the low-speed data channel carried in voice frame groups.
+//!
+//! Each 32-bit low-speed data word is split across a pair of frame groups: the LC frame
+//! group (LDU1) carries the first 2 bytes and the following CC frame group (LDU2) carries
+//! the last 2 bytes. Fragments are paired back up into a continuous byte stream, which
+//! can be consumed through `std::io::Read`.
+
+use std;
+use std::collections::VecDeque;
+use std::io;
+
+use message::nid::DataUnit;
+use message::receiver::MessageEvent;
+
+/// Maximum number of bytes held before the oldest are discarded.
+pub const MAX_BUFFERED: usize = 4096;
+
+/// Reassembles low-speed data fragments into a byte stream.
+pub struct LowSpeedDataReceiver {
+    /// Reassembled bytes that haven't been read yet.
+    buf: VecDeque<u8>,
+    /// Data unit of the current frame group, if in one.
+    unit: Option<DataUnit>,
+    /// First half of the current word, received in an LC frame group.
+    first: Option<u16>,
+    /// Number of words dropped due to a missing half.
+    dropped: u32,
+}
+
+impl LowSpeedDataReceiver {
+    /// Create a new `LowSpeedDataReceiver` with an empty stream.
+    pub fn new() -> LowSpeedDataReceiver {
+        LowSpeedDataReceiver {
+            buf: VecDeque::new(),
+            unit: None,
+            first: None,
+            dropped: 0,
+        }
+    }
+
+    /// Number of reassembled bytes available to read.
+    pub fn available(&self) -> usize { self.buf.len() }
+
+    /// Number of words that couldn't be reassembled because one of their halves was
+    /// missed.
+    pub fn dropped(&self) -> u32 { self.dropped }
+
+    /// Handle the given receiver event.
+    pub fn feed(&mut self, event: &MessageEvent) {
+        match *event {
+            MessageEvent::PacketNID(nid) => match nid.data_unit {
+                DataUnit::VoiceLCFrameGroup | DataUnit::VoiceCCFrameGroup =>
+                    self.unit = Some(nid.data_unit),
+                _ => self.reset(),
+            },
+            MessageEvent::LowSpeedDataFragment(frag) => match self.unit {
+                Some(DataUnit::VoiceLCFrameGroup) => self.first(frag as u16),
+                Some(DataUnit::VoiceCCFrameGroup) => self.second(frag as u16),
+                _ => {},
+            },
+            MessageEvent::VoiceHeader(_) => self.reset(),
+            _ => {},
+        }
+    }
+
+    /// Record the first half of a word, from an LC frame group.
+    pub fn first(&mut self, frag: u16) {
+        if self.first.is_some() {
+            self.dropped += 1;
+        }
+
+        self.first = Some(frag);
+    }
+
+    /// Record the second half of a word, from a CC frame group, appending the word to
+    /// the stream if its first half was received.
+    pub fn second(&mut self, frag: u16) {
+        let first = match self.first.take() {
+            Some(first) => first,
+            None => {
+                self.dropped += 1;
+                return;
+            },
+        };
+
+        self.push(first);
+        self.push(frag);
+    }
+
+    /// Discard any partially received word, such as at the end of a call.
+    pub fn reset(&mut self) {
+        if self.first.take().is_some() {
+            self.dropped += 1;
+        }
+
+        self.unit = None;
+    }
+
+    /// Append the bytes of the given fragment to the stream.
+    fn push(&mut self, frag: u16) {
+        while self.buf.len() + 2 > MAX_BUFFERED {
+            self.buf.pop_front();
+        }
+
+        self.buf.push_back((frag >> 8) as u8);
+        self.buf.push_back(frag as u8);
+    }
+}
+
+impl io::Read for LowSpeedDataReceiver {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = std::cmp::min(buf.len(), self.buf.len());
+
+        for (dest, src) in buf.iter_mut().zip(self.buf.drain(..n)) {
+            *dest = src;
+        }
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Read;
+    use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+    use message::receiver::MessageEvent;
+
+    fn nid(du: DataUnit) -> MessageEvent {
+        MessageEvent::PacketNID(NetworkId::new(NetworkAccessCode::Default, du))
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let mut r = LowSpeedDataReceiver::new();
+
+        r.feed(&nid(DataUnit::VoiceLCFrameGroup));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x2447));
+        assert_eq!(r.available(), 0);
+        r.feed(&nid(DataUnit::VoiceCCFrameGroup));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x5047));
+        assert_eq!(r.available(), 4);
+        r.feed(&nid(DataUnit::VoiceLCFrameGroup));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x4741));
+        r.feed(&nid(DataUnit::VoiceCCFrameGroup));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x2C31));
+
+        let mut buf = [0; 5];
+        assert_eq!(r.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"$GPGG");
+        assert_eq!(r.available(), 3);
+
+        let mut s = String::new();
+        r.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "A,1");
+        assert_eq!(r.read(&mut buf).unwrap(), 0);
+        assert_eq!(r.dropped(), 0);
+    }
+
+    #[test]
+    fn test_missing() {
+        let mut r = LowSpeedDataReceiver::new();
+
+        // Second half without a first half.
+        r.feed(&nid(DataUnit::VoiceCCFrameGroup));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x1234));
+        assert_eq!(r.dropped(), 1);
+
+        // First half interrupted by a terminator.
+        r.feed(&nid(DataUnit::VoiceLCFrameGroup));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x1234));
+        r.feed(&nid(DataUnit::VoiceSimpleTerminator));
+        r.feed(&MessageEvent::LowSpeedDataFragment(0x5678));
+        assert_eq!(r.dropped(), 2);
+
+        // Two first halves in a row.
+        r.first(0x1111);
+        r.first(0xAABB);
+        r.second(0xCCDD);
+        assert_eq!(r.dropped(), 3);
+
+        let mut buf = vec![];
+        r.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, vec![0xAA, 0xBB, 0xCC, 0xDD]);
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut r = LowSpeedDataReceiver::new();
+
+        for i in 0..MAX_BUFFERED / 4 + 1 {
+            r.first(i as u16);
+            r.second(0);
+        }
+
+        assert_eq!(r.available(), MAX_BUFFERED);
+
+        let mut buf = [0; 2];
+        r.read(&mut buf).unwrap();
+        assert_eq!(buf, [0, 1]);
+    }
+}
@@ -1,10 +1,16 @@
-//! Receive and decode voice-related data units.
+//! Receive, decode, and transmit voice-related data units.
 
+pub mod call;
 pub mod control;
 pub mod crypto;
 pub mod descramble;
 pub mod frame;
 pub mod frame_group;
 pub mod header;
+pub mod imbe;
+pub mod lsd;
+pub mod quality;
 pub mod rand;
+pub mod synth;
 pub mod term;
+pub mod transmit;
@@ -0,0 +1,323 @@
+//! Track voice quality from the FEC errors corrected in received voice frames.
+//!
+//! Each frame is classified as erased if its `u_0` chunk, which carries the pitch and
+//! gain, had too many corrections or if the frame had too many corrections overall. Bit
+//! error rate is estimated as the ratio of corrected bits to the FEC-protected bits in
+//! each frame.
+//!
+//! An unrecoverable error within a frame group causes the receiver to drop the rest of
+//! that group, so every frame remaining in the group is counted as lost.
+
+use message::nid::DataUnit;
+use message::receiver::MessageEvent;
+use voice::frame::VoiceFrame;
+
+/// Threshold on errors in `u_0` at or above which a frame is erased.
+pub const MAX_ERRORS_U0: usize = 2;
+/// Threshold on total errors above which a frame is erased.
+pub const MAX_ERRORS_TOTAL: usize = 10;
+/// Number of FEC-protected bits in each voice frame: 4 (23, 12) Golay words and 3 (15,
+/// 11) Hamming words.
+pub const PROTECTED_BITS: usize = 4 * 23 + 3 * 15;
+/// Number of voice frames in each frame group.
+const GROUP_FRAMES: u32 = 9;
+
+/// Check whether the given frame should be erased (repeated or muted) rather than
+/// synthesized.
+pub fn erased(frame: &VoiceFrame) -> bool {
+    frame.errors[0] >= MAX_ERRORS_U0 || total_errors(frame) > MAX_ERRORS_TOTAL
+}
+
+/// Total number of errors corrected in the given frame.
+pub fn total_errors(frame: &VoiceFrame) -> usize {
+    frame.errors.iter().fold(0, |s, &e| s + e)
+}
+
+/// Running voice quality statistics.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct QualityStats {
+    /// Number of frames received, including those lost to unrecoverable errors.
+    pub frames: u32,
+    /// Number of frames that were decoded but erased due to errors.
+    pub erased: u32,
+    /// Number of frames that couldn't be decoded at all.
+    pub lost: u32,
+    /// Total number of bits corrected in decoded frames.
+    pub bit_errors: u32,
+    /// Largest number of bits corrected in a single frame.
+    pub max_errors: u32,
+}
+
+impl QualityStats {
+    /// Number of frames that were successfully decoded.
+    pub fn decoded(&self) -> u32 { self.frames - self.lost }
+
+    /// Estimated bit error rate over decoded frames.
+    pub fn ber(&self) -> f32 {
+        match self.decoded() {
+            0 => 0.0,
+            n => self.bit_errors as f32 / (n as usize * PROTECTED_BITS) as f32,
+        }
+    }
+
+    /// Fraction of frames that were erased or lost.
+    pub fn erasure_rate(&self) -> f32 {
+        match self.frames {
+            0 => 0.0,
+            n => (self.erased + self.lost) as f32 / n as f32,
+        }
+    }
+
+    /// Record the given decoded frame.
+    fn record(&mut self, frame: &VoiceFrame) {
+        let errors = total_errors(frame) as u32;
+
+        self.frames += 1;
+        self.bit_errors += errors;
+
+        if errors > self.max_errors {
+            self.max_errors = errors;
+        }
+
+        if erased(frame) {
+            self.erased += 1;
+        }
+    }
+
+    /// Record the given number of frames that couldn't be decoded.
+    fn record_lost(&mut self, count: u32) {
+        self.frames += count;
+        self.lost += count;
+    }
+}
+
+/// Tracks voice quality statistics per call and across all calls.
+///
+/// A call begins with a voice header or the first frame group after a terminator, and
+/// ends with a voice terminator.
+pub struct QualityTracker {
+    /// Statistics for the current call.
+    call: QualityStats,
+    /// Statistics across all calls.
+    total: QualityStats,
+    /// Whether a call is currently in progress.
+    active: bool,
+    /// Number of frames received in the current frame group, if one is in progress.
+    group: Option<u32>,
+}
+
+impl QualityTracker {
+    /// Create a new `QualityTracker` with no recorded frames.
+    pub fn new() -> QualityTracker {
+        QualityTracker {
+            call: QualityStats::default(),
+            total: QualityStats::default(),
+            active: false,
+            group: None,
+        }
+    }
+
+    /// Statistics for the current, or most recently finished, call.
+    pub fn call(&self) -> &QualityStats { &self.call }
+
+    /// Statistics across all calls.
+    pub fn total(&self) -> &QualityStats { &self.total }
+
+    /// Handle the given receiver event. Return `Some(stats)` with the final statistics of
+    /// a call if the event ended it, and `None` otherwise.
+    pub fn feed(&mut self, event: &MessageEvent) -> Option<QualityStats> {
+        match *event {
+            MessageEvent::VoiceHeader(_) => {
+                self.start_call();
+                None
+            },
+            MessageEvent::PacketNID(nid) => {
+                self.interrupt_group();
+
+                match nid.data_unit {
+                    DataUnit::VoiceLCFrameGroup | DataUnit::VoiceCCFrameGroup => {
+                        if !self.active {
+                            self.start_call();
+                        }
+
+                        self.group = Some(0);
+
+                        None
+                    },
+                    DataUnit::VoiceSimpleTerminator | DataUnit::VoiceLCTerminator =>
+                        self.end_call(),
+                    _ => None,
+                }
+            },
+            MessageEvent::VoiceFrame(ref vf) => {
+                self.record(vf);
+
+                self.group = match self.group {
+                    Some(n) if n + 1 < GROUP_FRAMES => Some(n + 1),
+                    _ => None,
+                };
+
+                None
+            },
+            MessageEvent::Error(_) => {
+                self.interrupt_group();
+                None
+            },
+            _ => None,
+        }
+    }
+
+    /// Record the frames remaining in the current frame group, if any, as lost.
+    fn interrupt_group(&mut self) {
+        if let Some(n) = self.group.take() {
+            self.record_lost(GROUP_FRAMES - n);
+        }
+    }
+
+    /// Record the given decoded frame in the current call.
+    pub fn record(&mut self, frame: &VoiceFrame) {
+        if !self.active {
+            self.start_call();
+        }
+
+        self.call.record(frame);
+        self.total.record(frame);
+    }
+
+    /// Record the given number of frames lost to unrecoverable errors in the current
+    /// call.
+    pub fn record_lost(&mut self, count: u32) {
+        if !self.active {
+            self.start_call();
+        }
+
+        self.call.record_lost(count);
+        self.total.record_lost(count);
+    }
+
+    /// End the current call, if any, returning its final statistics.
+    pub fn end_call(&mut self) -> Option<QualityStats> {
+        if !self.active {
+            return None;
+        }
+
+        self.active = false;
+        self.group = None;
+
+        Some(self.call)
+    }
+
+    /// Start a new call, discarding the statistics of any previous call.
+    fn start_call(&mut self) {
+        self.call = QualityStats::default();
+        self.active = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use error::P25Error;
+    use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+    use message::receiver::MessageEvent;
+    use voice::frame::VoiceFrame;
+    use voice::header::VoiceHeaderFields;
+
+    fn frame(errors: [usize; 7]) -> VoiceFrame {
+        VoiceFrame {
+            chunks: [0; 8],
+            errors: errors,
+        }
+    }
+
+    fn nid(du: DataUnit) -> MessageEvent {
+        MessageEvent::PacketNID(NetworkId::new(NetworkAccessCode::Default, du))
+    }
+
+    #[test]
+    fn test_erased() {
+        assert!(!erased(&frame([0; 7])));
+        assert!(!erased(&frame([1, 3, 3, 1, 1, 1, 0])));
+        assert!(erased(&frame([2, 0, 0, 0, 0, 0, 0])));
+        assert!(erased(&frame([1, 3, 3, 3, 1, 0, 0])));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut s = QualityStats::default();
+
+        assert_eq!(s.ber(), 0.0);
+        assert_eq!(s.erasure_rate(), 0.0);
+
+        s.record(&frame([0; 7]));
+        s.record(&frame([1, 1, 0, 0, 0, 0, 0]));
+        s.record(&frame([3, 0, 0, 0, 0, 0, 0]));
+        s.record_lost(1);
+
+        assert_eq!(s.frames, 4);
+        assert_eq!(s.decoded(), 3);
+        assert_eq!(s.erased, 1);
+        assert_eq!(s.lost, 1);
+        assert_eq!(s.bit_errors, 5);
+        assert_eq!(s.max_errors, 3);
+        assert_eq!(s.ber(), 5.0 / (3.0 * 137.0));
+        assert_eq!(s.erasure_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut t = QualityTracker::new();
+
+        assert!(t.feed(&MessageEvent::VoiceHeader(VoiceHeaderFields::new([0; 15])))
+            .is_none());
+        assert!(t.feed(&nid(DataUnit::VoiceLCFrameGroup)).is_none());
+        assert!(t.feed(&MessageEvent::VoiceFrame(frame([1, 0, 0, 0, 0, 0, 0])))
+            .is_none());
+        assert!(t.feed(&MessageEvent::VoiceFrame(frame([2, 0, 0, 0, 0, 0, 0])))
+            .is_none());
+        // The error drops the 7 frames remaining in the frame group.
+        assert!(t.feed(&MessageEvent::Error(P25Error::GolayUnrecoverable)).is_none());
+        assert_eq!(t.call().frames, 9);
+        assert_eq!(t.call().lost, 7);
+
+        let s = t.feed(&nid(DataUnit::VoiceSimpleTerminator)).unwrap();
+        assert_eq!(s.frames, 9);
+        assert_eq!(s.erased, 1);
+        assert_eq!(s.lost, 7);
+        assert_eq!(s.bit_errors, 3);
+
+        // Errors outside a call aren't counted.
+        assert!(t.feed(&MessageEvent::Error(P25Error::GolayUnrecoverable)).is_none());
+        assert!(t.feed(&nid(DataUnit::VoiceLCTerminator)).is_none());
+
+        // A call can start without a header.
+        t.feed(&nid(DataUnit::VoiceCCFrameGroup));
+        assert_eq!(t.call().frames, 0);
+
+        for _ in 0..9 {
+            t.feed(&MessageEvent::VoiceFrame(frame([0; 7])));
+        }
+
+        // Errors after a complete frame group lose no frames.
+        t.feed(&MessageEvent::Error(P25Error::ReedSolomonUnrecoverable));
+        assert_eq!(t.call().frames, 9);
+
+        // An error in the last frame loses only that frame.
+        t.feed(&nid(DataUnit::VoiceLCFrameGroup));
+
+        for _ in 0..8 {
+            t.feed(&MessageEvent::VoiceFrame(frame([0; 7])));
+        }
+
+        t.feed(&MessageEvent::Error(P25Error::HammingUnrecoverable));
+
+        let s = t.feed(&nid(DataUnit::VoiceLCTerminator)).unwrap();
+        assert_eq!(s.frames, 18);
+        assert_eq!(s.lost, 1);
+        assert_eq!(s.erased, 0);
+
+        assert_eq!(t.total().frames, 27);
+        assert_eq!(t.total().erased, 1);
+        assert_eq!(t.total().lost, 8);
+    }
+}
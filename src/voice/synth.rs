@@ -0,0 +1,647 @@
+//! Synthesize PCM speech audio from IMBE voice frames.
+//!
+//! Each voice frame produces 20ms of 8kHz audio. The harmonics are divided into six
+//! blocks, and the spectral envelope is reconstructed from the inverse DCT of the gain
+//! vector, which gives the average of each block, and the inverse DCT of each block's
+//! higher-order coefficients, which gives its shape, plus the envelope predicted from the
+//! previous frame. Each harmonic of the fundamental is then synthesized either as a
+//! phase-continuous sinusoid (voiced) or as white noise shaped to the harmonic's band in
+//! the frequency domain (unvoiced). Components are crossfaded between frames so parameter
+//! changes don't produce discontinuities.
+//!
+//! Frames with too many FEC errors or reserved parameters are concealed by repeating the
+//! previous frame's parameters, and the output is muted after several consecutive bad
+//! frames.
+//!
+//! The gain and coefficient quantizer levels are uniform stand-ins for the standard's
+//! tables, matching the provisional bit layout in `imbe`.
+
+use std;
+
+use voice::frame::VoiceFrame;
+use voice::imbe::{BaseParams, ImbeParams};
+use voice::quality;
+
+/// Number of PCM samples produced for each voice frame.
+pub const FRAME_SAMPLES: usize = 160;
+/// Maximum number of harmonics in the spectrum.
+pub const MAX_HARMONICS: usize = 56;
+
+/// Number of consecutive concealed frames before the output is muted.
+const MAX_REPEATS: u32 = 3;
+/// Log2 amplitude of the lowest gain index, in place of the standard's gain table.
+const GAIN_MIN: f32 = 0.0;
+/// Log2 amplitude step between gain indexes.
+const GAIN_STEP: f32 = 0.2;
+/// Span of the uniform quantizer for each gain vector and higher-order DCT coefficient,
+/// in place of the standard's step sizes.
+const COEF_RANGE: f32 = 1.0;
+/// Weight of the previous frame's envelope when predicting the current envelope, known
+/// as `ρ` in the standard.
+const PREDICTION: f32 = 0.65;
+/// Number of blocks the harmonics are divided into.
+const BLOCKS: usize = 6;
+/// Number of samples in each segment of shaped noise, which is faded in over one frame
+/// and out over the next.
+const NOISE_SAMPLES: usize = 2 * FRAME_SAMPLES;
+
+/// Reconstructed parameters of a single frame.
+#[derive(Copy, Clone)]
+struct FrameModel {
+    /// Fundamental frequency in radians per sample.
+    fundamental: f32,
+    /// Number of harmonics.
+    harmonics: usize,
+    /// Whether each harmonic is voiced.
+    voiced: [bool; MAX_HARMONICS],
+    /// Log2 spectral amplitude of each harmonic.
+    log_amps: [f32; MAX_HARMONICS],
+}
+
+impl FrameModel {
+    /// Model with no spectral energy, used before the first frame.
+    pub fn silent() -> FrameModel {
+        FrameModel {
+            fundamental: 4.0 * std::f32::consts::PI / 39.5,
+            harmonics: 0,
+            voiced: [false; MAX_HARMONICS],
+            log_amps: [0.0; MAX_HARMONICS],
+        }
+    }
+
+    /// Reconstruct a model from the given parameters, predicting the spectral envelope
+    /// from the given previous model.
+    pub fn new(params: &ImbeParams, base: &BaseParams, prev: &FrameModel) -> FrameModel {
+        let mut voiced = [false; MAX_HARMONICS];
+
+        for (l, v) in voiced.iter_mut().take(base.harmonics).enumerate() {
+            *v = params.voiced[std::cmp::min(l / 3, base.bands - 1)];
+        }
+
+        let alloc = base.amplitude_bits();
+
+        // The first entry of the gain vector is the gain, `b_2`, and the rest are carried
+        // by `b_3`, ..., `b_7`.
+        let mut gains = [0.0; BLOCKS];
+        gains[0] = GAIN_MIN + params.gain as f32 * GAIN_STEP;
+
+        for m in 1..BLOCKS {
+            gains[m] = dequantize(params.amplitudes[m - 1], alloc[m - 1]);
+        }
+
+        let mut averages = [0.0; BLOCKS];
+        idct(&gains, &mut averages);
+
+        // The higher-order coefficients of each block are carried in order by `b_8`,
+        // ..., `b_{L+1}`.
+        let mut residuals = [0.0; MAX_HARMONICS];
+        let mut idx = BLOCKS - 1;
+        let mut start = 0;
+
+        for (&avg, &len) in averages.iter().zip(block_lengths(base.harmonics).iter()) {
+            let mut coefs = [0.0; MAX_HARMONICS];
+            coefs[0] = avg;
+
+            for c in coefs[1..len].iter_mut() {
+                *c = dequantize(params.amplitudes[idx], alloc[idx]);
+                idx += 1;
+            }
+
+            idct(&coefs[..len], &mut residuals[start..start + len]);
+            start += len;
+        }
+
+        let mut predicted = [0.0; MAX_HARMONICS];
+        let scale = prev.fundamental / base.fundamental;
+
+        for (l, p) in predicted.iter_mut().take(base.harmonics).enumerate() {
+            *p = prev.interp(scale * (l + 1) as f32);
+        }
+
+        let mean = predicted[..base.harmonics].iter().fold(0.0, |s, &p| s + p) /
+            base.harmonics as f32;
+
+        let mut log_amps = [0.0; MAX_HARMONICS];
+
+        for l in 0..base.harmonics {
+            log_amps[l] = residuals[l] + PREDICTION * (predicted[l] - mean);
+        }
+
+        FrameModel {
+            fundamental: base.fundamental,
+            harmonics: base.harmonics,
+            voiced: voiced,
+            log_amps: log_amps,
+        }
+    }
+
+    /// Linearly interpolate the log2 amplitude at the given fractional harmonic index,
+    /// where 1 is the first harmonic.
+    fn interp(&self, idx: f32) -> f32 {
+        if self.harmonics == 0 {
+            return 0.0;
+        }
+
+        let last = self.harmonics as f32;
+
+        if idx <= 1.0 {
+            return self.log_amps[0];
+        }
+
+        if idx >= last {
+            return self.log_amps[self.harmonics - 1];
+        }
+
+        let lo = idx.floor();
+        let frac = idx - lo;
+        let lo = lo as usize - 1;
+
+        self.log_amps[lo] * (1.0 - frac) + self.log_amps[lo + 1] * frac
+    }
+
+    /// Linear amplitude of the given harmonic, or zero if it doesn't exist.
+    fn amp(&self, l: usize) -> f32 {
+        if l < self.harmonics {
+            2.0f32.powf(self.log_amps[l])
+        } else {
+            0.0
+        }
+    }
+
+    /// Whether the given harmonic exists and is voiced.
+    fn is_voiced(&self, l: usize) -> bool { l < self.harmonics && self.voiced[l] }
+
+    /// Whether the given harmonic exists and is unvoiced.
+    fn is_unvoiced(&self, l: usize) -> bool { l < self.harmonics && !self.voiced[l] }
+}
+
+/// Dequantize the given coefficient index with the given number of bits.
+fn dequantize(idx: u16, bits: u8) -> f32 {
+    let levels = (1u32 << bits) as f32;
+    (idx as f32 + 0.5) * COEF_RANGE / levels - COEF_RANGE / 2.0
+}
+
+/// Length of each block of harmonics. The lengths differ by at most one, with the longer
+/// blocks at the top of the spectrum.
+fn block_lengths(harmonics: usize) -> [usize; BLOCKS] {
+    let mut lens = [harmonics / BLOCKS; BLOCKS];
+
+    for len in lens.iter_mut().skip(BLOCKS - harmonics % BLOCKS) {
+        *len += 1;
+    }
+
+    lens
+}
+
+/// Compute the inverse DCT of the given coefficients into the given output of the same
+/// length, where the first coefficient is the average of the output.
+fn idct(coefs: &[f32], out: &mut [f32]) {
+    use std::f32::consts::PI;
+
+    assert!(coefs.len() == out.len());
+
+    let n = coefs.len() as f32;
+
+    for (j, o) in out.iter_mut().enumerate() {
+        *o = coefs.iter().enumerate().skip(1).fold(coefs[0], |s, (k, &c)| {
+            s + 2.0 * c * (PI * k as f32 * (j as f32 + 0.5) / n).cos()
+        });
+    }
+}
+
+/// Synthesizes PCM audio from a stream of voice frames.
+pub struct Synthesizer {
+    /// Model of the previous frame.
+    prev: FrameModel,
+    /// Parameters of the previous good frame, used for concealment.
+    last: Option<(ImbeParams, BaseParams)>,
+    /// Number of consecutive concealed frames.
+    repeats: u32,
+    /// Phase of each voiced harmonic at the end of the previous frame.
+    phases: [f32; MAX_HARMONICS],
+    /// Shaped noise of the previous frame, whose second half is faded out over the
+    /// current frame.
+    noise: [f32; NOISE_SAMPLES],
+    /// State of the white noise generator.
+    seed: u32,
+}
+
+impl Synthesizer {
+    /// Create a new `Synthesizer` in the initial, silent state.
+    pub fn new() -> Synthesizer {
+        Synthesizer {
+            prev: FrameModel::silent(),
+            last: None,
+            repeats: 0,
+            phases: [0.0; MAX_HARMONICS],
+            noise: [0.0; NOISE_SAMPLES],
+            seed: 3147,
+        }
+    }
+
+    /// Synthesize the audio for the given received voice frame. The frame is concealed
+    /// if it should be erased due to errors or can't be unpacked.
+    pub fn feed(&mut self, frame: &VoiceFrame) -> [i16; FRAME_SAMPLES] {
+        if quality::erased(frame) {
+            return self.conceal();
+        }
+
+        match ImbeParams::new(frame) {
+            Ok(params) => self.synthesize(&params),
+            Err(_) => self.conceal(),
+        }
+    }
+
+    /// Synthesize the audio for the given parameters. The frame is concealed if the
+    /// pitch index is reserved.
+    pub fn synthesize(&mut self, params: &ImbeParams) -> [i16; FRAME_SAMPLES] {
+        let base = match params.base() {
+            Ok(b) => b,
+            Err(_) => return self.conceal(),
+        };
+
+        self.repeats = 0;
+        self.last = Some((*params, base));
+        self.render(params, &base)
+    }
+
+    /// Synthesize the audio for a lost or corrupted frame by repeating the previous good
+    /// frame, or silence if too many frames have been concealed in a row.
+    pub fn conceal(&mut self) -> [i16; FRAME_SAMPLES] {
+        self.repeats += 1;
+
+        if self.repeats >= MAX_REPEATS {
+            self.mute();
+            return [0; FRAME_SAMPLES];
+        }
+
+        match self.last {
+            Some((params, base)) => self.render(&params, &base),
+            None => [0; FRAME_SAMPLES],
+        }
+    }
+
+    /// Whether the output is currently muted due to concealed frames.
+    pub fn muted(&self) -> bool { self.repeats >= MAX_REPEATS }
+
+    /// Reset synthesis state so the next frame starts from silence.
+    fn mute(&mut self) {
+        self.prev = FrameModel::silent();
+        self.last = None;
+        self.noise = [0.0; NOISE_SAMPLES];
+    }
+
+    /// Generate the next white noise sample, with zero mean, using the standard's noise
+    /// generator.
+    fn next_noise(&mut self) -> f32 {
+        self.seed = (171 * self.seed + 11213) % 53125;
+        self.seed as f32 / 53125.0 - 0.5
+    }
+
+    /// Render the audio for the given parameters, crossfading from the previous frame.
+    fn render(&mut self, params: &ImbeParams, base: &BaseParams) -> [i16; FRAME_SAMPLES] {
+        let cur = FrameModel::new(params, base, &self.prev);
+        let prev = self.prev;
+
+        let mut out = [0.0f32; FRAME_SAMPLES];
+
+        self.voiced(&prev, &cur, &mut out);
+        self.unvoiced(&cur, &mut out);
+
+        self.prev = cur;
+
+        let mut pcm = [0; FRAME_SAMPLES];
+
+        for (p, &s) in pcm.iter_mut().zip(out.iter()) {
+            *p = s.max(std::i16::MIN as f32).min(std::i16::MAX as f32).round() as i16;
+        }
+
+        pcm
+    }
+
+    /// Add the voiced components of the given frames to the given output.
+    fn voiced(&mut self, prev: &FrameModel, cur: &FrameModel, out: &mut [f32]) {
+        let n = FRAME_SAMPLES as f32;
+        let dw = cur.fundamental - prev.fundamental;
+
+        for l in 0..MAX_HARMONICS {
+            let h = (l + 1) as f32;
+            let phase = self.phases[l];
+
+            match (prev.is_voiced(l), cur.is_voiced(l)) {
+                // Continue the harmonic with interpolated amplitude and frequency.
+                (true, true) => {
+                    let (a0, a1) = (prev.amp(l), cur.amp(l));
+
+                    for (i, s) in out.iter_mut().enumerate() {
+                        let t = i as f32;
+                        let a = a0 + (a1 - a0) * t / n;
+                        let theta = h * (prev.fundamental * t + dw * t * t / (2.0 * n));
+
+                        *s += a * (phase + theta).cos();
+                    }
+                },
+                // Fade out the previous harmonic.
+                (true, false) => {
+                    let a0 = prev.amp(l);
+
+                    for (i, s) in out.iter_mut().enumerate() {
+                        let t = i as f32;
+                        let theta = h * prev.fundamental * t;
+
+                        *s += a0 * (1.0 - t / n) * (phase + theta).cos();
+                    }
+                },
+                // Fade in the current harmonic.
+                (false, true) => {
+                    let a1 = cur.amp(l);
+
+                    for (i, s) in out.iter_mut().enumerate() {
+                        let t = i as f32;
+                        let theta = h * cur.fundamental * t;
+
+                        *s += a1 * t / n * (phase + theta).cos();
+                    }
+                },
+                (false, false) => {},
+            }
+
+            // Advance to the phase at the start of the next frame.
+            let advance = h * (prev.fundamental + cur.fundamental) / 2.0 * n;
+            self.phases[l] = (phase + advance) % (2.0 * std::f32::consts::PI);
+        }
+    }
+
+    /// Add the unvoiced components of the given frame to the given output, crossfading
+    /// from the previous frame's noise.
+    fn unvoiced(&mut self, cur: &FrameModel, out: &mut [f32]) {
+        let n = FRAME_SAMPLES as f32;
+        let mut noise = [0.0; NOISE_SAMPLES];
+
+        for s in noise.iter_mut() {
+            *s = self.next_noise();
+        }
+
+        shape(&mut noise, cur);
+
+        for (i, s) in out.iter_mut().enumerate() {
+            let t = i as f32 / n;
+            *s += self.noise[FRAME_SAMPLES + i] * (1.0 - t) + noise[i] * t;
+        }
+
+        self.noise = noise;
+    }
+}
+
+/// Shape the given white noise so the band around each unvoiced harmonic of the given
+/// model has the same power as a sinusoid with the harmonic's amplitude, and all other
+/// frequencies are removed.
+fn shape(noise: &mut [f32; NOISE_SAMPLES], model: &FrameModel) {
+    use std::f32::consts::PI;
+
+    const BINS: usize = NOISE_SAMPLES / 2;
+
+    let n = NOISE_SAMPLES as f32;
+    let angle = |m: usize, i: usize| 2.0 * PI * (m * i % NOISE_SAMPLES) as f32 / n;
+
+    let mut re = [0.0; BINS];
+    let mut im = [0.0; BINS];
+    let mut band = [None; BINS];
+    let mut power = [0.0; MAX_HARMONICS];
+
+    // Each bin belongs to the band of the nearest harmonic. DC and the Nyquist frequency
+    // are left out.
+    for m in 1..BINS {
+        let l = (angle(m, 1) / model.fundamental).round() as usize;
+
+        if l == 0 || !model.is_unvoiced(l - 1) {
+            continue;
+        }
+
+        for (i, &s) in noise.iter().enumerate() {
+            re[m] += s * angle(m, i).cos();
+            im[m] -= s * angle(m, i).sin();
+        }
+
+        band[m] = Some(l - 1);
+        power[l - 1] += re[m] * re[m] + im[m] * im[m];
+    }
+
+    let mut gain = [0.0; BINS];
+
+    for (g, b) in gain.iter_mut().zip(band.iter()) {
+        if let Some(l) = *b {
+            *g = model.amp(l) * n / (2.0 * power[l].sqrt());
+        }
+    }
+
+    for (i, s) in noise.iter_mut().enumerate() {
+        *s = (1..BINS).fold(0.0, |acc, m| {
+            acc + gain[m] * (re[m] * angle(m, i).cos() - im[m] * angle(m, i).sin())
+        }) * 2.0 / n;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use voice::frame::VoiceFrame;
+    use voice::imbe::{BaseParams, ImbeParams, MAX_AMPLITUDES, MAX_BANDS};
+
+    /// Create parameters with a nearly flat spectral envelope, with the coefficients
+    /// alternating around zero.
+    fn params(pitch: u8, voiced: bool, gain: u8) -> ImbeParams {
+        let base = BaseParams::new(pitch).unwrap();
+        let alloc = base.amplitude_bits();
+        let mut amplitudes = [0; MAX_AMPLITUDES];
+
+        for (i, (a, &bits)) in amplitudes.iter_mut().zip(alloc.iter()).enumerate() {
+            if bits > 0 {
+                *a = (1 << (bits - 1)) - (i % 2) as u16;
+            }
+        }
+
+        ImbeParams {
+            pitch: pitch,
+            voiced: [voiced; MAX_BANDS],
+            gain: gain,
+            amplitudes: amplitudes,
+        }
+    }
+
+    /// Normalized autocorrelation of the given samples at the given lag.
+    fn autocorr(s: &[i16], lag: usize) -> f32 {
+        let dot = |a: &[i16], b: &[i16]| {
+            a.iter().zip(b.iter()).fold(0.0, |acc, (&x, &y)| acc + x as f32 * y as f32)
+        };
+
+        dot(&s[..s.len() - lag], &s[lag..]) /
+            (dot(&s[..s.len() - lag], &s[..s.len() - lag]) *
+             dot(&s[lag..], &s[lag..])).sqrt()
+    }
+
+    fn energy(s: &[i16]) -> f32 {
+        s.iter().fold(0.0, |acc, &x| acc + x as f32 * x as f32) / s.len() as f32
+    }
+
+    #[test]
+    fn test_blocks() {
+        assert_eq!(block_lengths(9), [1, 1, 1, 2, 2, 2]);
+        assert_eq!(block_lengths(12), [2; BLOCKS]);
+        assert_eq!(block_lengths(56), [9, 9, 9, 9, 10, 10]);
+
+        for l in 9...56 {
+            assert_eq!(block_lengths(l).iter().fold(0, |s, &j| s + j), l);
+        }
+
+        // The first coefficient gives the average.
+        let mut out = [0.0; 4];
+        idct(&[1.5, 0.0, 0.0, 0.0], &mut out);
+        assert_eq!(out, [1.5; 4]);
+
+        idct(&[1.0, 0.5, -0.25], &mut out[..3]);
+        assert!((out.iter().take(3).fold(0.0, |s, &x| s + x) / 3.0 - 1.0).abs() < 1e-6);
+        assert!((out[0] - (1.0 + 3.0f32.sqrt() / 2.0 - 0.25)).abs() < 1e-6);
+        assert!((out[1] - 1.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_noise() {
+        let mut s = Synthesizer::new();
+
+        s.next_noise();
+        assert_eq!(s.seed, 18100);
+        s.next_noise();
+        assert_eq!(s.seed, (171 * 18100 + 11213) % 53125);
+
+        let mean = (0..53125).fold(0.0, |m, _| m + s.next_noise()) / 53125.0;
+        assert!(mean.abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_initial() {
+        let mut s = Synthesizer::new();
+        assert!(s.conceal().iter().all(|&x| x == 0));
+    }
+
+    #[test]
+    fn test_voiced() {
+        let mut s = Synthesizer::new();
+        // Pitch period of (121 + 39.5) / 2 = 80.25 samples.
+        let p = params(121, true, 30);
+        let mut pcm = vec![];
+
+        for _ in 0..4 {
+            pcm.extend_from_slice(&s.synthesize(&p)[..]);
+        }
+
+        let pcm = &pcm[2 * FRAME_SAMPLES..];
+
+        assert!(energy(pcm) > 1000.0);
+        assert!(autocorr(pcm, 80) > 0.8);
+        assert!(autocorr(pcm, 80) > autocorr(pcm, 40));
+    }
+
+    #[test]
+    fn test_unvoiced() {
+        let mut s = Synthesizer::new();
+        let p = params(121, false, 30);
+        let mut pcm = vec![];
+
+        for _ in 0..4 {
+            pcm.extend_from_slice(&s.synthesize(&p)[..]);
+        }
+
+        let pcm = &pcm[2 * FRAME_SAMPLES..];
+
+        assert!(energy(pcm) > 1000.0);
+        assert!(autocorr(pcm, 80) < 0.5);
+    }
+
+    #[test]
+    fn test_gain() {
+        let mut quiet = Synthesizer::new();
+        let mut loud = Synthesizer::new();
+
+        let mut q = 0.0;
+        let mut l = 0.0;
+
+        for _ in 0..3 {
+            q = energy(&quiet.synthesize(&params(80, true, 10))[..]);
+            l = energy(&loud.synthesize(&params(80, true, 30))[..]);
+        }
+
+        assert!(l > q * 100.0);
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let mut a = Synthesizer::new();
+        let mut b = Synthesizer::new();
+
+        for &(pitch, voiced) in [(50, true), (60, false), (70, true), (0, false)].iter() {
+            let p = params(pitch, voiced, 25);
+            assert_eq!(&a.synthesize(&p)[..], &b.synthesize(&p)[..]);
+        }
+    }
+
+    #[test]
+    fn test_feed() {
+        let mut a = Synthesizer::new();
+        let mut b = Synthesizer::new();
+
+        for &pitch in [10, 100, 200].iter() {
+            let p = params(pitch, true, 20);
+            let f = VoiceFrame::new(&VoiceFrame::encode(&p.to_chunks())).unwrap();
+
+            assert_eq!(&a.feed(&f)[..], &b.synthesize(&p)[..]);
+        }
+    }
+
+    #[test]
+    fn test_conceal() {
+        let mut s = Synthesizer::new();
+        let p = params(121, true, 30);
+
+        for _ in 0..3 {
+            s.synthesize(&p);
+        }
+
+        let mut bad = VoiceFrame::new(&VoiceFrame::encode(&p.to_chunks())).unwrap();
+        bad.errors[0] = 3;
+
+        // Frames are repeated until the mute threshold.
+        for _ in 0..MAX_REPEATS - 1 {
+            assert!(energy(&s.feed(&bad)[..]) > 1000.0);
+            assert!(!s.muted());
+        }
+
+        assert!(s.feed(&bad).iter().all(|&x| x == 0));
+        assert!(s.muted());
+        assert!(s.conceal().iter().all(|&x| x == 0));
+
+        // A good frame recovers from the mute.
+        s.synthesize(&p);
+        assert!(!s.muted());
+
+        // Too many total errors.
+        let mut bad = VoiceFrame::new(&VoiceFrame::encode(&p.to_chunks())).unwrap();
+        bad.errors = [1, 3, 3, 3, 1, 0, 0];
+        s.feed(&bad);
+        assert_eq!(s.repeats, 1);
+
+        // Reserved pitch index.
+        let f = VoiceFrame {
+            chunks: [0b111111 << 6, 0, 0, 0, 0, 0, 0, 0],
+            errors: [0; 7],
+        };
+        s.feed(&f);
+        assert_eq!(s.repeats, 2);
+
+        // Reserved pitch index given directly.
+        let mut p = params(121, true, 30);
+        p.pitch = 0b11111111;
+        s.synthesize(&p);
+        assert!(s.muted());
+    }
+}
@@ -2,9 +2,9 @@
 
 use collect_slice::CollectSlice;
 
-use bits::{Dibit, Hexbit, HexbitBytes};
+use bits::{Dibit, Hexbit, HexbitBytes, SoftDibit};
 use buffer::{Buffer, VoiceLCTermWordStorage, VoiceExtraStorage};
-use coding::{reed_solomon, golay};
+use coding::{chase, reed_solomon, golay};
 use consts::LINK_CONTROL_BYTES;
 use error::Result;
 use voice::control::LinkControlFields;
@@ -17,6 +17,10 @@ pub struct VoiceLCTerminatorReceiver {
     outer: Buffer<VoiceLCTermWordStorage>,
     /// Current buffered hexbits.
     inner: Buffer<VoiceExtraStorage>,
+    /// Hexbits in the current packet whose Golay code couldn't be decoded.
+    erasures: reed_solomon::Erasures,
+    /// Bit reliabilities for the current word, if received from soft symbols.
+    rel: chase::Reliabilities,
 }
 
 impl VoiceLCTerminatorReceiver {
@@ -25,6 +29,8 @@ impl VoiceLCTerminatorReceiver {
         VoiceLCTerminatorReceiver {
             outer: Buffer::new(VoiceLCTermWordStorage::new()),
             inner: Buffer::new(VoiceExtraStorage::new()),
+            erasures: reed_solomon::Erasures::new(),
+            rel: chase::Reliabilities::new(),
         }
     }
 
@@ -37,12 +43,25 @@ impl VoiceLCTerminatorReceiver {
             None => return None,
         };
 
-        let data = match golay::extended::decode(*buf as u32) {
-            Some((data, err)) => data,
-            // Let the following RS code attempt to correct these errors.
-            None => 0,
+        let rel = self.rel;
+        self.rel = chase::Reliabilities::new();
+
+        // Use soft decoding if every symbol in the word was soft.
+        let decoded = if rel.bits().len() == 24 {
+            golay::extended::decode_soft(*buf as u32, rel.bits())
+        } else {
+            golay::extended::decode(*buf as u32)
+        };
+
+        let (data, erased) = match decoded {
+            Some((data, err)) => (data, false),
+            // Let the following RS code correct both hexbits as erasures.
+            None => (0, true),
         };
 
+        self.erasures.feed(erased);
+        self.erasures.feed(erased);
+
         // Each 12-bit word is turned into 2 hexbits.
         assert!(self.inner.feed(Hexbit::new((data >> 6) as u8)).is_none());
 
@@ -51,7 +70,11 @@ impl VoiceLCTerminatorReceiver {
             None => return None,
         };
 
-        let data = match reed_solomon::short::decode(hexbits) {
+        let erasures = self.erasures;
+        self.erasures = reed_solomon::Erasures::new();
+
+        let data = match reed_solomon::short::decode_erasures(hexbits,
+                                                              erasures.positions()) {
             Some((data, err)) => data,
             None => return Some(Err(ReedSolomonUnrecoverable)),
         };
@@ -62,4 +85,71 @@ impl VoiceLCTerminatorReceiver {
 
         Some(Ok(LinkControlFields::new(bytes)))
     }
+
+    /// Feed in a soft baseband symbol, using the reliability of each bit to correct more
+    /// errors in the Golay code. The return value has the same meaning as for `feed`.
+    pub fn feed_soft(&mut self, s: SoftDibit) -> Option<Result<LinkControlFields>> {
+        self.rel.feed(s);
+        self.feed(s.hard())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use collect_slice::CollectSlice;
+    use bits::{Dibit, Hexbit, Hexbits};
+    use coding::{golay, reed_solomon};
+    use consts::{EXTRA_HEXBITS, LINK_CONTROL_BYTES};
+
+    #[test]
+    fn test_receive_erasures() {
+        let bytes: [u8; LINK_CONTROL_BYTES] = [
+            0b00001111, 0, 0, 0, 0, 0, 0x12, 0x34, 0x56,
+        ];
+
+        let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+        Hexbits::new(bytes.iter().cloned()).collect_slice_checked(&mut hexbits[..12]);
+        reed_solomon::short::encode(&mut hexbits);
+
+        // Corrupt 5 Golay words beyond their correction capacity, erasing 10 hexbits,
+        // which is more than the 6 errors the RS code can correct but within its 12
+        // erasures.
+        let erased = [0, 2, 5, 8, 11];
+        let mut dibits = vec![];
+
+        for (i, pair) in hexbits.chunks(2).enumerate() {
+            let data = (pair[0].bits() as u16) << 6 | pair[1].bits() as u16;
+            let mut word = golay::extended::encode(data);
+
+            if erased.contains(&i) {
+                word ^= 0b1111;
+                assert!(golay::extended::decode(word).is_none());
+            }
+
+            for shift in (0..12).rev() {
+                dibits.push(Dibit::new((word >> (shift * 2)) as u8 & 0b11));
+            }
+        }
+
+        // Treating the uncorrectable words as zero hexbits leaves too many errors.
+        let mut zeroed = hexbits;
+
+        for &i in erased.iter() {
+            zeroed[2 * i] = Hexbit::new(0);
+            zeroed[2 * i + 1] = Hexbit::new(0);
+        }
+
+        assert!(reed_solomon::short::decode(&mut zeroed)
+            .map_or(true, |(data, _)| data != &hexbits[..12]));
+
+        let mut recv = VoiceLCTerminatorReceiver::new();
+        let lcs: Vec<LinkControlFields> = dibits.iter()
+            .filter_map(|&d| recv.feed(d))
+            .map(|l| l.unwrap())
+            .collect();
+
+        assert_eq!(lcs.len(), 1);
+        assert_eq!(lcs[0].bytes(), &bytes);
+    }
 }
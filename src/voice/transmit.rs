@@ -0,0 +1,440 @@
+//! Encode and transmit voice-related data units.
+//!
+//! Each data unit is generated as a complete burst of dibit symbols, made up of the
+//! frame sync and NID followed by the coded packet, with status symbols interleaved
+//! throughout and padding to the next status symbol boundary. The resulting symbols can
+//! be fed directly into `baseband::encode::C4fmImpulses`.
+
+use collect_slice::CollectSlice;
+
+use baseband::sync::SYNC_GENERATOR;
+use bits::{Dibit, Dibits, Hexbit, Hexbits};
+use coding::{cyclic, golay, hamming, reed_solomon};
+use consts::{EXTRA_HEXBITS, HEADER_HEXBITS};
+use message::nid::{DataUnit, NetworkAccessCode, NetworkId};
+use message::status::{StatusCode, StatusInterleaver};
+use voice::control::LinkControlFields;
+use voice::crypto::CryptoControlFields;
+use voice::frame::VoiceFrame;
+use voice::header::VoiceHeaderFields;
+
+/// Generates the symbols of voice data units.
+pub struct VoiceTransmitter {
+    /// NAC transmitted in the NID of each data unit.
+    nac: NetworkAccessCode,
+    /// Status symbol to interleave.
+    status: StatusCode,
+}
+
+impl VoiceTransmitter {
+    /// Create a new `VoiceTransmitter` using the given NAC and status symbol.
+    pub fn new(nac: NetworkAccessCode, status: StatusCode) -> VoiceTransmitter {
+        VoiceTransmitter {
+            nac: nac,
+            status: status,
+        }
+    }
+
+    /// Update the status symbol interleaved into following data units.
+    pub fn update_status(&mut self, status: StatusCode) { self.status = status; }
+
+    /// Generate the symbols of a voice header (HDU) data unit carrying the given header
+    /// packet.
+    pub fn header(&self, header: &VoiceHeaderFields) -> Vec<Dibit> {
+        self.burst(DataUnit::VoiceHeader, encode_header(header))
+    }
+
+    /// Generate the symbols of a link control frame group (LDU1) data unit carrying the
+    /// given voice frame chunks, link control packet, and 16-bit low-speed data fragment.
+    pub fn lc_frame_group(&self, frames: &[[u32; 8]; 9], lc: &LinkControlFields,
+                          data: u16)
+        -> Vec<Dibit>
+    {
+        let mut extra = [Hexbit::default(); EXTRA_HEXBITS];
+
+        Hexbits::new(lc.bytes().iter().cloned()).collect_slice_checked(&mut extra[..12]);
+        reed_solomon::short::encode(&mut extra);
+
+        self.burst(DataUnit::VoiceLCFrameGroup, encode_frame_group(frames, &extra, data))
+    }
+
+    /// Generate the symbols of a crypto control frame group (LDU2) data unit carrying the
+    /// given voice frame chunks, crypto control packet, and 16-bit low-speed data
+    /// fragment.
+    pub fn cc_frame_group(&self, frames: &[[u32; 8]; 9], cc: &CryptoControlFields,
+                          data: u16)
+        -> Vec<Dibit>
+    {
+        let mut extra = [Hexbit::default(); EXTRA_HEXBITS];
+
+        Hexbits::new(cc.bytes().iter().cloned()).collect_slice_checked(&mut extra[..16]);
+        reed_solomon::medium::encode(&mut extra);
+
+        self.burst(DataUnit::VoiceCCFrameGroup, encode_frame_group(frames, &extra, data))
+    }
+
+    /// Generate the symbols of a simple terminator (TDU) data unit.
+    pub fn simple_terminator(&self) -> Vec<Dibit> {
+        self.burst(DataUnit::VoiceSimpleTerminator, vec![])
+    }
+
+    /// Generate the symbols of a link control terminator (TDULC) data unit carrying the
+    /// given link control packet.
+    pub fn lc_terminator(&self, lc: &LinkControlFields) -> Vec<Dibit> {
+        self.burst(DataUnit::VoiceLCTerminator, encode_lc_term(lc))
+    }
+
+    /// Wrap the given coded packet dibits with the frame sync and NID of the given data
+    /// unit, and interleave status symbols.
+    fn burst(&self, unit: DataUnit, packet: Vec<Dibit>) -> Vec<Dibit> {
+        let nid = NetworkId::new(self.nac, unit);
+        let mut dibits = vec![];
+
+        dibits.extend(Dibits::new(SYNC_GENERATOR.iter().cloned()));
+        dibits.extend(Dibits::new(nid.encode().iter().cloned()));
+        dibits.extend(packet);
+
+        StatusInterleaver::new(dibits.into_iter(), self.status).collect()
+    }
+}
+
+/// Append the given number of LSBs of the given word to the given dibits, MSB first.
+fn push_word(dibits: &mut Vec<Dibit>, word: u32, bits: usize) {
+    assert!(bits % 2 == 0);
+
+    for shift in (0..bits / 2).rev() {
+        dibits.push(Dibit::new((word >> (shift * 2)) as u8 & 0b11));
+    }
+}
+
+/// Encode the given voice header packet with the outer RS code and inner Golay code.
+fn encode_header(header: &VoiceHeaderFields) -> Vec<Dibit> {
+    let mut hexbits = [Hexbit::default(); HEADER_HEXBITS];
+
+    Hexbits::new(header.bytes().iter().cloned())
+        .collect_slice_checked(&mut hexbits[..20]);
+    reed_solomon::long::encode(&mut hexbits);
+
+    let mut dibits = vec![];
+
+    for h in hexbits.iter() {
+        push_word(&mut dibits, golay::shortened::encode(h.bits()), 18);
+    }
+
+    dibits
+}
+
+/// Encode the given link control packet with the outer RS code and inner Golay code
+/// used by the voice terminator.
+fn encode_lc_term(lc: &LinkControlFields) -> Vec<Dibit> {
+    let mut hexbits = [Hexbit::default(); EXTRA_HEXBITS];
+
+    Hexbits::new(lc.bytes().iter().cloned()).collect_slice_checked(&mut hexbits[..12]);
+    reed_solomon::short::encode(&mut hexbits);
+
+    let mut dibits = vec![];
+
+    // Each pair of hexbits is coded as a 12-bit word.
+    for pair in hexbits.chunks(2) {
+        let word = (pair[0].bits() as u16) << 6 | pair[1].bits() as u16;
+        push_word(&mut dibits, golay::extended::encode(word), 24);
+    }
+
+    dibits
+}
+
+/// Encode the given voice frames, RS-coded extra hexbits, and low-speed data fragment
+/// into a frame group.
+fn encode_frame_group(frames: &[[u32; 8]; 9], extra: &[Hexbit; EXTRA_HEXBITS],
+                      data: u16)
+    -> Vec<Dibit>
+{
+    // Each extra hexbit is coded as a 10-bit word, and the coded words are split into 6
+    // equal pieces.
+    let mut pieces = vec![];
+
+    for h in extra.iter() {
+        push_word(&mut pieces, hamming::shortened::encode(h.bits()) as u32, 10);
+    }
+
+    let mut pieces = pieces.chunks(pieces.len() / 6);
+    let mut dibits = vec![];
+
+    for (idx, chunks) in frames.iter().enumerate() {
+        dibits.extend_from_slice(&VoiceFrame::encode(chunks)[..]);
+
+        match idx {
+            1...6 => dibits.extend_from_slice(pieces.next().unwrap()),
+            7 => {
+                push_word(&mut dibits, cyclic::encode((data >> 8) as u8) as u32, 16);
+                push_word(&mut dibits, cyclic::encode(data as u8) as u32, 16);
+            },
+            _ => {},
+        }
+    }
+
+    dibits
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use bits::{Dibit, SoftDibit};
+    use consts::{NID_DIBITS, SYNC_SYMBOLS};
+    use message::status::{StatusDeinterleaver, StreamSymbol};
+    use trunking::fields::TalkGroup;
+    use voice::control::{LinkControlOpcode, LinkControlFields};
+    use voice::crypto::{CryptoAlgorithm, CryptoControlFields};
+    use voice::frame_group::{
+        FrameGroupEvent,
+        VoiceCCFrameGroupReceiver,
+        VoiceLCFrameGroupReceiver,
+    };
+    use voice::header::{VoiceHeaderFields, VoiceHeaderReceiver};
+    use voice::term::VoiceLCTerminatorReceiver;
+
+    /// Strip the frame sync, status symbols, and NID from the given burst and return the
+    /// remaining packet dibits.
+    fn packet(dibits: Vec<Dibit>) -> Vec<Dibit> {
+        let mut status = StatusDeinterleaver::new();
+
+        dibits[SYNC_SYMBOLS..].iter().filter_map(|&d| match status.feed(d) {
+            StreamSymbol::Status(s) => {
+                assert_eq!(s, StatusCode::InboundIdle);
+                None
+            },
+            StreamSymbol::Data(d) => Some(d),
+        }).skip(NID_DIBITS).collect()
+    }
+
+    fn frames() -> [[u32; 8]; 9] {
+        let mut frames = [[0; 8]; 9];
+
+        for (i, f) in frames.iter_mut().enumerate() {
+            let i = i as u32;
+
+            *f = [
+                0xABC ^ i,
+                0x123 + i,
+                0xFFF - i,
+                0x555 << (i % 2),
+                0x7FF - i,
+                0x2AA + i,
+                0x155 ^ i,
+                0x7F >> i % 7,
+            ];
+        }
+
+        frames
+    }
+
+    #[test]
+    fn test_header() {
+        let tx = VoiceTransmitter::new(NetworkAccessCode::Default,
+                                       StatusCode::InboundIdle);
+        let h = VoiceHeaderFields::new([
+            1, 2, 3, 4, 5, 6, 7, 8, 9,
+            0x90,
+            0x80,
+            0x12, 0x34,
+            0xAB, 0xCD,
+        ]);
+
+        let dibits = tx.header(&h);
+        assert_eq!(dibits.len(), 396);
+
+        let mut recv = VoiceHeaderReceiver::new();
+        let mut headers = vec![];
+
+        for &d in packet(dibits).iter() {
+            if let Some(h) = recv.feed(d) {
+                headers.push(h.unwrap());
+            }
+        }
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].crypto_init(), &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(headers[0].mfg(), 0x90);
+        assert_eq!(headers[0].crypto_alg(), CryptoAlgorithm::Unencrypted);
+        assert_eq!(headers[0].crypto_key(), 0x1234);
+        assert_eq!(headers[0].talk_group(), TalkGroup::Other(0xABCD));
+    }
+
+    #[test]
+    fn test_lc_frame_group() {
+        let tx = VoiceTransmitter::new(NetworkAccessCode::Default,
+                                       StatusCode::InboundIdle);
+        let lc = LinkControlFields::new([
+            0b00000000, 0x00, 0b10000000, 0x00, 0x12, 0x34, 0xDE, 0xAD, 0xBE,
+        ]);
+
+        let frames = frames();
+        let dibits = tx.lc_frame_group(&frames, &lc, 0xBEEF);
+        assert_eq!(dibits.len(), 864);
+
+        let mut recv = VoiceLCFrameGroupReceiver::new();
+        let mut vf = 0;
+        let mut extras = 0;
+        let mut frags = 0;
+
+        for &d in packet(dibits).iter() {
+            match recv.feed(d) {
+                Some(Ok(FrameGroupEvent::VoiceFrame(f))) => {
+                    assert_eq!(f.chunks, frames[vf]);
+                    assert_eq!(f.errors, [0; 7]);
+                    vf += 1;
+                },
+                Some(Ok(FrameGroupEvent::Extra(l))) => {
+                    assert_eq!(l.opcode(), Some(LinkControlOpcode::GroupVoiceTraffic));
+                    assert_eq!(l.bytes(), lc.bytes());
+                    extras += 1;
+                },
+                Some(Ok(FrameGroupEvent::DataFragment(data))) => {
+                    assert_eq!(data, 0xBEEF);
+                    frags += 1;
+                },
+                Some(Err(_)) => panic!(),
+                None => {},
+            }
+        }
+
+        assert!(recv.done());
+        assert_eq!(vf, 9);
+        assert_eq!(extras, 1);
+        assert_eq!(frags, 1);
+    }
+
+    #[test]
+    fn test_cc_frame_group() {
+        let tx = VoiceTransmitter::new(NetworkAccessCode::Default,
+                                       StatusCode::InboundIdle);
+        let cc = CryptoControlFields::new([
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 0x84, 0xDE, 0xAD,
+        ]);
+
+        let frames = frames();
+        let dibits = tx.cc_frame_group(&frames, &cc, 0x1234);
+        assert_eq!(dibits.len(), 864);
+
+        let mut recv = VoiceCCFrameGroupReceiver::new();
+        let mut vf = 0;
+        let mut extras = 0;
+
+        for &d in packet(dibits).iter() {
+            match recv.feed(d) {
+                Some(Ok(FrameGroupEvent::VoiceFrame(f))) => {
+                    assert_eq!(f.chunks, frames[vf]);
+                    vf += 1;
+                },
+                Some(Ok(FrameGroupEvent::Extra(c))) => {
+                    assert_eq!(c.init(), &[1, 2, 3, 4, 5, 6, 7, 8, 9]);
+                    assert_eq!(c.alg(), CryptoAlgorithm::Aes);
+                    assert_eq!(c.key(), 0xDEAD);
+                    extras += 1;
+                },
+                Some(Ok(FrameGroupEvent::DataFragment(data))) => assert_eq!(data, 0x1234),
+                Some(Err(_)) => panic!(),
+                None => {},
+            }
+        }
+
+        assert!(recv.done());
+        assert_eq!(vf, 9);
+        assert_eq!(extras, 1);
+    }
+
+    #[test]
+    fn test_terminators() {
+        let tx = VoiceTransmitter::new(NetworkAccessCode::Default,
+                                       StatusCode::InboundIdle);
+
+        let dibits = tx.simple_terminator();
+        assert_eq!(dibits.len(), 72);
+        assert!(packet(dibits).iter().all(|d| d.bits() == 0));
+
+        let lc = LinkControlFields::new([
+            0b00001111, 0, 0, 0, 0, 0, 0x12, 0x34, 0x56,
+        ]);
+
+        let dibits = tx.lc_terminator(&lc);
+        assert_eq!(dibits.len(), 216);
+
+        let mut recv = VoiceLCTerminatorReceiver::new();
+        let mut lcs = vec![];
+
+        for &d in packet(dibits).iter() {
+            if let Some(l) = recv.feed(d) {
+                lcs.push(l.unwrap());
+            }
+        }
+
+        assert_eq!(lcs.len(), 1);
+        assert_eq!(lcs[0].opcode(), Some(LinkControlOpcode::CallTermination));
+        assert_eq!(lcs[0].bytes(), lc.bytes());
+    }
+
+    /// Convert the given dibits to soft symbols at their ideal levels.
+    fn soft(dibits: Vec<Dibit>) -> Vec<SoftDibit> {
+        dibits.iter().map(|&d| SoftDibit::ideal(d)).collect()
+    }
+
+    #[test]
+    fn test_soft() {
+        let tx = VoiceTransmitter::new(NetworkAccessCode::Default,
+                                       StatusCode::InboundIdle);
+
+        let h = VoiceHeaderFields::new([
+            1, 2, 3, 4, 5, 6, 7, 8, 9, 0x90, 0x80, 0x12, 0x34, 0xAB, 0xCD,
+        ]);
+
+        let mut recv = VoiceHeaderReceiver::new();
+        let headers: Vec<VoiceHeaderFields> = soft(packet(tx.header(&h))).iter()
+            .filter_map(|&s| recv.feed_soft(s))
+            .map(|h| h.unwrap())
+            .collect();
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers[0].talk_group(), TalkGroup::Other(0xABCD));
+
+        let lc = LinkControlFields::new([
+            0b00000000, 0x00, 0b10000000, 0x00, 0x12, 0x34, 0xDE, 0xAD, 0xBE,
+        ]);
+
+        let frames = frames();
+        let mut recv = VoiceLCFrameGroupReceiver::new();
+        let mut vf = 0;
+        let mut extras = 0;
+
+        for &s in soft(packet(tx.lc_frame_group(&frames, &lc, 0xBEEF))).iter() {
+            match recv.feed_soft(s) {
+                Some(Ok(FrameGroupEvent::VoiceFrame(f))) => {
+                    assert_eq!(f.chunks, frames[vf]);
+                    assert_eq!(f.errors, [0; 7]);
+                    vf += 1;
+                },
+                Some(Ok(FrameGroupEvent::Extra(l))) => {
+                    assert_eq!(l.bytes(), lc.bytes());
+                    extras += 1;
+                },
+                Some(Ok(FrameGroupEvent::DataFragment(data))) => {
+                    assert_eq!(data, 0xBEEF);
+                },
+                Some(Err(_)) => panic!(),
+                None => {},
+            }
+        }
+
+        assert!(recv.done());
+        assert_eq!(vf, 9);
+        assert_eq!(extras, 1);
+
+        let mut recv = VoiceLCTerminatorReceiver::new();
+        let lcs: Vec<LinkControlFields> = soft(packet(tx.lc_terminator(&lc))).iter()
+            .filter_map(|&s| recv.feed_soft(s))
+            .map(|l| l.unwrap())
+            .collect();
+
+        assert_eq!(lcs.len(), 1);
+        assert_eq!(lcs[0].bytes(), lc.bytes());
+    }
+}